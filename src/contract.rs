@@ -1,16 +1,181 @@
 use cosmwasm_std::{
-    entry_point, to_binary, Binary, Deps, DepsMut, Env, MessageInfo,
-    Response, StdError, StdResult,
+    entry_point, from_slice, to_binary, to_vec, Api, Attribute, BankMsg, Binary, Coin, Uint128, Deps, DepsMut, Env, Event, MessageInfo,
+    Reply, Response, StdError, StdResult, Storage, SubMsg, SubMsgResult, WasmMsg,
 };
+use secret_toolkit::serialization::Serde;
+use secret_toolkit::storage::{Item, Keymap};
+use secret_toolkit::viewing_key::{ViewingKey, ViewingKeyStore};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
 use sha2::{Sha256, Digest};
 
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, StateResponse, StateCommitmentResponse, ValidationResponse, CurrentRootResponse, PendingValidationsResponse};
+use crate::msg::{
+    ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SubscriberMsg, OutboundFailure, OutboundFailuresResponse, WithdrawalReceiptResponse, WithdrawalReceiptsResponse, StateResponse, StateCommitmentResponse, ValidationResponse,
+    CurrentRootResponse, PendingValidationsResponse, LightClientUpdateResponse, CommitteeKey,
+    ConfigResponse, StatsResponse, NodeWorkItem, PendingForNodeResponse, TransferStatusResponse,
+    LeafIndexResponse, SharesForNodeResponse, ConfigUpdate, NodeInfo, NodesResponse, BuildCount,
+    BuildDiversityResponse, ComputeRootResponse, ValidationOutcomeResponse, ValidationStatusResponse, PauseLedgerResponse, ViewerAuth, ViewingKeyResponse,
+    AdminRecoveryResponse, ValidationCountsResponse, RootAtHeightResponse, RootStatus,
+    MissingVotersResponse, VoterKeysResponse, VoterKey, FinalizedResponse, LastFinalizedResponse, DiagnosisResponse, KeyType, NodeCosign, ThresholdKeyResponse, EpochsResponse, ThresholdSignatureResponse, NodeWorkStub, AssignedPayloadResponse, FinalizationCheck, BatchVote, BatchVoteResult, VoteResultEntry, VoteStatus, FaultEvidence, DeploymentIdentityResponse, VerifyInclusionResponse, RecipientLeg, TransferGroupStatusResponse, SnapshotResponse,
+    FinalizersResponse, CommitmentAtHeightResponse, StorageUsageResponse, AccountOverviewResponse, AccountCommitment, AccountPrivate, AccountPending, NodeStatsResponse, RewardSplitResponse, NodePayout, CommitteeSnapshotResponse, ValidationExpiryResponse, ProtocolInfoResponse, ProtocolLimits, LifetimeNodeCountResponse, IsNodeResponse, AuditBundleResponse, AuditPrivate, EquivocationsResponse, HealthResponse, HealthStatus, HealthReason, MigrationDryRunResponse, RecordCount,
+};
+use crate::protocol::{
+    normalize_public_key, canonicalize_shares, validate_shares, vote_digest, attestation_digest, deregistration_digest,
+    transition_content_hash, repoint_digest, purge_digest, user_key_digest, compromise_digest, deployment_prefix, root_attestation_digest, key_possession_digest,
+    key_type, canonicalize_asset_updates, asset_updates_digest, audit_bundle_hash, SUPPORTED_SHARE_ENCODINGS, USER_KEY_TYPES, NODE_KEY_TYPES, PARTIAL_SIGNATURE_LEN,
+    HASH_SCHEME, DIGEST_LEN, DOMAIN_TAGS, MAX_ASSET_UPDATES, MAX_ASSET_ID_LEN, CURRENT_PROTOCOL_VERSION,
+};
 use crate::state::{
-    State, MPCNode, StateCommitment, StateTransition, Transfer,
-    PendingValidation, NodeValidation, MerkleProofElement,
-    STATE, PENDING_VALIDATIONS, STATE_COMMITMENTS,
+    State, Config, Stats, VersionCount, MPCNode, StateCommitment, StateTransition, Transfer,
+    TransferRecord, TransferStatus, PendingValidation, NodeValidation, NonceCommitment, MerkleProofElement, CommitmentMetadata, EncryptedShares,
+    BuildAttestation, ValidationOutcome, OutcomeStatus, OperationKind, EventVerbosity, AdminRecovery,
+    BlockReason, NodeRoot, DataChallenge, CancelledVotes, NodeMetadata, CANCELLED_CONTENT, DEPENDENTS, PauseWindow, PAUSE_LEDGER, COMMITMENT_FINALIZERS, COMMITMENT_HISTORY, VotePrecedence, RewardPolicy, NodeEffort, NODE_EFFORT, CommitteeSnapshot, EpochMember, COMMITTEE_SNAPSHOTS, ThresholdKey, THRESHOLD_KEYS, KeyCompromise, COMPROMISED_KEYS, REGISTRATION_HEIGHTS, RegistrationWindow, REGISTRATION_WINDOW, RecentRejection, RECENT_REJECTIONS, ACCOUNT_REGISTRATIONS, Subscriber, SettledValidation, DesignatedFinalizer, SETTLED_VALIDATIONS, ContentVote, CONTENT_VOTES, EquivocationEvidence, EQUIVOCATIONS, USER_STORAGE, DeploymentIdentity, DEPLOYMENT, LastFinalized, LAST_FINALIZED, FinalizedRecord, RetainedPartial, RetainPartials, FINALIZED_RECORDS, TransferGroup, GroupLeg, TRANSFER_GROUPS, AssetLeaf, AssetUpdate, ASSET_LOCKS, AuditTrail, FINALIZED_HEIGHTS,
+    OutboundSend, OUTBOUND_SEQ, OUTBOUND_IN_FLIGHT, OUTBOUND_FAILURES, OUTBOUND_FAILURE_IDS, WithdrawalReceipt, SendStatus, WITHDRAWAL_RECEIPTS, RECIPIENT_RECEIPTS,
+    default_protocol_version, default_tree_depth, default_attestation_window,
+    default_recovery_supermajority, default_recovery_delay, default_root_history_retention, default_partial_retention,
+    default_max_transition_size, default_max_share_len, default_max_user_storage, default_pending_watermark,
+    default_validation_timeout, default_max_work_payloads,
+    STORAGE_SCHEMA_VERSION, STATE, CONFIG, STATS, ADMIN_RECOVERY, ROOT_HISTORY, CID_OWNERS, USER_KEYS, PREVIOUS_COMMITMENTS, PENDING_VALIDATIONS, STATE_COMMITMENTS, TRANSFERS, LEAF_OWNERS, NODE_METADATA, NODE_WORK,
+    VALIDATION_OUTCOMES, VOTE_PRESENCE, OBSERVERS,
+    LegacyState, LEGACY_STATE, LEGACY_STATE_COMMITMENTS, LEGACY_STATE_COMMITMENTS_V2, LegacyContentVote, LEGACY_EQUIVOCATIONS, LEGACY_PAUSE_LEDGER, LEGACY_CONFIG, ChallengeBond, CHALLENGE_BONDS, SUBSCRIPTION_FEES, KeyRotation, USER_KEY_NONCES, KEY_ROTATIONS, USER_OUTCOMES, USER_PENDING, PURGE_NONCES, FINALIZED_BY, FINALIZED_CHALLENGES,
+    COMMITMENT_ORDER, COMMITMENT_SLOTS, COMMITMENT_SEQ,
 };
 
+/// Storage layout this code reads and writes. Bump it with every change to a
+/// stored type and add the matching step to `migrate`.
+pub(crate) const CURRENT_SCHEMA_VERSION: u16 = 12;
+
+/// Single-node committees are only allowed for development deployments
+pub(crate) const MIN_PRODUCTION_THRESHOLD: u32 = 2;
+
+/// Root history entries removed per finalization at most, so pruning cost
+/// stays constant however large the backlog is
+const PRUNE_PER_FINALIZATION: u64 = 2;
+
+/// Blocks after a cancellation during which an identical resubmission
+/// inherits opted-in nodes' votes
+const CARRYOVER_WINDOW: u64 = 1_000;
+
+/// Finalizer records kept per user; older nonces are pruned
+const MAX_FINALIZER_HISTORY: u64 = 64;
+
+/// Replaced commitments kept per user for GetCommitmentAtHeight
+const MAX_COMMITMENT_HISTORY: u64 = 64;
+
+/// Largest coordinator_metadata blob a submission may attach
+const MAX_COORDINATOR_METADATA: usize = 1024;
+
+/// Dependencies one transition may declare, and how far a chain of pending
+/// dependencies may reach back from it
+const MAX_DEPENDENCIES: usize = 8;
+const MAX_DEPENDENCY_DEPTH: usize = 8;
+
+/// Validations that may depend on one validation, not counting those that
+/// failed, so settling it releases or unwinds a bounded number
+const MAX_DEPENDENTS: usize = 8;
+
+/// Pause windows kept individually; older ones are merged pairwise
+const MAX_PAUSE_WINDOWS: usize = 16;
+
+/// Share bytes a vote verifies per effort unit, on top of one unit per vote
+const EFFORT_BYTES_PER_UNIT: u64 = 256;
+
+/// Most votes a single ValidateBatch may carry
+const MAX_BATCH_VOTES: usize = 50;
+
+/// Most recipients a single batch transfer may fan out to
+const MAX_BATCH_RECIPIENTS: usize = 50;
+
+/// Conditional transfers lock on a SHA-256 digest
+const HASHLOCK_SIZE: usize = 32;
+const MAX_PREIMAGE_SIZE: usize = 256;
+
+/// Leaf indices are derived from a 64-bit digest prefix
+const MAX_TREE_DEPTH: u8 = 64;
+
+const MAX_GIT_COMMIT_LEN: usize = 64;
+const MAX_BINARY_HASH_LEN: usize = 64;
+/// Blocks a challenged transition has to prove its data is available
+const DA_RESPONSE_WINDOW: u64 = 100;
+
+/// Old state root of a user with no commitment yet
+const EMPTY_USER_ROOT: [u8; 32] = [0; 32];
+
+/// Longest IPFS gateway hint accepted on a transition
+const MAX_GATEWAY_HINT_LEN: usize = 128;
+
+/// Stubs listed after the full items of a GetPendingForNode page
+const MAX_WORK_STUBS: usize = 100;
+
+/// Pending validations listed per GetAccountOverview
+const MAX_OVERVIEW_PENDING: usize = 20;
+
+/// Key changes kept per user
+const MAX_KEY_ROTATIONS: usize = 8;
+
+/// Length of a nonce commitment: the x coordinate of a secp256k1 point
+const NONCE_COMMITMENT_LEN: usize = 32;
+
+/// Blocks after the nonce round closes before a committer that has not
+/// voted can be replaced by the next node to commit
+const NONCE_ROUND_TIMEOUT: u64 = 100;
+
+/// Blocks BackfillCommitment stays open after migrating to schema 3 (about a
+/// week at 6s blocks)
+const BACKFILL_WINDOW: u64 = 100_800;
+
+/// Cosigns a node needs to report another node's key compromised
+const COMPROMISE_COSIGNERS: usize = 2;
+
+/// Subscribers one validation may notify, and the gas each notification may
+/// burn; a failing or exhausted subscriber is replied to and ignored
+const MAX_SUBSCRIBERS: usize = 4;
+const SUBSCRIBER_GAS_LIMIT: u64 = 200_000;
+const SUBSCRIBER_REPLY_ID: u64 = 1;
+
+/// Reply ids above this carry an outbound send id; those below are fixed ids
+/// such as SUBSCRIBER_REPLY_ID
+const OUTBOUND_REPLY_BASE: u64 = 1 << 32;
+
+/// Sends RetryOutbound may dispatch per recipient; past it only
+/// RedirectOutbound can release the funds
+const MAX_SEND_ATTEMPTS: u32 = 5;
+
+/// Failed sends parked at once. Another failure past it fails the
+/// transaction that dispatched the send, until parked ones are released.
+const MAX_OUTBOUND_FAILURES: usize = 200;
+
+/// Failed sends or receipts listed per page
+const MAX_SEND_PAGE: u32 = 50;
+
+/// Threshold keys listed per page
+const MAX_EPOCH_PAGE: u32 = 50;
+
+/// Stored records a migration dry run decodes per page
+const MAX_MIGRATION_PAGE: u32 = 500;
+
+/// Content votes remembered per node for equivocation checks, and
+/// equivocations listed per node; the oldest go first
+const MAX_CONTENT_VOTES: usize = 32;
+const MAX_EQUIVOCATIONS: usize = 16;
+
+/// Upper bound on leaves accepted by the ComputeRoot query
+const MAX_ROOT_LEAVES: usize = 1024;
+
+/// Largest serialized GetAuditBundle response; bigger bundles are refused
+const MAX_AUDIT_BUNDLE_LEN: usize = 32 * 1024;
+
+/// Accounts one PurgeStaleAccounts call removes at most
+const MAX_ACCOUNT_PURGES: u32 = 20;
+
+/// Commitments per ExportSnapshot page by default, and at most
+const DEFAULT_SNAPSHOT_LIMIT: u32 = 30;
+const MAX_SNAPSHOT_LIMIT: u32 = 100;
+
+/// Export slots one ExportSnapshot page reads at most, emptied ones included
+const MAX_SNAPSHOT_SCAN: u64 = 500;
+
 // ============================================================================
 // INSTANTIATE
 // ============================================================================
@@ -18,22 +183,164 @@ use crate::state::{
 #[entry_point]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
-    _info: MessageInfo,
+    env: Env,
+    info: MessageInfo,
     msg: InstantiateMsg,
 ) -> StdResult<Response> {
-    let state = State {
+    // A zero threshold would let any single vote (even an invalid one) finalize
+    if msg.threshold == 0 {
+        return Err(StdError::generic_err("Threshold must be at least 1"));
+    }
+    if msg.threshold < MIN_PRODUCTION_THRESHOLD && !msg.dev_mode {
+        return Err(StdError::generic_err(format!(
+            "Threshold below {} requires dev_mode",
+            MIN_PRODUCTION_THRESHOLD
+        )));
+    }
+
+    let mut state = State {
         mpc_nodes: vec![],
         threshold: msg.threshold,
-        current_state_root: vec![0; 32],  // Genesis root
+        current_state_root: genesis_root(&msg.deployment_salt),
         block_height: 0,
+        last_validation_id: String::new(),
+        last_threshold_signature: vec![],
+        last_signers: vec![],
+        last_signed_ids: vec![],
+        epoch: 0,
+        last_protocol_version: default_protocol_version(),
+        oldest_retained_height: 0,
+        oldest_partials_height: 0,
+        pending_committee: true,
+        deployment_salt: msg.deployment_salt.clone(),
+        key_epoch: 0,
+        last_epoch: 0,
+        last_asset_digest: vec![],
+        backfill_until: None,
     };
 
+    // One-shot bootstrap: each node gets RegisterMPCNode's checks, and the
+    // committee as a whole must be able to reach threshold
+    for node in &msg.initial_nodes {
+        let address = deps.api.addr_validate(&node.address)?;
+        if state.mpc_nodes.iter().any(|n| n.address == address) {
+            return Err(StdError::generic_err(format!("Duplicate initial node {}", address)));
+        }
+        let public_key = normalize_public_key(&node.public_key)?;
+        let key_type = key_type(&public_key);
+        if let Some(declared) = node.key_type.filter(|declared| *declared != key_type) {
+            return Err(StdError::generic_err(format!("Key of initial node {} is not {:?}", address, declared)));
+        }
+        add_node(&mut state, address.as_str(), public_key)?;
+    }
+    if !msg.initial_nodes.is_empty() && !mark_committee_ready(&mut state) {
+        return Err(StdError::generic_err(format!(
+            "Initial committee of {} cannot meet threshold {}",
+            msg.initial_nodes.len(), msg.threshold
+        )));
+    }
+
     STATE.save(deps.storage, &state)?;
+    DEPLOYMENT.save(deps.storage, &DeploymentIdentity {
+        chain_id: env.block.chain_id.clone(),
+        contract_address: env.contract.address.to_string(),
+        genesis_time: env.block.time.seconds(),
+    })?;
+
+    let tree_depth = msg.tree_depth.unwrap_or_else(default_tree_depth);
+    if tree_depth == 0 || tree_depth > MAX_TREE_DEPTH {
+        return Err(StdError::generic_err(format!("Tree depth must be between 1 and {}", MAX_TREE_DEPTH)));
+    }
+
+    // Recovery must need more than a bare majority of the committee
+    let recovery_supermajority = msg.recovery_supermajority.unwrap_or_else(default_recovery_supermajority);
+    if recovery_supermajority <= 50 || recovery_supermajority > 100 {
+        return Err(StdError::generic_err("Recovery supermajority must be between 51 and 100 percent"));
+    }
+
+    let config = Config {
+        supported_protocol_versions: msg.supported_protocol_versions
+            .unwrap_or_else(|| vec![CURRENT_PROTOCOL_VERSION]),
+        tree_depth,
+        recovery_supermajority,
+        recovery_delay: msg.recovery_delay.unwrap_or_else(default_recovery_delay),
+        accept_submissions_at: msg.accept_submissions_at,
+        ..default_config(info.sender.to_string(), msg.dev_mode)
+    };
+
+    CONFIG.save(deps.storage, &config)?;
+    STATS.save(deps.storage, &Stats::default())?;
+    STORAGE_SCHEMA_VERSION.save(deps.storage, &CURRENT_SCHEMA_VERSION)?;
+
+    // Seed viewing key generation; users add their own entropy on creation
+    ViewingKey::set_seed(deps.storage, &viewing_key_seed(&env, info.sender.as_str()));
 
     Ok(Response::new()
         .add_attribute("action", "instantiate")
-        .add_attribute("threshold", msg.threshold.to_string()))
+        .add_attribute("threshold", msg.threshold.to_string())
+        .add_attribute("dev_mode", config.dev_mode.to_string())
+        .add_attribute("pending_committee", state.pending_committee.to_string())
+        .add_attribute("initial_nodes", state.mpc_nodes.len().to_string())
+        .add_attribute("owner", config.owner))
+}
+
+/// Config an instance starts from before InstantiateMsg's overrides; also
+/// what the schema 0 migration seeds for instances that never had one
+fn default_config(owner: String, dev_mode: bool) -> Config {
+    Config {
+        dev_mode,
+        owner,
+        pending_owner: None,
+        supported_protocol_versions: vec![CURRENT_PROTOCOL_VERSION],
+        tree_depth: default_tree_depth(),
+        attestation_window: default_attestation_window(),
+        unanimous_operations: vec![],
+        event_verbosity: EventVerbosity::default(),
+        recovery_supermajority: default_recovery_supermajority(),
+        recovery_delay: default_recovery_delay(),
+        root_history_retention: default_root_history_retention(),
+        unique_cids: false,
+        validation_cooldown: 0,
+        strict_root_agreement: false,
+        challenge_window: 0,
+        commitment_confirmations: 0,
+        vote_precedence: VotePrecedence::RejectWins,
+        reward_policy: RewardPolicy::Equal,
+        reject_threshold: 0,
+        retain_partials: RetainPartials::None,
+        partial_retention: default_partial_retention(),
+        max_transition_size: default_max_transition_size(),
+        max_share_len: default_max_share_len(),
+        max_user_storage: default_max_user_storage(),
+        pending_watermark: default_pending_watermark(),
+        validation_timeout: default_validation_timeout(),
+        accept_submissions_at: None,
+        require_nonempty_merkle_proof: false,
+        repoint_cosign: false,
+        max_work_payloads: default_max_work_payloads(),
+        require_nonce_commitments: false,
+        registration_cooldown: 0,
+        registration_window: 0,
+        max_registrations_per_window: 0,
+        breaker_window: 0,
+        breaker_max_rejections: 0,
+        subscription_fee: None,
+        equivocation_limit: 0,
+        expiry_bounty: None,
+        finalizer_window: 0,
+        account_ttl_seconds: 0,
+        max_validation_blocks: 0,
+        da_challenge_bond: None,
+        outbound_failure_watermark: 0,
+    }
+}
+
+fn viewing_key_seed(env: &Env, sender: &str) -> Vec<u8> {
+    let mut seed = Sha256::new();
+    seed.update(env.contract.address.as_bytes());
+    seed.update(env.block.time.nanos().to_le_bytes());
+    seed.update(sender.as_bytes());
+    seed.finalize().to_vec()
 }
 
 // ============================================================================
@@ -42,6 +349,19 @@ pub fn instantiate(
 
 #[entry_point]
 pub fn execute(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> StdResult<Response> {
+    require_current_schema(deps.storage)?;
+
+    let settler = info.sender.to_string();
+    let response = dispatch(deps.branch(), env, info, msg)?;
+    pay_settlements(deps.storage, &settler, response)
+}
+
+fn dispatch(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
@@ -49,441 +369,12133 @@ pub fn execute(
 ) -> StdResult<Response> {
     match msg {
         ExecuteMsg::RegisterMPCNode { public_key } =>
-            register_mpc_node(deps, info, public_key),
+            register_mpc_node(deps, env, info, public_key),
         ExecuteMsg::SubmitStateTransition { transition } =>
             submit_state_transition(deps, env, info, transition),
-        ExecuteMsg::ValidateTransition { validation_id, valid, partial_signature } =>
-            validate_transition(deps, env, info, validation_id, valid, partial_signature),
+        ExecuteMsg::CommitNonce { validation_id, nonce_commitment } =>
+            commit_nonce(deps, env, info, validation_id, nonce_commitment),
+        ExecuteMsg::SubmitPartial { validation_id, partial_signature, attested_root } =>
+            validate_transition(deps, env, info, validation_id, true, partial_signature, attested_root),
+        ExecuteMsg::ValidateTransition { validation_id, valid, partial_signature, attested_root } =>
+            validate_transition(deps, env, info, validation_id, valid, partial_signature, attested_root),
+        ExecuteMsg::ValidateBatch { votes, max_items, cursor } =>
+            validate_batch(deps, env, info, votes, max_items, cursor),
+        ExecuteMsg::CancelTransition { validation_id } =>
+            cancel_transition(deps, env, info, validation_id),
+        ExecuteMsg::PurgeMyHistory { keep_current, nonce, signature } =>
+            purge_my_history(deps, env, info, keep_current, nonce, signature),
+        ExecuteMsg::SetUserKey { public_key, nonce, signature } =>
+            set_user_key(deps, env, info, public_key, nonce, signature),
+        ExecuteMsg::RepointStorage { new_ipfs_cid, content_hash, signature, cosign } =>
+            repoint_storage(deps, env, info, new_ipfs_cid, content_hash, signature, cosign),
+        ExecuteMsg::SetNodesActive { node_ids, active } =>
+            set_nodes_active(deps, env, info, node_ids, active),
+        ExecuteMsg::UpdateNodeMetadata { auto_carryover_votes } =>
+            update_node_metadata(deps, info, auto_carryover_votes),
         ExecuteMsg::FinalizeTransition { validation_id } =>
-            finalize_transition(deps, env, validation_id),
+            finalize_transition(deps, env, info, validation_id),
         ExecuteMsg::SubmitTransfer { transfer } =>
             submit_transfer(deps, env, info, transfer),
+        ExecuteMsg::UpdateTransferCids { sender_validation_id, recipient_validation_id, sender_ipfs, recipient_ipfs } =>
+            update_transfer_cids(deps, info, sender_validation_id, recipient_validation_id, sender_ipfs, recipient_ipfs),
+        ExecuteMsg::SubmitConditionalTransfer { transfer, hashlock, deadline } =>
+            submit_conditional_transfer(deps, env, info, transfer, hashlock, deadline),
+        ExecuteMsg::SubmitBatchTransfer { sender_transition, recipients } =>
+            submit_batch_transfer(deps, env, info, sender_transition, recipients),
+        ExecuteMsg::RevealPreimage { transfer_id, preimage } =>
+            reveal_preimage(deps, env, transfer_id, preimage),
+        ExecuteMsg::ResolveTransfer { transfer_id } =>
+            resolve_transfer(deps, env, info, transfer_id),
+        ExecuteMsg::AttestBuild { git_commit, binary_hash, signature } =>
+            attest_build(deps, env, info, git_commit, binary_hash, signature),
+        ExecuteMsg::CreateViewingKey { entropy } =>
+            create_viewing_key(deps, env, info, entropy),
+        ExecuteMsg::SetViewingKey { key } =>
+            set_viewing_key(deps, info, key),
+        ExecuteMsg::SetObserver { address, authorized } =>
+            set_observer(deps, info, address, authorized),
+        ExecuteMsg::UpdateConfig(update) =>
+            update_config(deps, info, update),
+        ExecuteMsg::TransferOwnership { new_owner } =>
+            transfer_ownership(deps, info, new_owner),
+        ExecuteMsg::AcceptOwnership {} =>
+            accept_ownership(deps, info),
+        ExecuteMsg::StageNextEpochKey { public_key, proof_of_possession } =>
+            stage_next_epoch_key(deps, env, info, public_key, proof_of_possession),
+        ExecuteMsg::AdvanceEpoch {} =>
+            advance_epoch(deps, env, info),
+        ExecuteMsg::RotateThresholdKey { public_key } =>
+            rotate_threshold_key(deps, env, info, public_key),
+        ExecuteMsg::Pause {} =>
+            pause(deps, env, info),
+        ExecuteMsg::Unpause {} =>
+            unpause(deps, env, info),
+        ExecuteMsg::SlashNode { evidence } =>
+            slash_node(deps, env, info, evidence),
+        ExecuteMsg::ReportKeyCompromise { node_id, signature_by_compromised_key, cosigns } =>
+            report_key_compromise(deps, env, info, node_id, signature_by_compromised_key, cosigns),
+        ExecuteMsg::DeregisterMPCNode { signature } =>
+            deregister_mpc_node(deps, env, info, signature),
+        ExecuteMsg::ChallengeDataAvailability { validation_id } =>
+            challenge_data_availability(deps, env, info, validation_id),
+        ExecuteMsg::ProveAvailability { validation_id, sample } =>
+            prove_availability(deps, env, validation_id, sample),
+        ExecuteMsg::ExpireDataChallenge { validation_id } =>
+            expire_data_challenge(deps, env, validation_id),
+        ExecuteMsg::RequestRevalidation { validation_id, node_id } =>
+            request_revalidation(deps, info, validation_id, node_id),
+        ExecuteMsg::SetOperator { operator } =>
+            set_operator(deps, info, operator),
+        ExecuteMsg::ChallengeFinalized { user_address, reason } =>
+            challenge_finalized(deps, env, info, user_address, reason),
+        ExecuteMsg::ProposeAdminRecovery { new_admin } =>
+            propose_admin_recovery(deps, env, info, new_admin),
+        ExecuteMsg::ApproveAdminRecovery {} =>
+            approve_admin_recovery(deps, env, info),
+        ExecuteMsg::VetoAdminRecovery {} =>
+            veto_admin_recovery(deps, info),
+        ExecuteMsg::ExecuteAdminRecovery {} =>
+            execute_admin_recovery(deps, env),
+        ExecuteMsg::BackfillCommitment { user_address, metadata } =>
+            backfill_commitment(deps, env, info, user_address, metadata),
+        ExecuteMsg::PurgeStaleAccounts { limit } =>
+            purge_stale_accounts(deps, env, limit),
+        ExecuteMsg::ExpireValidation { validation_id } =>
+            expire_validation(deps, env, info, validation_id),
+        ExecuteMsg::SubscribeValidation { validation_id, subscriber, code_hash } =>
+            subscribe_validation(deps, info, validation_id, subscriber, code_hash),
+        ExecuteMsg::RetryOutbound { id } =>
+            retry_outbound(deps, id),
+        ExecuteMsg::RedirectOutbound { id, new_recipient } =>
+            redirect_outbound(deps, info, id, new_recipient),
     }
 }
 
-fn register_mpc_node(
-    deps: DepsMut,
-    info: MessageInfo,
-    public_key: Vec<u8>,
-) -> StdResult<Response> {
-    let mut state = STATE.load(deps.storage)?;
+/// Refunds the bounties, returns the challenge bonds and notifies the
+/// subscribers of every validation this message settled. Each notification replies only on error and the reply
+/// swallows it, so a subscriber can never revert the settlement that
+/// notified it; refunds go out through `send_bounty` for the same reason.
+/// The subscription fees go to `settler`, whose transaction runs the callbacks.
+fn pay_settlements(storage: &mut dyn Storage, settler: &str, response: Response) -> StdResult<Response> {
+    let settled = match SETTLED_VALIDATIONS.may_load(storage)? {
+        Some(settled) => settled,
+        None => return Ok(response),
+    };
+    SETTLED_VALIDATIONS.remove(storage);
 
-    // Check if node from this address is already registered
-    let existing_idx = state.mpc_nodes.iter().position(|n| n.address == info.sender.to_string());
+    let mut messages = Vec::new();
+    for SettledValidation { validation_id, subscribers, refund } in settled {
+        if let Some((recipient, amount)) = refund {
+            messages.push(send_bounty(storage, recipient, amount, &validation_id)?);
+        }
+        if let Some(bond) = CHALLENGE_BONDS.get(storage, &validation_id) {
+            CHALLENGE_BONDS.remove(storage, &validation_id)?;
+            messages.push(send_bounty(storage, bond.challenger, bond.amount, &bond_origin(&validation_id))?);
+        }
+        if subscribers.is_empty() {
+            continue;
+        }
+        let fees = SUBSCRIPTION_FEES.get(storage, &validation_id).unwrap_or_default();
+        SUBSCRIPTION_FEES.remove(storage, &validation_id)?;
+        for (subscriber, fee) in subscribers.iter().zip(fees) {
+            let origin = subscription_origin(&validation_id, &subscriber.address);
+            messages.push(send_bounty(storage, settler.to_string(), fee, &origin)?);
+        }
+        // Loaded now rather than at settlement so a precedence noted after
+        // record_outcome is included
+        let outcome = VALIDATION_OUTCOMES.get(storage, &validation_id)
+            .ok_or_else(|| StdError::generic_err("Settled validation has no outcome"))?;
+        let msg = to_binary(&SubscriberMsg::ValidationSettled { outcome })?;
+        for subscriber in subscribers {
+            let execute = WasmMsg::Execute {
+                contract_addr: subscriber.address,
+                code_hash: subscriber.code_hash,
+                msg: msg.clone(),
+                funds: vec![],
+            };
+            messages.push(SubMsg::reply_on_error(execute, SUBSCRIBER_REPLY_ID).with_gas_limit(SUBSCRIBER_GAS_LIMIT));
+        }
+    }
+    Ok(response.add_submessages(messages))
+}
 
-    if let Some(idx) = existing_idx {
-        // Update existing node's public key
-        let node_id = state.mpc_nodes[idx].node_id;
-        state.mpc_nodes[idx].public_key = public_key;
-        state.mpc_nodes[idx].active = true;
+/// Bank send of a bounty under a fresh outbound id. A failed send must not
+/// revert the settlement that owes it, so it replies; the reply also comes on
+/// success, which is what clears the in-flight entry.
+fn send_bounty(storage: &mut dyn Storage, recipient: String, amount: Coin, origin: &str) -> StdResult<SubMsg> {
+    let id = OUTBOUND_SEQ.may_load(storage)?.unwrap_or(0) + 1;
+    OUTBOUND_SEQ.save(storage, &id)?;
+    WITHDRAWAL_RECEIPTS.insert(storage, &origin.to_string(), &WithdrawalReceipt {
+        validation_id: origin.to_string(),
+        recipient: recipient.clone(),
+        amount: amount.clone(),
+        send_id: id,
+        status: SendStatus::InFlight,
+        error: None,
+    })?;
+    RECIPIENT_RECEIPTS.add_suffix(recipient.as_bytes()).insert(storage, &origin.to_string())?;
+    dispatch_send(storage, id, OutboundSend {
+        recipient,
+        amount,
+        origin: origin.to_string(),
+        attempts: 0,
+        last_error: None,
+    })
+}
 
-        STATE.save(deps.storage, &state)?;
+fn dispatch_send(storage: &mut dyn Storage, id: u64, mut send: OutboundSend) -> StdResult<SubMsg> {
+    send.attempts += 1;
+    let msg = BankMsg::Send { to_address: send.recipient.clone(), amount: vec![send.amount.clone()] };
+    update_receipt(storage, &send, SendStatus::InFlight)?;
+    OUTBOUND_IN_FLIGHT.insert(storage, &id, &send)?;
+    Ok(SubMsg::reply_always(msg, OUTBOUND_REPLY_BASE + id))
+}
 
-        return Ok(Response::new()
-            .add_attribute("action", "register_mpc_node")
-            .add_attribute("node_id", node_id.to_string())
-            .add_attribute("address", info.sender.to_string())
-            .add_attribute("updated", "true"));
+/// Brings the receipt of `send`'s validation up to date, re-indexing it if
+/// the send was redirected
+fn update_receipt(storage: &mut dyn Storage, send: &OutboundSend, status: SendStatus) -> StdResult<()> {
+    let mut receipt = match WITHDRAWAL_RECEIPTS.get(storage, &send.origin) {
+        Some(receipt) => receipt,
+        None => return Ok(()),
+    };
+    if receipt.recipient != send.recipient {
+        let previous = RECIPIENT_RECEIPTS.add_suffix(receipt.recipient.as_bytes());
+        if previous.contains(storage, &send.origin) {
+            previous.remove(storage, &send.origin)?;
+        }
+        RECIPIENT_RECEIPTS.add_suffix(send.recipient.as_bytes()).insert(storage, &send.origin)?;
+        receipt.recipient = send.recipient.clone();
     }
+    receipt.status = status;
+    receipt.error = send.last_error.clone();
+    WITHDRAWAL_RECEIPTS.insert(storage, &send.origin, &receipt)
+}
 
-    // New registration
-    let node_id = state.mpc_nodes.len() as u32 + 1;
-
-    state.mpc_nodes.push(MPCNode {
-        address: info.sender.to_string(),
-        node_id,
-        public_key,
-        active: true,
-    });
+#[entry_point]
+pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
+    match (msg.id, msg.result) {
+        (SUBSCRIBER_REPLY_ID, SubMsgResult::Err(error)) => Ok(Response::new()
+            .add_attribute("action", "subscriber_failed")
+            .add_attribute("error", error)),
+        (id, result) if id > OUTBOUND_REPLY_BASE => settle_send(deps.storage, id - OUTBOUND_REPLY_BASE, result),
+        (id, _) => Err(StdError::generic_err(format!("Unexpected reply {}", id))),
+    }
+}
 
-    STATE.save(deps.storage, &state)?;
+/// A failed send keeps its funds in the contract, parked under its id in
+/// OUTBOUND_FAILURES
+fn settle_send(storage: &mut dyn Storage, id: u64, result: SubMsgResult) -> StdResult<Response> {
+    let mut send = OUTBOUND_IN_FLIGHT.get(storage, &id)
+        .ok_or_else(|| StdError::generic_err(format!("No outbound send {} in flight", id)))?;
+    OUTBOUND_IN_FLIGHT.remove(storage, &id)?;
 
-    Ok(Response::new()
-        .add_attribute("action", "register_mpc_node")
-        .add_attribute("node_id", node_id.to_string())
-        .add_attribute("address", info.sender.to_string()))
+    let response = Response::new()
+        .add_attribute("send_id", id.to_string())
+        .add_attribute("origin", send.origin.clone());
+    match result {
+        SubMsgResult::Ok(_) => {
+            update_receipt(storage, &send, SendStatus::Delivered)?;
+            Ok(response.add_attribute("action", "outbound_delivered"))
+        }
+        SubMsgResult::Err(error) => {
+            send.last_error = Some(error.clone());
+            update_receipt(storage, &send, SendStatus::Failed)?;
+            park_failure(storage, id, &send)?;
+            Ok(response
+                .add_attribute("action", "outbound_failed")
+                .add_attribute("error", error))
+        }
+    }
 }
 
-fn submit_state_transition(
-    deps: DepsMut,
-    env: Env,
-    _info: MessageInfo,
-    transition: StateTransition,
-) -> StdResult<Response> {
-    let _state = STATE.load(deps.storage)?;
+fn park_failure(storage: &mut dyn Storage, id: u64, send: &OutboundSend) -> StdResult<()> {
+    let mut ids = OUTBOUND_FAILURE_IDS.may_load(storage)?.unwrap_or_default();
+    if ids.len() >= MAX_OUTBOUND_FAILURES {
+        return Err(StdError::generic_err(format!(
+            "{} failed sends are parked; retry or redirect them first",
+            ids.len()
+        )));
+    }
+    if let Err(position) = ids.binary_search(&id) {
+        ids.insert(position, id);
+    }
+    OUTBOUND_FAILURE_IDS.save(storage, &ids)?;
+    OUTBOUND_FAILURES.insert(storage, &id, send)
+}
 
-    // 1. Verify user signature
-    if !verify_user_signature(&transition) {
-        return Err(StdError::generic_err("Invalid user signature"));
+fn unpark_failure(storage: &mut dyn Storage, id: u64) -> StdResult<()> {
+    let mut ids = OUTBOUND_FAILURE_IDS.may_load(storage)?.unwrap_or_default();
+    if let Ok(position) = ids.binary_search(&id) {
+        ids.remove(position);
     }
+    OUTBOUND_FAILURE_IDS.save(storage, &ids)?;
+    OUTBOUND_FAILURES.remove(storage, &id)
+}
 
-    // 2. Create pending validation
-    let validation_id = format!("{}-{}", env.block.height, transition.user_address);
+fn retry_outbound(deps: DepsMut, id: u64) -> StdResult<Response> {
+    let send = OUTBOUND_FAILURES.get(deps.storage, &id)
+        .ok_or_else(|| StdError::generic_err(format!("No failed send {}", id)))?;
+    if send.attempts >= MAX_SEND_ATTEMPTS {
+        return Err(StdError::generic_err(format!(
+            "Send {} failed {} times; only RedirectOutbound can release it",
+            id, send.attempts
+        )));
+    }
+    unpark_failure(deps.storage, id)?;
+    let attempt = send.attempts + 1;
 
-    let pending_validation = PendingValidation {
-        validation_id: validation_id.clone(),
-        transition: transition.clone(),
-        validations: vec![],
-        threshold_reached: false,
-        created_at: env.block.time.seconds(),
-    };
+    Ok(Response::new()
+        .add_attribute("action", "retry_outbound")
+        .add_attribute("send_id", id.to_string())
+        .add_attribute("attempt", attempt.to_string())
+        .add_submessage(dispatch_send(deps.storage, id, send)?))
+}
 
-    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &pending_validation)?;
+fn redirect_outbound(deps: DepsMut, info: MessageInfo, id: u64, new_recipient: String) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    let mut send = OUTBOUND_FAILURES.get(deps.storage, &id)
+        .ok_or_else(|| StdError::generic_err(format!("No failed send {}", id)))?;
+    let new_recipient = deps.api.addr_validate(&new_recipient)?.to_string();
+    unpark_failure(deps.storage, id)?;
 
+    let previous = std::mem::replace(&mut send.recipient, new_recipient.clone());
+    send.attempts = 0;
     Ok(Response::new()
-        .add_attribute("action", "submit_state_transition")
-        .add_attribute("validation_id", validation_id)
-        .add_attribute("user", transition.user_address)
-        .add_attribute("old_root", hex::encode(transition.old_state_root))
-        .add_attribute("new_root", hex::encode(transition.new_state_root)))
+        .add_attribute("action", "redirect_outbound")
+        .add_attribute("send_id", id.to_string())
+        .add_attribute("previous_recipient", previous)
+        .add_attribute("recipient", new_recipient)
+        .add_submessage(dispatch_send(deps.storage, id, send)?))
 }
 
-fn validate_transition(
+fn subscribe_validation(
     deps: DepsMut,
-    _env: Env,
     info: MessageInfo,
     validation_id: String,
-    valid: bool,
-    partial_signature: Vec<u8>,
+    subscriber: String,
+    code_hash: String,
 ) -> StdResult<Response> {
-    let state = STATE.load(deps.storage)?;
-
-    // 1. Verify sender is an MPC node
-    let node = state.mpc_nodes.iter()
-        .find(|n| n.address == info.sender.to_string() && n.active)
-        .ok_or_else(|| StdError::generic_err("Not an active MPC node"))?;
-
-    let node_id = node.node_id; // Copy node_id before moving state
-
-    // 2. Find pending validation
-    let mut validation = PENDING_VALIDATIONS.get(deps.storage, &validation_id)
-        .ok_or_else(|| StdError::generic_err("Validation not found"))?;
+    let config = CONFIG.load(deps.storage)?;
+    let fee = config.subscription_fee
+        .ok_or_else(|| StdError::generic_err("Subscriptions need a configured fee"))?;
+    if !pays_exactly(&info.funds, &fee) {
+        return Err(StdError::generic_err(format!("Subscribing costs exactly {}", fee)));
+    }
 
-    // 3. Check not already validated by this node
-    if validation.validations.iter().any(|v| v.node_id == node_id) {
-        return Err(StdError::generic_err("Already validated"));
+    let mut validation = load_pending(deps.storage, &validation_id)?;
+    if !validation_parties(deps.storage, &validation).contains(&info.sender.to_string()) {
+        return Err(StdError::generic_err("Only the validation's parties may subscribe"));
     }
+    let address = deps.api.addr_validate(&subscriber)?.to_string();
+    if code_hash.len() != 64 || !code_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(StdError::generic_err("Code hash must be 64 hex characters"));
+    }
+    if validation.subscribers.iter().any(|s| s.address == address) {
+        return Err(StdError::generic_err("Already subscribed"));
+    }
+    if validation.subscribers.len() >= MAX_SUBSCRIBERS {
+        return Err(StdError::generic_err(format!("At most {} subscribers per validation", MAX_SUBSCRIBERS)));
+    }
+    validation.subscribers.push(Subscriber { address: address.clone(), code_hash });
+    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+    let mut fees = SUBSCRIPTION_FEES.get(deps.storage, &validation_id).unwrap_or_default();
+    fees.push(fee);
+    SUBSCRIPTION_FEES.insert(deps.storage, &validation_id, &fees)?;
 
-    // 4. Add validation
-    validation.validations.push(NodeValidation {
-        node_id,
-        valid,
-        partial_signature,
-    });
+    Ok(Response::new()
+        .add_attribute("action", "subscribe_validation")
+        .add_attribute("validation_id", validation_id)
+        .add_attribute("subscriber", address))
+}
 
-    // 5. Check if threshold reached - if so, auto-finalize!
-    let valid_count = validation.validations.iter().filter(|v| v.valid).count();
-    let threshold_reached = valid_count >= state.threshold as usize;
+/// The user a validation moves and, for a transfer or batch leg, every
+/// account on the other side of it
+fn validation_parties(storage: &dyn Storage, validation: &PendingValidation) -> Vec<String> {
+    let mut parties = vec![validation.transition.user_address.clone()];
+    if let Some(record) = validation.transfer_id.as_ref().and_then(|id| TRANSFERS.get(storage, id)) {
+        parties.extend([record.sender, record.recipient, record.submitter]);
+    }
+    if let Some(group) = validation.group_id.as_ref().and_then(|id| TRANSFER_GROUPS.get(storage, id)) {
+        parties.push(group.sender);
+        parties.extend(group.legs.into_iter().map(|leg| leg.recipient));
+    }
+    parties
+}
 
-    if threshold_reached {
-        // Auto-finalize: aggregate signatures and update state
-        let threshold_signature = aggregate_signatures(&validation.validations);
+/// Outbound origin of a subscription fee, apart from the validation's bounty
+fn subscription_origin(validation_id: &str, subscriber: &str) -> String {
+    format!("{}/subscription/{}", validation_id, subscriber)
+}
 
-        // Update state root
-        let mut updated_state = state;
-        updated_state.current_state_root = validation.transition.new_state_root.clone();
-        updated_state.block_height += 1;
-        STATE.save(deps.storage, &updated_state)?;
+/// True if `funds` are `price` and nothing else
+fn pays_exactly(funds: &[Coin], price: &Coin) -> bool {
+    matches!(funds, [coin] if coin == price)
+}
 
-        // Store state commitment
-        let commitment = StateCommitment {
-            user_address: validation.transition.user_address.clone(),
-            state_root: validation.transition.new_state_root.clone(),
-            ipfs_cid: validation.transition.new_state_ipfs.clone(),
-            merkle_proof: serialize_merkle_proof(&validation.transition.merkle_proof),
-            nonce: 0,
-            updated_at: _env.block.time.seconds(),
+/// The expiry bounty each of the `validations` a submission creates holds,
+/// once `funds` pay for exactly that many
+fn expiry_bounty(storage: &dyn Storage, funds: &[Coin], validations: usize) -> StdResult<Option<Coin>> {
+    let Some(bounty) = CONFIG.load(storage)?.expiry_bounty else {
+        return match funds {
+            [] => Ok(None),
+            _ => Err(StdError::generic_err("Submitting takes no funds")),
         };
-        STATE_COMMITMENTS.insert(deps.storage, &commitment.user_address, &commitment)?;
-
-        // Remove pending validation
-        PENDING_VALIDATIONS.remove(deps.storage, &validation_id)?;
-
-        return Ok(Response::new()
-            .add_attribute("action", "validate_and_finalize")
-            .add_attribute("node_id", node_id.to_string())
-            .add_attribute("valid", valid.to_string())
-            .add_attribute("threshold_reached", "true")
-            .add_attribute("finalized", "true")
-            .add_attribute("user", validation.transition.user_address)
-            .add_attribute("new_root", hex::encode(updated_state.current_state_root))
-            .add_attribute("block_height", updated_state.block_height.to_string())
-            .add_attribute("threshold_signature", hex::encode(threshold_signature)));
+    };
+    let total = bounty.amount.checked_mul(Uint128::from(validations as u128))?;
+    if !pays_exactly(funds, &Coin { denom: bounty.denom.clone(), amount: total }) {
+        return Err(StdError::generic_err(format!(
+            "Submitting requires a {} expiry bounty per validation, {}{} in all",
+            bounty, total, bounty.denom
+        )));
     }
-
-    // Threshold not reached yet - just save validation
-    validation.threshold_reached = false;
-    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
-
-    Ok(Response::new()
-        .add_attribute("action", "validate_transition")
-        .add_attribute("node_id", node_id.to_string())
-        .add_attribute("valid", valid.to_string())
-        .add_attribute("threshold_reached", "false"))
+    Ok(Some(bounty))
 }
 
-fn finalize_transition(
+/// Settles a validation past validation_expires_at or its block window as Expired, unwinding
+/// its transfer or batch like a rejection, and pays its bounty to the caller
+fn expire_validation(
     deps: DepsMut,
     env: Env,
+    info: MessageInfo,
     validation_id: String,
 ) -> StdResult<Response> {
-    let mut state = STATE.load(deps.storage)?;
-
-    // 1. Find validation
-    let validation = PENDING_VALIDATIONS.get(deps.storage, &validation_id)
-        .ok_or_else(|| StdError::generic_err("Validation not found"))?;
-
-    // 2. Verify threshold reached
-    if !validation.threshold_reached {
-        return Err(StdError::generic_err("Threshold not reached"));
+    let mut validation = load_pending(deps.storage, &validation_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    if !validation_expired(deps.storage, &config, &validation, &env)? {
+        let expires_at = validation_expires_at(deps.storage, &validation, env.block.time.seconds())?;
+        return Err(StdError::generic_err(format!("Validation expires at {}", expires_at)));
     }
 
-    // 3. Aggregate TSS signatures
-    let threshold_signature = aggregate_signatures(&validation.validations);
+    // Taken off first so clear_pending does not refund it to the submitter
+    let bounty = validation.bounty.take();
+    fail_validation(deps.storage, &env, &validation, OutcomeStatus::Expired)?;
 
-    // 4. Update state root (THIS IS THE KEY!)
-    // The new state root becomes part of the global Merkle tree
-    state.current_state_root = validation.transition.new_state_root.clone();
-    state.block_height += 1;
+    let mut response = Response::new()
+        .add_attribute("action", "expire_validation")
+        .add_attribute("validation_id", validation_id.clone())
+        .add_attribute("outcome", OutcomeStatus::Expired.as_str());
+    if let Some(bounty) = bounty {
+        response = response
+            .add_attribute("bounty", bounty.to_string())
+            .add_submessage(send_bounty(deps.storage, info.sender.to_string(), bounty, &validation_id)?);
+    }
+    Ok(response)
+}
 
-    STATE.save(deps.storage, &state)?;
+/// Forgets accounts that registered a key, never submitted, and have been
+/// idle for config.account_ttl_seconds. A purged user may register again.
+fn purge_stale_accounts(deps: DepsMut, env: Env, limit: Option<u32>) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if config.account_ttl_seconds == 0 {
+        return Err(StdError::generic_err("Account purging is disabled"));
+    }
+    let limit = limit.unwrap_or(MAX_ACCOUNT_PURGES).min(MAX_ACCOUNT_PURGES) as usize;
+    let now = env.block.time.seconds();
 
-    // 5. Store state commitment
-    let commitment = StateCommitment {
-        user_address: validation.transition.user_address.clone(),
-        state_root: validation.transition.new_state_root.clone(),
-        ipfs_cid: validation.transition.new_state_ipfs.clone(),
-        merkle_proof: serialize_merkle_proof(&validation.transition.merkle_proof),
-        nonce: 0,  // Would extract from validated shares
-        updated_at: env.block.time.seconds(),
-    };
+    let mut stale = Vec::new();
+    for item in ACCOUNT_REGISTRATIONS.iter(deps.storage)? {
+        let (user_address, registered_at) = item?;
+        if registered_at.saturating_add(config.account_ttl_seconds) <= now {
+            stale.push(user_address);
+            if stale.len() == limit {
+                break;
+            }
+        }
+    }
 
-    STATE_COMMITMENTS.insert(
-        deps.storage,
-        &commitment.user_address,
-        &commitment
-    )?;
+    let mut events = Vec::with_capacity(stale.len());
+    for user_address in stale {
+        ACCOUNT_REGISTRATIONS.remove(deps.storage, &user_address)?;
+        USER_KEYS.remove(deps.storage, &user_address)?;
+        KEY_ROTATIONS.remove(deps.storage, &user_address)?;
+        // The key nonce stays, so a signature from before the purge cannot
+        // register its key again
 
-    // 6. Remove pending validation
-    PENDING_VALIDATIONS.remove(deps.storage, &validation_id)?;
+        // A leaf is only reserved at first finalization, but one left behind
+        // by a reverted first commitment is freed with the account
+        let index = leaf_index(&user_address, config.tree_depth);
+        let freed = LEAF_OWNERS.get(deps.storage, &index).as_deref() == Some(user_address.as_str());
+        if freed {
+            LEAF_OWNERS.remove(deps.storage, &index)?;
+        }
+        events.push(Event::new("account_purged")
+            .add_attribute("user", user_address)
+            .add_attribute("leaf_freed", freed.to_string()));
+    }
 
     Ok(Response::new()
-        .add_attribute("action", "finalize_transition")
-        .add_attribute("user", validation.transition.user_address)
-        .add_attribute("new_root", hex::encode(state.current_state_root))
-        .add_attribute("block_height", state.block_height.to_string())
-        .add_attribute("ipfs_cid", validation.transition.new_state_ipfs)
-        .add_attribute("threshold_signature", hex::encode(threshold_signature)))
+        .add_attribute("action", "purge_stale_accounts")
+        .add_attribute("purged", events.len().to_string())
+        .add_events(events))
 }
 
-fn submit_transfer(
-    mut deps: DepsMut,
+fn register_mpc_node(
+    deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    transfer: Transfer,
+    public_key: Vec<u8>,
 ) -> StdResult<Response> {
-    // Submit both sender and recipient transitions
-    // In production, these would be linked atomically
+    let mut state = STATE.load(deps.storage)?;
+    let public_key = normalize_public_key(&public_key)?;
+    if COMPROMISED_KEYS.contains(deps.storage, &public_key) {
+        return Err(StdError::generic_err("Key was reported compromised"));
+    }
+    throttle_registration(deps.storage, info.sender.as_str(), env.block.height)?;
 
-    let response1 = submit_state_transition(
-        deps.branch(),
-        env.clone(),
-        info.clone(),
-        transfer.sender_transition,
-    )?;
+    // Check if node from this address is already registered
+    let existing_idx = state.mpc_nodes.iter().position(|n| n.address == info.sender);
 
-    let response2 = submit_state_transition(
-        deps,
-        env,
-        info,
-        transfer.recipient_transition,
-    )?;
+    if let Some(idx) = existing_idx {
+        if state.mpc_nodes[idx].slashed {
+            return Err(StdError::generic_err("Node was slashed"));
+        }
+        // Keys change through StageNextEpochKey, which proves possession.
+        // Active nodes switch at AdvanceEpoch; a node rejoining may take its
+        // staged key now, since rejoining starts an epoch anyway.
+        freeze_epoch(deps.storage, &state)?;
+        let node = &mut state.mpc_nodes[idx];
+        if node.public_key != public_key {
+            if node.active || node.staged_public_key.as_ref() != Some(&public_key) {
+                return Err(StdError::generic_err("Registered nodes change keys with StageNextEpochKey"));
+            }
+            node.staged_public_key = None;
+            node.public_key = public_key;
+        }
+        node.active = true;
+        let node_id = node.node_id;
+        let committee_ready = mark_committee_ready(&mut state);
+        start_epoch(deps.storage, &mut state, env.block.height)?;
+
+        STATE.save(deps.storage, &state)?;
+
+        return Ok(Response::new()
+            .add_attribute("action", "register_mpc_node")
+            .add_attribute("node_id", node_id.to_string())
+            .add_attribute("address", info.sender.to_string())
+            .add_attribute("updated", "true")
+            .add_attributes(committee_ready.then_some(("committee_ready", "true"))));
+    }
+
+    // New registration
+    freeze_epoch(deps.storage, &state)?;
+    let node_id = add_node(&mut state, info.sender.as_str(), public_key)?;
+    let committee_ready = mark_committee_ready(&mut state);
+    start_epoch(deps.storage, &mut state, env.block.height)?;
+
+    STATE.save(deps.storage, &state)?;
 
     Ok(Response::new()
-        .add_attribute("action", "submit_transfer")
-        .add_attribute("sender", transfer.sender)
-        .add_attribute("recipient", transfer.recipient)
-        .add_attribute("amount_commitment", hex::encode(transfer.amount_commitment))
-        .add_attributes(response1.attributes)
-        .add_attributes(response2.attributes))
+        .add_attribute("action", "register_mpc_node")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("address", info.sender.to_string())
+        .add_attributes(committee_ready.then_some(("committee_ready", "true"))))
 }
 
-// ============================================================================
-// QUERY
-// ============================================================================
+/// Refuses registrations that come too fast, per address and overall, so
+/// registering and deregistering cannot be repeated to pick node ids or
+/// committee composition. Counts the registration otherwise.
+fn throttle_registration(storage: &mut dyn Storage, address: &str, height: u64) -> StdResult<()> {
+    let config = CONFIG.load(storage)?;
+    if config.registration_cooldown > 0 {
+        if let Some(last) = REGISTRATION_HEIGHTS.get(storage, &address.to_string()) {
+            if height < last.saturating_add(config.registration_cooldown) {
+                return Err(StdError::generic_err("Registering too frequently"));
+            }
+        }
+    }
+    REGISTRATION_HEIGHTS.insert(storage, &address.to_string(), &height)?;
 
-#[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetState {} => {
+    if config.max_registrations_per_window > 0 {
+        let started_at = height - height % config.registration_window;
+        let mut window = REGISTRATION_WINDOW.may_load(storage)?
+            .filter(|w| w.started_at == started_at)
+            .unwrap_or(RegistrationWindow { started_at, count: 0 });
+        if window.count >= config.max_registrations_per_window {
+            return Err(StdError::generic_err("Registration limit reached for this window"));
+        }
+        window.count += 1;
+        REGISTRATION_WINDOW.save(storage, &window)?;
+    }
+    Ok(())
+}
+
+/// Appends a new active node for `address` with an already normalized key
+fn add_node(state: &mut State, address: &str, public_key: Vec<u8>) -> StdResult<u32> {
+    if state.mpc_nodes.iter().any(|n| n.is_operated_by(address)) {
+        return Err(StdError::generic_err("Address is already another node's operator"));
+    }
+    if !NODE_KEY_TYPES.contains(&key_type(&public_key)) {
+        return Err(StdError::generic_err(format!("Node keys must be one of {:?}", NODE_KEY_TYPES)));
+    }
+    let node_id = u32::try_from(state.mpc_nodes.len()).ok()
+        .and_then(|len| len.checked_add(1))
+        .ok_or_else(|| StdError::generic_err("Node registry full"))?;
+
+    state.mpc_nodes.push(MPCNode {
+        address: address.to_string(),
+        node_id,
+        public_key,
+        active: true,
+        operator: None,
+        last_active_height: None,
+        staged_public_key: None,
+        slashed: false,
+        offenses: 0,
+    });
+    Ok(node_id)
+}
+
+/// Clears `pending_committee` once enough nodes are active to meet the
+/// threshold. Returns true only on the registration that does so.
+fn mark_committee_ready(state: &mut State) -> bool {
+    let active = state.mpc_nodes.iter().filter(|n| n.active).count();
+    if state.pending_committee && active >= state.threshold as usize {
+        state.pending_committee = false;
+        return true;
+    }
+    false
+}
+
+fn submit_state_transition(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    transition: StateTransition,
+) -> StdResult<Response> {
+    let bounty = expiry_bounty(deps.storage, &info.funds, 1)?;
+    let (_, response) = create_validation(deps, &env, transition, None, None, bounty)?;
+    Ok(response)
+}
+
+/// Validates a transition and stores it as a pending validation, optionally
+/// linked to the transfer it is a leg of.
+/// Creates one pending validation holding `bounty`, which the caller took
+/// from the message's funds. Returns its id with the submit attributes.
+fn create_validation(
+    deps: DepsMut,
+    env: &Env,
+    mut transition: StateTransition,
+    transfer_id: Option<String>,
+    group_id: Option<String>,
+    bounty: Option<Coin>,
+) -> StdResult<(String, Response)> {
+    let state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    if is_paused(deps.storage)? {
+        return Err(StdError::generic_err("Contract paused"));
+    }
+    if let Some(opens) = config.accept_submissions_at.filter(|opens| env.block.time < *opens) {
+        return Err(StdError::generic_err(format!("Submissions open at {}", opens.seconds())));
+    }
+
+    // Nothing could reach threshold before the committee is large enough
+    if state.pending_committee {
+        let active = state.mpc_nodes.iter().filter(|n| n.active).count();
+        return Err(StdError::generic_err(format!(
+            "Committee not ready: {} of {} nodes registered",
+            active, state.threshold
+        )));
+    }
+
+    // Bounds gas and storage per submission, whatever the transition carries
+    if to_vec(&transition)?.len() as u64 > config.max_transition_size {
+        return Err(StdError::generic_err("Transition too large"));
+    }
+
+    // Opaque to the contract; kept beside the transition, never hashed with it
+    let coordinator_metadata = transition.coordinator_metadata.take();
+    if coordinator_metadata.as_ref().is_some_and(|m| m.len() > MAX_COORDINATOR_METADATA) {
+        return Err(StdError::generic_err(format!(
+            "Coordinator metadata exceeds {} bytes",
+            MAX_COORDINATOR_METADATA
+        )));
+    }
+
+    // 1. Verify user signature
+    if !verify_user_signature(&transition) {
+        return Err(StdError::generic_err("Invalid user signature"));
+    }
+
+    // 2. Check the client speaks a protocol version we accept
+    if !config.supported_protocol_versions.contains(&transition.protocol_version) {
+        let supported: Vec<String> = config.supported_protocol_versions.iter()
+            .map(|v| v.to_string())
+            .collect();
+        return Err(StdError::generic_err(format!(
+            "Unsupported protocol version {} (supported: {})",
+            transition.protocol_version,
+            supported.join(", ")
+        )));
+    }
+
+    if let Some(hint) = &transition.ipfs_gateway_hint {
+        if hint.is_empty() || hint.len() > MAX_GATEWAY_HINT_LEN {
+            return Err(StdError::generic_err(format!(
+                "Gateway hint must be 1 to {} bytes",
+                MAX_GATEWAY_HINT_LEN
+            )));
+        }
+    }
+
+    // The new root becomes the user's leaf and the old one is compared to it
+    if transition.new_state_root.len() != DIGEST_LEN || transition.old_state_root.len() != DIGEST_LEN {
+        return Err(StdError::generic_err("Invalid root length"));
+    }
+    if transition.content_hash.len() != DIGEST_LEN {
+        return Err(StdError::generic_err(format!("Content hash must be {} bytes", DIGEST_LEN)));
+    }
+
+    if config.require_nonempty_merkle_proof && transition.merkle_proof.is_empty() {
+        if STATE_COMMITMENTS.contains(deps.storage, &transition.user_address) {
+            return Err(StdError::generic_err("Merkle proof required"));
+        }
+        if transition.old_state_root != EMPTY_USER_ROOT {
+            return Err(StdError::generic_err("An empty merkle proof requires the empty old state root"));
+        }
+    }
+
+    // 3. Store shares in canonical node_id order so lookups can binary search
+    canonicalize_shares(&mut transition)?;
+    validate_shares(&transition, config.max_share_len)?;
+    canonicalize_asset_updates(&mut transition)?;
+
+    // Each asset moves under at most one pending validation at a time
+    for update in &transition.asset_updates {
+        if let Some(holder) = ASSET_LOCKS.get(deps.storage, &asset_key(&transition.user_address, &update.asset_id)) {
+            return Err(StdError::generic_err(format!("Asset {} is locked by validation {}", update.asset_id, holder)));
+        }
+    }
+
+    // 4. The user's leaf must not already belong to someone else
+    let index = leaf_index(&transition.user_address, config.tree_depth);
+    if let Some(owner) = LEAF_OWNERS.get(deps.storage, &index) {
+        if owner != transition.user_address {
+            return Err(StdError::generic_err(format!("Leaf index {} collides with another user", index)));
+        }
+    }
+
+    // Optionally keep encrypted state pointers one-per-user
+    if config.unique_cids {
+        if let Some(owner) = CID_OWNERS.get(deps.storage, &transition.new_state_ipfs) {
+            if owner != transition.user_address {
+                return Err(StdError::generic_err("CID already in use by another user"));
+            }
+        }
+    }
+
+    let mut stats = STATS.load(deps.storage)?;
+    match stats.submissions_by_version.iter_mut().find(|c| c.version == transition.protocol_version) {
+        Some(entry) => entry.count += 1,
+        None => stats.submissions_by_version.push(VersionCount {
+            version: transition.protocol_version,
+            count: 1,
+        }),
+    }
+    stats.validation_counts.pending += 1;
+    STATS.save(deps.storage, &stats)?;
+
+    // Any submission is activity; the account is never purged from now on
+    if ACCOUNT_REGISTRATIONS.contains(deps.storage, &transition.user_address) {
+        ACCOUNT_REGISTRATIONS.remove(deps.storage, &transition.user_address)?;
+    }
+
+    // 5. Create pending validation
+    let validation_id = derive_validation_id(deps.storage, env, &transition.user_address)?;
+    if !transition.depends_on.is_empty() {
+        if transfer_id.is_some() || group_id.is_some() {
+            return Err(StdError::generic_err("Transfer legs cannot declare dependencies"));
+        }
+        check_dependencies(deps.storage, &validation_id, &transition.depends_on)?;
+        for dependency in &transition.depends_on {
+            add_dependent(deps.storage, dependency, &validation_id)?;
+        }
+    }
+
+    // An identical resubmission of a cancelled plain transition inherits the
+    // votes of nodes that opted in
+    let content_hash = transition_content_hash(&transition)?;
+    let carried = match (&transfer_id, &group_id) {
+        // Carried partials were signed without this validation's nonce round
+        (None, None) if !config.require_nonce_commitments => carry_over_votes(deps.storage, env, &content_hash, &validation_id, &state)?,
+        _ => vec![],
+    };
+
+    let mut pending_validation = PendingValidation {
+        validation_id: validation_id.clone(),
+        transition: transition.clone(),
+        validations: carried,
+        threshold_reached: false,
+        created_at: env.block.time.seconds(),
+        protocol_version: transition.protocol_version,
+        transfer_id,
+        blocked: None,
+        group_id,
+        da_challenge: None,
+        precedence_fired: None,
+        coordinator_metadata,
+        key_epoch: state.key_epoch,
+        epoch: state.epoch,
+        nonce_commitments: vec![],
+        subscribers: vec![],
+        bounty,
+        finalizer: None,
+        submission_height: env.block.height,
+    };
+
+    let carried_votes = pending_validation.validations.len();
+    if carried_votes > 0 {
+        let valid_count = pending_validation.validations.iter().filter(|v| v.valid).count();
+        pending_validation.threshold_reached = valid_count >= required_votes(deps.storage, &state, &pending_validation)?;
+    }
+
+    // Submissions (including queued transfer legs) stop once the user is over quota
+    let user_address = &pending_validation.transition.user_address;
+    let used = USER_STORAGE.get(deps.storage, user_address).unwrap_or(0);
+    let needed = used + pending_bytes(&pending_validation)?;
+    if needed > config.max_user_storage {
+        return Err(StdError::generic_err(format!(
+            "StorageQuotaExceeded {{ used: {}, quota: {} }}",
+            used, config.max_user_storage
+        )));
+    }
+    charge_storage(deps.storage, user_address, 0, needed - used)?;
+
+    for update in &transition.asset_updates {
+        ASSET_LOCKS.insert(deps.storage, &asset_key(user_address, &update.asset_id), &validation_id)?;
+    }
+    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &pending_validation)?;
+    file_work(deps.storage, &config, &pending_validation)?;
+    USER_PENDING.add_suffix(user_address.as_bytes()).insert(deps.storage, &validation_id)?;
+
+    let response = Events::new(config.event_verbosity)
+        .minimal("action", "submit_state_transition")
+        .minimal("validation_id", validation_id.clone())
+        .minimal_if_some("carried_votes", (carried_votes > 0).then(|| carried_votes.to_string()))
+        .minimal_if_some("threshold_reached", pending_validation.threshold_reached.then_some("true"))
+        .standard("user", &transition.user_address)
+        .standard("old_root", hex::encode(&transition.old_state_root))
+        .standard("new_root", hex::encode(&transition.new_state_root))
+        .standard("protocol_version", transition.protocol_version.to_string())
+        .debug("content_hash", hex::encode(content_hash))
+        .debug("created_at", env.block.time.seconds().to_string())
+        .into_response();
+    Ok((validation_id, response))
+}
+
+/// First signing round. The signer set is the first required_votes nodes to
+/// commit; the round closes once they have. A committer still silent
+/// NONCE_ROUND_TIMEOUT blocks after the round closed loses its seat to the
+/// next node that commits, so one node cannot stall the validation.
+fn commit_nonce(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    validation_id: String,
+    nonce_commitment: Vec<u8>,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if !config.require_nonce_commitments {
+        return Err(StdError::generic_err("Nonce commitments are not enabled"));
+    }
+    if is_paused(deps.storage)? {
+        return Err(StdError::generic_err("Contract paused"));
+    }
+    if nonce_commitment.len() != NONCE_COMMITMENT_LEN {
+        return Err(StdError::generic_err(format!("Nonce commitment must be {} bytes", NONCE_COMMITMENT_LEN)));
+    }
+
+    let state = STATE.load(deps.storage)?;
+    let node_id = get_active_node(&state, info.sender.as_str())?.node_id;
+    let mut validation = load_pending(deps.storage, &validation_id)?;
+    if validation.nonce_commitments.iter().any(|c| c.node_id == node_id) {
+        return Err(StdError::generic_err("Nonce already committed"));
+    }
+    let required = required_votes(deps.storage, &state, &validation)?;
+    let mut evicted = vec![];
+    if validation.nonce_commitments.len() >= required {
+        let closed_at = validation.nonce_commitments.iter().map(|c| c.committed_at).max().unwrap_or(0);
+        if env.block.height < closed_at.saturating_add(NONCE_ROUND_TIMEOUT) {
+            return Err(StdError::generic_err("Nonce round closed"));
+        }
+        let voted: Vec<u32> = validation.validations.iter().map(|v| v.node_id).collect();
+        validation.nonce_commitments.retain(|c| {
+            let silent = !voted.contains(&c.node_id);
+            if silent {
+                evicted.push(c.node_id.to_string());
+            }
+            !silent
+        });
+        if evicted.is_empty() {
+            return Err(StdError::generic_err("Nonce round closed"));
+        }
+    }
+
+    validation.nonce_commitments.push(NonceCommitment {
+        node_id,
+        commitment: nonce_commitment,
+        committed_at: env.block.height,
+    });
+    let committed = validation.nonce_commitments.len();
+    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "commit_nonce")
+        .add_attribute("validation_id", validation_id)
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("committed", format!("{}/{}", committed, required))
+        .add_attributes((!evicted.is_empty()).then(|| ("evicted", evicted.join(","))))
+        .add_attributes((committed >= required).then_some(("round_closed", "true"))))
+}
+
+/// Why a valid vote's partial does not verify under the key `node_id` held
+/// in the validation's committee epoch. BLS partials cannot be checked on
+/// chain and are taken as they are.
+fn partial_signature_error(
+    deps: Deps,
+    state: &State,
+    validation: &PendingValidation,
+    node_id: u32,
+    valid: bool,
+    partial_signature: &[u8],
+) -> StdResult<Option<String>> {
+    if !valid {
+        return Ok(None);
+    }
+    let Some(key) = committee_keys(deps.storage, state, validation.epoch, &[node_id]).pop() else {
+        return Ok(Some(format!("Node {} was not in the committee of epoch {}", node_id, validation.epoch)));
+    };
+    if key_type(&key.public_key) != KeyType::Secp256k1 {
+        return Ok(None);
+    }
+    let identity = DEPLOYMENT.load(deps.storage)?;
+    let digest = vote_digest(
+        &identity.chain_id,
+        &identity.contract_address,
+        &validation.validation_id,
+        validation.protocol_version,
+        &validation.transition.new_state_root,
+        &asset_updates_digest(&validation.transition.asset_updates),
+    );
+    if !deps.api.secp256k1_verify(&digest, partial_signature, &key.public_key).unwrap_or(false) {
+        return Ok(Some("Partial signature does not verify under the node's key".to_string()));
+    }
+    Ok(None)
+}
+
+/// Why a valid vote by `node_id` cannot be taken yet under
+/// config.require_nonce_commitments: the node must be in the signer set, the
+/// set's nonces must all be in, and the partial must be signed with the
+/// nonce the node committed to
+fn signing_round_error(
+    storage: &dyn Storage,
+    state: &State,
+    validation: &PendingValidation,
+    node_id: u32,
+    valid: bool,
+    partial_signature: &[u8],
+) -> StdResult<Option<String>> {
+    if !valid || !CONFIG.load(storage)?.require_nonce_commitments {
+        return Ok(None);
+    }
+    let Some(commitment) = validation.nonce_commitments.iter().find(|c| c.node_id == node_id) else {
+        return Ok(Some("Nonce not committed".to_string()));
+    };
+    let required = required_votes(storage, state, validation)?;
+    let committed = validation.nonce_commitments.len();
+    if committed < required {
+        return Ok(Some(format!("Nonce round open: {}/{} committed", committed, required)));
+    }
+    // A compact signature starts with r, the x coordinate of its nonce point
+    Ok((partial_signature.get(..NONCE_COMMITMENT_LEN) != Some(commitment.commitment.as_slice()))
+        .then(|| "Partial not signed with the committed nonce".to_string()))
+}
+
+fn validate_transition(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    validation_id: String,
+    valid: bool,
+    partial_signature: Vec<u8>,
+    attested_root: Option<Vec<u8>>,
+) -> StdResult<Response> {
+    let vote = BatchVote { validation_id: validation_id.clone(), valid, partial_signature, attested_root };
+    let RecordedVote { response, contested } = record_vote(deps.branch(), env, info, vote)?;
+    let entry = vote_result(deps.storage, validation_id, contested);
+    Ok(response.set_data(to_binary(&entry)?))
+}
+
+/// Result entry for a vote that was recorded: the validation was pending
+/// before, so any outcome now was settled by this vote
+fn vote_result(storage: &dyn Storage, validation_id: String, contested: bool) -> VoteResultEntry {
+    let finalized = VALIDATION_OUTCOMES.get(storage, &validation_id)
+        .map(|o| o.status == OutcomeStatus::Finalized)
+        .unwrap_or(false);
+    VoteResultEntry {
+        validation_id,
+        status: if finalized { VoteStatus::Finalized } else { VoteStatus::Accepted },
+        finalized,
+        error: None,
+        contested,
+    }
+}
+
+
+/// Copies votes recorded for a recently cancelled transition with the same
+/// content hash, from active nodes that opted in. Consumes the cancelled entry.
+fn carry_over_votes(
+    storage: &mut dyn Storage,
+    env: &Env,
+    content_hash: &[u8],
+    validation_id: &str,
+    state: &State,
+) -> StdResult<Vec<NodeValidation>> {
+    let cancelled = match CANCELLED_CONTENT.get(storage, &content_hash.to_vec()) {
+        Some(cancelled) => cancelled,
+        None => return Ok(vec![]),
+    };
+    CANCELLED_CONTENT.remove(storage, &content_hash.to_vec())?;
+    if env.block.height > cancelled.cancelled_at + CARRYOVER_WINDOW {
+        return Ok(vec![]);
+    }
+
+    let mut carried = vec![];
+    for vote in cancelled.votes {
+        let active = get_node(state, vote.node_id).is_ok_and(|n| n.active);
+        let opted_in = NODE_METADATA.get(storage, &vote.node_id).map(|m| m.auto_carryover_votes).unwrap_or(false);
+        if !active || !opted_in {
+            continue;
+        }
+        VOTE_PRESENCE.insert(storage, &vote_presence_key(validation_id, vote.node_id))?;
+        carried.push(NodeValidation {
+            carried_from: Some(vote.signed_validation_id(&cancelled.validation_id).to_string()),
+            voted_at: env.block.height,
+            ..vote
+        });
+    }
+    Ok(carried)
+}
+
+fn cancel_transition(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    validation_id: String,
+) -> StdResult<Response> {
+    let validation = load_pending(deps.storage, &validation_id)?;
+    if info.sender != validation.transition.user_address {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    if validation.transfer_id.is_some() || validation.group_id.is_some() {
+        return Err(StdError::generic_err("Transfer legs cannot be cancelled"));
+    }
+
+    clear_pending(deps.storage, &validation)?;
+    record_outcome(deps.storage, &env, &validation_id, OutcomeStatus::Cancelled)?;
+    CANCELLED_CONTENT.insert(deps.storage, &transition_content_hash(&validation.transition)?, &CancelledVotes {
+        validation_id: validation_id.clone(),
+        cancelled_at: env.block.height,
+        votes: validation.validations,
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_transition")
+        .add_attribute("validation_id", validation_id)
+        .add_attribute("outcome", OutcomeStatus::Cancelled.as_str()))
+}
+
+/// Registers or rotates the sender's key. The current key signs a rotation,
+/// so a hijacked sender account alone cannot take over what the key
+/// authorizes; a first key signs for itself, proving possession.
+fn set_user_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    public_key: Vec<u8>,
+    nonce: u64,
+    signature: Vec<u8>,
+) -> StdResult<Response> {
+    let public_key = normalize_public_key(&public_key)?;
+    if !USER_KEY_TYPES.contains(&key_type(&public_key)) {
+        return Err(StdError::generic_err("User keys must be secp256k1"));
+    }
+    let user_address = info.sender.to_string();
+    let expected = USER_KEY_NONCES.get(deps.storage, &user_address).unwrap_or(0);
+    if nonce != expected {
+        return Err(StdError::generic_err(format!("Key nonce must be {}", expected)));
+    }
+    let previous_key = USER_KEYS.get(deps.storage, &user_address);
+    let signer = previous_key.as_ref().unwrap_or(&public_key);
+    let identity = DEPLOYMENT.load(deps.storage)?;
+    let digest = user_key_digest(&identity.chain_id, &identity.contract_address, &user_address, &public_key, nonce);
+    if !deps.api.secp256k1_verify(&digest, &signature, signer).unwrap_or(false) {
+        return Err(StdError::generic_err(match previous_key {
+            Some(_) => "Key change must be signed by the current key",
+            None => "Key must sign its own registration",
+        }));
+    }
+    USER_KEY_NONCES.insert(deps.storage, &user_address, &(nonce + 1))?;
+    USER_KEYS.insert(deps.storage, &user_address, &public_key)?;
+
+    let mut rotations = KEY_ROTATIONS.get(deps.storage, &user_address).unwrap_or_default();
+    rotations.push(KeyRotation { previous_key: previous_key.clone(), public_key: public_key.clone(), height: env.block.height });
+    if rotations.len() > MAX_KEY_ROTATIONS {
+        rotations.remove(0);
+    }
+    KEY_ROTATIONS.insert(deps.storage, &user_address, &rotations)?;
+
+    // A first key is an account that has yet to do anything; the TTL runs
+    // from here and rotating the key does not restart it
+    let registered = ACCOUNT_REGISTRATIONS.contains(deps.storage, &user_address);
+    if !registered && !STATE_COMMITMENTS.contains(deps.storage, &user_address) && !USER_STORAGE.contains(deps.storage, &user_address) {
+        ACCOUNT_REGISTRATIONS.insert(deps.storage, &user_address, &env.block.time.seconds())?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_user_key")
+        .add_attribute("user", info.sender)
+        .add_attribute("rotated", previous_key.is_some().to_string())
+        .add_attribute("public_key", hex::encode(&public_key)))
+}
+
+/// Points the sender's commitment at re-uploaded content. Nothing the nodes
+/// validated changes, so no threshold vote is held; the user's key signs the
+/// move, plus one active node when config.repoint_cosign is on.
+fn repoint_storage(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_ipfs_cid: String,
+    content_hash: Vec<u8>,
+    signature: Vec<u8>,
+    cosign: Option<NodeCosign>,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let user_address = info.sender.to_string();
+    let previous = STATE_COMMITMENTS.get(deps.storage, &user_address)
+        .ok_or_else(|| StdError::generic_err("Commitment not found"))?;
+
+    if new_ipfs_cid.is_empty() {
+        return Err(StdError::generic_err("CID must not be empty"));
+    }
+    if content_hash.len() != DIGEST_LEN {
+        return Err(StdError::generic_err(format!("Content hash must be {} bytes", DIGEST_LEN)));
+    }
+    if config.unique_cids {
+        if let Some(owner) = CID_OWNERS.get(deps.storage, &new_ipfs_cid) {
+            if owner != user_address {
+                return Err(StdError::generic_err("CID already in use by another user"));
+            }
+        }
+    }
+
+    let user_key = USER_KEYS.get(deps.storage, &user_address)
+        .ok_or_else(|| StdError::generic_err("No user key registered"))?;
+    let digest = repoint_digest(env.contract.address.as_str(), &previous.state_root, &new_ipfs_cid, &content_hash, previous.nonce);
+    if !deps.api.secp256k1_verify(&digest, &signature, &user_key).unwrap_or(false) {
+        return Err(StdError::generic_err("Invalid repoint signature"));
+    }
+
+    let cosigned_by = match (config.repoint_cosign, cosign) {
+        (false, _) => None,
+        (true, None) => return Err(StdError::generic_err("Repoint requires a node co-signature")),
+        (true, Some(cosign)) => {
             let state = STATE.load(deps.storage)?;
-            to_binary(&StateResponse { state })
+            let node = get_node(&state, cosign.node_id)?;
+            if !node.active || !deps.api.secp256k1_verify(&digest, &cosign.signature, &node.public_key).unwrap_or(false) {
+                return Err(StdError::generic_err("Invalid node co-signature"));
+            }
+            Some(cosign.node_id)
+        }
+    };
+
+    let (updated_at, clamped) = monotonic_updated_at(Some(&previous), env.block.time.seconds());
+    let commitment = StateCommitment {
+        ipfs_cid: new_ipfs_cid,
+        content_hash,
+        updated_at,
+        clamped,
+        ..previous.clone()
+    };
+
+    if CID_OWNERS.get(deps.storage, &previous.ipfs_cid).as_ref() == Some(&user_address) {
+        CID_OWNERS.remove(deps.storage, &previous.ipfs_cid)?;
+    }
+    CID_OWNERS.insert(deps.storage, &commitment.ipfs_cid, &user_address)?;
+    charge_storage(deps.storage, &user_address, commitment_bytes(Some(&previous))?, commitment_bytes(Some(&commitment))?)?;
+    STATE_COMMITMENTS.insert(deps.storage, &user_address, &commitment)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "repoint_storage")
+        .add_attribute("user", user_address)
+        .add_attribute("previous_ipfs", previous.ipfs_cid)
+        .add_attribute("ipfs_cid", commitment.ipfs_cid)
+        .add_attribute("nonce", commitment.nonce.to_string())
+        .add_attributes(cosigned_by.map(|node_id| ("cosigned_by", node_id.to_string()))))
+}
+
+/// Removes the sender's previous and historical commitments, finalizer
+/// records and settled outcomes. Refused while a challenge could still
+/// restore the previous commitment; outcomes a pending dependency still
+/// needs are kept.
+fn purge_my_history(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    keep_current: bool,
+    nonce: u64,
+    signature: Vec<u8>,
+) -> StdResult<Response> {
+    let user_address = info.sender.to_string();
+    let user_key = USER_KEYS.get(deps.storage, &user_address)
+        .ok_or_else(|| StdError::generic_err("No user key registered"))?;
+    let expected = PURGE_NONCES.get(deps.storage, &user_address).unwrap_or(0);
+    if nonce != expected {
+        return Err(StdError::generic_err(format!("Purge nonce must be {}", expected)));
+    }
+    let identity = DEPLOYMENT.load(deps.storage)?;
+    let digest = purge_digest(&identity.chain_id, &identity.contract_address, &user_address, keep_current, nonce);
+    if !deps.api.secp256k1_verify(&digest, &signature, &user_key).unwrap_or(false) {
+        return Err(StdError::generic_err("Invalid purge signature"));
+    }
+    PURGE_NONCES.insert(deps.storage, &user_address, &(nonce + 1))?;
+
+    let current = STATE_COMMITMENTS.get(deps.storage, &user_address);
+    if let Some(current) = &current {
+        if env.block.height < current.lock_at_height {
+            return Err(StdError::generic_err(format!(
+                "Commitment open to challenge until height {}",
+                current.lock_at_height
+            )));
+        }
+    }
+
+    let mut previous_commitments = 0;
+    if let Some(previous) = PREVIOUS_COMMITMENTS.get(deps.storage, &user_address) {
+        charge_storage(deps.storage, &user_address, commitment_bytes(Some(&previous))?, 0)?;
+        PREVIOUS_COMMITMENTS.remove(deps.storage, &user_address)?;
+        previous_commitments += 1;
+    }
+
+    let mut historical_commitments = 0;
+    if let Some(current) = &current {
+        for nonce in current.nonce.saturating_sub(MAX_COMMITMENT_HISTORY)..current.nonce {
+            let key = nonce_key(&user_address, nonce);
+            if let Some(old) = COMMITMENT_HISTORY.get(deps.storage, &key) {
+                charge_storage(deps.storage, &user_address, commitment_bytes(Some(&old))?, 0)?;
+                COMMITMENT_HISTORY.remove(deps.storage, &key)?;
+                historical_commitments += 1;
+            }
+        }
+    }
+
+    // Only the last MAX_FINALIZER_HISTORY nonces can still be stored
+    let mut finalizer_records = 0;
+    if let Some(current) = &current {
+        let newest = if keep_current { current.nonce.checked_sub(1) } else { Some(current.nonce) };
+        if let Some(newest) = newest {
+            for nonce in newest.saturating_sub(MAX_FINALIZER_HISTORY)..=newest {
+                let key = nonce_key(&user_address, nonce);
+                if let Some(node_ids) = COMMITMENT_FINALIZERS.get(deps.storage, &key) {
+                    charge_storage(deps.storage, &user_address, finalizers_bytes(&user_address, nonce, &node_ids), 0)?;
+                    COMMITMENT_FINALIZERS.remove(deps.storage, &key)?;
+                    finalizer_records += 1;
+                }
+            }
+        }
+    }
+
+    // Outcomes a pending validation still depends on stay
+    let index = USER_OUTCOMES.add_suffix(user_address.as_bytes());
+    let outcomes: Vec<String> = index.iter(deps.storage)?
+        .collect::<StdResult<Vec<String>>>()?
+        .into_iter()
+        .filter(|id| !DEPENDENTS.get(deps.storage, id).unwrap_or_default()
+            .iter()
+            .any(|dependent| PENDING_VALIDATIONS.contains(deps.storage, dependent)))
+        .collect();
+    for validation_id in &outcomes {
+        VALIDATION_OUTCOMES.remove(deps.storage, validation_id)?;
+        index.remove(deps.storage, validation_id)?;
+        take_dependents(deps.storage, validation_id)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "purge_history")
+        .add_attribute("user", user_address)
+        .add_attribute("previous_commitments", previous_commitments.to_string())
+        .add_attribute("historical_commitments", historical_commitments.to_string())
+        .add_attribute("finalizer_records", finalizer_records.to_string())
+        .add_attribute("outcomes", outcomes.len().to_string()))
+}
+
+fn update_node_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    auto_carryover_votes: Option<bool>,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+    let node_id = get_operated_node(&state, info.sender.as_str())?.node_id;
+
+    let mut metadata: NodeMetadata = NODE_METADATA.get(deps.storage, &node_id).unwrap_or_default();
+    if let Some(carryover) = auto_carryover_votes {
+        metadata.auto_carryover_votes = carryover;
+    }
+    NODE_METADATA.insert(deps.storage, &node_id, &metadata)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_node_metadata")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("auto_carryover_votes", metadata.auto_carryover_votes.to_string()))
+}
+
+fn validate_batch(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    votes: Vec<BatchVote>,
+    max_items: Option<u32>,
+    cursor: Option<u32>,
+) -> StdResult<Response> {
+    if votes.is_empty() || votes.len() > MAX_BATCH_VOTES {
+        return Err(StdError::generic_err(format!("Batch must have between 1 and {} votes", MAX_BATCH_VOTES)));
+    }
+    if max_items == Some(0) {
+        return Err(StdError::generic_err("max_items must be at least 1"));
+    }
+
+    // The cursor indexes the caller's own list, so settlements elsewhere
+    // between calls cannot shift it
+    let start = cursor.unwrap_or(0) as usize;
+    if start >= votes.len() {
+        return Err(StdError::generic_err("Cursor is past the end of the batch"));
+    }
+    let end = max_items.map_or(votes.len(), |max| votes.len().min(start + max as usize));
+    let resume_cursor = (end < votes.len()).then_some(end as u32);
+    let node_id = get_active_node(&STATE.load(deps.storage)?, info.sender.as_str())?.node_id;
+    let config = CONFIG.load(deps.storage)?;
+
+    // Per-item problems are classified up front so a skipped vote never
+    // leaves partial writes; anything else still fails the whole batch
+    let mut results = Vec::with_capacity(end - start);
+    for vote in votes.into_iter().take(end).skip(start) {
+        let skipped = |status, error: String| VoteResultEntry {
+            validation_id: vote.validation_id.clone(),
+            status,
+            finalized: false,
+            error: Some(error),
+            contested: false,
+        };
+        // Each recorded vote moves the node's last active height
+        let state = STATE.load(deps.storage)?;
+        let result = match load_pending(deps.storage, &vote.validation_id) {
+            Err(e) => {
+                let error = match e {
+                    StdError::GenericErr { msg, .. } => msg,
+                    other => other.to_string(),
+                };
+                let status = if VALIDATION_OUTCOMES.contains(deps.storage, &vote.validation_id) {
+                    VoteStatus::AlreadySettled
+                } else {
+                    VoteStatus::ValidationMissing
+                };
+                skipped(status, error)
+            }
+            Ok(_) if VOTE_PRESENCE.contains(deps.storage, &vote_presence_key(&vote.validation_id, node_id)) =>
+                skipped(VoteStatus::Duplicate, "Already validated".to_string()),
+            Ok(_) if check_cooldown(&config, get_active_node(&state, info.sender.as_str())?, env.block.height).is_err() =>
+                skipped(VoteStatus::Throttled, "Node validating too frequently".to_string()),
+            Ok(_) if vote.valid && vote.partial_signature.len() != PARTIAL_SIGNATURE_LEN =>
+                skipped(VoteStatus::SignatureRejected, format!("Partial signature must be {} bytes", PARTIAL_SIGNATURE_LEN)),
+            Ok(validation) => {
+                let error = match signing_round_error(deps.storage, &state, &validation, node_id, vote.valid, &vote.partial_signature)? {
+                    Some(error) => Some(error),
+                    None => partial_signature_error(deps.as_ref(), &state, &validation, node_id, vote.valid, &vote.partial_signature)?,
+                };
+                match error {
+                    Some(error) => skipped(VoteStatus::SignatureRejected, error),
+                    None => {
+                        let validation_id = vote.validation_id.clone();
+                        let recorded = record_vote(deps.branch(), env.clone(), info.clone(), vote)?;
+                        vote_result(deps.storage, validation_id, recorded.contested)
+                    }
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    let count = |status: VoteStatus| results.iter().filter(|r| r.status == status).count().to_string();
+    let response = Response::new()
+        .add_attribute("action", "validate_batch")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("votes", results.len().to_string());
+    let response = [
+        VoteStatus::Accepted,
+        VoteStatus::Duplicate,
+        VoteStatus::Finalized,
+        VoteStatus::ValidationMissing,
+        VoteStatus::AlreadySettled,
+        VoteStatus::Throttled,
+        VoteStatus::SignatureRejected,
+    ].into_iter().fold(response, |response, status| response.add_attribute(status.as_str(), count(status)));
+
+    // Per-vote events are not forwarded, so contested ones are named here
+    let contested: Vec<&str> = results.iter()
+        .filter(|r| r.contested)
+        .map(|r| r.validation_id.as_str())
+        .collect();
+    Ok(response
+        .add_attributes((!contested.is_empty()).then(|| ("contested", contested.join(","))))
+        .add_attributes(resume_cursor.map(|cursor| ("resume_cursor", cursor.to_string())))
+        .set_data(to_binary(&BatchVoteResult { results, resume_cursor })?))
+}
+
+/// Throttles nodes that vote faster than the configured cooldown
+fn check_cooldown(config: &Config, node: &MPCNode, height: u64) -> StdResult<()> {
+    if let (Some(last), true) = (node.last_active_height, config.validation_cooldown > 0) {
+        if height < last.saturating_add(config.validation_cooldown) {
+            return Err(StdError::generic_err("Node validating too frequently"));
+        }
+    }
+    Ok(())
+}
+
+/// A vote record_vote took, and whether it crossed the accept and reject
+/// thresholds at once
+struct RecordedVote {
+    response: Response,
+    contested: bool,
+}
+
+/// Records one node vote
+fn record_vote(deps: DepsMut, env: Env, info: MessageInfo, vote: BatchVote) -> StdResult<RecordedVote> {
+    let BatchVote { validation_id, valid, partial_signature, attested_root } = vote;
+    let mut state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+
+    if is_paused(deps.storage)? {
+        return Err(StdError::generic_err("Contract paused"));
+    }
+
+    // 1. Verify sender is an MPC node
+    let node = get_active_node(&state, info.sender.as_str())?;
+
+    let node_id = node.node_id; // Copy node_id before moving state
+
+    check_cooldown(&config, node, env.block.height)?;
+
+    // Malformed partials would only surface later, when the aggregate fails to verify
+    if valid && partial_signature.len() != PARTIAL_SIGNATURE_LEN {
+        return Err(StdError::generic_err(format!("Partial signature must be {} bytes", PARTIAL_SIGNATURE_LEN)));
+    }
+
+    if config.strict_root_agreement && valid && attested_root.as_ref().map(|r| r.len()) != Some(32) {
+        return Err(StdError::generic_err("Strict mode requires a 32-byte attested root"));
+    }
+
+    // 2. Check not already validated by this node (before loading the record)
+    let presence_key = vote_presence_key(&validation_id, node_id);
+    if VOTE_PRESENCE.contains(deps.storage, &presence_key) {
+        return Err(StdError::generic_err("Already validated"));
+    }
+
+    // 3. Find pending validation
+    let mut validation = load_pending(deps.storage, &validation_id)?;
+    if past_block_window(deps.storage, &config, &validation, &env)? {
+        return Err(StdError::generic_err("Validation window closed"));
+    }
+    if let Some(error) = signing_round_error(deps.storage, &state, &validation, node_id, valid, &partial_signature)? {
+        return Err(StdError::generic_err(error));
+    }
+    if let Some(error) = partial_signature_error(deps.as_ref(), &state, &validation, node_id, valid, &partial_signature)? {
+        return Err(StdError::generic_err(error));
+    }
+    VOTE_PRESENCE.insert(deps.storage, &presence_key)?;
+
+    get_node_mut(&mut state, node_id)?.last_active_height = Some(env.block.height);
+    let committed = STATE_COMMITMENTS.get(deps.storage, &validation.transition.user_address);
+    let vote = ContentVote {
+        content_hash: transition_content_hash(&validation.transition)?,
+        validation_id: validation_id.clone(),
+        valid,
+        voted_at: env.block.height,
+        committed_root: committed.as_ref().map(|c| c.state_root.clone()).unwrap_or_default(),
+        committed_nonce: committed.map_or(0, |c| c.nonce),
+    };
+    let equivocation = note_content_vote(deps.storage, &config, &mut state, node_id, vote)?;
+    STATE.save(deps.storage, &state)?;
+
+    // Effort is what the node's own transaction did; carried-over votes add none
+    let share_bytes = find_share(&validation.transition, node_id)
+        .map_or(0, |share| share.encrypted_data.len() as u64);
+    record_effort(deps.storage, state.epoch, node_id, 1 + share_bytes / EFFORT_BYTES_PER_UNIT)?;
+
+    // 4. Add validation
+    validation.validations.push(NodeValidation {
+        node_id,
+        valid,
+        partial_signature,
+        attested_root,
+        voted_at: env.block.height,
+        carried_from: None,
+    });
+
+    // A share holder's key reported compromised since submission blocks it now
+    let newly_blocked = validation.blocked.is_none();
+    if newly_blocked {
+        validation.blocked = block_reason(deps.storage, &validation)?;
+    }
+
+    // Strict mode: any valid voter attesting different bytes blocks the validation
+    let quarantined = matches!(validation.blocked, Some(BlockReason::KeyCompromised { .. }));
+    if config.strict_root_agreement && !quarantined {
+        let expected = &validation.transition.new_state_root;
+        let divergent: Vec<NodeRoot> = validation.validations.iter()
+            .filter(|v| v.valid && v.attested_root.as_ref() != Some(expected))
+            .map(|v| NodeRoot { node_id: v.node_id, root: v.attested_root.clone().unwrap_or_default() })
+            .collect();
+        if !divergent.is_empty() {
+            validation.blocked = Some(BlockReason::RootDisagreement { expected: expected.clone(), divergent });
+        }
+    }
+
+    if let Some(BlockReason::RootDisagreement { divergent, .. }) = &validation.blocked {
+        let divergent_attr = divergent.iter()
+            .map(|d| format!("{}:{}", d.node_id, hex::encode(&d.root)))
+            .collect::<Vec<_>>()
+            .join(",");
+        PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+        return Ok(RecordedVote { response: Events::new(config.event_verbosity)
+            .minimal("action", "validate_transition")
+            .minimal("validation_id", validation_id)
+            .minimal("blocked", "root_disagreement")
+            .standard("node_id", node_id.to_string())
+            .standard("valid", valid.to_string())
+            .debug("divergent_roots", divergent_attr)
+            .into_response()
+            .add_events(equivocation), contested: false });
+    }
+    if let Some(BlockReason::KeyCompromised { node_id: holder }) = &validation.blocked {
+        // The user can only cancel; the share cannot be replaced in place
+        let quarantine = newly_blocked.then(|| Event::new("key_compromise_quarantine")
+            .add_attribute("validation_id", validation_id.clone())
+            .add_attribute("user", validation.transition.user_address.clone())
+            .add_attribute("node_id", holder.to_string())
+            .add_attribute("required_action", "cancel"));
+        PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+        return Ok(RecordedVote { response: Events::new(config.event_verbosity)
+            .minimal("action", "validate_transition")
+            .minimal("validation_id", validation_id)
+            .minimal("blocked", "key_compromised")
+            .standard("node_id", node_id.to_string())
+            .standard("valid", valid.to_string())
+            .into_response()
+            .add_events(equivocation)
+            .add_events(quarantine), contested: false });
+    }
+
+    // 5. Check if threshold reached - if so, auto-finalize!
+    let valid_count = validation.validations.iter().filter(|v| v.valid).count();
+    let threshold_reached = valid_count >= required_votes(deps.storage, &state, &validation)?;
+
+    // Batch legs, hashlocked legs and challenged transitions wait for
+    // something outside this vote
+    let challenged = validation.da_challenge.as_ref().map(|c| c.is_open()).unwrap_or(false);
+    let awaiting_dependencies = !unmet_dependencies(deps.storage, &validation.transition).is_empty();
+    let locked = challenged || awaiting_dependencies || validation.group_id.is_some() || match &validation.transfer_id {
+        Some(transfer_id) => TRANSFERS.get(deps.storage, transfer_id)
+            .map(|t| t.is_locked())
+            .unwrap_or(false),
+        None => false,
+    };
+
+    let events = Events::new(config.event_verbosity)
+        .events(equivocation)
+        .standard("node_id", node_id.to_string())
+        .standard("valid", valid.to_string())
+        .standard("valid_votes", valid_count.to_string());
+
+    // A transition is rejected once config.reject_threshold nodes vote
+    // invalid, and a batch leg also once it can no longer reach threshold
+    // (which unwinds the whole batch). Both decisions are evaluated before
+    // acting; when this vote makes both true at once, config.vote_precedence
+    // picks one and the outcome records it. A leg that reached threshold in
+    // an earlier vote stays accepted.
+    let rejections = validation.validations.len() - valid_count;
+    let mut doomed = config.reject_threshold > 0 && rejections >= config.reject_threshold as usize;
+    if validation.group_id.is_some() {
+        let required = required_votes(deps.storage, &state, &validation)?;
+        let active = state.mpc_nodes.iter().filter(|n| n.active).count();
+        doomed |= active.saturating_sub(rejections) < required;
+    }
+    doomed &= !validation.threshold_reached;
+    let fired = (doomed && threshold_reached).then_some(config.vote_precedence);
+    let contested = fired.is_some();
+    let events = events
+        .minimal_if_some("contested", fired.map(|_| "true"))
+        .minimal_if_some("precedence", fired.map(|rule| rule.as_str()));
+    if doomed && fired != Some(VotePrecedence::AcceptWins) {
+        fail_validation(deps.storage, &env, &validation, OutcomeStatus::Rejected)?;
+        if let Some(rule) = fired {
+            note_precedence(deps.storage, &validation_id, rule)?;
+        }
+        let alert = note_rejection(deps.storage, &config, &validation.transition.user_address, env.block.height, env.block.time.seconds())?;
+        let action = match validation.group_id {
+            Some(_) => "validate_and_unwind_batch",
+            None => "validate_and_reject",
+        };
+        return Ok(RecordedVote { response: events
+            .minimal("action", action)
+            .minimal("validation_id", validation_id)
+            .minimal_if_some("group_id", validation.group_id.clone())
+            .minimal("outcome", OutcomeStatus::Rejected.as_str())
+            .into_response()
+            .add_events(alert), contested });
+    }
+    validation.precedence_fired = fired;
+
+    if threshold_reached && locked {
+        // Hashlocked leg: keep votable but wait for RevealPreimage to finalize
+        validation.threshold_reached = true;
+        if validation.finalizer.is_none() && config.finalizer_window > 0 {
+            validation.finalizer = designate_finalizer(&validation, env.block.height + config.finalizer_window);
+        }
+        PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+
+        // The last batch leg to reach threshold settles all of them
+        if let Some(group_id) = validation.group_id.clone() {
+            if let Some(status) = settle_group_if_ready(deps.storage, &env, &group_id)? {
+                let outcome = match status {
+                    TransferStatus::Completed => OutcomeStatus::Finalized,
+                    _ => OutcomeStatus::Superseded,
+                };
+                return Ok(RecordedVote { response: events
+                    .minimal("action", "validate_and_settle_batch")
+                    .minimal("validation_id", validation_id)
+                    .minimal("group_id", group_id)
+                    .minimal("outcome", outcome.as_str())
+                    .standard("threshold_reached", "true")
+                    .into_response(), contested });
+            }
+        }
+
+        return Ok(RecordedVote { response: events
+            .minimal("action", "validate_transition")
+            .minimal("validation_id", validation_id)
+            .minimal_if_some("awaiting_dependencies", awaiting_dependencies.then_some("true"))
+            .minimal_if_some("finalizer", validation.finalizer.as_ref().map(|f| f.node_id.to_string()))
+            .standard("threshold_reached", "true")
+            .standard("locked", "true")
+            .into_response(), contested });
+    }
+
+    if threshold_reached && plan_finalization(deps.storage, &validation) == FinalizationPlan::Superseded {
+        supersede(deps.storage, &env, &validation)?;
+        return Ok(RecordedVote { response: events
+            .minimal("action", "validate_and_supersede")
+            .minimal("validation_id", validation_id)
+            .minimal("finalized", "false")
+            .minimal("outcome", OutcomeStatus::Superseded.as_str())
+            .standard("threshold_reached", "true")
+            .debug("settled_at", env.block.time.seconds().to_string())
+            .into_response(), contested });
+    }
+
+    if threshold_reached {
+        // Auto-finalize: aggregate signatures and update state
+        let mut updated_state = state;
+        let finalization = apply_finalization(deps.storage, &env, &mut updated_state, &validation)?;
+
+        return Ok(RecordedVote { response: events
+            .minimal("action", "validate_and_finalize")
+            .minimal("validation_id", validation_id)
+            .minimal("finalized", "true")
+            .minimal("outcome", OutcomeStatus::Finalized.as_str())
+            .minimal_if_some("warning", finalization.warning())
+            .standard("threshold_reached", "true")
+            .standard("user", validation.transition.user_address)
+            .standard("new_root", hex::encode(&updated_state.current_state_root))
+            .standard("block_height", updated_state.block_height.to_string())
+            .standard("history_pruned", finalization.pruned.to_string())
+            .standard("bundle_hash", hex::encode(finalization.bundle_hash))
+            .debug("threshold_signature", hex::encode(finalization.threshold_signature))
+            .debug("committee_hash", hex::encode(committee_hash(deps.storage, &updated_state)?))
+            .debug("settled_at", env.block.time.seconds().to_string())
+            .into_response(), contested });
+    }
+
+    // Threshold not reached yet - just save validation
+    validation.threshold_reached = false;
+    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+
+    Ok(RecordedVote { response: events
+        .minimal("action", "validate_transition")
+        .minimal("validation_id", validation_id)
+        .standard("threshold_reached", "false")
+        .into_response(), contested })
+}
+
+/// Lowest-id node with a valid vote, given the first chance to finalize
+fn designate_finalizer(validation: &PendingValidation, until: u64) -> Option<DesignatedFinalizer> {
+    validation.validations.iter()
+        .filter(|v| v.valid)
+        .map(|v| v.node_id)
+        .min()
+        .map(|node_id| DesignatedFinalizer { node_id, until })
+}
+
+/// Why `validation` cannot finalize: what was stored on it, or else a share
+/// encrypted to a key reported compromised since. Reports touch no
+/// validations, so the share holders' keys in the validation's committee
+/// epoch are checked here instead.
+fn block_reason(storage: &dyn Storage, validation: &PendingValidation) -> StdResult<Option<BlockReason>> {
+    if validation.blocked.is_some() {
+        return Ok(validation.blocked.clone());
+    }
+    let state = STATE.load(storage)?;
+    let holders: Vec<u32> = validation.transition.encrypted_shares.iter().map(|share| share.node_id).collect();
+    Ok(committee_keys(storage, &state, validation.epoch, &holders).into_iter()
+        .find(|key| COMPROMISED_KEYS.contains(storage, &key.public_key))
+        .map(|key| BlockReason::KeyCompromised { node_id: key.node_id }))
+}
+
+fn finalize_transition(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    validation_id: String,
+) -> StdResult<Response> {
+    let mut state = STATE.load(deps.storage)?;
+
+    // 1. Find validation
+    let validation = load_pending(deps.storage, &validation_id)?;
+
+    // 2. Refuse while any precondition but the user's context fails; a
+    // moved context supersedes below instead
+    if let Some(error) = finalization_error(deps.as_ref(), &env, &validation)? {
+        return Err(StdError::generic_err(error));
+    }
+    let valid_count = validation.validations.iter().filter(|v| v.valid).count();
+    let config = CONFIG.load(deps.storage)?;
+    let verbosity = config.event_verbosity;
+
+    // Within the window, racing callers succeed without touching state so
+    // only the designated node pays for the finalization
+    if let Some(finalizer) = &validation.finalizer {
+        let designated = get_node(&state, finalizer.node_id)?.is_operated_by(info.sender.as_str());
+        if env.block.height < finalizer.until && !designated {
+            return Ok(Events::new(verbosity)
+                .minimal("action", "finalize_transition")
+                .minimal("validation_id", &validation_id)
+                .minimal("result", "already_finalized")
+                .standard("finalizer", finalizer.node_id.to_string())
+                .standard("finalizer_until", finalizer.until.to_string())
+                .into_response());
+        }
+    }
+
+    let events = Events::new(verbosity)
+        .minimal("action", "finalize_transition")
+        .minimal("validation_id", &validation_id)
+        .standard("user", &validation.transition.user_address)
+        .standard("valid_votes", valid_count.to_string());
+
+    if plan_finalization(deps.storage, &validation) == FinalizationPlan::Superseded {
+        supersede(deps.storage, &env, &validation)?;
+        return Ok(events
+            .minimal("outcome", OutcomeStatus::Superseded.as_str())
+            .debug("settled_at", env.block.time.seconds().to_string())
+            .into_response());
+    }
+
+    // 3. Aggregate signatures, update root, store commitment, remove pending
+    let finalization = apply_finalization(deps.storage, &env, &mut state, &validation)?;
+
+    Ok(events
+        .minimal("outcome", OutcomeStatus::Finalized.as_str())
+        .minimal_if_some("warning", finalization.warning())
+        .standard("new_root", hex::encode(&state.current_state_root))
+        .standard("block_height", state.block_height.to_string())
+        .standard("ipfs_cid", validation.transition.new_state_ipfs)
+        .standard("history_pruned", finalization.pruned.to_string())
+        .standard("bundle_hash", hex::encode(finalization.bundle_hash))
+        .debug("threshold_signature", hex::encode(finalization.threshold_signature))
+        .debug("committee_hash", hex::encode(committee_hash(deps.storage, &state)?))
+        .debug("settled_at", env.block.time.seconds().to_string())
+        .into_response())
+}
+
+fn submit_transfer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    transfer: Transfer,
+) -> StdResult<Response> {
+    create_transfer(deps, &env, &info, "submit_transfer", transfer, None, None)
+}
+
+/// Swaps the IPFS pointers of both legs together. The content hashes stay,
+/// so the content nodes voted on and ProveAvailability checks is unchanged
+/// and existing votes keep counting. Only the account that submitted the
+/// transfer may call it; a leg's CID changed by anyone but that leg's user
+/// drops the leg's votes so nodes re-check the new pointer.
+fn update_transfer_cids(
+    deps: DepsMut,
+    info: MessageInfo,
+    sender_validation_id: String,
+    recipient_validation_id: String,
+    sender_ipfs: String,
+    recipient_ipfs: String,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut sender_leg = load_pending(deps.storage, &sender_validation_id)?;
+    let mut recipient_leg = load_pending(deps.storage, &recipient_validation_id)?;
+
+    let transfer_id = sender_leg.transfer_id.clone()
+        .ok_or_else(|| StdError::generic_err("Validation is not a transfer leg"))?;
+    let transfer = TRANSFERS.get(deps.storage, &transfer_id)
+        .ok_or_else(|| StdError::generic_err("Transfer not found"))?;
+    if transfer.sender_validation_id != sender_validation_id || transfer.recipient_validation_id != recipient_validation_id {
+        return Err(StdError::generic_err("Validations are not the legs of one transfer"));
+    }
+    if transfer.submitter.is_empty() || info.sender != transfer.submitter {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    let sender_revote = reset_repointed_leg(deps.storage, &mut sender_leg, "sender", &sender_ipfs, info.sender.as_str())?;
+    let recipient_revote = reset_repointed_leg(deps.storage, &mut recipient_leg, "recipient", &recipient_ipfs, info.sender.as_str())?;
+
+    for (leg, ipfs) in [(&mut sender_leg, sender_ipfs), (&mut recipient_leg, recipient_ipfs)] {
+        if ipfs.is_empty() {
+            return Err(StdError::generic_err("CID must not be empty"));
+        }
+        let user_address = leg.transition.user_address.clone();
+        if config.unique_cids {
+            if let Some(owner) = CID_OWNERS.get(deps.storage, &ipfs) {
+                if owner != user_address {
+                    return Err(StdError::generic_err("CID already in use by another user"));
+                }
+            }
+        }
+        let released = pending_bytes(leg)?;
+        leg.transition.new_state_ipfs = ipfs;
+        charge_storage(deps.storage, &user_address, released, pending_bytes(leg)?)?;
+        PENDING_VALIDATIONS.insert(deps.storage, &leg.validation_id, leg)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "update_transfer_cids")
+        .add_attribute("transfer_id", transfer_id)
+        .add_attribute("sender_ipfs", sender_leg.transition.new_state_ipfs)
+        .add_attribute("recipient_ipfs", recipient_leg.transition.new_state_ipfs)
+        .add_attribute("sender_votes_reset", sender_revote.to_string())
+        .add_attribute("recipient_votes_reset", recipient_revote.to_string()))
+}
+
+/// Drops a transfer leg's votes when `caller` points it at a new CID without
+/// being the leg's user, who alone may do so once it has reached threshold.
+/// Returns whether the votes were dropped.
+fn reset_repointed_leg(
+    storage: &mut dyn Storage,
+    leg: &mut PendingValidation,
+    role: &str,
+    ipfs: &str,
+    caller: &str,
+) -> StdResult<bool> {
+    if leg.transition.new_state_ipfs == ipfs || caller == leg.transition.user_address {
+        return Ok(false);
+    }
+    if leg.threshold_reached {
+        return Err(StdError::generic_err(format!("Only the {} can repoint a leg past threshold", role)));
+    }
+    for vote in leg.validations.drain(..) {
+        VOTE_PRESENCE.remove(storage, &vote_presence_key(&leg.validation_id, vote.node_id))?;
+    }
+    Ok(true)
+}
+
+fn submit_conditional_transfer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    transfer: Transfer,
+    hashlock: Vec<u8>,
+    deadline: u64,
+) -> StdResult<Response> {
+    if hashlock.len() != HASHLOCK_SIZE {
+        return Err(StdError::generic_err(format!("Hashlock must be {} bytes", HASHLOCK_SIZE)));
+    }
+    if deadline <= env.block.time.seconds() {
+        return Err(StdError::generic_err("Deadline must be in the future"));
+    }
+
+    let response = create_transfer(deps, &env, &info, "submit_conditional_transfer", transfer, Some(hashlock), Some(deadline))?;
+    Ok(response.add_attribute("deadline", deadline.to_string()))
+}
+
+/// Submits both legs of a transfer and records the link between them
+fn create_transfer(
+    mut deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    action: &str,
+    transfer: Transfer,
+    hashlock: Option<Vec<u8>>,
+    deadline: Option<u64>,
+) -> StdResult<Response> {
+    // Submit both sender and recipient transitions, linked by the transfer record
+    let transfer_id = format!("{}-{}-{}", env.block.height, transfer.sender, transfer.recipient);
+    if TRANSFERS.contains(deps.storage, &transfer_id) {
+        return Err(StdError::generic_err("Transfer already exists"));
+    }
+
+    let bounty = expiry_bounty(deps.storage, &info.funds, 2)?;
+    let (sender_validation_id, response1) = create_validation(
+        deps.branch(),
+        env,
+        transfer.sender_transition,
+        Some(transfer_id.clone()),
+        None,
+        bounty.clone(),
+    )?;
+
+    let (recipient_validation_id, response2) = create_validation(
+        deps.branch(),
+        env,
+        transfer.recipient_transition,
+        Some(transfer_id.clone()),
+        None,
+        bounty,
+    )?;
+
+    let record = TransferRecord {
+        transfer_id: transfer_id.clone(),
+        sender: transfer.sender.clone(),
+        recipient: transfer.recipient.clone(),
+        sender_validation_id,
+        recipient_validation_id,
+        amount_commitment: transfer.amount_commitment.clone(),
+        sender_finalized: false,
+        recipient_finalized: false,
+        status: TransferStatus::Pending,
+        hashlock,
+        deadline,
+        preimage: None,
+        created_at: env.block.time.seconds(),
+        submitter: info.sender.to_string(),
+    };
+    TRANSFERS.insert(deps.storage, &transfer_id, &record)?;
+    if deadline.is_some() {
+        // The legs were filed before their transfer's deadline was recorded
+        let config = CONFIG.load(deps.storage)?;
+        for validation_id in [&record.sender_validation_id, &record.recipient_validation_id] {
+            let validation = load_pending(deps.storage, validation_id)?;
+            file_work(deps.storage, &config, &validation)?;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", action)
+        .add_attribute("transfer_id", transfer_id)
+        .add_attribute("sender", transfer.sender)
+        .add_attribute("recipient", transfer.recipient)
+        .add_attribute("amount_commitment", hex::encode(transfer.amount_commitment))
+        .add_attributes(leg_attributes("sender", response1.attributes))
+        .add_attributes(leg_attributes("recipient", response2.attributes)))
+}
+
+/// A transfer leg's submit attributes keyed `<leg>_<key>`, so the legs'
+/// validation_id, new_root, ... stay distinguishable. The leg's own action
+/// is dropped in favour of the transfer's.
+fn leg_attributes(leg: &str, attributes: Vec<Attribute>) -> Vec<Attribute> {
+    attributes.into_iter()
+        .filter(|a| a.key != "action")
+        .map(|a| Attribute::new(format!("{}_{}", leg, a.key), a.value))
+        .collect()
+}
+
+fn submit_batch_transfer(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    sender_transition: StateTransition,
+    recipients: Vec<RecipientLeg>,
+) -> StdResult<Response> {
+    if recipients.is_empty() || recipients.len() > MAX_BATCH_RECIPIENTS {
+        return Err(StdError::generic_err(format!(
+            "Batch must have between 1 and {} recipients",
+            MAX_BATCH_RECIPIENTS
+        )));
+    }
+
+    // Every leg is keyed by its user, so users must be distinct within a batch
+    let sender = sender_transition.user_address.clone();
+    let mut seen = vec![sender.clone()];
+    for leg in &recipients {
+        if leg.transition.user_address != leg.recipient {
+            return Err(StdError::generic_err("Recipient transition belongs to another user"));
+        }
+        if seen.contains(&leg.recipient) {
+            return Err(StdError::generic_err(format!("Duplicate batch participant {}", leg.recipient)));
+        }
+        seen.push(leg.recipient.clone());
+    }
+
+    let group_id = format!("{}-{}-batch", env.block.height, sender);
+    if TRANSFER_GROUPS.contains(deps.storage, &group_id) {
+        return Err(StdError::generic_err("Transfer already exists"));
+    }
+
+    let bounty = expiry_bounty(deps.storage, &info.funds, recipients.len() + 1)?;
+    let (sender_validation_id, sender_response) =
+        create_validation(deps.branch(), &env, sender_transition, None, Some(group_id.clone()), bounty.clone())?;
+    let mut attributes = leg_attributes("sender", sender_response.attributes);
+    let mut legs = Vec::with_capacity(recipients.len());
+    for (i, leg) in recipients.into_iter().enumerate() {
+        let (validation_id, leg_response) =
+            create_validation(deps.branch(), &env, leg.transition, None, Some(group_id.clone()), bounty.clone())?;
+        legs.push(GroupLeg {
+            recipient: leg.recipient,
+            validation_id,
+            amount_commitment: leg.amount_commitment,
+        });
+        attributes.extend(leg_attributes(&format!("recipient{}", i), leg_response.attributes));
+    }
+
+    let group = TransferGroup {
+        group_id: group_id.clone(),
+        sender: sender.clone(),
+        sender_validation_id,
+        legs,
+        status: TransferStatus::Pending,
+    };
+    TRANSFER_GROUPS.insert(deps.storage, &group_id, &group)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "submit_batch_transfer")
+        .add_attribute("group_id", group_id)
+        .add_attribute("sender", sender)
+        .add_attribute("recipients", group.legs.len().to_string())
+        .add_attributes(attributes))
+}
+
+/// Finalizes every leg of a batch once all of them have reached threshold.
+/// Returns the group's new status, or None while legs are still outstanding.
+fn settle_group_if_ready(storage: &mut dyn Storage, env: &Env, group_id: &str) -> StdResult<Option<TransferStatus>> {
+    let mut group = TRANSFER_GROUPS.get(storage, &group_id.to_string())
+        .ok_or_else(|| StdError::generic_err("Transfer group not found"))?;
+
+    let mut legs = Vec::with_capacity(group.legs.len() + 1);
+    for validation_id in group.validation_ids() {
+        match PENDING_VALIDATIONS.get(storage, &validation_id) {
+            Some(leg) if leg.threshold_reached && !leg.da_challenge.as_ref().map(|c| c.is_open()).unwrap_or(false) => {
+                if block_reason(storage, &leg)?.is_some() {
+                    return Ok(None);
+                }
+                legs.push(leg)
+            }
+            Some(_) => return Ok(None),
+            None => return Err(StdError::generic_err("Batch leg not pending")),
+        }
+    }
+
+    // A superseded leg cannot apply, so none may
+    if legs.iter().any(|leg| plan_finalization(storage, leg) == FinalizationPlan::Superseded) {
+        for leg in &legs {
+            supersede(storage, env, leg)?;
+        }
+        group.status = TransferStatus::Unwound;
+    } else {
+        let mut state = STATE.load(storage)?;
+        for leg in &legs {
+            apply_finalization(storage, env, &mut state, leg)?;
+        }
+        group.status = TransferStatus::Completed;
+    }
+    TRANSFER_GROUPS.insert(storage, &group_id.to_string(), &group)?;
+    Ok(Some(group.status))
+}
+
+/// Settles a validation that can no longer finalize with `status`. Legs
+/// settle together, so the rest of its batch or transfer unwinds.
+fn fail_validation(
+    storage: &mut dyn Storage,
+    env: &Env,
+    validation: &PendingValidation,
+    status: OutcomeStatus,
+) -> StdResult<()> {
+    if validation.group_id.is_some() {
+        return unwind_group(storage, env, validation, status);
+    }
+
+    clear_pending(storage, validation)?;
+    record_outcome(storage, env, &validation.validation_id, status)?;
+    if let Some(transfer_id) = &validation.transfer_id {
+        if let Some(mut transfer) = TRANSFERS.get(storage, transfer_id) {
+            for leg_id in [&transfer.sender_validation_id, &transfer.recipient_validation_id] {
+                if let Some(leg) = PENDING_VALIDATIONS.get(storage, leg_id) {
+                    clear_pending(storage, &leg)?;
+                    record_outcome(storage, env, leg_id, OutcomeStatus::Unwound)?;
+                }
+            }
+            transfer.status = TransferStatus::Unwound;
+            TRANSFERS.insert(storage, transfer_id, &transfer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Settles a batch whose `rejected` leg can no longer finalize: that leg is
+/// recorded with `status` and every other leg is unwound
+fn unwind_group(
+    storage: &mut dyn Storage,
+    env: &Env,
+    rejected: &PendingValidation,
+    status: OutcomeStatus,
+) -> StdResult<()> {
+    let group_id = rejected.group_id.clone().unwrap_or_default();
+    let mut group = TRANSFER_GROUPS.get(storage, &group_id)
+        .ok_or_else(|| StdError::generic_err("Transfer group not found"))?;
+
+    for validation_id in group.validation_ids() {
+        if validation_id == rejected.validation_id {
+            clear_pending(storage, rejected)?;
+            record_outcome(storage, env, &validation_id, status.clone())?;
+        } else if let Some(leg) = PENDING_VALIDATIONS.get(storage, &validation_id) {
+            clear_pending(storage, &leg)?;
+            record_outcome(storage, env, &validation_id, OutcomeStatus::Unwound)?;
+        }
+    }
+    group.status = TransferStatus::Unwound;
+    TRANSFER_GROUPS.insert(storage, &group_id, &group)
+}
+
+fn reveal_preimage(
+    deps: DepsMut,
+    env: Env,
+    transfer_id: String,
+    preimage: Vec<u8>,
+) -> StdResult<Response> {
+    let mut transfer = TRANSFERS.get(deps.storage, &transfer_id)
+        .ok_or_else(|| StdError::generic_err("Transfer not found"))?;
+
+    let hashlock = match (&transfer.hashlock, transfer.status.clone()) {
+        (Some(hashlock), TransferStatus::Pending) if transfer.preimage.is_none() => hashlock.clone(),
+        _ => return Err(StdError::generic_err("Transfer is not awaiting a preimage")),
+    };
+
+    if let Some(at) = transfer.deadline {
+        if (Deadline { clock: Clock::Time, since: transfer.created_at, at }).passed(deps.storage, &env)? {
+            return Err(StdError::generic_err("Transfer deadline passed"));
+        }
+    }
+    if preimage.len() > MAX_PREIMAGE_SIZE {
+        return Err(StdError::generic_err(format!("Preimage exceeds {} bytes", MAX_PREIMAGE_SIZE)));
+    }
+    if Sha256::digest(&preimage).to_vec() != hashlock {
+        return Err(StdError::generic_err("Preimage does not match hashlock"));
+    }
+
+    // Both legs must be ready so they finalize together
+    let sender_leg = PENDING_VALIDATIONS.get(deps.storage, &transfer.sender_validation_id)
+        .ok_or_else(|| StdError::generic_err("Sender validation not found"))?;
+    let recipient_leg = PENDING_VALIDATIONS.get(deps.storage, &transfer.recipient_validation_id)
+        .ok_or_else(|| StdError::generic_err("Recipient validation not found"))?;
+    if !sender_leg.threshold_reached || !recipient_leg.threshold_reached {
+        return Err(StdError::generic_err("Threshold not reached"));
+    }
+    if block_reason(deps.storage, &sender_leg)?.is_some() || block_reason(deps.storage, &recipient_leg)?.is_some() {
+        return Err(StdError::generic_err("Validation blocked"));
+    }
+    if [&sender_leg, &recipient_leg].iter().any(|leg| leg.da_challenge.as_ref().map(|c| c.is_open()).unwrap_or(false)) {
+        return Err(StdError::generic_err("Data availability challenge open"));
+    }
+
+    // A superseded leg cannot apply, so neither may: unwind the pair
+    if plan_finalization(deps.storage, &sender_leg) == FinalizationPlan::Superseded
+        || plan_finalization(deps.storage, &recipient_leg) == FinalizationPlan::Superseded
+    {
+        supersede(deps.storage, &env, &sender_leg)?;
+        supersede(deps.storage, &env, &recipient_leg)?;
+        transfer.status = TransferStatus::Unwound;
+        TRANSFERS.insert(deps.storage, &transfer_id, &transfer)?;
+        return Ok(Response::new()
+            .add_attribute("action", "reveal_preimage")
+            .add_attribute("transfer_id", transfer_id)
+            .add_attribute("outcome", OutcomeStatus::Superseded.as_str()));
+    }
+
+    transfer.preimage = Some(preimage.clone());
+    TRANSFERS.insert(deps.storage, &transfer_id, &transfer)?;
+
+    let mut state = STATE.load(deps.storage)?;
+    apply_finalization(deps.storage, &env, &mut state, &sender_leg)?;
+    apply_finalization(deps.storage, &env, &mut state, &recipient_leg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "reveal_preimage")
+        .add_attribute("transfer_id", transfer_id)
+        .add_attribute("preimage", hex::encode(preimage))
+        .add_attribute("new_root", hex::encode(state.current_state_root))
+        .add_attribute("block_height", state.block_height.to_string()))
+}
+
+fn resolve_transfer(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    transfer_id: String,
+) -> StdResult<Response> {
+    let mut transfer = TRANSFERS.get(deps.storage, &transfer_id)
+        .ok_or_else(|| StdError::generic_err("Transfer not found"))?;
+
+    if info.sender != transfer.sender && info.sender != transfer.recipient {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    if transfer.status != TransferStatus::Pending || !transfer.is_locked() {
+        return Err(StdError::generic_err("Transfer cannot be unwound"));
+    }
+    let passed = match transfer.deadline {
+        Some(at) => Deadline { clock: Clock::Time, since: transfer.created_at, at }.passed(deps.storage, &env)?,
+        None => false,
+    };
+    if !passed {
+        return Err(StdError::generic_err("Transfer deadline not reached"));
+    }
+
+    // Neither leg can have finalized while locked, so both are still pending
+    for validation_id in [&transfer.sender_validation_id, &transfer.recipient_validation_id] {
+        if let Some(validation) = PENDING_VALIDATIONS.get(deps.storage, validation_id) {
+            clear_pending(deps.storage, &validation)?;
+        }
+        record_outcome(deps.storage, &env, validation_id, OutcomeStatus::Unwound)?;
+    }
+
+    transfer.status = TransferStatus::Unwound;
+    TRANSFERS.insert(deps.storage, &transfer_id, &transfer)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "resolve_transfer")
+        .add_attribute("transfer_id", transfer_id)
+        .add_attribute("status", "unwound"))
+}
+
+/// Loads a pending validation, telling already-settled ids apart from unknown ones
+fn load_pending(storage: &dyn Storage, validation_id: &str) -> StdResult<PendingValidation> {
+    if let Some(validation) = PENDING_VALIDATIONS.get(storage, &validation_id.to_string()) {
+        return Ok(validation);
+    }
+
+    match VALIDATION_OUTCOMES.get(storage, &validation_id.to_string()) {
+        Some(outcome) => Err(StdError::generic_err(format!("Validation already {}", outcome.status.as_str()))),
+        None => Err(StdError::generic_err("Validation not found")),
+    }
+}
+
+/// Removes a pending validation together with its vote presence entries
+fn clear_pending(storage: &mut dyn Storage, validation: &PendingValidation) -> StdResult<()> {
+    for vote in &validation.validations {
+        VOTE_PRESENCE.remove(storage, &vote_presence_key(&validation.validation_id, vote.node_id))?;
+    }
+    charge_storage(storage, &validation.transition.user_address, pending_bytes(validation)?, 0)?;
+    let bonded = CHALLENGE_BONDS.get(storage, &validation.validation_id).is_some();
+    if !validation.subscribers.is_empty() || validation.bounty.is_some() || bonded {
+        let mut settled = SETTLED_VALIDATIONS.may_load(storage)?.unwrap_or_default();
+        settled.push(SettledValidation {
+            validation_id: validation.validation_id.clone(),
+            subscribers: validation.subscribers.clone(),
+            refund: validation.bounty.clone().map(|bounty| (validation.transition.user_address.clone(), bounty)),
+        });
+        SETTLED_VALIDATIONS.save(storage, &settled)?;
+    }
+    for update in &validation.transition.asset_updates {
+        let key = asset_key(&validation.transition.user_address, &update.asset_id);
+        if ASSET_LOCKS.get(storage, &key).as_ref() == Some(&validation.validation_id) {
+            ASSET_LOCKS.remove(storage, &key)?;
+        }
+    }
+    for share in &validation.transition.encrypted_shares {
+        let mut work = NODE_WORK.get(storage, &share.node_id).unwrap_or_default();
+        work.retain(|(_, validation_id)| validation_id != &validation.validation_id);
+        NODE_WORK.insert(storage, &share.node_id, &work)?;
+    }
+    let user_pending = USER_PENDING.add_suffix(validation.transition.user_address.as_bytes());
+    if user_pending.contains(storage, &validation.validation_id) {
+        user_pending.remove(storage, &validation.validation_id)?;
+    }
+    PENDING_VALIDATIONS.remove(storage, &validation.validation_id)
+}
+
+/// Files a pending validation, or refiles it once its deadline is known,
+/// under every node it dealt a share to
+fn file_work(storage: &mut dyn Storage, config: &Config, validation: &PendingValidation) -> StdResult<()> {
+    let key = (expiry_deadline(storage, config, validation).at, validation.validation_id.clone());
+    for share in &validation.transition.encrypted_shares {
+        let mut work = NODE_WORK.get(storage, &share.node_id).unwrap_or_default();
+        work.retain(|(_, validation_id)| validation_id != &validation.validation_id);
+        let position = work.partition_point(|filed| filed < &key);
+        work.insert(position, key.clone());
+        NODE_WORK.insert(storage, &share.node_id, &work)?;
+    }
+    Ok(())
+}
+
+/// Moves a user's storage counter from `released` to `added` bytes for one
+/// entry. Sizes are serialized lengths, so the counter is approximate but
+/// rises and falls with what is actually stored under the user's keys.
+fn charge_storage(storage: &mut dyn Storage, user_address: &str, released: u64, added: u64) -> StdResult<()> {
+    let key = user_address.to_string();
+    let used = USER_STORAGE.get(storage, &key).unwrap_or(0);
+    let used = used.saturating_sub(released) + added;
+    if used == 0 {
+        USER_STORAGE.remove(storage, &key)
+    } else {
+        USER_STORAGE.insert(storage, &key, &used)
+    }
+}
+
+fn pending_bytes(validation: &PendingValidation) -> StdResult<u64> {
+    Ok(to_vec(&validation.transition)?.len() as u64)
+}
+
+fn commitment_bytes(commitment: Option<&StateCommitment>) -> StdResult<u64> {
+    Ok(match commitment {
+        Some(commitment) => to_vec(commitment)?.len() as u64,
+        None => 0,
+    })
+}
+
+fn finalizers_bytes(user_address: &str, nonce: u64, node_ids: &[u32]) -> u64 {
+    (nonce_key(user_address, nonce).len() + 4 * node_ids.len()) as u64
+}
+
+/// Gives `user_address` the next export slot unless it already holds one
+fn order_commitment(storage: &mut dyn Storage, user_address: &str) -> StdResult<()> {
+    let user_address = user_address.to_string();
+    if COMMITMENT_SLOTS.contains(storage, &user_address) {
+        return Ok(());
+    }
+    let slot = COMMITMENT_SEQ.may_load(storage)?.unwrap_or(0);
+    COMMITMENT_ORDER.insert(storage, &slot, &user_address)?;
+    COMMITMENT_SLOTS.insert(storage, &user_address, &slot)?;
+    COMMITMENT_SEQ.save(storage, &(slot + 1))
+}
+
+/// Empties the user's export slot once it holds no commitment
+fn unorder_commitment(storage: &mut dyn Storage, user_address: &str) -> StdResult<()> {
+    let user_address = user_address.to_string();
+    if let Some(slot) = COMMITMENT_SLOTS.get(storage, &user_address) {
+        COMMITMENT_ORDER.remove(storage, &slot)?;
+        COMMITMENT_SLOTS.remove(storage, &user_address)?;
+    }
+    Ok(())
+}
+
+/// Keeps a commitment the user just moved past and drops the one that fell
+/// out of MAX_COMMITMENT_HISTORY
+fn record_history(storage: &mut dyn Storage, user_address: &str, replaced: &StateCommitment) -> StdResult<()> {
+    let key = nonce_key(user_address, replaced.nonce);
+    let overwritten = COMMITMENT_HISTORY.get(storage, &key);
+    charge_storage(storage, user_address, commitment_bytes(overwritten.as_ref())?, commitment_bytes(Some(replaced))?)?;
+    COMMITMENT_HISTORY.insert(storage, &key, replaced)?;
+
+    if replaced.nonce >= MAX_COMMITMENT_HISTORY {
+        let expired_key = nonce_key(user_address, replaced.nonce - MAX_COMMITMENT_HISTORY);
+        if let Some(old) = COMMITMENT_HISTORY.get(storage, &expired_key) {
+            charge_storage(storage, user_address, commitment_bytes(Some(&old))?, 0)?;
+            COMMITMENT_HISTORY.remove(storage, &expired_key)?;
+        }
+    }
+    Ok(())
+}
+
+/// The user's commitment at `height`: the current one if it was confirmed
+/// by then, else the newest kept one that was. A commitment counts once
+/// config.commitment_confirmations blocks have passed since it finalized,
+/// as for GetStateCommitment, and heights past `now` are read as `now`.
+/// Nonces only grow with height, so the walk stops at the first match.
+fn commitment_at_height(storage: &dyn Storage, user_address: &str, height: u64, now: u64) -> StdResult<Option<StateCommitment>> {
+    let confirmations = CONFIG.load(storage)?.commitment_confirmations;
+    let height = height.min(now);
+    let confirmed = |c: &StateCommitment| c.finalized_at_height.saturating_add(confirmations) <= height;
+    let Some(current) = STATE_COMMITMENTS.get(storage, &user_address.to_string()) else {
+        return Ok(None);
+    };
+    if confirmed(&current) {
+        return Ok(Some(current));
+    }
+    let oldest = current.nonce.saturating_sub(MAX_COMMITMENT_HISTORY);
+    for nonce in (oldest..current.nonce).rev() {
+        match COMMITMENT_HISTORY.get(storage, &nonce_key(user_address, nonce)) {
+            Some(commitment) if confirmed(&commitment) => return Ok(Some(commitment)),
+            Some(_) => {}
+            None => return Err(StdError::generic_err("Commitment history no longer kept for that height")),
+        }
+    }
+    if oldest > 0 {
+        return Err(StdError::generic_err("Commitment history no longer kept for that height"));
+    }
+    Ok(None)
+}
+
+/// Records who finalized `nonce` and drops the user's record that fell out
+/// of MAX_FINALIZER_HISTORY
+fn record_finalizers(storage: &mut dyn Storage, user_address: &str, nonce: u64, node_ids: &[u32]) -> StdResult<()> {
+    let key = nonce_key(user_address, nonce);
+    let replaced = COMMITMENT_FINALIZERS.get(storage, &key)
+        .map(|old| finalizers_bytes(user_address, nonce, &old))
+        .unwrap_or(0);
+    charge_storage(storage, user_address, replaced, finalizers_bytes(user_address, nonce, node_ids))?;
+    COMMITMENT_FINALIZERS.insert(storage, &key, &node_ids.to_vec())?;
+
+    if nonce >= MAX_FINALIZER_HISTORY {
+        let expired = nonce - MAX_FINALIZER_HISTORY;
+        let expired_key = nonce_key(user_address, expired);
+        if let Some(old) = COMMITMENT_FINALIZERS.get(storage, &expired_key) {
+            charge_storage(storage, user_address, finalizers_bytes(user_address, expired, &old), 0)?;
+            COMMITMENT_FINALIZERS.remove(storage, &expired_key)?;
+        }
+    }
+    Ok(())
+}
+
+fn effort_key(epoch: u64, node_id: u32) -> String {
+    format!("{}/{}", epoch, node_id)
+}
+
+fn record_effort(storage: &mut dyn Storage, epoch: u64, node_id: u32, units: u64) -> StdResult<()> {
+    let key = effort_key(epoch, node_id);
+    let mut effort = NODE_EFFORT.get(storage, &key).unwrap_or_default();
+    effort.votes += 1;
+    effort.effort_units += units;
+    NODE_EFFORT.insert(storage, &key, &effort)
+}
+
+/// Splits `amount` among the nodes that voted in `epoch`. Bounded by the
+/// committee size, not by how many votes were cast.
+fn reward_split(storage: &dyn Storage, state: &State, policy: RewardPolicy, epoch: u64, amount: Uint128) -> RewardSplitResponse {
+    let efforts: Vec<(u32, NodeEffort)> = state.mpc_nodes.iter()
+        .filter_map(|n| NODE_EFFORT.get(storage, &effort_key(epoch, n.node_id)).map(|e| (n.node_id, e)))
+        .filter(|(_, e)| e.votes > 0)
+        .collect();
+
+    let weight = |effort: &NodeEffort| match policy {
+        RewardPolicy::Equal => 1u128,
+        RewardPolicy::EffortWeighted => effort.effort_units as u128,
+    };
+    let total_weight: u128 = efforts.iter().map(|(_, e)| weight(e)).sum();
+
+    let payouts: Vec<NodePayout> = efforts.iter()
+        .map(|(node_id, effort)| NodePayout {
+            node_id: *node_id,
+            amount: match total_weight {
+                0 => Uint128::zero(),
+                total => amount.multiply_ratio(weight(effort), total),
+            },
+        })
+        .collect();
+    let paid: Uint128 = payouts.iter().map(|p| p.amount).sum();
+    RewardSplitResponse { policy, payouts, remainder: amount - paid }
+}
+
+fn nonce_key(user_address: &str, nonce: u64) -> String {
+    format!("{}/{}", user_address, nonce)
+}
+
+fn vote_presence_key(validation_id: &str, node_id: u32) -> String {
+    format!("{}/{}", validation_id, node_id)
+}
+
+fn asset_key(user_address: &str, asset_id: &str) -> String {
+    format!("{}/{}", user_address, asset_id)
+}
+
+fn record_outcome(
+    storage: &mut dyn Storage,
+    env: &Env,
+    validation_id: &str,
+    status: OutcomeStatus,
+) -> StdResult<()> {
+    // Every settlement passes through here, so the counters never need a scan
+    let mut stats = STATS.load(storage)?;
+    let counts = &mut stats.validation_counts;
+    counts.pending = counts.pending.saturating_sub(1);
+    match status {
+        OutcomeStatus::Finalized => counts.finalized += 1,
+        OutcomeStatus::Superseded | OutcomeStatus::Rejected | OutcomeStatus::DataUnavailable
+            | OutcomeStatus::DependencyFailed => counts.rejected += 1,
+        OutcomeStatus::Unwound | OutcomeStatus::Cancelled | OutcomeStatus::Expired => counts.expired += 1,
+    }
+    STATS.save(storage, &stats)?;
+
+    let failed = status != OutcomeStatus::Finalized;
+    VALIDATION_OUTCOMES.insert(storage, &validation_id.to_string(), &ValidationOutcome {
+        validation_id: validation_id.to_string(),
+        status,
+        settled_at: env.block.time.seconds(),
+        precedence: None,
+        contested: false,
+    })?;
+    index_outcome(storage, validation_id)?;
+
+    // Whatever stops a validation from finalizing stops its dependents too
+    if failed {
+        for dependent in take_dependents(storage, validation_id)? {
+            if let Some(pending) = PENDING_VALIDATIONS.get(storage, &dependent) {
+                fail_validation(storage, env, &pending, OutcomeStatus::DependencyFailed)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Files a settled validation under its user for PurgeMyHistory
+fn index_outcome(storage: &mut dyn Storage, validation_id: &str) -> StdResult<()> {
+    if let Some((_, user_address)) = validation_id.split_once('-') {
+        USER_OUTCOMES.add_suffix(user_address.as_bytes()).insert(storage, &validation_id.to_string())?;
+    }
+    Ok(())
+}
+
+/// Marks a recorded outcome as contested and decided by `rule`
+fn note_precedence(storage: &mut dyn Storage, validation_id: &str, rule: VotePrecedence) -> StdResult<()> {
+    let key = validation_id.to_string();
+    let mut outcome = VALIDATION_OUTCOMES.get(storage, &key)
+        .ok_or_else(|| StdError::generic_err("Outcome not found"))?;
+    outcome.precedence = Some(rule);
+    outcome.contested = true;
+    VALIDATION_OUTCOMES.insert(storage, &key, &outcome)
+}
+
+/// Builds a response whose attributes are filtered by the configured
+/// verbosity. Only events are affected; `set_data` payloads are not.
+struct Events {
+    verbosity: EventVerbosity,
+    response: Response,
+}
+
+impl Events {
+    fn new(verbosity: EventVerbosity) -> Self {
+        Events { verbosity, response: Response::new() }
+    }
+
+    fn at(mut self, level: EventVerbosity, key: &str, value: impl Into<String>) -> Self {
+        if self.verbosity >= level {
+            self.response = self.response.add_attribute(key, value);
+        }
+        self
+    }
+
+    fn minimal(self, key: &str, value: impl Into<String>) -> Self {
+        self.at(EventVerbosity::Minimal, key, value)
+    }
+
+    fn standard(self, key: &str, value: impl Into<String>) -> Self {
+        self.at(EventVerbosity::Standard, key, value)
+    }
+
+    fn debug(self, key: &str, value: impl Into<String>) -> Self {
+        self.at(EventVerbosity::Debug, key, value)
+    }
+
+    fn minimal_if_some(self, key: &str, value: Option<impl Into<String>>) -> Self {
+        match value {
+            Some(value) => self.minimal(key, value),
+            None => self,
+        }
+    }
+
+    /// Typed events go out whatever the verbosity
+    fn events(mut self, events: impl IntoIterator<Item = Event>) -> Self {
+        self.response = self.response.add_events(events);
+        self
+    }
+
+    fn into_response(self) -> Response {
+        self.response
+    }
+}
+
+/// Remembers `vote` among the node's recent content votes. A recent vote on
+/// the same content with the opposite outcome, cast while the user's
+/// committed root and nonce were the same, is recorded as equivocation and
+/// counted as an offense; config.equivocation_limit offenses deactivate the
+/// node. Once the user's state has moved, a stale resubmission can honestly
+/// be judged differently.
+fn note_content_vote(
+    storage: &mut dyn Storage,
+    config: &Config,
+    state: &mut State,
+    node_id: u32,
+    vote: ContentVote,
+) -> StdResult<Option<Event>> {
+    let vote_height = vote.voted_at;
+    let mut recent = CONTENT_VOTES.get(storage, &node_id).unwrap_or_default();
+    let contradicted = recent.iter()
+        .rev()
+        .find(|v| {
+            v.content_hash == vote.content_hash
+                && v.valid != vote.valid
+                && v.committed_root == vote.committed_root
+                && v.committed_nonce == vote.committed_nonce
+        })
+        .cloned();
+    recent.push(vote.clone());
+    if recent.len() > MAX_CONTENT_VOTES {
+        recent.remove(0);
+    }
+    CONTENT_VOTES.insert(storage, &node_id, &recent)?;
+
+    let first = match contradicted {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    let event = Event::new("equivocation")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("content_hash", hex::encode(&vote.content_hash))
+        .add_attribute("first_validation_id", first.validation_id.clone())
+        .add_attribute("first_valid", first.valid.to_string())
+        .add_attribute("second_validation_id", vote.validation_id.clone())
+        .add_attribute("second_valid", vote.valid.to_string());
+
+    let mut evidence = EQUIVOCATIONS.get(storage, &node_id).unwrap_or_default();
+    evidence.push(EquivocationEvidence { node_id, content_hash: vote.content_hash.clone(), first, second: vote });
+    if evidence.len() > MAX_EQUIVOCATIONS {
+        evidence.remove(0);
+    }
+    EQUIVOCATIONS.insert(storage, &node_id, &evidence)?;
+
+    let node = get_node_mut(state, node_id)?;
+    node.offenses += 1;
+    let offenses = node.offenses;
+    let deactivated = config.equivocation_limit > 0 && offenses >= config.equivocation_limit && node.active;
+    if deactivated {
+        freeze_epoch(storage, state)?;
+        get_node_mut(state, node_id)?.active = false;
+        start_epoch(storage, state, vote_height)?;
+    }
+    Ok(Some(event
+        .add_attribute("offenses", offenses.to_string())
+        .add_attribute("deactivated", deactivated.to_string())))
+}
+
+/// SHA-256 over the active committee's node ids and public keys
+fn committee_hash(storage: &dyn Storage, state: &State) -> StdResult<Vec<u8>> {
+    let identity = DEPLOYMENT.load(storage)?;
+    let mut hasher = Sha256::new();
+    hasher.update(deployment_prefix(&identity.chain_id, &identity.contract_address));
+    for node in state.mpc_nodes.iter().filter(|n| n.active) {
+        hasher.update(node.node_id.to_be_bytes());
+        hasher.update(&node.public_key);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+fn classify(validation: &PendingValidation) -> OperationKind {
+    match validation.transfer_id {
+        Some(_) => OperationKind::Transfer,
+        None => OperationKind::StateTransition,
+    }
+}
+
+/// Valid votes needed to finalize: the threshold, or every active node for
+/// operations configured as unanimous.
+fn required_votes(storage: &dyn Storage, state: &State, validation: &PendingValidation) -> StdResult<usize> {
+    let config = CONFIG.load(storage)?;
+    let threshold = state.threshold as usize;
+    if config.unanimous_operations.contains(&classify(validation)) {
+        let active = state.mpc_nodes.iter().filter(|n| n.active).count();
+        return Ok(active.max(threshold));
+    }
+    Ok(threshold)
+}
+
+#[derive(Debug, PartialEq)]
+enum FinalizationPlan {
+    Apply,
+    Superseded,
+}
+
+/// Decides whether a validation that reached threshold may still be applied.
+/// Two transitions for the same user built on the same old root are mutually
+/// exclusive: once one finalizes, the other's old root no longer matches the
+/// user's leaf. A user's first transition has no leaf to conflict with.
+/// Asset updates are all-or-nothing: one stale old leaf supersedes the lot.
+fn plan_finalization(storage: &dyn Storage, validation: &PendingValidation) -> FinalizationPlan {
+    let current = STATE_COMMITMENTS.get(storage, &validation.transition.user_address);
+    if current.as_ref().is_some_and(|c| c.state_root != validation.transition.old_state_root) {
+        return FinalizationPlan::Superseded;
+    }
+    let held = current.map(|c| c.asset_leaves).unwrap_or_default();
+    let stale = validation.transition.asset_updates.iter().any(|update| {
+        let leaf = held.iter().find(|a| a.asset_id == update.asset_id).map_or(&[][..], |a| a.leaf.as_slice());
+        leaf != update.old_leaf.as_slice()
+    });
+    if stale {
+        return FinalizationPlan::Superseded;
+    }
+    FinalizationPlan::Apply
+}
+
+/// A user's asset leaves after `updates`, kept sorted by asset_id
+fn apply_asset_updates(mut leaves: Vec<AssetLeaf>, updates: &[AssetUpdate]) -> Vec<AssetLeaf> {
+    for update in updates {
+        match leaves.binary_search_by(|a| a.asset_id.cmp(&update.asset_id)) {
+            Ok(i) => leaves[i].leaf = update.new_leaf.clone(),
+            Err(i) => leaves.insert(i, AssetLeaf { asset_id: update.asset_id.clone(), leaf: update.new_leaf.clone() }),
+        }
+    }
+    leaves
+}
+
+/// Block time deadline of a pending validation: its timeout, or an earlier
+/// transfer deadline
+fn expiry_deadline(storage: &dyn Storage, config: &Config, validation: &PendingValidation) -> Deadline {
+    let deadline = validation.transfer_id.as_ref()
+        .and_then(|transfer_id| TRANSFERS.get(storage, transfer_id))
+        .and_then(|transfer| transfer.deadline);
+    let expires_at = validation.created_at + config.validation_timeout;
+    let at = deadline.map_or(expires_at, |deadline| deadline.min(expires_at));
+    Deadline { clock: Clock::Time, since: validation.created_at, at }
+}
+
+/// Height deadline of a pending validation: the first block past
+/// config.max_validation_blocks. Chains with irregular block times bound it
+/// by height instead of, or as well as, time; validations that predate
+/// submission_height are bounded by time only.
+fn block_window_deadline(config: &Config, validation: &PendingValidation) -> Option<Deadline> {
+    (config.max_validation_blocks > 0 && validation.submission_height > 0).then(|| Deadline {
+        clock: Clock::Height,
+        since: validation.submission_height,
+        at: validation.submission_height + config.max_validation_blocks + 1,
+    })
+}
+
+/// Block time a pending validation expires at, pushed back by any pause
+/// since it was created
+fn validation_expires_at(storage: &dyn Storage, validation: &PendingValidation, now: u64) -> StdResult<u64> {
+    expiry_deadline(storage, &CONFIG.load(storage)?, validation).adjusted(storage, now)
+}
+
+/// Whether a validation has outlived its block window, not counting blocks
+/// spent paused
+fn past_block_window(storage: &dyn Storage, config: &Config, validation: &PendingValidation, env: &Env) -> StdResult<bool> {
+    match block_window_deadline(config, validation) {
+        Some(deadline) => deadline.passed(storage, env),
+        None => Ok(false),
+    }
+}
+
+/// Whether either of a validation's deadlines has passed
+fn validation_expired(storage: &dyn Storage, config: &Config, validation: &PendingValidation, env: &Env) -> StdResult<bool> {
+    Ok(expiry_deadline(storage, config, validation).passed(storage, env)?
+        || past_block_window(storage, config, validation, env)?)
+}
+
+/// One precondition of apply_finalization: DiagnoseValidation reports
+/// `check` and `detail`, and FinalizeTransition refuses with `error` when it
+/// fails. Checks without an error are settled by plan_finalization instead.
+struct Precondition {
+    check: &'static str,
+    passed: bool,
+    detail: String,
+    error: Option<String>,
+}
+
+/// Every precondition finalize_transition applies before apply_finalization,
+/// in the order it applies them, so a stuck validation shows which one holds
+/// it. Nothing is written.
+fn finalization_preconditions(deps: Deps, env: &Env, validation: &PendingValidation) -> StdResult<Vec<Precondition>> {
+    let state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
+    let valid: Vec<&NodeValidation> = validation.validations.iter().filter(|v| v.valid).collect();
+    let required = required_votes(deps.storage, &state, validation)?;
+
+    // Signers' keys in the committee the validation was submitted to
+    let signers: Vec<u32> = valid.iter().map(|v| v.node_id).collect();
+    let committee = committee_keys(deps.storage, &state, validation.epoch, &signers);
+    let keys: Vec<(&NodeValidation, Option<&[u8]>)> = valid.iter()
+        .map(|v| {
+            let key = committee.iter()
+                .find(|k| k.node_id == v.node_id)
+                .map(|k| k.public_key.as_slice())
+                .filter(|k| !k.is_empty());
+            (*v, key)
+        })
+        .collect();
+    let missing: Vec<String> = keys.iter()
+        .filter(|(_, key)| key.is_none())
+        .map(|(v, _)| v.node_id.to_string())
+        .collect();
+
+    // BLS partials are checked when the aggregate is, not one by one
+    let identity = DEPLOYMENT.load(deps.storage)?;
+    let asset_digest = asset_updates_digest(&validation.transition.asset_updates);
+    let mut unverified = Vec::new();
+    for (vote, key) in &keys {
+        let Some(key) = key.filter(|k| key_type(k) == KeyType::Secp256k1) else { continue };
+        let digest = vote_digest(
+            &identity.chain_id,
+            &identity.contract_address,
+            vote.signed_validation_id(&validation.validation_id),
+            validation.protocol_version,
+            &validation.transition.new_state_root,
+            &asset_digest,
+        );
+        if !deps.api.secp256k1_verify(&digest, &vote.partial_signature, key).unwrap_or(false) {
+            unverified.push(vote.node_id.to_string());
+        }
+    }
+
+    let superseded = plan_finalization(deps.storage, validation) == FinalizationPlan::Superseded;
+    let unmet = unmet_dependencies(deps.storage, &validation.transition);
+    let now = env.block.time.seconds();
+    let expires_at = validation_expires_at(deps.storage, validation, now)?;
+    let window_closed = past_block_window(deps.storage, &config, validation, env)?;
+
+    // Every lock holding the validation, with what FinalizeTransition says
+    // about it
+    let hashlocked = validation.transfer_id.as_ref()
+        .and_then(|transfer_id| TRANSFERS.get(deps.storage, transfer_id))
+        .is_some_and(|t| t.is_locked());
+    let blocked = block_reason(deps.storage, validation)?.map(|reason| {
+        let error = match &reason {
+            BlockReason::RootDisagreement { .. } => "Validation blocked: root disagreement".to_string(),
+            BlockReason::KeyCompromised { node_id } => format!("Validation blocked: key of node {} compromised", node_id),
+        };
+        (reason.as_str(), error)
+    });
+    let locks: Vec<(&str, String)> = [
+        blocked,
+        validation.da_challenge.as_ref().is_some_and(|c| c.is_open())
+            .then(|| ("data_challenge", "Data availability challenge open".to_string())),
+        hashlocked.then(|| ("hashlock", "Transfer locked until preimage is revealed".to_string())),
+        validation.group_id.is_some().then(|| ("batch_leg", "Batch legs finalize together".to_string())),
+    ].into_iter().flatten().collect();
+
+    let precondition = |check, passed, detail: String, error: Option<String>| Precondition { check, passed, detail, error };
+    Ok(vec![
+        precondition(
+            "lock_consistent",
+            locks.is_empty(),
+            locks.iter().map(|(lock, _)| *lock).collect::<Vec<_>>().join(","),
+            locks.first().map(|(_, error)| error.clone()),
+        ),
+        precondition(
+            "threshold_met",
+            valid.len() >= required,
+            format!("{}/{} valid votes", valid.len(), required),
+            Some("Threshold not reached".to_string()),
+        ),
+        precondition(
+            "committee_keys_present",
+            missing.is_empty(),
+            if missing.is_empty() { "all signers registered".to_string() } else { format!("no key for nodes {}", missing.join(",")) },
+            Some(format!("No committee key for nodes {}", missing.join(","))),
+        ),
+        precondition(
+            "aggregate_verifiable",
+            unverified.is_empty(),
+            if unverified.is_empty() { "partials verify".to_string() } else { format!("partials fail for nodes {}", unverified.join(",")) },
+            Some(format!("Partial signatures do not verify for nodes {}", unverified.join(","))),
+        ),
+        precondition("dependencies_met", unmet.is_empty(), unmet.join(","), Some("Dependencies not finalized".to_string())),
+        precondition(
+            "deadline_ok",
+            now < expires_at && !window_closed,
+            if window_closed { "block window closed".to_string() } else { format!("expires at {}", expires_at) },
+            Some(if window_closed { "Validation window closed" } else { "Validation expired" }.to_string()),
+        ),
+        precondition(
+            "context_finalizable",
+            !superseded,
+            if superseded { "user root moved past old_state_root".to_string() } else { "old_state_root matches".to_string() },
+            None,
+        ),
+    ])
+}
+
+/// The error FinalizeTransition refuses a validation with, if any
+fn finalization_error(deps: Deps, env: &Env, validation: &PendingValidation) -> StdResult<Option<String>> {
+    Ok(finalization_preconditions(deps, env, validation)?.into_iter()
+        .find(|p| !p.passed && p.error.is_some())
+        .and_then(|p| p.error))
+}
+
+/// finalization_preconditions as DiagnoseValidation reports them
+fn finalization_checks(deps: Deps, env: &Env, validation: &PendingValidation) -> StdResult<Vec<FinalizationCheck>> {
+    Ok(finalization_preconditions(deps, env, validation)?.into_iter()
+        .map(|p| FinalizationCheck { check: p.check.to_string(), passed: p.passed, detail: p.detail })
+        .collect())
+}
+
+/// Settles a validation without applying it; clear_pending refunds its bounty.
+fn supersede(storage: &mut dyn Storage, env: &Env, validation: &PendingValidation) -> StdResult<()> {
+    clear_pending(storage, validation)?;
+    record_outcome(storage, env, &validation.validation_id, OutcomeStatus::Superseded)
+}
+
+/// Applies a validation that reached threshold: aggregates the partial
+/// signatures, advances the global root and stores the user's commitment.
+fn apply_finalization(
+    storage: &mut dyn Storage,
+    env: &Env,
+    state: &mut State,
+    validation: &PendingValidation,
+) -> StdResult<Finalization> {
+    let threshold_signature = aggregate_signatures(&validation.validations);
+    let root_before = state.current_state_root.clone();
+
+    // Update state root (THIS IS THE KEY!)
+    // The new state root becomes part of the global Merkle tree
+    state.current_state_root = validation.transition.new_state_root.clone();
+    state.block_height += 1;
+
+    // Remember the aggregate for light client updates
+    state.last_validation_id = validation.validation_id.clone();
+    state.last_threshold_signature = threshold_signature.clone();
+    state.last_protocol_version = validation.protocol_version;
+    state.last_epoch = validation.epoch;
+    state.last_asset_digest = asset_updates_digest(&validation.transition.asset_updates);
+    state.last_signers = validation.validations.iter()
+        .filter(|v| v.valid)
+        .map(|v| v.node_id)
+        .collect();
+    state.last_signed_ids = validation.validations.iter()
+        .filter(|v| v.valid)
+        .map(|v| v.signed_validation_id(&validation.validation_id).to_string())
+        .collect();
+
+    // Keys each signer was checked against, frozen for audits
+    let keys = committee_keys(storage, state, validation.epoch, &state.last_signers);
+    let signer_keys = state.last_signers.iter()
+        .map(|node_id| EpochMember {
+            node_id: *node_id,
+            public_key: keys.iter().find(|k| k.node_id == *node_id).map(|k| k.public_key.clone()).unwrap_or_default(),
+        })
+        .collect();
+
+    let config = CONFIG.load(storage)?;
+    let record = FinalizedRecord {
+        validation_id: validation.validation_id.clone(),
+        user_address: validation.transition.user_address.clone(),
+        new_root: state.current_state_root.clone(),
+        threshold_signature: threshold_signature.clone(),
+        signers: state.last_signers.clone(),
+        partials: retained_partials(&config.retain_partials, &validation.validations),
+        key_epoch: validation.key_epoch,
+        asset_digest: state.last_asset_digest.clone(),
+        audit: Some(AuditTrail {
+            old_state_root: validation.transition.old_state_root.clone(),
+            new_state_ipfs: validation.transition.new_state_ipfs.clone(),
+            content_hash: validation.transition.content_hash.clone(),
+            protocol_version: validation.protocol_version,
+            transition_hash: transition_content_hash(&validation.transition)?,
+            asset_updates: validation.transition.asset_updates.clone(),
+            epoch: validation.epoch,
+            signed_validation_ids: state.last_signed_ids.clone(),
+            signer_keys,
+            root_before,
+        }),
+    };
+    let bundle_hash = audit_bundle(storage, state.block_height, &record, false)?.bundle_hash;
+    ROOT_HISTORY.insert(storage, &state.block_height, &state.current_state_root)?;
+    FINALIZED_RECORDS.insert(storage, &state.block_height, &record)?;
+    FINALIZED_HEIGHTS.insert(storage, &validation.validation_id, &state.block_height)?;
+    LAST_FINALIZED.save(storage, &LastFinalized {
+        validation_id: validation.validation_id.clone(),
+        user_address: validation.transition.user_address.clone(),
+        new_root: state.current_state_root.clone(),
+        block_height: state.block_height,
+        finalized_at_height: env.block.height,
+        threshold_signature: threshold_signature.clone(),
+        signers: state.last_signers.clone(),
+    })?;
+    let pruned = prune_root_history(storage, state, config.root_history_retention)?;
+    prune_partials(storage, state, config.partial_retention)?;
+
+    STATE.save(storage, state)?;
+
+    // Reserve the user's leaf (collisions were rejected at submit)
+    let user_address = &validation.transition.user_address;
+    let index = leaf_index(user_address, config.tree_depth);
+    match LEAF_OWNERS.get(storage, &index) {
+        Some(owner) if &owner != user_address => {
+            return Err(StdError::generic_err(format!("Leaf index {} collides with another user", index)));
+        }
+        Some(_) => {}
+        None => LEAF_OWNERS.insert(storage, &index, user_address)?,
+    }
+
+    // Per-user updated_at never goes backwards, even if block time does
+    let previous = STATE_COMMITMENTS.get(storage, user_address);
+    let (updated_at, clamped) = monotonic_updated_at(previous.as_ref(), env.block.time.seconds());
+
+    // Store state commitment
+    let commitment = StateCommitment {
+        user_address: user_address.clone(),
+        state_root: validation.transition.new_state_root.clone(),
+        ipfs_cid: validation.transition.new_state_ipfs.clone(),
+        ipfs_gateway_hint: validation.transition.ipfs_gateway_hint.clone(),
+        merkle_proof: serialize_merkle_proof(&validation.transition.merkle_proof),
+        nonce: previous.as_ref().map(|p| p.nonce + 1).unwrap_or(0),
+        updated_at,
+        leaf_index: index,
+        clamped,
+        lock_at_height: env.block.height + config.challenge_window,
+        finalized_at_height: env.block.height,
+        content_hash: validation.transition.content_hash.clone(),
+        epoch: validation.epoch,
+        committee_hash: committee_hash(storage, state)?,
+        threshold_signature: threshold_signature.clone(),
+        // Every listed asset moves in this one write, or none does
+        asset_leaves: apply_asset_updates(
+            previous.as_ref().map(|p| p.asset_leaves.clone()).unwrap_or_default(),
+            &validation.transition.asset_updates,
+        ),
+    };
+
+    // Keep what a challenge would restore
+    let replaced = PREVIOUS_COMMITMENTS.get(storage, user_address);
+    match &previous {
+        Some(previous) if config.challenge_window > 0 => {
+            charge_storage(storage, user_address, commitment_bytes(replaced.as_ref())?, commitment_bytes(Some(previous))?)?;
+            PREVIOUS_COMMITMENTS.insert(storage, user_address, previous)?
+        }
+        _ => {
+            charge_storage(storage, user_address, commitment_bytes(replaced.as_ref())?, 0)?;
+            PREVIOUS_COMMITMENTS.remove(storage, user_address)?
+        }
+    }
+
+    // Move the user's entry in the CID reverse index to the new pointer
+    if let Some(previous) = &previous {
+        if CID_OWNERS.get(storage, &previous.ipfs_cid).as_ref() == Some(user_address) {
+            CID_OWNERS.remove(storage, &previous.ipfs_cid)?;
+        }
+    }
+    CID_OWNERS.insert(storage, &commitment.ipfs_cid, user_address)?;
+
+    charge_storage(storage, user_address, commitment_bytes(previous.as_ref())?, commitment_bytes(Some(&commitment))?)?;
+    STATE_COMMITMENTS.insert(storage, &commitment.user_address, &commitment)?;
+    order_commitment(storage, user_address)?;
+    FINALIZED_BY.insert(storage, user_address, &validation.validation_id)?;
+    record_finalizers(storage, user_address, commitment.nonce, &state.last_signers)?;
+    if let Some(previous) = &previous {
+        record_history(storage, user_address, previous)?;
+    }
+
+    // Remove pending validation
+    clear_pending(storage, validation)?;
+    record_outcome(storage, env, &validation.validation_id, OutcomeStatus::Finalized)?;
+    if let Some(rule) = validation.precedence_fired {
+        note_precedence(storage, &validation.validation_id, rule)?;
+    }
+
+    // Track transfer completion across both legs
+    if let Some(transfer_id) = &validation.transfer_id {
+        if let Some(mut transfer) = TRANSFERS.get(storage, transfer_id) {
+            if transfer.sender_validation_id == validation.validation_id {
+                transfer.sender_finalized = true;
+            }
+            if transfer.recipient_validation_id == validation.validation_id {
+                transfer.recipient_finalized = true;
+            }
+            if transfer.sender_finalized && transfer.recipient_finalized {
+                transfer.status = TransferStatus::Completed;
+            }
+            TRANSFERS.insert(storage, transfer_id, &transfer)?;
+        }
+    }
+
+    release_dependents(storage, env, state, &validation.validation_id)?;
+
+    Ok(Finalization { threshold_signature, bundle_hash, pruned, clamped })
+}
+
+/// Settles dependents of a just-finalized validation that were only waiting
+/// on it. Chains are bounded by MAX_DEPENDENCY_DEPTH at submit.
+fn release_dependents(storage: &mut dyn Storage, env: &Env, state: &mut State, validation_id: &str) -> StdResult<()> {
+    for dependent in DEPENDENTS.get(storage, &validation_id.to_string()).unwrap_or_default() {
+        let Some(pending) = PENDING_VALIDATIONS.get(storage, &dependent) else {
+            continue;
+        };
+        let challenged = pending.da_challenge.as_ref().map(|c| c.is_open()).unwrap_or(false);
+        if !pending.threshold_reached || block_reason(storage, &pending)?.is_some() || challenged
+            || !unmet_dependencies(storage, &pending.transition).is_empty()
+        {
+            continue;
+        }
+        match plan_finalization(storage, &pending) {
+            FinalizationPlan::Superseded => supersede(storage, env, &pending)?,
+            FinalizationPlan::Apply => {
+                apply_finalization(storage, env, state, &pending)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_dependent(storage: &mut dyn Storage, dependency: &str, validation_id: &str) -> StdResult<()> {
+    let key = dependency.to_string();
+    let mut dependents = live_dependents(storage, dependency);
+    if !dependents.iter().any(|id| id == validation_id) {
+        dependents.push(validation_id.to_string());
+        DEPENDENTS.insert(storage, &key, &dependents)?;
+    }
+    Ok(())
+}
+
+/// Dependents of `dependency` that are pending, finalized, or whose outcome
+/// was purged; the ones that failed no longer hold a slot
+fn live_dependents(storage: &dyn Storage, dependency: &str) -> Vec<String> {
+    DEPENDENTS.get(storage, &dependency.to_string())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|id| PENDING_VALIDATIONS.contains(storage, id)
+            || VALIDATION_OUTCOMES.get(storage, id).is_none_or(|o| o.status == OutcomeStatus::Finalized))
+        .collect()
+}
+
+fn take_dependents(storage: &mut dyn Storage, validation_id: &str) -> StdResult<Vec<String>> {
+    let key = validation_id.to_string();
+    let dependents = DEPENDENTS.get(storage, &key).unwrap_or_default();
+    if !dependents.is_empty() {
+        DEPENDENTS.remove(storage, &key)?;
+    }
+    Ok(dependents)
+}
+
+/// Declared dependencies of `transition` that have not finalized yet
+fn unmet_dependencies(storage: &dyn Storage, transition: &StateTransition) -> Vec<String> {
+    transition.depends_on.iter()
+        .filter(|id| VALIDATION_OUTCOMES.get(storage, id).map(|o| o.status) != Some(OutcomeStatus::Finalized))
+        .cloned()
+        .collect()
+}
+
+/// Every dependency must be pending or finalized with room for another
+/// dependent, and following pending dependencies back must neither return to
+/// `validation_id` nor run deeper than MAX_DEPENDENCY_DEPTH.
+fn check_dependencies(storage: &dyn Storage, validation_id: &str, depends_on: &[String]) -> StdResult<()> {
+    if depends_on.len() > MAX_DEPENDENCIES {
+        return Err(StdError::generic_err(format!("At most {} dependencies", MAX_DEPENDENCIES)));
+    }
+
+    let mut frontier = Vec::new();
+    for (i, dependency) in depends_on.iter().enumerate() {
+        if depends_on[..i].contains(dependency) {
+            return Err(StdError::generic_err(format!("Duplicate dependency {}", dependency)));
+        }
+        if live_dependents(storage, dependency).len() >= MAX_DEPENDENTS {
+            return Err(StdError::generic_err(format!(
+                "Dependency {} already has {} dependents",
+                dependency, MAX_DEPENDENTS
+            )));
+        }
+        if let Some(pending) = PENDING_VALIDATIONS.get(storage, dependency) {
+            frontier.push(pending);
+            continue;
+        }
+        match VALIDATION_OUTCOMES.get(storage, dependency) {
+            Some(outcome) if outcome.status == OutcomeStatus::Finalized => {}
+            Some(outcome) => {
+                return Err(StdError::generic_err(format!("Dependency {} already {}", dependency, outcome.status.as_str())));
+            }
+            None => return Err(StdError::generic_err(format!("Dependency {} not found", dependency))),
+        }
+    }
+
+    let mut visited: Vec<String> = Vec::new();
+    let mut depth = 1;
+    while !frontier.is_empty() {
+        if frontier.iter().any(|v| v.validation_id == validation_id) {
+            return Err(StdError::generic_err("Dependency cycle"));
+        }
+        if depth > MAX_DEPENDENCY_DEPTH {
+            return Err(StdError::generic_err(format!("Dependency chain deeper than {}", MAX_DEPENDENCY_DEPTH)));
+        }
+        visited.extend(frontier.iter().map(|v| v.validation_id.clone()));
+        frontier = frontier.iter()
+            .flat_map(|v| v.transition.depends_on.iter())
+            .filter(|id| !visited.contains(id))
+            .filter_map(|id| PENDING_VALIDATIONS.get(storage, id))
+            .collect();
+        depth += 1;
+    }
+    Ok(())
+}
+
+struct Finalization {
+    threshold_signature: Vec<u8>,
+    bundle_hash: Vec<u8>,  // audit_bundle_hash of the finalization's audit bundle
+    pruned: u64,  // Root history entries removed by this finalization
+    clamped: bool,  // The commitment's updated_at had to be raised
+}
+
+impl Finalization {
+    fn warning(&self) -> Option<&'static str> {
+        self.clamped.then_some("updated_at_clamped")
+    }
+}
+
+/// Timestamp for a commitment write: the block time, or one second past the
+/// stored value if the block time is lower. Equal timestamps are allowed.
+fn monotonic_updated_at(previous: Option<&StateCommitment>, now: u64) -> (u64, bool) {
+    match previous {
+        Some(previous) if now < previous.updated_at => (previous.updated_at + 1, true),
+        _ => (now, false),
+    }
+}
+
+/// Advances the retention cursor by at most PRUNE_PER_FINALIZATION heights,
+/// removing the entries it passes. The caller saves `state`.
+fn prune_root_history(storage: &mut dyn Storage, state: &mut State, retention: u64) -> StdResult<u64> {
+    // Keep heights (block_height - retention, block_height]
+    let keep_from = state.block_height.saturating_sub(retention) + 1;
+    let mut pruned = 0;
+    while state.oldest_retained_height < keep_from && pruned < PRUNE_PER_FINALIZATION {
+        ROOT_HISTORY.remove(storage, &state.oldest_retained_height)?;
+        if let Some(record) = FINALIZED_RECORDS.get(storage, &state.oldest_retained_height) {
+            FINALIZED_HEIGHTS.remove(storage, &record.validation_id)?;
+            FINALIZED_RECORDS.remove(storage, &state.oldest_retained_height)?;
+        }
+        state.oldest_retained_height += 1;
+        pruned += 1;
+    }
+    Ok(pruned)
+}
+
+/// Strips partials from finalized records older than `retention`, keeping
+/// their aggregate. Bounded per call like root history pruning; records the
+/// root history already dropped are skipped.
+fn prune_partials(storage: &mut dyn Storage, state: &mut State, retention: u64) -> StdResult<()> {
+    let keep_from = state.block_height.saturating_sub(retention) + 1;
+    state.oldest_partials_height = state.oldest_partials_height.max(state.oldest_retained_height);
+    let mut stripped = 0;
+    while state.oldest_partials_height < keep_from && stripped < PRUNE_PER_FINALIZATION {
+        let height = state.oldest_partials_height;
+        if let Some(mut record) = FINALIZED_RECORDS.get(storage, &height) {
+            if !record.partials.is_empty() {
+                record.partials.clear();
+                FINALIZED_RECORDS.insert(storage, &height, &record)?;
+            }
+        }
+        state.oldest_partials_height += 1;
+        stripped += 1;
+    }
+    Ok(())
+}
+
+/// Audit bundle of the record finalized at `block_height`, with the signed
+/// messages rebuilt from what its signers covered. `private` is filled in
+/// only for an authenticated user.
+fn audit_bundle(storage: &dyn Storage, block_height: u64, record: &FinalizedRecord, authenticated: bool) -> StdResult<AuditBundleResponse> {
+    let audit = record.audit.clone()
+        .ok_or_else(|| StdError::generic_err("Finalized before audit trails were kept"))?;
+    let identity = DEPLOYMENT.load(storage)?;
+    let signed_messages = audit.signed_validation_ids.iter()
+        .map(|validation_id| vote_digest(
+            &identity.chain_id,
+            &identity.contract_address,
+            validation_id,
+            audit.protocol_version,
+            &record.new_root,
+            &record.asset_digest,
+        ))
+        .collect();
+    let mut bundle = AuditBundleResponse {
+        validation_id: record.validation_id.clone(),
+        block_height,
+        new_state_root: record.new_root.clone(),
+        protocol_version: audit.protocol_version,
+        transition_hash: audit.transition_hash,
+        asset_updates: audit.asset_updates,
+        epoch: audit.epoch,
+        key_epoch: record.key_epoch,
+        signers: record.signers.clone(),
+        signer_keys: audit.signer_keys,
+        signed_validation_ids: audit.signed_validation_ids,
+        signed_messages,
+        threshold_signature: record.threshold_signature.clone(),
+        partials: record.partials.clone(),
+        root_before: audit.root_before,
+        root_after: record.new_root.clone(),
+        committee: COMMITTEE_SNAPSHOTS.get(storage, &audit.epoch),
+        private: authenticated.then(|| AuditPrivate {
+            user_address: record.user_address.clone(),
+            old_state_root: audit.old_state_root,
+            new_state_ipfs: audit.new_state_ipfs,
+            content_hash: audit.content_hash,
+        }),
+        bundle_hash: vec![],
+    };
+    bundle.bundle_hash = audit_bundle_hash(&bundle);
+    Ok(bundle)
+}
+
+/// Valid partials to keep on a finalized record, ordered by node_id
+fn retained_partials(mode: &RetainPartials, validations: &[NodeValidation]) -> Vec<RetainedPartial> {
+    let mut partials: Vec<RetainedPartial> = validations.iter()
+        .filter(|v| v.valid)
+        .map(|v| RetainedPartial { node_id: v.node_id, partial_signature: v.partial_signature.clone() })
+        .collect();
+    partials.sort_by_key(|p| p.node_id);
+    match mode {
+        RetainPartials::None => vec![],
+        RetainPartials::All => partials,
+        RetainPartials::FirstK(k) => partials.into_iter().take(*k as usize).collect(),
+    }
+}
+
+fn attest_build(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    git_commit: String,
+    binary_hash: Vec<u8>,
+    signature: Vec<u8>,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+
+    let node = get_owned_node(&state, info.sender.as_str())?;
+
+    if git_commit.is_empty() || git_commit.len() > MAX_GIT_COMMIT_LEN {
+        return Err(StdError::generic_err("Invalid git commit"));
+    }
+    if binary_hash.is_empty() || binary_hash.len() > MAX_BINARY_HASH_LEN {
+        return Err(StdError::generic_err("Invalid binary hash"));
+    }
+
+    // Binding the contract address keeps attestations from being replayed elsewhere
+    let digest = attestation_digest(env.contract.address.as_str(), &git_commit, &binary_hash);
+    let verified = deps.api.secp256k1_verify(&digest, &signature, &node.public_key)
+        .unwrap_or(false);
+    if !verified {
+        return Err(StdError::generic_err("Invalid attestation signature"));
+    }
+
+    let mut metadata = NODE_METADATA.get(deps.storage, &node.node_id).unwrap_or_default();
+    metadata.attestation = Some(BuildAttestation {
+        git_commit: git_commit.clone(),
+        binary_hash: binary_hash.clone(),
+        attested_at: env.block.time.seconds(),
+    });
+    NODE_METADATA.insert(deps.storage, &node.node_id, &metadata)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "attest_build")
+        .add_attribute("node_id", node.node_id.to_string())
+        .add_attribute("git_commit", git_commit)
+        .add_attribute("binary_hash", hex::encode(binary_hash)))
+}
+
+fn stage_next_epoch_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    public_key: Vec<u8>,
+    proof_of_possession: Vec<u8>,
+) -> StdResult<Response> {
+    let mut state = STATE.load(deps.storage)?;
+    let public_key = normalize_public_key(&public_key)?;
+    if key_type(&public_key) != KeyType::Secp256k1 {
+        return Err(StdError::generic_err("Proof of possession can only be checked for secp256k1 keys"));
+    }
+    if COMPROMISED_KEYS.contains(deps.storage, &public_key) {
+        return Err(StdError::generic_err("Key was reported compromised"));
+    }
+
+    let next_epoch = state.epoch + 1;
+    let node = get_owned_node_mut(&mut state, info.sender.as_str())?;
+
+    let digest = key_possession_digest(env.contract.address.as_str(), node.node_id, next_epoch, &public_key);
+    let verified = deps.api.secp256k1_verify(&digest, &proof_of_possession, &public_key)
+        .unwrap_or(false);
+    if !verified {
+        return Err(StdError::generic_err("Invalid proof of possession"));
+    }
+
+    node.staged_public_key = Some(public_key);
+    let node_id = node.node_id;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "stage_next_epoch_key")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("epoch", next_epoch.to_string()))
+}
+
+/// Owner-only: refuses submissions and votes, and stops deadlines, until Unpause
+fn pause(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    if is_paused(deps.storage)? {
+        return Err(StdError::generic_err("Already paused"));
+    }
+    let paused_at = env.block.time.seconds();
+    open_pause_window(deps.storage, paused_at, env.block.height)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "pause")
+        .add_attribute("paused_at", paused_at.to_string()))
+}
+
+fn open_pause_window(storage: &mut dyn Storage, paused_at: u64, paused_at_height: u64) -> StdResult<()> {
+    let mut ledger = PAUSE_LEDGER.may_load(storage)?.unwrap_or_default();
+    ledger.push(PauseWindow {
+        paused_at,
+        resumed_at: None,
+        paused_for: 0,
+        paused_at_height,
+        resumed_at_height: None,
+        paused_blocks: 0,
+    });
+
+    // Only the last window can be open, so the two oldest are closed here.
+    // The merged window keeps their total, so only deadlines that started
+    // between them lose precision.
+    if ledger.len() > MAX_PAUSE_WINDOWS {
+        let second = ledger.remove(1);
+        ledger[0].resumed_at = second.resumed_at;
+        ledger[0].paused_for += second.paused_for;
+        ledger[0].resumed_at_height = second.resumed_at_height;
+        ledger[0].paused_blocks += second.paused_blocks;
+    }
+    PAUSE_LEDGER.save(storage, &ledger)
+}
+
+/// Counts a rejection of `user_address`'s validation at `height` and pauses
+/// the contract once more than config.breaker_max_rejections distinct users
+/// were rejected within the last config.breaker_window blocks. A user
+/// counts once however many of their validations fail, so no single account
+/// can trip it by submitting transitions the committee rejects. Only the
+/// owner's Unpause resumes it; the count starts over.
+///
+/// Only the committee's verdicts count, so record_vote is the sole caller.
+/// The other ways fail_validation settles a validation are left out:
+/// Cancelled and Expired are user or keeper actions, Superseded is an
+/// ordinary race, a lone node can force DataUnavailable, and
+/// DependencyFailed and Unwound only repeat a failure already seen. None of
+/// them says anything about the committee or the code it runs.
+fn note_rejection(storage: &mut dyn Storage, config: &Config, user_address: &str, height: u64, now: u64) -> StdResult<Option<Event>> {
+    if config.breaker_max_rejections == 0 || is_paused(storage)? {
+        return Ok(None);
+    }
+    let mut recent = RECENT_REJECTIONS.may_load(storage)?.unwrap_or_default();
+    recent.retain(|r| height.saturating_sub(r.height) < config.breaker_window && r.user_address != user_address);
+    recent.push(RecentRejection { user_address: user_address.to_string(), height });
+    if recent.len() <= config.breaker_max_rejections as usize {
+        RECENT_REJECTIONS.save(storage, &recent)?;
+        return Ok(None);
+    }
+
+    RECENT_REJECTIONS.remove(storage);
+    open_pause_window(storage, now, height)?;
+    Ok(Some(Event::new("circuit_breaker_tripped")
+        .add_attribute("rejections", recent.len().to_string())
+        .add_attribute("window", config.breaker_window.to_string())
+        .add_attribute("paused_at", now.to_string())
+        .add_attribute("required_action", "unpause")))
+}
+
+fn unpause(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let mut ledger = PAUSE_LEDGER.may_load(deps.storage)?.unwrap_or_default();
+    let window = match ledger.last_mut() {
+        Some(window) if window.resumed_at.is_none() => window,
+        _ => return Err(StdError::generic_err("Not paused")),
+    };
+    let resumed_at = env.block.time.seconds();
+    window.resumed_at = Some(resumed_at);
+    window.paused_for = resumed_at.saturating_sub(window.paused_at);
+    window.resumed_at_height = Some(env.block.height);
+    window.paused_blocks = env.block.height.saturating_sub(window.paused_at_height);
+    let paused_for = window.paused_for;
+    PAUSE_LEDGER.save(deps.storage, &ledger)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unpause")
+        .add_attribute("paused_for", paused_for.to_string()))
+}
+
+fn is_paused(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(PAUSE_LEDGER.may_load(storage)?
+        .and_then(|ledger| ledger.last().map(|w| w.resumed_at.is_none()))
+        .unwrap_or(false))
+}
+
+/// What a deadline is counted in
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Clock {
+    Time,
+    Height,
+}
+
+impl Clock {
+    fn now(self, env: &Env) -> u64 {
+        match self {
+            Clock::Time => env.block.time.seconds(),
+            Clock::Height => env.block.height,
+        }
+    }
+}
+
+/// A deadline at `at` that started counting at `since`. Pausing stops
+/// submissions and votes, so every deadline those can miss is checked
+/// through here, which pushes it back by the seconds or blocks spent paused
+/// since it started. Windows nothing paused can miss (data availability
+/// answers, challenge locks, finalizer turns) stay plain heights.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Deadline {
+    clock: Clock,
+    since: u64,
+    at: u64,
+}
+
+impl Deadline {
+    /// `at` pushed back by the pause between `since` and `now`
+    fn adjusted(&self, storage: &dyn Storage, now: u64) -> StdResult<u64> {
+        Ok(self.at + paused_since(storage, self.clock, self.since, now)?)
+    }
+
+    fn passed(&self, storage: &dyn Storage, env: &Env) -> StdResult<bool> {
+        let now = self.clock.now(env);
+        Ok(now >= self.adjusted(storage, now)?)
+    }
+}
+
+/// Seconds or blocks the contract spent paused between `since` and `now`
+fn paused_since(storage: &dyn Storage, clock: Clock, since: u64, now: u64) -> StdResult<u64> {
+    let ledger = PAUSE_LEDGER.may_load(storage)?.unwrap_or_default();
+    Ok(ledger.iter()
+        .map(|w| match clock {
+            Clock::Time => paused_within(w.paused_at, w.resumed_at, w.paused_for, since, now),
+            Clock::Height => paused_within(w.paused_at_height, w.resumed_at_height, w.paused_blocks, since, now),
+        })
+        .sum())
+}
+
+/// Part of one pause window, in seconds or blocks, that falls between
+/// `since` and `now`; a merged window counts at most its recorded total
+fn paused_within(paused_at: u64, resumed_at: Option<u64>, recorded: u64, since: u64, now: u64) -> u64 {
+    let end = resumed_at.unwrap_or(now).min(now);
+    let total = match resumed_at {
+        Some(_) => recorded,
+        None => end.saturating_sub(paused_at),
+    };
+    end.saturating_sub(paused_at.max(since)).min(total)
+}
+
+fn epoch_members(state: &State) -> Vec<EpochMember> {
+    state.mpc_nodes.iter()
+        .filter(|n| n.active)
+        .map(|n| EpochMember { node_id: n.node_id, public_key: n.public_key.clone() })
+        .collect()
+}
+
+/// Keeps the current epoch's committee before it changes. Only epoch 0 can
+/// lack a snapshot; every later epoch is snapshotted as it starts.
+fn freeze_epoch(storage: &mut dyn Storage, state: &State) -> StdResult<()> {
+    if !COMMITTEE_SNAPSHOTS.contains(storage, &state.epoch) {
+        COMMITTEE_SNAPSHOTS.insert(storage, &state.epoch, &CommitteeSnapshot {
+            epoch: state.epoch,
+            started_at: 0,
+            threshold: state.threshold,
+            members: epoch_members(state),
+        })?;
+    }
+    Ok(())
+}
+
+/// Starts a new committee epoch for the registry as it now stands. Called
+/// after every change to the active set or a member's key, between a
+/// freeze_epoch and the save, so each epoch's snapshot is exactly the
+/// committee its validations are verified against. Returns whether the new
+/// committee can reach the threshold.
+fn start_epoch(storage: &mut dyn Storage, state: &mut State, height: u64) -> StdResult<bool> {
+    state.epoch += 1;
+    let members = epoch_members(state);
+    let can_finalize = members.len() >= state.threshold as usize;
+    COMMITTEE_SNAPSHOTS.insert(storage, &state.epoch, &CommitteeSnapshot {
+        epoch: state.epoch,
+        started_at: height,
+        threshold: state.threshold,
+        members,
+    })?;
+    Ok(can_finalize)
+}
+
+/// Keys of `signers` as of committee `epoch`: the live registry for the
+/// current epoch, the epoch's snapshot for earlier ones
+fn committee_keys(storage: &dyn Storage, state: &State, epoch: u64, signers: &[u32]) -> Vec<CommitteeKey> {
+    let keys: Vec<(u32, Vec<u8>)> = match COMMITTEE_SNAPSHOTS.get(storage, &epoch) {
+        Some(snapshot) if epoch != state.epoch => snapshot.members.into_iter().map(|m| (m.node_id, m.public_key)).collect(),
+        _ => state.mpc_nodes.iter().map(|n| (n.node_id, n.public_key.clone())).collect(),
+    };
+    keys.into_iter()
+        .filter(|(node_id, _)| signers.contains(node_id))
+        .map(|(node_id, public_key)| CommitteeKey { node_id, public_key })
+        .collect()
+}
+
+fn rotate_threshold_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    public_key: Vec<u8>,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let public_key = normalize_public_key(&public_key)?;
+    let mut state = STATE.load(deps.storage)?;
+    if let Some(current) = THRESHOLD_KEYS.get(deps.storage, &state.key_epoch) {
+        if current.public_key == public_key {
+            return Err(StdError::generic_err("Threshold key unchanged"));
+        }
+    }
+
+    // Validations already pending keep the epoch they were submitted in
+    state.key_epoch += 1;
+    THRESHOLD_KEYS.insert(deps.storage, &state.key_epoch, &ThresholdKey {
+        key_epoch: state.key_epoch,
+        public_key,
+        committee_epoch: state.epoch,
+        rotated_at: env.block.height,
+    })?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "rotate_threshold_key")
+        .add_attribute("key_epoch", state.key_epoch.to_string())
+        .add_attribute("committee_epoch", state.epoch.to_string()))
+}
+
+/// Switches every staged key in at once. Active nodes that staged nothing sit
+/// the new epoch out; they rejoin by re-registering.
+fn advance_epoch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    freeze_epoch(deps.storage, &state)?;
+
+    let mut rotated = 0;
+    let mut deactivated = vec![];
+    for node in state.mpc_nodes.iter_mut() {
+        match node.staged_public_key.take() {
+            Some(public_key) => {
+                node.public_key = public_key;
+                rotated += 1;
+            }
+            None if node.active => {
+                node.active = false;
+                deactivated.push(node.node_id.to_string());
+            }
+            None => {}
+        }
+    }
+    let can_finalize = start_epoch(deps.storage, &mut state, env.block.height)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "advance_epoch")
+        .add_attribute("epoch", state.epoch.to_string())
+        .add_attribute("rotated", rotated.to_string())
+        .add_attribute("deactivated", deactivated.join(","))
+        .add_attribute("can_finalize", can_finalize.to_string())
+        .add_attributes((!can_finalize).then_some(("warning", "threshold_unreachable"))))
+}
+
+/// Slashes a node that signed a valid vote for a root its validation did not
+/// finalize with. Everything is checked against the finalized record and the
+/// node's registered key; the owner's word adds nothing.
+fn slash_node(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    evidence: FaultEvidence,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let record = FINALIZED_RECORDS.get(deps.storage, &evidence.height)
+        .filter(|record| record.validation_id == evidence.validation_id)
+        .ok_or_else(|| StdError::generic_err("No finalized record for that validation at that height"))?;
+    if evidence.signed_root == record.new_root {
+        return Err(StdError::generic_err("Evidence agrees with the finalized root"));
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    freeze_epoch(deps.storage, &state)?;
+    let node = get_node_mut(&mut state, evidence.node_id)?;
+    if node.slashed {
+        return Err(StdError::generic_err("Node was slashed"));
+    }
+
+    let identity = DEPLOYMENT.load(deps.storage)?;
+    let digest = vote_digest(
+        &identity.chain_id,
+        &identity.contract_address,
+        &evidence.validation_id,
+        evidence.protocol_version,
+        &evidence.signed_root,
+        &record.asset_digest,
+    );
+    let verified = deps.api.secp256k1_verify(&digest, &evidence.partial_signature, &node.public_key)
+        .unwrap_or(false);
+    if !verified {
+        return Err(StdError::generic_err("Evidence signature does not verify under the node's key"));
+    }
+
+    node.slashed = true;
+    node.active = false;
+    start_epoch(deps.storage, &mut state, env.block.height)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "slash_node")
+        .add_attribute("node_id", evidence.node_id.to_string())
+        .add_attribute("validation_id", evidence.validation_id)
+        .add_attribute("signed_root", hex::encode(evidence.signed_root))
+        .add_attribute("finalized_root", hex::encode(record.new_root)))
+}
+
+/// Takes a node out at once and bans its key for good. Pending validations
+/// with a share encrypted to that key are not touched here; block_reason
+/// blocks them when they are next voted on or finalized.
+fn report_key_compromise(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    node_id: u32,
+    signature_by_compromised_key: Option<Vec<u8>>,
+    cosigns: Vec<NodeCosign>,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut state = STATE.load(deps.storage)?;
+    let node = get_node(&state, node_id)?;
+    let public_key = node.public_key.clone();
+    if COMPROMISED_KEYS.contains(deps.storage, &public_key) {
+        return Err(StdError::generic_err("Key was already reported compromised"));
+    }
+
+    let identity = DEPLOYMENT.load(deps.storage)?;
+    let digest = compromise_digest(&identity.chain_id, &identity.contract_address, node_id, &public_key);
+    let proven = match &signature_by_compromised_key {
+        Some(signature) if !deps.api.secp256k1_verify(&digest, signature, &public_key).unwrap_or(false) =>
+            return Err(StdError::generic_err("Invalid compromise signature")),
+        Some(_) => true,
+        None => false,
+    };
+
+    let reporter = if info.sender == node.address {
+        "node_owner"
+    } else if info.sender == config.owner {
+        "admin"
+    } else {
+        let reporter_id = get_active_node(&state, info.sender.as_str())?.node_id;
+        let mut cosigners: Vec<u32> = Vec::with_capacity(cosigns.len());
+        for cosign in &cosigns {
+            if cosign.node_id == reporter_id || cosign.node_id == node_id || cosigners.contains(&cosign.node_id) {
+                return Err(StdError::generic_err("Cosigns must come from distinct other nodes"));
+            }
+            let cosigner = get_node(&state, cosign.node_id)?;
+            if !cosigner.active || !deps.api.secp256k1_verify(&digest, &cosign.signature, &cosigner.public_key).unwrap_or(false) {
+                return Err(StdError::generic_err("Invalid node co-signature"));
+            }
+            cosigners.push(cosign.node_id);
+        }
+        if cosigners.len() < COMPROMISE_COSIGNERS {
+            return Err(StdError::generic_err(format!(
+                "Reporting another node needs {} co-signatures", COMPROMISE_COSIGNERS,
+            )));
+        }
+        "node"
+    };
+
+    COMPROMISED_KEYS.insert(deps.storage, &public_key, &KeyCompromise {
+        node_id,
+        reported_by: info.sender.to_string(),
+        reported_at: env.block.height,
+        proven,
+        quarantined: vec![],
+    })?;
+    freeze_epoch(deps.storage, &state)?;
+    get_node_mut(&mut state, node_id)?.active = false;
+    let can_finalize = start_epoch(deps.storage, &mut state, env.block.height)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "report_key_compromise")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("reporter", reporter)
+        .add_attribute("proven", proven.to_string())
+        .add_attribute("can_finalize", can_finalize.to_string())
+        .add_attributes((!can_finalize).then_some(("warning", "threshold_unreachable"))))
+}
+
+fn deregister_mpc_node(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    signature: Vec<u8>,
+) -> StdResult<Response> {
+    let mut state = STATE.load(deps.storage)?;
+    freeze_epoch(deps.storage, &state)?;
+
+    let node_id = active_node_id(&state, &info)?;
+    let node = get_node_mut(&mut state, node_id)?;
+
+    // Prove ownership of the registered key, not just the address
+    let digest = deregistration_digest(env.contract.address.as_str(), node.node_id, &node.public_key);
+    let verified = deps.api.secp256k1_verify(&digest, &signature, &node.public_key)
+        .unwrap_or(false);
+    if !verified {
+        return Err(StdError::generic_err("Invalid deregistration signature"));
+    }
+
+    node.active = false;
+    node.operator = None;
+    let node_id = node.node_id;
+    start_epoch(deps.storage, &mut state, env.block.height)?;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "deregister_mpc_node")
+        .add_attribute("node_id", node_id.to_string()))
+}
+
+fn set_nodes_active(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    node_ids: Vec<u32>,
+    active: bool,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    freeze_epoch(deps.storage, &state)?;
+    for node_id in &node_ids {
+        let node = get_node_mut(&mut state, *node_id)?;
+        if active && node.slashed {
+            return Err(StdError::generic_err(format!("Node {} was slashed", node_id)));
+        }
+        if active && COMPROMISED_KEYS.contains(deps.storage, &node.public_key) {
+            return Err(StdError::generic_err(format!("Key of node {} was reported compromised", node_id)));
+        }
+        node.active = active;
+    }
+    let committee_ready = mark_committee_ready(&mut state);
+    // Freezing is allowed to break liveness, but say so
+    let can_finalize = start_epoch(deps.storage, &mut state, env.block.height)?;
+    STATE.save(deps.storage, &state)?;
+    let active_nodes = state.mpc_nodes.iter().filter(|n| n.active).count();
+
+    Ok(Response::new()
+        .add_attribute("action", "set_nodes_active")
+        .add_attribute("node_ids", node_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(","))
+        .add_attribute("active", active.to_string())
+        .add_attribute("active_nodes", active_nodes.to_string())
+        .add_attribute("can_finalize", can_finalize.to_string())
+        .add_attributes((!can_finalize).then_some(("warning", "threshold_unreachable")))
+        .add_attributes(committee_ready.then_some(("committee_ready", "true"))))
+}
+
+fn create_viewing_key(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    entropy: String,
+) -> StdResult<Response> {
+    let key = ViewingKey::create(deps.storage, &info, &env, info.sender.as_str(), entropy.as_bytes());
+
+    Ok(Response::new()
+        .add_attribute("action", "create_viewing_key")
+        .set_data(to_binary(&ViewingKeyResponse { key })?))
+}
+
+fn set_viewing_key(
+    deps: DepsMut,
+    info: MessageInfo,
+    key: String,
+) -> StdResult<Response> {
+    ViewingKey::set(deps.storage, info.sender.as_str(), &key);
+
+    Ok(Response::new()
+        .add_attribute("action", "set_viewing_key"))
+}
+
+fn set_observer(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    authorized: bool,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let address = deps.api.addr_validate(&address)?.to_string();
+    if authorized {
+        OBSERVERS.insert(deps.storage, &address)?;
+    } else {
+        OBSERVERS.remove(deps.storage, &address)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "set_observer")
+        .add_attribute("address", address)
+        .add_attribute("authorized", authorized.to_string()))
+}
+
+fn update_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    update: ConfigUpdate,
+) -> StdResult<Response> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    if let Some(versions) = update.supported_protocol_versions {
+        if versions.is_empty() {
+            return Err(StdError::generic_err("At least one protocol version must be supported"));
+        }
+        config.supported_protocol_versions = versions;
+    }
+
+    if let Some(window) = update.attestation_window {
+        config.attestation_window = window;
+    }
+
+    if let Some(operations) = update.unanimous_operations {
+        config.unanimous_operations = operations;
+    }
+
+    if let Some(verbosity) = update.event_verbosity {
+        config.event_verbosity = verbosity;
+    }
+
+    if let Some(retention) = update.root_history_retention {
+        if retention == 0 {
+            return Err(StdError::generic_err("Root history retention must be at least 1"));
+        }
+        config.root_history_retention = retention;
+    }
+
+    if let Some(mode) = update.retain_partials {
+        config.retain_partials = mode;
+    }
+
+    if let Some(retention) = update.partial_retention {
+        config.partial_retention = retention;
+    }
+
+    if let Some(size) = update.max_transition_size {
+        config.max_transition_size = size;
+    }
+
+    if let Some(len) = update.max_share_len {
+        config.max_share_len = len;
+    }
+
+    if let Some(quota) = update.max_user_storage {
+        config.max_user_storage = quota;
+    }
+
+    if let Some(watermark) = update.pending_watermark {
+        config.pending_watermark = watermark;
+    }
+
+    if let Some(timeout) = update.validation_timeout {
+        config.validation_timeout = timeout;
+    }
+
+    if let Some(cooldown) = update.registration_cooldown {
+        config.registration_cooldown = cooldown;
+    }
+
+    if let Some(window) = update.registration_window {
+        config.registration_window = window;
+    }
+
+    if let Some(max) = update.max_registrations_per_window {
+        config.max_registrations_per_window = max;
+    }
+
+    if config.max_registrations_per_window > 0 && config.registration_window == 0 {
+        return Err(StdError::generic_err("A registration cap needs a registration window"));
+    }
+
+    if let Some(window) = update.breaker_window {
+        config.breaker_window = window;
+    }
+
+    if let Some(max) = update.breaker_max_rejections {
+        config.breaker_max_rejections = max;
+    }
+
+    if config.breaker_max_rejections > 0 && config.breaker_window == 0 {
+        return Err(StdError::generic_err("A circuit breaker needs a window"));
+    }
+
+    if let Some(limit) = update.equivocation_limit {
+        config.equivocation_limit = limit;
+    }
+
+    if let Some(ttl) = update.account_ttl_seconds {
+        config.account_ttl_seconds = ttl;
+    }
+
+    if let Some(blocks) = update.max_validation_blocks {
+        config.max_validation_blocks = blocks;
+    }
+
+    if let Some(window) = update.finalizer_window {
+        config.finalizer_window = window;
+    }
+
+    if let Some(bounty) = update.expiry_bounty {
+        config.expiry_bounty = (!bounty.amount.is_zero()).then_some(bounty);
+    }
+
+    if let Some(bond) = update.da_challenge_bond {
+        config.da_challenge_bond = (!bond.amount.is_zero()).then_some(bond);
+    }
+
+    if let Some(watermark) = update.outbound_failure_watermark {
+        config.outbound_failure_watermark = watermark;
+    }
+
+    if let Some(fee) = update.subscription_fee {
+        config.subscription_fee = (!fee.amount.is_zero()).then_some(fee);
+    }
+
+    if let Some(max) = update.max_work_payloads {
+        if max == 0 {
+            return Err(StdError::generic_err("Max work payloads must be at least 1"));
+        }
+        config.max_work_payloads = max;
+    }
+
+    if let Some(confirmations) = update.commitment_confirmations {
+        config.commitment_confirmations = confirmations;
+    }
+
+    if let Some(precedence) = update.vote_precedence {
+        config.vote_precedence = precedence;
+    }
+
+    if let Some(policy) = update.reward_policy {
+        config.reward_policy = policy;
+    }
+
+    if let Some(reject_threshold) = update.reject_threshold {
+        // Fewer would let a minority veto what threshold nodes accept; more
+        // could never be reached
+        let state = STATE.load(deps.storage)?;
+        let active = state.mpc_nodes.iter().filter(|n| n.active).count() as u32;
+        if reject_threshold != 0 && (reject_threshold < state.threshold || reject_threshold > active) {
+            return Err(StdError::generic_err(format!(
+                "Reject threshold must be 0 or between the threshold ({}) and the active node count ({})",
+                state.threshold, active,
+            )));
+        }
+        config.reject_threshold = reject_threshold;
+    }
+
+    if let Some(unique_cids) = update.unique_cids {
+        config.unique_cids = unique_cids;
+    }
+
+    if let Some(require) = update.require_nonempty_merkle_proof {
+        config.require_nonempty_merkle_proof = require;
+    }
+
+    if let Some(cosign) = update.repoint_cosign {
+        config.repoint_cosign = cosign;
+    }
+
+    if let Some(require) = update.require_nonce_commitments {
+        config.require_nonce_commitments = require;
+    }
+
+    if let Some(cooldown) = update.validation_cooldown {
+        config.validation_cooldown = cooldown;
+    }
+
+    if let Some(strict) = update.strict_root_agreement {
+        config.strict_root_agreement = strict;
+    }
+
+    if let Some(window) = update.challenge_window {
+        config.challenge_window = window;
+    }
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_config"))
+}
+
+fn transfer_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_owner: String,
+) -> StdResult<Response> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let new_owner = deps.api.addr_validate(&new_owner)?.to_string();
+    config.pending_owner = Some(new_owner.clone());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer_ownership")
+        .add_attribute("owner", config.owner)
+        .add_attribute("pending_owner", new_owner))
+}
+
+fn accept_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    match &config.pending_owner {
+        Some(pending) if info.sender == *pending => {}
+        _ => return Err(StdError::generic_err("Unauthorized")),
+    }
+
+    let previous_owner = std::mem::replace(&mut config.owner, info.sender.to_string());
+    config.pending_owner = None;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("previous_owner", previous_owner)
+        .add_attribute("owner", config.owner))
+}
+
+fn challenge_finalized(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    user_address: String,
+    reason: String,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+    let node_id = get_active_node(&state, info.sender.as_str())
+        .map_err(|_| StdError::generic_err("Unauthorized"))?
+        .node_id;
+
+    let current = STATE_COMMITMENTS.get(deps.storage, &user_address)
+        .ok_or_else(|| StdError::generic_err("Commitment not found"))?;
+    if env.block.height >= current.lock_at_height {
+        return Err(StdError::generic_err("Commitment is locked"));
+    }
+
+    // Only the user's own stores are rolled back, so nothing may have been
+    // built on the finalization being reverted
+    let validation_id = FINALIZED_BY.get(deps.storage, &user_address)
+        .ok_or_else(|| StdError::generic_err("Commitment has no recorded finalization"))?;
+    if !DEPENDENTS.get(deps.storage, &validation_id).unwrap_or_default().is_empty() {
+        return Err(StdError::generic_err("Other validations depend on this finalization"));
+    }
+
+    let mut challengers = FINALIZED_CHALLENGES.get(deps.storage, &validation_id).unwrap_or_default();
+    if challengers.contains(&node_id) {
+        return Err(StdError::generic_err("Already challenged by this node"));
+    }
+    challengers.push(node_id);
+    let response = Response::new()
+        .add_attribute("action", "challenge_finalized")
+        .add_attribute("user", &user_address)
+        .add_attribute("validation_id", &validation_id)
+        .add_attribute("challenger", info.sender.to_string())
+        .add_attribute("reason", reason)
+        .add_attribute("challenges", challengers.len().to_string())
+        .add_attribute("required", state.threshold.to_string());
+    if (challengers.len() as u32) < state.threshold {
+        FINALIZED_CHALLENGES.insert(deps.storage, &validation_id, &challengers)?;
+        return Ok(response.add_attribute("reverted", "false"));
+    }
+    FINALIZED_CHALLENGES.remove(deps.storage, &validation_id)?;
+    FINALIZED_BY.remove(deps.storage, &user_address)?;
+
+    // Restore the prior commitment (now immutable), or clear the user's leaf
+    if CID_OWNERS.get(deps.storage, &current.ipfs_cid).as_ref() == Some(&user_address) {
+        CID_OWNERS.remove(deps.storage, &current.ipfs_cid)?;
+    }
+    let restored_root = match PREVIOUS_COMMITMENTS.get(deps.storage, &user_address) {
+        Some(mut previous) => {
+            previous.lock_at_height = env.block.height;
+            let (current_bytes, previous_bytes) = (commitment_bytes(Some(&current))?, commitment_bytes(Some(&previous))?);
+            charge_storage(deps.storage, &user_address, current_bytes + previous_bytes, previous_bytes)?;
+            CID_OWNERS.insert(deps.storage, &previous.ipfs_cid, &user_address)?;
+            STATE_COMMITMENTS.insert(deps.storage, &user_address, &previous)?;
+            PREVIOUS_COMMITMENTS.remove(deps.storage, &user_address)?;
+            previous.state_root
+        }
+        None => {
+            charge_storage(deps.storage, &user_address, commitment_bytes(Some(&current))?, 0)?;
+            STATE_COMMITMENTS.remove(deps.storage, &user_address)?;
+            unorder_commitment(deps.storage, &user_address)?;
+            LEAF_OWNERS.remove(deps.storage, &current.leaf_index)?;
+            vec![]
+        }
+    };
+    let mut stats = STATS.load(deps.storage)?;
+    stats.validation_counts.finalized = stats.validation_counts.finalized.saturating_sub(1);
+    stats.validation_counts.challenged += 1;
+    STATS.save(deps.storage, &stats)?;
+
+    Ok(response
+        .add_attribute("reverted", "true")
+        .add_attribute("reverted_root", hex::encode(current.state_root))
+        .add_attribute("restored_root", hex::encode(restored_root)))
+}
+
+fn challenge_data_availability(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    validation_id: String,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+    let node_id = active_node_id(&state, &info)?;
+
+    let mut validation = load_pending(deps.storage, &validation_id)?;
+    // One challenge per validation, so a node cannot stall it indefinitely
+    if validation.da_challenge.is_some() {
+        return Err(StdError::generic_err("Validation already challenged"));
+    }
+    // The bond makes a false claim cost the node the delay it caused
+    let bond = CONFIG.load(deps.storage)?.da_challenge_bond
+        .ok_or_else(|| StdError::generic_err("Data availability challenges need a configured bond"))?;
+    if !pays_exactly(&info.funds, &bond) {
+        return Err(StdError::generic_err(format!("Challenging requires a bond of exactly {}", bond)));
+    }
+    CHALLENGE_BONDS.insert(deps.storage, &validation_id, &ChallengeBond {
+        challenger: info.sender.to_string(),
+        amount: bond.clone(),
+    })?;
+
+    let deadline = env.block.height + DA_RESPONSE_WINDOW;
+    validation.da_challenge = Some(DataChallenge {
+        node_id,
+        opened_at: env.block.height,
+        deadline,
+        answered: false,
+    });
+    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "challenge_data_availability")
+        .add_attribute("validation_id", validation_id)
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("bond", bond.to_string())
+        .add_attribute("deadline", deadline.to_string()))
+}
+
+fn prove_availability(
+    deps: DepsMut,
+    env: Env,
+    validation_id: String,
+    sample: Binary,
+) -> StdResult<Response> {
+    let mut validation = load_pending(deps.storage, &validation_id)?;
+    let challenge = match &mut validation.da_challenge {
+        Some(challenge) if challenge.is_open() => challenge,
+        _ => return Err(StdError::generic_err("No open data availability challenge")),
+    };
+    if env.block.height >= challenge.deadline {
+        return Err(StdError::generic_err("Data availability challenge expired"));
+    }
+    if Sha256::digest(sample.as_slice()).to_vec() != validation.transition.content_hash {
+        return Err(StdError::generic_err("Sample does not match content hash"));
+    }
+    challenge.answered = true;
+    PENDING_VALIDATIONS.insert(deps.storage, &validation_id, &validation)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "prove_availability")
+        .add_attribute("validation_id", &validation_id);
+
+    // The content was there, so the challenger's bond goes to the user
+    if let Some(bond) = CHALLENGE_BONDS.get(deps.storage, &validation_id) {
+        CHALLENGE_BONDS.remove(deps.storage, &validation_id)?;
+        let user = validation.transition.user_address.clone();
+        response = response
+            .add_attribute("bond_forfeited", bond.amount.to_string())
+            .add_submessage(send_bounty(deps.storage, user, bond.amount, &bond_origin(&validation_id))?);
+    }
+
+    // A batch waiting only on this leg settles now; other legs finalize
+    // through FinalizeTransition or RevealPreimage as usual
+    if let (Some(group_id), true) = (&validation.group_id, validation.threshold_reached) {
+        if let Some(status) = settle_group_if_ready(deps.storage, &env, group_id)? {
+            let status = if status == TransferStatus::Completed { "completed" } else { "unwound" };
+            return Ok(response.add_attribute("group_status", status));
+        }
+    }
+    Ok(response)
+}
+
+fn expire_data_challenge(
+    deps: DepsMut,
+    env: Env,
+    validation_id: String,
+) -> StdResult<Response> {
+    let validation = load_pending(deps.storage, &validation_id)?;
+    match &validation.da_challenge {
+        Some(challenge) if challenge.is_open() => {
+            if env.block.height < challenge.deadline {
+                return Err(StdError::generic_err("Data availability challenge still open"));
+            }
+        }
+        _ => return Err(StdError::generic_err("No open data availability challenge")),
+    }
+
+    // Settling refunds the user's deposit and returns the challenger's bond
+    fail_validation(deps.storage, &env, &validation, OutcomeStatus::DataUnavailable)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "expire_data_challenge")
+        .add_attribute("validation_id", validation_id)
+        .add_attribute("outcome", OutcomeStatus::DataUnavailable.as_str()))
+}
+
+/// Outbound origin of a challenge bond, apart from the validation's bounty
+fn bond_origin(validation_id: &str) -> String {
+    format!("{}/bond", validation_id)
+}
+
+fn request_revalidation(
+    deps: DepsMut,
+    info: MessageInfo,
+    validation_id: String,
+    node_id: u32,
+) -> StdResult<Response> {
+    let validation = load_pending(deps.storage, &validation_id)?;
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner && info.sender != validation.transition.user_address {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let state = STATE.load(deps.storage)?;
+    if !get_node(&state, node_id)?.active {
+        return Err(StdError::not_found("Active MPC node"));
+    }
+    if VOTE_PRESENCE.contains(deps.storage, &vote_presence_key(&validation_id, node_id)) {
+        return Err(StdError::generic_err("Already validated"));
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "request_revalidation")
+        .add_attribute("validation_id", validation_id)
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("requested_by", info.sender.to_string()))
+}
+
+fn set_operator(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator: Option<String>,
+) -> StdResult<Response> {
+    let mut state = STATE.load(deps.storage)?;
+    let operator = operator.map(|o| deps.api.addr_validate(&o)).transpose()?.map(|o| o.to_string());
+
+    // One address acts for at most one node, in at most one role
+    if let Some(operator) = &operator {
+        if state.mpc_nodes.iter().any(|n| n.address != info.sender && n.is_operated_by(operator)) {
+            return Err(StdError::generic_err("Operator already acts for another node"));
+        }
+        if *operator == info.sender {
+            return Err(StdError::generic_err("Operator must differ from the owner"));
+        }
+    }
+
+    // Only the owner key can delegate
+    let node = get_owned_node_mut(&mut state, info.sender.as_str())?;
+    node.operator = operator.clone();
+    let node_id = node.node_id;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_operator")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("operator", operator.unwrap_or_default()))
+}
+
+fn propose_admin_recovery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_admin: String,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+    let node_id = active_node_id(&state, &info)?;
+
+    if ADMIN_RECOVERY.may_load(deps.storage)?.is_some() {
+        return Err(StdError::generic_err("Admin recovery already in progress"));
+    }
+
+    let mut recovery = AdminRecovery {
+        new_admin: deps.api.addr_validate(&new_admin)?.to_string(),
+        approvals: vec![node_id],
+        proposed_at: env.block.time.seconds(),
+        quorum_reached_at: None,
+    };
+    let required = update_recovery_quorum(deps.storage, &env, &state, &mut recovery)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "propose_admin_recovery")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("new_admin", recovery.new_admin)
+        .add_attribute("approvals", "1")
+        .add_attribute("required_approvals", required.to_string()))
+}
+
+fn approve_admin_recovery(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+    let node_id = active_node_id(&state, &info)?;
+
+    let mut recovery = ADMIN_RECOVERY.may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("No admin recovery in progress"))?;
+    if recovery.approvals.contains(&node_id) {
+        return Err(StdError::generic_err("Already approved"));
+    }
+    recovery.approvals.push(node_id);
+    let required = update_recovery_quorum(deps.storage, &env, &state, &mut recovery)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "approve_admin_recovery")
+        .add_attribute("node_id", node_id.to_string())
+        .add_attribute("approvals", recovery.approvals.len().to_string())
+        .add_attribute("required_approvals", required.to_string());
+    if let Some(reached_at) = recovery.quorum_reached_at {
+        let config = CONFIG.load(deps.storage)?;
+        response = response.add_attribute("executable_at", (reached_at + config.recovery_delay).to_string());
+    }
+    Ok(response)
+}
+
+fn veto_admin_recovery(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+
+    let recovery = ADMIN_RECOVERY.may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("No admin recovery in progress"))?;
+    ADMIN_RECOVERY.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "veto_admin_recovery")
+        .add_attribute("new_admin", recovery.new_admin))
+}
+
+fn execute_admin_recovery(
+    deps: DepsMut,
+    env: Env,
+) -> StdResult<Response> {
+    let state = STATE.load(deps.storage)?;
+    let mut config = CONFIG.load(deps.storage)?;
+    let recovery = ADMIN_RECOVERY.may_load(deps.storage)?
+        .ok_or_else(|| StdError::generic_err("No admin recovery in progress"))?;
+
+    // Re-count against the current committee in case nodes left since
+    let active_approvals = recovery.approvals.iter()
+        .filter(|id| get_node(&state, **id).is_ok_and(|n| n.active))
+        .count() as u32;
+    let reached_at = match recovery.quorum_reached_at {
+        Some(reached_at) if active_approvals >= recovery_quorum(&state, &config) => reached_at,
+        _ => return Err(StdError::generic_err("Recovery quorum not reached")),
+    };
+    let veto = Deadline { clock: Clock::Time, since: reached_at, at: reached_at + config.recovery_delay };
+    if !veto.passed(deps.storage, &env)? {
+        return Err(StdError::generic_err("Recovery veto period has not elapsed"));
+    }
+
+    let previous_owner = std::mem::replace(&mut config.owner, recovery.new_admin);
+    config.pending_owner = None;
+    CONFIG.save(deps.storage, &config)?;
+    ADMIN_RECOVERY.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "execute_admin_recovery")
+        .add_attribute("previous_owner", previous_owner)
+        .add_attribute("owner", config.owner))
+}
+
+/// The active node `sender` votes for, as its owner or its operator
+fn get_active_node<'a>(state: &'a State, sender: &str) -> StdResult<&'a MPCNode> {
+    state.mpc_nodes.iter()
+        .find(|n| n.active && n.is_operated_by(sender))
+        .ok_or_else(|| StdError::not_found("Active MPC node"))
+}
+
+/// Node `node_id` by position: ids are assigned 1..=len in registration order
+/// and nodes are never removed. The stored id is checked all the same.
+fn get_node(state: &State, node_id: u32) -> StdResult<&MPCNode> {
+    node_id.checked_sub(1)
+        .and_then(|idx| state.mpc_nodes.get(idx as usize))
+        .filter(|n| n.node_id == node_id)
+        .ok_or_else(|| StdError::not_found(format!("MPC node {}", node_id)))
+}
+
+fn get_node_mut(state: &mut State, node_id: u32) -> StdResult<&mut MPCNode> {
+    node_id.checked_sub(1)
+        .and_then(|idx| state.mpc_nodes.get_mut(idx as usize))
+        .filter(|n| n.node_id == node_id)
+        .ok_or_else(|| StdError::not_found(format!("MPC node {}", node_id)))
+}
+
+/// The node `sender` acts for, active or not; an address owns or operates
+/// at most one
+fn get_operated_node<'a>(state: &'a State, sender: &str) -> StdResult<&'a MPCNode> {
+    state.mpc_nodes.iter()
+        .find(|n| n.is_operated_by(sender))
+        .ok_or_else(|| StdError::generic_err("Not a registered MPC node"))
+}
+
+/// The node registered under `owner`'s key, for duties an operator cannot
+/// perform
+fn get_owned_node<'a>(state: &'a State, owner: &str) -> StdResult<&'a MPCNode> {
+    state.mpc_nodes.iter()
+        .find(|n| n.address == owner)
+        .ok_or_else(|| StdError::generic_err("Not a registered MPC node"))
+}
+
+fn get_owned_node_mut<'a>(state: &'a mut State, owner: &str) -> StdResult<&'a mut MPCNode> {
+    state.mpc_nodes.iter_mut()
+        .find(|n| n.address == owner)
+        .ok_or_else(|| StdError::generic_err("Not a registered MPC node"))
+}
+
+/// Owner-key duties (recovery approvals) are not delegated to the operator
+fn active_node_id(state: &State, info: &MessageInfo) -> StdResult<u32> {
+    state.mpc_nodes.iter()
+        .find(|n| n.address == info.sender && n.active)
+        .map(|n| n.node_id)
+        .ok_or_else(|| StdError::not_found("Active MPC node"))
+}
+
+/// Approvals needed to recover the admin: the configured percentage of active
+/// nodes, rounded up.
+fn recovery_quorum(state: &State, config: &Config) -> u32 {
+    let active = state.mpc_nodes.iter().filter(|n| n.active).count() as u32;
+    (active * config.recovery_supermajority as u32).div_ceil(100)
+}
+
+/// Saves the recovery, starting the veto window the first time quorum is met
+fn update_recovery_quorum(
+    storage: &mut dyn Storage,
+    env: &Env,
+    state: &State,
+    recovery: &mut AdminRecovery,
+) -> StdResult<u32> {
+    let config = CONFIG.load(storage)?;
+    let required = recovery_quorum(state, &config);
+    if recovery.quorum_reached_at.is_none() && recovery.approvals.len() as u32 >= required {
+        recovery.quorum_reached_at = Some(env.block.time.seconds());
+    }
+    ADMIN_RECOVERY.save(storage, recovery)?;
+    Ok(required)
+}
+
+// ============================================================================
+// MIGRATE
+// ============================================================================
+
+/// Failing record keys a dry run lists; `total_failures` counts the rest
+const MAX_REPORTED_FAILURES: usize = 20;
+
+#[entry_point]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> StdResult<Response> {
+    let MigrateMsg::Execute { owner } = msg;
+    let owner = owner.map(|owner| deps.api.addr_validate(&owner)).transpose()?;
+    let plan = plan_migration(deps.storage, &env, owner.as_ref().map(|owner| owner.as_str()), None)?;
+    if let Some(first) = plan.failed_keys.first() {
+        return Err(StdError::generic_err(format!(
+            "{} records fail to migrate, first: {}",
+            plan.total_failures, first
+        )));
+    }
+
+    let from_schema = plan.from_schema;
+    commit_migration(deps.storage, plan)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_schema", from_schema.to_string())
+        .add_attribute("to_schema", CURRENT_SCHEMA_VERSION.to_string()))
+}
+
+/// One page of QueryMsg::MigrationDryRun: the whole plan, with map records
+/// decoded only after `start_after`
+fn migration_dry_run(
+    deps: Deps,
+    env: &Env,
+    owner: Option<String>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<MigrationDryRunResponse> {
+    let owner = owner.map(|owner| deps.api.addr_validate(&owner)).transpose()?;
+    let start = start_after.unwrap_or(0);
+    let end = start + limit.unwrap_or(MAX_MIGRATION_PAGE).clamp(1, MAX_MIGRATION_PAGE) as u64;
+    let plan = plan_migration(deps.storage, env, owner.as_ref().map(|owner| owner.as_str()), Some(start..end))?;
+    Ok(MigrationDryRunResponse {
+        from_schema: plan.from_schema,
+        to_schema: CURRENT_SCHEMA_VERSION,
+        next_start_after: (end < plan.map_records).then_some(end),
+        counts: plan.counts,
+        failed_keys: plan.failed_keys,
+        total_failures: plan.total_failures,
+    })
+}
+
+/// Fills missing finalization metadata on a commitment stored before schema
+/// 3, from what the operator recovered of its finalizing transaction. The
+/// root ties the metadata to the commitment it describes.
+fn backfill_commitment(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    user_address: String,
+    metadata: CommitmentMetadata,
+) -> StdResult<Response> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    let state = STATE.load(deps.storage)?;
+    match state.backfill_until {
+        None => return Err(StdError::generic_err("No backfill window was opened")),
+        Some(until) if env.block.height >= until => return Err(StdError::generic_err("Backfill window closed")),
+        Some(_) => {}
+    }
+
+    let mut commitment = STATE_COMMITMENTS.get(deps.storage, &user_address)
+        .ok_or_else(|| StdError::generic_err("Commitment not found"))?;
+    if commitment.metadata_complete() {
+        return Err(StdError::generic_err("Commitment metadata already complete"));
+    }
+    if metadata.state_root != commitment.state_root {
+        return Err(StdError::generic_err("Root does not match the stored commitment"));
+    }
+    if metadata.finalized_at_height == 0 || metadata.finalized_at_height > env.block.height {
+        return Err(StdError::generic_err("Invalid finalization height"));
+    }
+    if metadata.committee_hash.len() != 32 || metadata.threshold_signature.is_empty() {
+        return Err(StdError::generic_err("Committee hash must be 32 bytes and the signature non-empty"));
+    }
+
+    // Only what is missing; fields the commitment already has stay
+    if commitment.finalized_at_height == 0 {
+        commitment.finalized_at_height = metadata.finalized_at_height;
+    }
+    if commitment.committee_hash.is_empty() {
+        commitment.committee_hash = metadata.committee_hash;
+    }
+    if commitment.threshold_signature.is_empty() {
+        commitment.threshold_signature = metadata.threshold_signature;
+    }
+    STATE_COMMITMENTS.insert(deps.storage, &user_address, &commitment)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "backfill_commitment")
+        .add_attribute("user", user_address)
+        .add_attribute("nonce", commitment.nonce.to_string()))
+}
+
+/// Everything the pending migration would write, built without writing.
+/// `commit_migration` applies it; a dry run only reports it.
+struct MigrationPlan {
+    from_schema: u16,
+    adopt_identity: Option<DeploymentIdentity>,
+    open_backfill_until: Option<u64>,
+    // Schema 4 indexes outcomes by user, and dependencies that had settled
+    // before their dependent was submitted
+    index_outcomes: bool,
+    // Schema 5 records the user's committed state with each content vote
+    reencode_content_votes: bool,
+    // Schema 6 records the heights of pause windows
+    upgrade_pause_ledger: Option<Vec<PauseWindow>>,
+    // Schema 8 counts breaker rejections per user; the old count is dropped
+    reset_rejections: bool,
+    // Schema 9 indexes parked sends by id
+    index_outbound_failures: bool,
+    // Schema 10 files pending validations under the nodes holding their shares
+    index_node_work: bool,
+    // Schema 11 indexes pending validations by user
+    index_user_pending: bool,
+    // Schema 12 gives every committed user an export slot
+    index_commitment_order: bool,
+    // Schema 0 to 1: first-release records rewritten, and items it never had
+    upgrade_state: Option<State>,
+    upgrade_commitments: Vec<StateCommitment>,
+    seed_config: Option<Config>,
+    seed_stats: Option<Stats>,
+    viewing_key_seed: Option<Vec<u8>>,
+    counts: Vec<RecordCount>,
+    failed_keys: Vec<String>,
+    total_failures: u64,
+    // Positions in all checked maps, one after another, whose records a dry
+    // run decodes; none for Execute, which must stay bounded
+    scan: Option<Range<u64>>,
+    map_records: u64,
+}
+
+impl MigrationPlan {
+    fn fail(&mut self, key: String) {
+        self.total_failures += 1;
+        if self.failed_keys.len() < MAX_REPORTED_FAILURES {
+            self.failed_keys.push(key);
+        }
+    }
+
+    /// Counts the entries of `map`, and decodes those inside the scan into
+    /// the current layout
+    fn check_map<K, T>(&mut self, storage: &dyn Storage, store: &str, map: &Keymap<K, T>) -> StdResult<()>
+    where
+        K: Serialize + DeserializeOwned + ToString,
+        T: Serialize + DeserializeOwned,
+    {
+        let records = map.get_len(storage)? as u64;
+        let offset = self.map_records;
+        self.map_records += records;
+        self.counts.push(RecordCount { store: store.to_string(), records });
+
+        let Some(scan) = self.scan.clone() else { return Ok(()) };
+        let from = scan.start.saturating_sub(offset).min(records);
+        let to = scan.end.saturating_sub(offset).min(records);
+        for key in map.iter_keys(storage)?.skip(from as usize).take((to - from) as usize) {
+            let key = match key {
+                Ok(key) => key,
+                Err(_) => {
+                    self.fail(format!("{}/<undecodable key>", store));
+                    continue;
+                }
+            };
+            if map.get(storage, &key).is_none() {
+                self.fail(format!("{}/{}", store, key.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a required item; one the plan seeds or rewrites is skipped
+    fn check_item<T, Ser>(&mut self, storage: &dyn Storage, store: &str, item: &Item<T, Ser>, planned: bool) -> StdResult<()>
+    where
+        T: Serialize + DeserializeOwned,
+        Ser: Serde,
+    {
+        let records = match item.may_load(storage) {
+            _ if planned => 1,
+            Ok(Some(_)) => 1,
+            // Every handler loads it, so migrating without it bricks the instance
+            Ok(None) => {
+                self.fail(format!("{}/<missing>", store));
+                0
+            }
+            Err(_) => {
+                self.fail(store.to_string());
+                1
+            }
+        };
+        self.counts.push(RecordCount { store: store.to_string(), records });
+        Ok(())
+    }
+}
+
+/// Read-and-transform phase of `migrate`: refuses migrations that can never
+/// apply, and decodes each stored record into the layout this code uses
+fn plan_migration(storage: &dyn Storage, env: &Env, owner: Option<&str>, scan: Option<Range<u64>>) -> StdResult<MigrationPlan> {
+    // State copied from another chain or contract must never be adopted
+    let adopt_identity = match DEPLOYMENT.may_load(storage)? {
+        Some(identity) if identity.chain_id != env.block.chain_id => {
+            return Err(StdError::generic_err(format!(
+                "State belongs to chain {}, not {}",
+                identity.chain_id, env.block.chain_id
+            )));
+        }
+        Some(identity) if identity.contract_address != env.contract.address.as_str() => {
+            return Err(StdError::generic_err(format!(
+                "State belongs to contract {}",
+                identity.contract_address
+            )));
+        }
+        Some(_) => None,
+        // Instances from before the binding adopt the current deployment
+        None => Some(DeploymentIdentity {
+            chain_id: env.block.chain_id.clone(),
+            contract_address: env.contract.address.to_string(),
+            genesis_time: env.block.time.seconds(),
+        }),
+    };
+
+    let from_schema = stored_schema_version(storage)?;
+    if from_schema > CURRENT_SCHEMA_VERSION {
+        return Err(StdError::generic_err(format!(
+            "Cannot migrate storage schema {} down to {}",
+            from_schema, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    // Data moves for each schema step are planned here, oldest first
+    if from_schema < 2 && PENDING_VALIDATIONS.get_len(storage)? > 0 {
+        // Schema 2 adds data availability fields to pending validations
+        return Err(StdError::generic_err("Pending validations must settle before migrating to schema 2"));
+    }
+
+    // Schema 3 stores finalization metadata and asset leaves on commitments;
+    // older ones are re-encoded without them and get a window for the owner
+    // to backfill the metadata
+    let open_backfill_until = (from_schema < 3 && STATE_COMMITMENTS.get_len(storage)? > 0)
+        .then(|| env.block.height + BACKFILL_WINDOW);
+
+    let mut plan = MigrationPlan {
+        from_schema,
+        adopt_identity,
+        open_backfill_until,
+        index_outcomes: from_schema < 4,
+        reencode_content_votes: from_schema < 5,
+        upgrade_pause_ledger: None,
+        reset_rejections: from_schema < 8,
+        index_outbound_failures: from_schema < 9,
+        index_node_work: from_schema < 10,
+        index_user_pending: from_schema < 11,
+        index_commitment_order: from_schema < 12,
+        upgrade_state: None,
+        upgrade_commitments: vec![],
+        seed_config: None,
+        seed_stats: None,
+        viewing_key_seed: None,
+        counts: vec![],
+        failed_keys: vec![],
+        total_failures: 0,
+        scan,
+        map_records: 0,
+    };
+    // Schema 7 stores Config as Json, so serde defaults fill fields that
+    // older records lack; a record that fails to decode is left to check_item
+    if from_schema < 7 {
+        if let Ok(Some(legacy)) = LEGACY_CONFIG.may_load(storage) {
+            plan.seed_config = Some(from_slice(&to_vec(&legacy)?)?);
+        }
+    }
+    if from_schema < 1 {
+        plan_first_release_upgrade(storage, env, owner, &mut plan)?;
+    } else if from_schema < 3 {
+        plan.upgrade_commitments = upgrade_schema_2_commitments(storage, &mut plan)?;
+    }
+
+    // Schema 6 windows record heights. Closed windows from before it pause
+    // no block window; one still open counts its blocks from the migration.
+    if from_schema < 6 {
+        match LEGACY_PAUSE_LEDGER.may_load(storage) {
+            Ok(Some(windows)) => {
+                plan.upgrade_pause_ledger = Some(windows.into_iter().map(|w| PauseWindow {
+                    paused_at: w.paused_at,
+                    resumed_at: w.resumed_at,
+                    paused_for: w.paused_for,
+                    paused_at_height: if w.resumed_at.is_none() { env.block.height } else { 0 },
+                    resumed_at_height: w.resumed_at.map(|_| 0),
+                    paused_blocks: 0,
+                }).collect());
+            }
+            Ok(None) => {}
+            Err(_) => plan.fail("pause_ledger".to_string()),
+        }
+    }
+
+    let (state_planned, config_planned, stats_planned) =
+        (plan.upgrade_state.is_some(), plan.seed_config.is_some(), plan.seed_stats.is_some());
+    plan.check_item(storage, "state", &STATE, state_planned)?;
+    plan.check_item(storage, "config", &CONFIG, config_planned)?;
+    plan.check_item(storage, "stats", &STATS, stats_planned)?;
+    plan.check_map(storage, "pending_validations", &PENDING_VALIDATIONS)?;
+    if state_planned || (1..3).contains(&from_schema) {
+        // Decoded in their older layout by the upgrade itself
+        let records = plan.upgrade_commitments.len() as u64;
+        plan.counts.push(RecordCount { store: "state_commitments".to_string(), records });
+    } else {
+        plan.check_map(storage, "state_commitments", &STATE_COMMITMENTS)?;
+    }
+    plan.check_map(storage, "transfers", &TRANSFERS)?;
+    plan.check_map(storage, "transfer_groups", &TRANSFER_GROUPS)?;
+    plan.check_map(storage, "leaf_owners", &LEAF_OWNERS)?;
+    plan.check_map(storage, "node_metadata", &NODE_METADATA)?;
+    plan.check_map(storage, "validation_outcomes", &VALIDATION_OUTCOMES)?;
+    plan.check_map(storage, "outbound_failures", &OUTBOUND_FAILURES)?;
+    Ok(plan)
+}
+
+/// Schema 0 to 1. The first release stored a bare State and the commitments
+/// and nothing else: both are rewritten in the current layout, and Config,
+/// Stats and the viewing key seed are created. Instances that already hold
+/// current-layout records (newer code run before the marker existed) keep them.
+fn plan_first_release_upgrade(
+    storage: &dyn Storage,
+    env: &Env,
+    owner: Option<&str>,
+    plan: &mut MigrationPlan,
+) -> StdResult<()> {
+    if STATE.may_load(storage).is_err() {
+        // Anything that decodes as neither layout is reported by check_item
+        if let Ok(Some(legacy)) = LEGACY_STATE.may_load(storage) {
+            plan.upgrade_state = Some(upgrade_legacy_state(legacy, plan));
+        }
+    }
+    let threshold = match (&plan.upgrade_state, STATE.may_load(storage)) {
+        (Some(state), _) => state.threshold,
+        (None, Ok(Some(state))) => state.threshold,
+        _ => return Ok(()),
+    };
+
+    // Without an owner no config can be seeded, and check_item fails the plan.
+    // A migration never turns dev_mode on, so a committee below the
+    // production threshold is refused rather than seeded into dev mode.
+    if plan.seed_config.is_none() && matches!(CONFIG.may_load(storage), Ok(None)) {
+        if threshold < MIN_PRODUCTION_THRESHOLD {
+            plan.fail(format!("state/threshold {} below {}", threshold, MIN_PRODUCTION_THRESHOLD));
+        }
+        if let Some(owner) = owner {
+            plan.seed_config = Some(default_config(owner.to_string(), false));
+            plan.viewing_key_seed = Some(viewing_key_seed(env, owner));
+        }
+    }
+    if matches!(STATS.may_load(storage), Ok(None)) {
+        let mut stats = Stats::default();
+        stats.validation_counts.pending = PENDING_VALIDATIONS.get_len(storage)? as u64;
+        plan.seed_stats = Some(stats);
+    }
+
+    if plan.upgrade_state.is_some() {
+        let tree_depth = match (&plan.seed_config, CONFIG.may_load(storage)) {
+            (Some(config), _) => config.tree_depth,
+            (None, Ok(Some(config))) => config.tree_depth,
+            _ => default_tree_depth(),
+        };
+        plan.upgrade_commitments = upgrade_legacy_commitments(storage, tree_depth, plan)?;
+    }
+    Ok(())
+}
+
+/// Nodes keep their ids; a key the current checks would refuse fails the plan
+fn upgrade_legacy_state(legacy: LegacyState, plan: &mut MigrationPlan) -> State {
+    let mut mpc_nodes = Vec::with_capacity(legacy.mpc_nodes.len());
+    for node in legacy.mpc_nodes {
+        let public_key = match normalize_public_key(&node.public_key) {
+            Ok(key) if NODE_KEY_TYPES.contains(&key_type(&key)) => key,
+            _ => {
+                plan.fail(format!("state/node {}", node.node_id));
+                node.public_key
+            }
+        };
+        mpc_nodes.push(MPCNode {
+            address: node.address,
+            node_id: node.node_id,
+            public_key,
+            active: node.active,
+            operator: None,
+            last_active_height: None,
+            staged_public_key: None,
+            slashed: false,
+            offenses: 0,
+        });
+    }
+
+    let mut state = State {
+        mpc_nodes,
+        threshold: legacy.threshold,
+        current_state_root: legacy.current_state_root,
+        block_height: legacy.block_height,
+        last_validation_id: String::new(),
+        last_threshold_signature: vec![],
+        last_signers: vec![],
+        last_signed_ids: vec![],
+        epoch: 0,
+        last_protocol_version: default_protocol_version(),
+        oldest_retained_height: 0,
+        oldest_partials_height: 0,
+        pending_committee: true,
+        deployment_salt: vec![],
+        key_epoch: 0,
+        last_epoch: 0,
+        last_asset_digest: vec![],
+        backfill_until: None,
+    };
+    mark_committee_ready(&mut state);
+    state
+}
+
+/// Commitments gain the leaf each user would be given today; two users
+/// sharing one fail the plan, as they would fail to finalize
+fn upgrade_legacy_commitments(
+    storage: &dyn Storage,
+    tree_depth: u8,
+    plan: &mut MigrationPlan,
+) -> StdResult<Vec<StateCommitment>> {
+    let mut leaves = BTreeMap::new();
+    let mut commitments = vec![];
+    for item in LEGACY_STATE_COMMITMENTS.iter(storage)? {
+        let (user_address, legacy) = match item {
+            Ok(entry) => entry,
+            Err(_) => {
+                plan.fail("state_commitments/<undecodable>".to_string());
+                continue;
+            }
+        };
+        let index = leaf_index(&user_address, tree_depth);
+        if leaves.insert(index, user_address.clone()).is_some() {
+            plan.fail(format!("state_commitments/{}", user_address));
+            continue;
+        }
+        commitments.push(StateCommitment {
+            user_address,
+            state_root: legacy.state_root,
+            ipfs_cid: legacy.ipfs_cid,
+            merkle_proof: legacy.merkle_proof,
+            nonce: legacy.nonce,
+            updated_at: legacy.updated_at,
+            leaf_index: index,
+            clamped: false,
+            lock_at_height: 0,
+            ipfs_gateway_hint: None,
+            finalized_at_height: 0,
+            content_hash: vec![],
+            epoch: 0,
+            committee_hash: vec![],
+            threshold_signature: vec![],
+            asset_leaves: vec![],
+        });
+    }
+    Ok(commitments)
+}
+
+/// Schema 2 to 3: every commitment decoded in the schema 2 layout and
+/// rewritten with empty finalization metadata, which BackfillCommitment
+/// fills in, and no asset leaves
+fn upgrade_schema_2_commitments(storage: &dyn Storage, plan: &mut MigrationPlan) -> StdResult<Vec<StateCommitment>> {
+    let mut commitments = vec![];
+    for item in LEGACY_STATE_COMMITMENTS_V2.iter(storage)? {
+        let Ok((user_address, legacy)) = item else {
+            plan.fail("state_commitments/<undecodable>".to_string());
+            continue;
+        };
+        commitments.push(StateCommitment {
+            user_address,
+            state_root: legacy.state_root,
+            ipfs_cid: legacy.ipfs_cid,
+            merkle_proof: legacy.merkle_proof,
+            nonce: legacy.nonce,
+            updated_at: legacy.updated_at,
+            leaf_index: legacy.leaf_index,
+            clamped: legacy.clamped,
+            lock_at_height: legacy.lock_at_height,
+            ipfs_gateway_hint: legacy.ipfs_gateway_hint,
+            finalized_at_height: 0,
+            content_hash: vec![],
+            epoch: 0,
+            committee_hash: vec![],
+            threshold_signature: vec![],
+            asset_leaves: vec![],
+        });
+    }
+    Ok(commitments)
+}
+
+/// Write phase of `migrate`
+fn commit_migration(storage: &mut dyn Storage, plan: MigrationPlan) -> StdResult<()> {
+    if let Some(identity) = plan.adopt_identity {
+        DEPLOYMENT.save(storage, &identity)?;
+    }
+    if let Some(state) = plan.upgrade_state {
+        STATE.save(storage, &state)?;
+    }
+    for commitment in plan.upgrade_commitments {
+        LEAF_OWNERS.insert(storage, &commitment.leaf_index, &commitment.user_address)?;
+        STATE_COMMITMENTS.insert(storage, &commitment.user_address, &commitment)?;
+    }
+    if let Some(config) = plan.seed_config {
+        CONFIG.save(storage, &config)?;
+    }
+    if let Some(stats) = plan.seed_stats {
+        STATS.save(storage, &stats)?;
+    }
+    if let Some(seed) = plan.viewing_key_seed {
+        ViewingKey::set_seed(storage, &seed);
+    }
+    if plan.index_outcomes {
+        for validation_id in VALIDATION_OUTCOMES.iter_keys(storage)?.collect::<StdResult<Vec<String>>>()? {
+            index_outcome(storage, &validation_id)?;
+        }
+        let pending = PENDING_VALIDATIONS.iter(storage)?.collect::<StdResult<Vec<_>>>()?;
+        for (validation_id, validation) in pending {
+            for dependency in &validation.transition.depends_on {
+                add_dependent(storage, dependency, &validation_id)?;
+            }
+        }
+    }
+    if let Some(ledger) = plan.upgrade_pause_ledger {
+        PAUSE_LEDGER.save(storage, &ledger)?;
+    }
+    if plan.reencode_content_votes {
+        // Recent votes are only kept to compare against the next one, so
+        // they are dropped; recorded evidence is kept with an unknown state
+        let legacy_vote = |v: LegacyContentVote| ContentVote {
+            content_hash: v.content_hash,
+            validation_id: v.validation_id,
+            valid: v.valid,
+            voted_at: v.voted_at,
+            committed_root: vec![],
+            committed_nonce: 0,
+        };
+        for node in STATE.load(storage)?.mpc_nodes {
+            CONTENT_VOTES.remove(storage, &node.node_id)?;
+            if let Some(evidence) = LEGACY_EQUIVOCATIONS.get(storage, &node.node_id) {
+                let evidence = evidence.into_iter().map(|e| EquivocationEvidence {
+                    node_id: e.node_id,
+                    content_hash: e.content_hash,
+                    first: legacy_vote(e.first),
+                    second: legacy_vote(e.second),
+                }).collect::<Vec<_>>();
+                EQUIVOCATIONS.insert(storage, &node.node_id, &evidence)?;
+            }
+        }
+    }
+    if plan.reset_rejections {
+        RECENT_REJECTIONS.remove(storage);
+    }
+    if plan.index_outbound_failures {
+        let mut ids = OUTBOUND_FAILURES.iter_keys(storage)?.collect::<StdResult<Vec<u64>>>()?;
+        ids.sort_unstable();
+        OUTBOUND_FAILURE_IDS.save(storage, &ids)?;
+    }
+    if plan.index_node_work {
+        let config = CONFIG.load(storage)?;
+        for validation in PENDING_VALIDATIONS.iter(storage)?.map(|item| item.map(|(_, v)| v)).collect::<StdResult<Vec<_>>>()? {
+            file_work(storage, &config, &validation)?;
+        }
+    }
+    if plan.index_user_pending {
+        for validation in PENDING_VALIDATIONS.iter(storage)?.map(|item| item.map(|(_, v)| v)).collect::<StdResult<Vec<_>>>()? {
+            USER_PENDING.add_suffix(validation.transition.user_address.as_bytes()).insert(storage, &validation.validation_id)?;
+        }
+    }
+    if plan.index_commitment_order {
+        let mut users = STATE_COMMITMENTS.iter_keys(storage)?.collect::<StdResult<Vec<String>>>()?;
+        users.sort();
+        for user in &users {
+            order_commitment(storage, user)?;
+        }
+    }
+    if let Some(until) = plan.open_backfill_until {
+        let mut state = STATE.load(storage)?;
+        state.backfill_until = Some(until);
+        STATE.save(storage, &state)?;
+    }
+    // Bumped last, so handlers never see a half-moved layout as current
+    STORAGE_SCHEMA_VERSION.save(storage, &CURRENT_SCHEMA_VERSION)
+}
+
+/// Built from the config and constants the submit and registration checks
+/// read, never from copies of them
+fn protocol_info(config: &Config) -> ProtocolInfoResponse {
+    ProtocolInfoResponse {
+        protocol_versions: config.supported_protocol_versions.clone(),
+        share_encodings: SUPPORTED_SHARE_ENCODINGS.to_vec(),
+        user_key_types: USER_KEY_TYPES.to_vec(),
+        node_key_types: NODE_KEY_TYPES.to_vec(),
+        hash_scheme: HASH_SCHEME.to_string(),
+        domain_tags: DOMAIN_TAGS.iter().map(|tag| tag.to_string()).collect(),
+        limits: ProtocolLimits {
+            digest_len: DIGEST_LEN as u32,
+            partial_signature_len: PARTIAL_SIGNATURE_LEN as u32,
+            max_transition_size: config.max_transition_size,
+            max_share_len: config.max_share_len,
+            max_coordinator_metadata: MAX_COORDINATOR_METADATA as u32,
+            max_gateway_hint_len: MAX_GATEWAY_HINT_LEN as u32,
+            nonce_commitment_len: NONCE_COMMITMENT_LEN as u32,
+            max_dependencies: MAX_DEPENDENCIES as u32,
+            max_dependents: MAX_DEPENDENTS as u32,
+            max_batch_votes: MAX_BATCH_VOTES as u32,
+            max_asset_updates: MAX_ASSET_UPDATES as u32,
+            max_asset_id_len: MAX_ASSET_ID_LEN as u32,
+        },
+    }
+}
+
+/// Reads only State, Config, the maintained validation counters and the
+/// parked-send index (capped at MAX_OUTBOUND_FAILURES), so the probe costs
+/// the same however much is pending
+fn health(storage: &dyn Storage) -> StdResult<HealthResponse> {
+    if stored_schema_version(storage)? != CURRENT_SCHEMA_VERSION {
+        return Ok(HealthResponse {
+            status: HealthStatus::MigrationRequired,
+            reasons: vec![HealthReason::MigrationRequired],
+        });
+    }
+
+    let state = STATE.load(storage)?;
+    let config = CONFIG.load(storage)?;
+    let stats = STATS.load(storage)?;
+
+    let mut reasons = vec![];
+    let paused = is_paused(storage)?;
+    if paused {
+        reasons.push(HealthReason::Paused);
+    }
+    if state.pending_committee {
+        reasons.push(HealthReason::CommitteeNotReady);
+    }
+    if state.mpc_nodes.iter().filter(|n| n.active).count() < state.threshold as usize {
+        reasons.push(HealthReason::BelowThreshold);
+    }
+    if stats.validation_counts.pending > config.pending_watermark {
+        reasons.push(HealthReason::PendingBacklog);
+    }
+    let parked = OUTBOUND_FAILURE_IDS.may_load(storage)?.unwrap_or_default().len();
+    if parked > config.outbound_failure_watermark as usize {
+        reasons.push(HealthReason::OutboundFailures);
+    }
+
+    let status = if paused {
+        HealthStatus::Paused
+    } else if reasons.is_empty() {
+        HealthStatus::Healthy
+    } else {
+        HealthStatus::Degraded
+    };
+    Ok(HealthResponse { status, reasons })
+}
+
+/// Instances created before the marker existed are schema 0
+fn stored_schema_version(storage: &dyn Storage) -> StdResult<u16> {
+    Ok(STORAGE_SCHEMA_VERSION.may_load(storage)?.unwrap_or(0))
+}
+
+/// Refuses to touch storage laid out for another schema, rather than failing
+/// later with a deserialization error
+fn require_current_schema(storage: &dyn Storage) -> StdResult<()> {
+    let have = stored_schema_version(storage)?;
+    if have != CURRENT_SCHEMA_VERSION {
+        return Err(StdError::generic_err(format!(
+            "MigrationRequired {{ have: {}, need: {} }}",
+            have, CURRENT_SCHEMA_VERSION
+        )));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// QUERY
+// ============================================================================
+
+#[entry_point]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    // GetConfig and GetHealth stay answerable on an un-migrated instance so
+    // clients can see why everything else fails; ComputeRoot reads no storage,
+    // and MigrationDryRun exists to read storage that is not yet migrated
+    if !matches!(msg, QueryMsg::GetConfig {} | QueryMsg::GetHealth {} | QueryMsg::ComputeRoot { .. } | QueryMsg::MigrationDryRun { .. }) {
+        require_current_schema(deps.storage)?;
+    }
+
+    match msg {
+        QueryMsg::GetState {} => {
+            let state = STATE.load(deps.storage)?;
+            to_binary(&StateResponse { state })
+        }
+        QueryMsg::GetStateCommitment { user_address, viewing_key } => {
+            // The CID and proof are private to the user
+            authenticate(deps, &ViewerAuth { address: user_address.clone(), viewing_key })?;
+            let commitment = STATE_COMMITMENTS.get(deps.storage, &user_address)
+                .ok_or_else(|| StdError::generic_err("Commitment not found"))?;
+            let config = CONFIG.load(deps.storage)?;
+            if env.block.height < commitment.finalized_at_height + config.commitment_confirmations {
+                return Err(StdError::generic_err("Commitment not yet confirmed"));
+            }
+            let metadata_complete = commitment.metadata_complete();
+            to_binary(&StateCommitmentResponse { commitment, metadata_complete })
+        }
+        QueryMsg::GetValidation { validation_id, auth } => {
+            authenticate(deps, &auth)?;
+            if !OBSERVERS.contains(deps.storage, &auth.address) {
+                return Err(StdError::generic_err("Unauthorized"));
+            }
+            let validation = PENDING_VALIDATIONS.get(deps.storage, &validation_id)
+                .ok_or_else(|| StdError::generic_err("Not found"))?;
+            to_binary(&ValidationResponse { validation })
+        }
+        QueryMsg::GetCurrentRoot {} => {
+            let state = STATE.load(deps.storage)?;
+            to_binary(&CurrentRootResponse { root: state.current_state_root })
+        }
+        QueryMsg::ListPendingValidations {} => {
+            // Iterate through all pending validations
+            let validation_ids: Vec<String> = PENDING_VALIDATIONS
+                .iter(deps.storage)?
+                .map(|item| {
+                    let (key, _) = item?;
+                    Ok(key)
+                })
+                .collect::<StdResult<Vec<String>>>()?;
+            to_binary(&PendingValidationsResponse { validation_ids })
+        }
+        QueryMsg::GetLightClientUpdate {} => {
+            let state = STATE.load(deps.storage)?;
+            let identity = DEPLOYMENT.load(deps.storage)?;
+            let root_attestation = root_attestation_digest(
+                &identity.chain_id,
+                &identity.contract_address,
+                state.block_height,
+                &state.current_state_root,
+            );
+            let committee = committee_keys(deps.storage, &state, state.last_epoch, &state.last_signers);
+            to_binary(&LightClientUpdateResponse {
+                root: state.current_state_root,
+                block_height: state.block_height,
+                validation_id: state.last_validation_id,
+                threshold_signature: state.last_threshold_signature,
+                protocol_version: state.last_protocol_version,
+                signers: state.last_signers,
+                signed_validation_ids: state.last_signed_ids,
+                threshold: state.threshold,
+                epoch: state.last_epoch,
+                committee,
+                asset_digest: state.last_asset_digest,
+                chain_id: identity.chain_id,
+                contract_address: identity.contract_address,
+                root_attestation,
+            })
+        }
+        QueryMsg::GetConfig {} => {
+            let storage_schema_version = stored_schema_version(deps.storage)?;
+            let config = if storage_schema_version == CURRENT_SCHEMA_VERSION {
+                Some(CONFIG.load(deps.storage)?)
+            } else {
+                None
+            };
+            to_binary(&ConfigResponse {
+                config,
+                storage_schema_version,
+                required_schema_version: CURRENT_SCHEMA_VERSION,
+            })
+        }
+        QueryMsg::GetStats {} => {
+            let stats = STATS.load(deps.storage)?;
+            to_binary(&StatsResponse { stats })
+        }
+        QueryMsg::GetMissingVoters { validation_id } => {
+            let validation = load_pending(deps.storage, &validation_id)?;
+            let state = STATE.load(deps.storage)?;
+            let node_ids = state.mpc_nodes.iter()
+                .filter(|n| n.active && !validation.validations.iter().any(|v| v.node_id == n.node_id))
+                .map(|n| n.node_id)
+                .collect();
+            to_binary(&MissingVotersResponse { node_ids })
+        }
+        QueryMsg::GetVoterKeys { validation_id } => {
+            // The keys of the committee the validation was submitted to, which
+            // a finalized record's audit trail froze
+            let voters = match PENDING_VALIDATIONS.get(deps.storage, &validation_id) {
+                Some(validation) => {
+                    let signers: Vec<u32> = validation.validations.iter().filter(|v| v.valid).map(|v| v.node_id).collect();
+                    let committee = committee_keys(deps.storage, &STATE.load(deps.storage)?, validation.epoch, &signers);
+                    signers.iter()
+                        .filter_map(|node_id| committee.iter().find(|k| k.node_id == *node_id))
+                        .map(|k| VoterKey { node_id: k.node_id, public_key: k.public_key.clone() })
+                        .collect()
+                }
+                None => {
+                    let Some(record) = FINALIZED_HEIGHTS.get(deps.storage, &validation_id)
+                        .and_then(|height| FINALIZED_RECORDS.get(deps.storage, &height)) else {
+                        return load_pending(deps.storage, &validation_id).and(Err(StdError::generic_err("Validation not found")));
+                    };
+                    let audit = record.audit
+                        .ok_or_else(|| StdError::generic_err("Record predates audit trails; its signers' keys were not kept"))?;
+                    audit.signer_keys.into_iter()
+                        .map(|m| VoterKey { node_id: m.node_id, public_key: m.public_key })
+                        .collect()
+                }
+            };
+            to_binary(&VoterKeysResponse { voters })
+        }
+        QueryMsg::GetRootAtHeight { height } => {
+            let state = STATE.load(deps.storage)?;
+            let response = match ROOT_HISTORY.get(deps.storage, &height) {
+                Some(root) => RootAtHeightResponse { status: RootStatus::Found, root: Some(root) },
+                None if height < state.oldest_retained_height => RootAtHeightResponse { status: RootStatus::Pruned, root: None },
+                None => RootAtHeightResponse { status: RootStatus::NotFound, root: None },
+            };
+            to_binary(&response)
+        }
+        QueryMsg::VerifyInclusion { leaf, proof, expected_root } => {
+            let root = match expected_root {
+                Some(root) => root,
+                None => STATE.load(deps.storage)?.current_state_root,
+            };
+            to_binary(&VerifyInclusionResponse { valid: verify_merkle_proof(&leaf, &proof, &root), root })
+        }
+        QueryMsg::GetProtocolInfo {} => to_binary(&protocol_info(&CONFIG.load(deps.storage)?)),
+        QueryMsg::GetDeploymentIdentity {} => {
+            to_binary(&DeploymentIdentityResponse { identity: DEPLOYMENT.load(deps.storage)? })
+        }
+        QueryMsg::GetFinalized { height } => {
+            let state = STATE.load(deps.storage)?;
+            let response = match FINALIZED_RECORDS.get(deps.storage, &height) {
+                Some(record) => FinalizedResponse { status: RootStatus::Found, record: Some(record) },
+                None if height < state.oldest_retained_height => FinalizedResponse { status: RootStatus::Pruned, record: None },
+                None => FinalizedResponse { status: RootStatus::NotFound, record: None },
+            };
+            to_binary(&response)
+        }
+        QueryMsg::GetLastFinalized {} => {
+            to_binary(&LastFinalizedResponse { last: LAST_FINALIZED.may_load(deps.storage)? })
+        }
+        QueryMsg::GetFinalizersForUser { user_address, nonce } => {
+            let node_ids = COMMITMENT_FINALIZERS.get(deps.storage, &nonce_key(&user_address, nonce))
+                .ok_or_else(|| StdError::generic_err("No finalized commitment with that nonce"))?;
+            to_binary(&FinalizersResponse { node_ids })
+        }
+        QueryMsg::GetHealth {} => to_binary(&health(deps.storage)?),
+        QueryMsg::GetLifetimeNodeCount {} => {
+            // The registry is append-only: deregistering deactivates and
+            // re-registering reuses the address's entry, so its length is
+            // the lifetime count
+            let state = STATE.load(deps.storage)?;
+            to_binary(&LifetimeNodeCountResponse {
+                registered: state.mpc_nodes.len() as u32,
+                active: state.mpc_nodes.iter().filter(|n| n.active).count() as u32,
+            })
+        }
+        QueryMsg::GetAuditBundle { validation_id, viewing_key } => {
+            let not_found = || StdError::generic_err(format!("No retained finalization for {}", validation_id));
+            let height = FINALIZED_HEIGHTS.get(deps.storage, &validation_id).ok_or_else(not_found)?;
+            let record = FINALIZED_RECORDS.get(deps.storage, &height).ok_or_else(not_found)?;
+            let authenticated = match viewing_key {
+                Some(viewing_key) => {
+                    authenticate(deps, &ViewerAuth { address: record.user_address.clone(), viewing_key })?;
+                    true
+                }
+                None => false,
+            };
+            let bundle = to_binary(&audit_bundle(deps.storage, height, &record, authenticated)?)?;
+            if bundle.len() > MAX_AUDIT_BUNDLE_LEN {
+                return Err(StdError::generic_err(format!(
+                    "Audit bundle is {} bytes, over the {} byte cap",
+                    bundle.len(),
+                    MAX_AUDIT_BUNDLE_LEN
+                )));
+            }
+            Ok(bundle)
+        }
+        QueryMsg::ListOutboundFailures { start_after, limit } => {
+            let limit = limit.unwrap_or(MAX_SEND_PAGE).min(MAX_SEND_PAGE) as usize;
+            let ids = OUTBOUND_FAILURE_IDS.may_load(deps.storage)?.unwrap_or_default();
+            let start = start_after.map_or(0, |cursor| ids.partition_point(|id| *id <= cursor));
+            let page = &ids[start..ids.len().min(start + limit)];
+            let failures = page.iter()
+                .filter_map(|id| OUTBOUND_FAILURES.get(deps.storage, id).map(|send| OutboundFailure { id: *id, send }))
+                .collect();
+            let next_start_after = if start + limit < ids.len() { page.last().copied() } else { None };
+            to_binary(&OutboundFailuresResponse { failures, next_start_after })
+        }
+        QueryMsg::GetWithdrawalReceipt { auth, validation_id } => {
+            authenticate(deps, &auth)?;
+            // Anyone else is answered as for a validation without one
+            let receipt = WITHDRAWAL_RECEIPTS.get(deps.storage, &validation_id)
+                .filter(|receipt| receipt.recipient == auth.address);
+            to_binary(&WithdrawalReceiptResponse { receipt })
+        }
+        QueryMsg::ListWithdrawalReceipts { auth, start_after, limit } => {
+            authenticate(deps, &auth)?;
+            let limit = limit.unwrap_or(MAX_SEND_PAGE).min(MAX_SEND_PAGE) as usize;
+            // Only the caller's own receipts are read. A redirect removes one
+            // and reorders the set, so ids are sorted for a stable cursor.
+            let mut ids = RECIPIENT_RECEIPTS.add_suffix(auth.address.as_bytes())
+                .iter(deps.storage)?
+                .collect::<StdResult<Vec<String>>>()?;
+            ids.sort();
+            let start = match &start_after {
+                Some(cursor) => ids.partition_point(|id| id <= cursor),
+                None => 0,
+            };
+            let page = &ids[start..ids.len().min(start + limit)];
+            let receipts = page.iter().filter_map(|id| WITHDRAWAL_RECEIPTS.get(deps.storage, id)).collect();
+            let next_start_after = if start + limit < ids.len() { page.last().cloned() } else { None };
+            to_binary(&WithdrawalReceiptsResponse { receipts, next_start_after })
+        }
+        QueryMsg::IsNode { address } => {
+            // An active node wins over an inactive one the address also
+            // operates, matching what get_active_node accepts
+            let state = STATE.load(deps.storage)?;
+            let node = get_operated_node(&state, &address).ok();
+            to_binary(&IsNodeResponse {
+                is_registered: node.is_some(),
+                is_active: node.is_some_and(|n| n.active),
+                node_id: node.map(|n| n.node_id),
+            })
+        }
+        QueryMsg::GetCommitmentAtHeight { user_address, height, viewing_key } => {
+            authenticate(deps, &ViewerAuth { address: user_address.clone(), viewing_key })?;
+            let commitment = commitment_at_height(deps.storage, &user_address, height, env.block.height)?;
+            to_binary(&CommitmentAtHeightResponse { height, commitment })
+        }
+        QueryMsg::GetStorageUsage { user } => {
+            let config = CONFIG.load(deps.storage)?;
+            let bytes = USER_STORAGE.get(deps.storage, &user).unwrap_or(0);
+            to_binary(&StorageUsageResponse { user, bytes, quota: config.max_user_storage })
+        }
+        QueryMsg::GetAccountOverview { user_address, viewing_key } => {
+            let authenticated = match viewing_key {
+                Some(viewing_key) => {
+                    authenticate(deps, &ViewerAuth { address: user_address.clone(), viewing_key })?;
+                    true
+                }
+                None => false,
+            };
+            to_binary(&account_overview(deps.storage, env.block.height, user_address, authenticated)?)
+        }
+        QueryMsg::GetValidationCounts {} => {
+            let counts = STATS.load(deps.storage)?.validation_counts;
+            to_binary(&ValidationCountsResponse {
+                pending: counts.pending,
+                finalized: counts.finalized,
+                rejected: counts.rejected,
+                expired: counts.expired,
+                challenged: counts.challenged,
+            })
+        }
+        QueryMsg::GetPendingForNode { node_id, auth, cursor } => {
+            authenticate_node(deps, node_id, &auth)?;
+            to_binary(&pending_for_node(deps.storage, &env, node_id, cursor)?)
+        }
+        QueryMsg::GetAssignedPayload { validation_id, node_id, auth } => {
+            authenticate_node(deps, node_id, &auth)?;
+            let validation = load_pending(deps.storage, &validation_id)?;
+            let share = find_share(&validation.transition, node_id)
+                .ok_or_else(|| StdError::generic_err("No share for node"))?;
+            to_binary(&AssignedPayloadResponse { item: work_item(deps.storage, &env, &validation, share)? })
+        }
+        QueryMsg::GetTransferStatus { transfer_id } => {
+            let transfer = TRANSFERS.get(deps.storage, &transfer_id)
+                .ok_or_else(|| StdError::generic_err("Transfer not found"))?;
+            to_binary(&TransferStatusResponse { transfer })
+        }
+        QueryMsg::GetTransferGroupStatus { group_id } => {
+            let group = TRANSFER_GROUPS.get(deps.storage, &group_id)
+                .ok_or_else(|| StdError::generic_err("Transfer group not found"))?;
+            to_binary(&TransferGroupStatusResponse { group })
+        }
+        QueryMsg::GetLeafIndex { user_address } => {
+            let config = CONFIG.load(deps.storage)?;
+            let leaf_index = leaf_index(&user_address, config.tree_depth);
+            let owner = LEAF_OWNERS.get(deps.storage, &leaf_index);
+            to_binary(&LeafIndexResponse { leaf_index, owner })
+        }
+        QueryMsg::GetSharesForNode { validation_id, node_id, auth } => {
+            authenticate_node(deps, node_id, &auth)?;
+            let validation = PENDING_VALIDATIONS.get(deps.storage, &validation_id)
+                .ok_or_else(|| StdError::generic_err("Not found"))?;
+            let share = find_share(&validation.transition, node_id)
+                .cloned()
+                .ok_or_else(|| StdError::generic_err("No share for node"))?;
+            to_binary(&SharesForNodeResponse { share })
+        }
+        QueryMsg::ListNodes { include_attestations } => {
+            let state = STATE.load(deps.storage)?;
+            to_binary(&NodesResponse { nodes: node_infos(deps.storage, state.mpc_nodes, include_attestations) })
+        }
+        QueryMsg::MigrationDryRun { owner, start_after, limit } => {
+            to_binary(&migration_dry_run(deps, &env, owner, start_after, limit)?)
+        }
+        QueryMsg::ExportSnapshot { auth, start_after, limit } => {
+            authenticate(deps, &auth)?;
+            if auth.address != CONFIG.load(deps.storage)?.owner {
+                return Err(StdError::generic_err("Unauthorized"));
+            }
+            let state = STATE.load(deps.storage)?;
+            let limit = limit.unwrap_or(DEFAULT_SNAPSHOT_LIMIT).clamp(1, MAX_SNAPSHOT_LIMIT) as usize;
+
+            // Export slots never shift, so a cursor stays valid across removals
+            let end = COMMITMENT_SEQ.may_load(deps.storage)?.unwrap_or(0);
+            let start = start_after.map_or(0, |slot| slot + 1);
+            let mut commitments = vec![];
+            let mut slot = start;
+            while slot < end && commitments.len() < limit && slot - start < MAX_SNAPSHOT_SCAN {
+                if let Some(commitment) = COMMITMENT_ORDER.get(deps.storage, &slot)
+                    .and_then(|user| STATE_COMMITMENTS.get(deps.storage, &user))
+                {
+                    commitments.push(commitment);
+                }
+                slot += 1;
+            }
+            let next_start_after = (slot < end).then(|| slot - 1);
+
+            let nodes = if start_after.is_none() {
+                node_infos(deps.storage, state.mpc_nodes, true)
+            } else {
+                vec![]
+            };
+            to_binary(&SnapshotResponse {
+                block_height: state.block_height,
+                current_state_root: state.current_state_root,
+                nodes,
+                commitments,
+                next_start_after,
+            })
+        }
+        QueryMsg::GetBuildDiversity {} => {
+            let state = STATE.load(deps.storage)?;
+            let config = CONFIG.load(deps.storage)?;
+            let now = env.block.time.seconds();
+
+            let mut builds: Vec<BuildCount> = vec![];
+            let mut unknown = 0;
+            for node in state.mpc_nodes.iter().filter(|n| n.active) {
+                let current = NODE_METADATA.get(deps.storage, &node.node_id)
+                    .and_then(|m| m.attestation)
+                    .filter(|a| now.saturating_sub(a.attested_at) <= config.attestation_window);
+                match current {
+                    Some(attestation) => match builds.iter_mut().find(|b| b.binary_hash == attestation.binary_hash) {
+                        Some(build) => build.count += 1,
+                        None => builds.push(BuildCount { binary_hash: attestation.binary_hash, count: 1 }),
+                    },
+                    None => unknown += 1,
+                }
+            }
+            to_binary(&BuildDiversityResponse { builds, unknown })
+        }
+        QueryMsg::ComputeRoot { leaves } => {
+            if leaves.is_empty() {
+                return Err(StdError::generic_err("No leaves supplied"));
+            }
+            if leaves.len() > MAX_ROOT_LEAVES {
+                return Err(StdError::generic_err(format!("At most {} leaves allowed", MAX_ROOT_LEAVES)));
+            }
+            to_binary(&ComputeRootResponse { root: compute_merkle_root(&leaves) })
+        }
+        QueryMsg::GetAdminRecovery {} => {
+            let state = STATE.load(deps.storage)?;
+            let config = CONFIG.load(deps.storage)?;
+            to_binary(&AdminRecoveryResponse {
+                recovery: ADMIN_RECOVERY.may_load(deps.storage)?,
+                required_approvals: recovery_quorum(&state, &config),
+            })
+        }
+        QueryMsg::GetNodeStats { node_id, epoch } => {
+            let state = STATE.load(deps.storage)?;
+            get_node(&state, node_id)?;
+            let epoch = epoch.unwrap_or(state.epoch);
+            let effort = NODE_EFFORT.get(deps.storage, &effort_key(epoch, node_id)).unwrap_or_default();
+            to_binary(&NodeStatsResponse { node_id, epoch, votes: effort.votes, effort_units: effort.effort_units })
+        }
+        QueryMsg::ListEquivocations { node_id } => {
+            let state = STATE.load(deps.storage)?;
+            let node = get_node(&state, node_id)?;
+            to_binary(&EquivocationsResponse {
+                node_id,
+                offenses: node.offenses,
+                equivocations: EQUIVOCATIONS.get(deps.storage, &node_id).unwrap_or_default(),
+            })
+        }
+        QueryMsg::GetRewardSplit { epoch, amount } => {
+            let state = STATE.load(deps.storage)?;
+            let config = CONFIG.load(deps.storage)?;
+            to_binary(&reward_split(deps.storage, &state, config.reward_policy, epoch, amount))
+        }
+        QueryMsg::GetCommitteeSnapshot { epoch } => {
+            let state = STATE.load(deps.storage)?;
+            let snapshot = COMMITTEE_SNAPSHOTS.get(deps.storage, &epoch)
+                .ok_or_else(|| StdError::generic_err("Committee snapshot not found"))?;
+            to_binary(&CommitteeSnapshotResponse { current_epoch: state.epoch, snapshot })
+        }
+        QueryMsg::ListEpochs { start_after, limit } => {
+            // Key epochs run 1..=key_epoch without gaps, so a page is a range
+            let state = STATE.load(deps.storage)?;
+            let limit = limit.unwrap_or(MAX_EPOCH_PAGE).clamp(1, MAX_EPOCH_PAGE) as u64;
+            let first = start_after.map_or(1, |cursor| cursor.saturating_add(1));
+            let last = state.key_epoch.min(first.saturating_add(limit - 1));
+            let keys: Vec<ThresholdKey> = (first..=last)
+                .filter_map(|key_epoch| THRESHOLD_KEYS.get(deps.storage, &key_epoch))
+                .collect();
+            let next_start_after = (last < state.key_epoch).then_some(last);
+            to_binary(&EpochsResponse { current_epoch: state.epoch, current_key_epoch: state.key_epoch, keys, next_start_after })
+        }
+        QueryMsg::GetThresholdKey { key_epoch } => {
+            let state = STATE.load(deps.storage)?;
+            let key = THRESHOLD_KEYS.get(deps.storage, &key_epoch.unwrap_or(state.key_epoch))
+                .ok_or_else(|| StdError::generic_err("Threshold key not found"))?;
+            to_binary(&ThresholdKeyResponse { current_key_epoch: state.key_epoch, key })
+        }
+        QueryMsg::VerifyThresholdSignature { validation_id, signature } => {
+            // What the signature covers, and the key epoch it was bound to at submission
+            let (key_epoch, protocol_version, new_root, asset_digest) = match PENDING_VALIDATIONS.get(deps.storage, &validation_id) {
+                Some(validation) => (
+                    validation.key_epoch,
+                    validation.protocol_version,
+                    validation.transition.new_state_root,
+                    asset_updates_digest(&validation.transition.asset_updates),
+                ),
+                None => {
+                    let Some(record) = FINALIZED_HEIGHTS.get(deps.storage, &validation_id)
+                        .and_then(|height| FINALIZED_RECORDS.get(deps.storage, &height)) else {
+                        // Neither pending nor finalized: say what became of it
+                        return load_pending(deps.storage, &validation_id).and(Err(StdError::generic_err("Validation not found")));
+                    };
+                    let audit = record.audit
+                        .ok_or_else(|| StdError::generic_err("Record predates audit trails; its protocol version is unknown"))?;
+                    (record.key_epoch, audit.protocol_version, record.new_root, record.asset_digest)
+                }
+            };
+            let key = THRESHOLD_KEYS.get(deps.storage, &key_epoch)
+                .ok_or_else(|| StdError::generic_err("No threshold key when submitted"))?;
+            let identity = DEPLOYMENT.load(deps.storage)?;
+            let digest = vote_digest(
+                &identity.chain_id,
+                &identity.contract_address,
+                &validation_id,
+                protocol_version,
+                &new_root,
+                &asset_digest,
+            );
+            to_binary(&ThresholdSignatureResponse {
+                key_epoch: key.key_epoch,
+                valid: deps.api.secp256k1_verify(&digest, &signature, &key.public_key).unwrap_or(false),
+            })
+        }
+        QueryMsg::GetValidationExpiry { validation_id } => {
+            let config = CONFIG.load(deps.storage)?;
+            let validation = load_pending(deps.storage, &validation_id)?;
+            to_binary(&ValidationExpiryResponse {
+                created_at: validation.created_at,
+                timeout: config.validation_timeout,
+                expires_at: validation_expires_at(deps.storage, &validation, env.block.time.seconds())?,
+            })
+        }
+        QueryMsg::DiagnoseValidation { validation_id } => {
+            let validation = load_pending(deps.storage, &validation_id)?;
+            let checks = finalization_checks(deps, &env, &validation)?;
+            to_binary(&DiagnosisResponse {
+                validation_id,
+                finalizable: checks.iter().all(|c| c.passed),
+                checks,
+            })
+        }
+        QueryMsg::GetPauseLedger {} => {
+            let windows = PAUSE_LEDGER.may_load(deps.storage)?.unwrap_or_default();
+            to_binary(&PauseLedgerResponse {
+                paused: windows.last().is_some_and(|w| w.resumed_at.is_none()),
+                windows,
+            })
+        }
+        QueryMsg::GetValidationStatus { validation_id } => {
+            let response = match PENDING_VALIDATIONS.get(deps.storage, &validation_id) {
+                Some(pending) => ValidationStatusResponse {
+                    unmet_dependencies: unmet_dependencies(deps.storage, &pending.transition),
+                    validation_id,
+                    outcome: None,
+                    threshold_reached: pending.threshold_reached,
+                    subscribers: pending.subscribers,
+                },
+                None => {
+                    let outcome = VALIDATION_OUTCOMES.get(deps.storage, &validation_id)
+                        .ok_or_else(|| StdError::generic_err("Validation not found"))?;
+                    ValidationStatusResponse {
+                        validation_id,
+                        threshold_reached: outcome.status == OutcomeStatus::Finalized,
+                        outcome: Some(outcome.status),
+                        unmet_dependencies: vec![],
+                        subscribers: vec![],
+                    }
+                }
+            };
+            to_binary(&response)
+        }
+        QueryMsg::GetValidationOutcome { validation_id } => {
+            let outcome = VALIDATION_OUTCOMES.get(deps.storage, &validation_id)
+                .ok_or_else(|| StdError::generic_err("Outcome not found"))?;
+            to_binary(&ValidationOutcomeResponse { outcome })
+        }
+    }
+}
+
+// ============================================================================
+// HELPER FUNCTIONS
+// ============================================================================
+
+/// Checks a viewing key against the address it claims to belong to
+fn node_infos(storage: &dyn Storage, nodes: Vec<MPCNode>, include_attestations: bool) -> Vec<NodeInfo> {
+    nodes.into_iter()
+        .map(|node| {
+            let attestation = if include_attestations {
+                NODE_METADATA.get(storage, &node.node_id).and_then(|m| m.attestation)
+            } else {
+                None
+            };
+            NodeInfo { node, attestation }
+        })
+        .collect()
+}
+
+fn authenticate(deps: Deps, auth: &ViewerAuth) -> StdResult<()> {
+    ViewingKey::check(deps.storage, &auth.address, &auth.viewing_key)
+        .map_err(|_| StdError::generic_err("Unauthorized"))
+}
+
+/// Joins a user's commitment, key, pending validations and storage use.
+/// Pending validations are found by scanning; only the oldest
+/// MAX_OVERVIEW_PENDING are detailed.
+fn account_overview(storage: &dyn Storage, height: u64, user_address: String, authenticated: bool) -> StdResult<AccountOverviewResponse> {
+    let config = CONFIG.load(storage)?;
+    let commitment = STATE_COMMITMENTS.get(storage, &user_address).map(|c| {
+        let confirmed = height >= c.finalized_at_height + config.commitment_confirmations;
+        let metadata_complete = c.metadata_complete();
+        AccountCommitment {
+            state_root: c.state_root,
+            nonce: c.nonce,
+            leaf_index: c.leaf_index,
+            updated_at: c.updated_at,
+            finalized_at_height: c.finalized_at_height,
+            confirmed,
+            epoch: c.epoch,
+            metadata_complete,
+            private: (authenticated && confirmed).then_some(AccountPrivate {
+                ipfs_cid: c.ipfs_cid,
+                content_hash: c.content_hash,
+                merkle_proof: c.merkle_proof,
+                ipfs_gateway_hint: c.ipfs_gateway_hint,
+            }),
+        }
+    });
+
+    let mut pending = Vec::new();
+    for validation_id in USER_PENDING.add_suffix(user_address.as_bytes()).iter(storage)? {
+        let validation = load_pending(storage, &validation_id?)?;
+        pending.push((validation.created_at, validation.validation_id.clone(), validation));
+    }
+    pending.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+    let pending_total = pending.len() as u32;
+    let pending = pending.into_iter().take(MAX_OVERVIEW_PENDING)
+        .map(|(created_at, validation_id, validation)| Ok(AccountPending {
+            validation_id,
+            created_at,
+            valid_votes: validation.validations.iter().filter(|v| v.valid).count() as u32,
+            threshold_reached: validation.threshold_reached,
+            blocked: block_reason(storage, &validation)?.map(|b| b.as_str().to_string()),
+            unmet_dependencies: unmet_dependencies(storage, &validation.transition),
+        }))
+        .collect::<StdResult<Vec<_>>>()?;
+
+    Ok(AccountOverviewResponse {
+        user_key: USER_KEYS.get(storage, &user_address),
+        key_nonce: USER_KEY_NONCES.get(storage, &user_address).unwrap_or(0),
+        key_rotations: KEY_ROTATIONS.get(storage, &user_address).unwrap_or_default(),
+        purge_nonce: PURGE_NONCES.get(storage, &user_address).unwrap_or(0),
+        commitment,
+        pending,
+        pending_total,
+        storage: StorageUsageResponse {
+            bytes: USER_STORAGE.get(storage, &user_address).unwrap_or(0),
+            user: user_address.clone(),
+            quota: config.max_user_storage,
+        },
+        user_address,
+    })
+}
+
+fn work_item(storage: &dyn Storage, env: &Env, validation: &PendingValidation, share: &EncryptedShares) -> StdResult<NodeWorkItem> {
+    let (expires_at, expires_at_height) = work_deadlines(storage, env, validation)?;
+    Ok(NodeWorkItem {
+        validation_id: validation.validation_id.clone(),
+        protocol_version: validation.protocol_version,
+        created_at: validation.created_at,
+        expires_at,
+        expires_at_height,
+        share: share.clone(),
+        group: validation.group_id.as_ref().and_then(|group_id| TRANSFER_GROUPS.get(storage, group_id)),
+    })
+}
+
+/// Block time and, under a block window, height a validation expires at,
+/// each pushed back by any pause since it was created
+fn work_deadlines(storage: &dyn Storage, env: &Env, validation: &PendingValidation) -> StdResult<(u64, Option<u64>)> {
+    let config = CONFIG.load(storage)?;
+    let expires_at = validation_expires_at(storage, validation, env.block.time.seconds())?;
+    let expires_at_height = block_window_deadline(&config, validation)
+        .map(|deadline| deadline.adjusted(storage, env.block.height))
+        .transpose()?;
+    Ok((expires_at, expires_at_height))
+}
+
+/// Page of a node's work after `cursor` ("deadline:validation_id" of the
+/// last full item returned), in NODE_WORK order. Only the first
+/// config.max_work_payloads carry shares; at most MAX_WORK_STUBS stubs
+/// follow them.
+fn pending_for_node(storage: &dyn Storage, env: &Env, node_id: u32, cursor: Option<String>) -> StdResult<PendingForNodeResponse> {
+    let config = CONFIG.load(storage)?;
+    let after = cursor.map(|cursor| {
+        cursor.split_once(':')
+            .and_then(|(deadline, validation_id)| Some((deadline.parse::<u64>().ok()?, validation_id.to_string())))
+            .ok_or_else(|| StdError::generic_err("Invalid cursor"))
+    }).transpose()?;
+
+    let work = NODE_WORK.get(storage, &node_id).unwrap_or_default();
+    let start = after.map_or(0, |after| work.partition_point(|filed| filed <= &after));
+    let budget = config.max_work_payloads as usize;
+    let mut items = Vec::new();
+    let mut stubs = Vec::new();
+    let mut last_item = None;
+    for key in work[start..].iter().take(budget + MAX_WORK_STUBS) {
+        let validation = load_pending(storage, &key.1)?;
+        let share = find_share(&validation.transition, node_id)
+            .ok_or_else(|| StdError::generic_err("No share for node"))?;
+        if items.len() < budget {
+            items.push(work_item(storage, env, &validation, share)?);
+            last_item = Some(key);
+        } else {
+            let (expires_at, expires_at_height) = work_deadlines(storage, env, &validation)?;
+            stubs.push(NodeWorkStub {
+                validation_id: validation.validation_id.clone(),
+                expires_at,
+                expires_at_height,
+                share_digest: Sha256::digest(to_vec(share)?).to_vec(),
+            });
+        }
+    }
+
+    let next_cursor = match last_item {
+        Some((deadline, validation_id)) if work.len() > start + items.len() => Some(format!("{}:{}", deadline, validation_id)),
+        _ => None,
+    };
+    Ok(PendingForNodeResponse { items, stubs, next_cursor })
+}
+
+/// Encrypted shares are only readable by the node they were dealt to.
+fn authenticate_node(deps: Deps, node_id: u32, auth: &ViewerAuth) -> StdResult<()> {
+    authenticate(deps, auth)?;
+    let state = STATE.load(deps.storage)?;
+    if !get_node(&state, node_id).is_ok_and(|n| n.is_operated_by(&auth.address)) {
+        return Err(StdError::generic_err("Unauthorized"));
+    }
+    Ok(())
+}
+
+/// Leaf position of a user in a sparse Merkle tree of the given depth:
+/// the first `depth` bits of SHA-256(address).
+pub fn leaf_index(user_address: &str, depth: u8) -> u64 {
+    let digest = Sha256::digest(user_address.as_bytes());
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&digest[..8]);
+    let bits = u64::from_be_bytes(prefix);
+    match depth {
+        0 => 0,
+        d if d >= 64 => bits,
+        d => bits >> (64 - d as u32),
+    }
+}
+
+/// Looks up a node's share; relies on the canonical share order set at submit
+fn find_share(transition: &StateTransition, node_id: u32) -> Option<&EncryptedShares> {
+    transition.encrypted_shares
+        .binary_search_by_key(&node_id, |s| s.node_id)
+        .ok()
+        .map(|idx| &transition.encrypted_shares[idx])
+}
+
+/// `<height>-<user>`, prefixed with `<salt tag>:` on salted deployments.
+/// Everything after the first '-' is the user address.
+fn derive_validation_id(storage: &dyn Storage, env: &Env, user_address: &str) -> StdResult<String> {
+    let salt = STATE.load(storage)?.deployment_salt;
+    Ok(match salt.is_empty() {
+        true => format!("{}-{}", env.block.height, user_address),
+        false => format!("{}:{}-{}", salt_tag(&salt), env.block.height, user_address),
+    })
+}
+
+fn salt_tag(salt: &[u8]) -> String {
+    hex::encode(&Sha256::digest(salt)[..4])
+}
+
+/// All zeros unless salted, so unsalted instances keep their genesis root
+fn genesis_root(salt: &[u8]) -> Vec<u8> {
+    if salt.is_empty() {
+        return vec![0; 32];
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(b"genesis");
+    hasher.update(salt);
+    hasher.finalize().to_vec()
+}
+
+fn verify_user_signature(transition: &StateTransition) -> bool {
+    // In production: verify ECDSA/EdDSA signature
+    // For POC: simplified check
+    !transition.user_signature.is_empty()
+}
+
+fn aggregate_signatures(validations: &[NodeValidation]) -> Vec<u8> {
+    // Aggregate TSS partial signatures into threshold signature
+    // In production: proper BLS aggregation
+    // For POC: concatenate the partials of the valid votes
+    validations.iter()
+        .filter(|v| v.valid)
+        .flat_map(|v| v.partial_signature.clone())
+        .collect()
+}
+
+/// Verifies a light client update: every signer's 64-byte partial must verify
+/// against its committee key over the vote digest, and there must be at least
+/// `threshold` of them.
+pub fn verify_light_client_update(api: &dyn Api, update: &LightClientUpdateResponse) -> StdResult<bool> {
+    if update.signers.is_empty() || update.signers.len() < update.threshold as usize {
+        return Ok(false);
+    }
+    if update.threshold_signature.len() != update.signers.len() * 64 {
+        return Ok(false);
+    }
+
+    let attestation = root_attestation_digest(&update.chain_id, &update.contract_address, update.block_height, &update.root);
+    if update.root_attestation != attestation {
+        return Ok(false);
+    }
+
+    for (i, (node_id, partial)) in update.signers.iter().zip(update.threshold_signature.chunks(64)).enumerate() {
+        let validation_id = update.signed_validation_ids.get(i).unwrap_or(&update.validation_id);
+        let digest = vote_digest(&update.chain_id, &update.contract_address, validation_id, update.protocol_version, &update.root, &update.asset_digest);
+        let key = match update.committee.iter().find(|k| k.node_id == *node_id) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let verified = api.secp256k1_verify(&digest, partial, &key.public_key)
+            .map_err(|e| StdError::generic_err(e.to_string()))?;
+        if !verified {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Parent node of two Merkle children
+pub fn hash_pair(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().to_vec()
+}
+
+/// Root of a binary Merkle tree over `leaves` (used as-is, not re-hashed).
+/// An unpaired node at the end of a level is promoted unchanged.
+pub fn compute_merkle_root(leaves: &[Vec<u8>]) -> Vec<u8> {
+    let mut level: Vec<Vec<u8>> = leaves.to_vec();
+    while level.len() > 1 {
+        level = level.chunks(2)
+            .map(|pair| match pair {
+                [left, right] => hash_pair(left, right),
+                [single] => single.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level.pop().unwrap_or_default()
+}
+
+/// Whether folding `proof` up from `leaf` with [`hash_pair`] reaches `root`.
+/// `is_left` marks a sibling on the left. Proofs deeper than the largest
+/// supported tree are rejected without hashing.
+pub fn verify_merkle_proof(leaf: &[u8], proof: &[MerkleProofElement], root: &[u8]) -> bool {
+    if proof.len() > MAX_TREE_DEPTH as usize {
+        return false;
+    }
+    let computed = proof.iter().fold(leaf.to_vec(), |node, sibling| {
+        if sibling.is_left {
+            hash_pair(&sibling.hash, &node)
+        } else {
+            hash_pair(&node, &sibling.hash)
+        }
+    });
+    computed == root
+}
+
+fn serialize_merkle_proof(proof: &[MerkleProofElement]) -> Vec<u8> {
+    // Serialize proof for storage
+    proof.iter()
+        .flat_map(|e| e.hash.clone())
+        .collect()
+}
+
+// ============================================================================
+// MPC VALIDATION LOGIC (Reference for off-chain nodes)
+// ============================================================================
+
+/// This runs OFF-CHAIN on each MPC node when they receive validation request
+#[allow(dead_code)]
+pub fn hash_shares(balance: i64, nonce: i64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(balance.to_le_bytes());
+    hasher.update(nonce.to_le_bytes());
+    hasher.finalize().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::*;
+    use crate::testing::*;
+    use crate::state::ShareEncoding;
+    use crate::msg::InitialNode;
+    use crate::protocol::VOTE_TAG;
+
+    #[test]
+    fn test_complete_mpc_flow() {
+        // 1-2. Instantiate and register three nodes with real keys
+        let (mut deps, env) = setup_contract(2, 3);
+
+        // 3. User submits a signed state transition
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[1; 32], &[2; 32], 0, 3);
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+
+        // 4. MPC nodes validate with signed votes
+        let res = drive_to_threshold(&mut deps, &env, &validation_id, &transition, 2).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "finalized" && a.value == "true"));
+
+        // 5. Threshold reached on the second vote auto-finalized, so an
+        // explicit finalize has nothing left to do
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FinalizeTransition {
+                validation_id: validation_id.clone(),
+            }
+        ).unwrap_err();
+
+        // 6. Query state commitment (with alice's viewing key)
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SetViewingKey { key: "alice-key".to_string() }
+        ).unwrap();
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetStateCommitment {
+                user_address: "alice".to_string(),
+                viewing_key: "alice-key".to_string(),
+            }
+        ).unwrap();
+
+        let commitment: StateCommitmentResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(commitment.commitment.ipfs_cid, transition.new_state_ipfs);
+
+        // 7. The signed votes form a light client update that verifies
+        let res = query(deps.as_ref(), env, QueryMsg::GetLightClientUpdate {}).unwrap();
+        let update: LightClientUpdateResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert!(verify_light_client_update(&deps.api, &update).unwrap());
+    }
+
+    #[test]
+    fn test_light_client_update_verifies() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+
+        instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), InstantiateMsg { threshold: 2, ..Default::default() }).unwrap();
+
+        for i in 1..=3u8 {
+            let public_key = node_signing_key(i).verifying_key().to_encoded_point(true).as_bytes().to_vec();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(&format!("node{}", i), &[]),
+                ExecuteMsg::RegisterMPCNode { public_key },
+            ).unwrap();
+        }
+
+        let transition = StateTransition {
+            user_address: "alice".to_string(),
+            old_state_root: vec![0; 32],
+            new_state_root: vec![7; 32],
+            merkle_proof: vec![],
+            new_state_ipfs: "QmLight".to_string(),
+            ipfs_gateway_hint: None,
+            content_hash: vec![0; 32],
+            user_signature: vec![1],
+            encrypted_shares: vec![],
+            vss_commitments: vec![],
+            vss_proof_polynomial: vec![],
+            protocol_version: CURRENT_PROTOCOL_VERSION,
+            coordinator_metadata: None,
+            depends_on: vec![],
+            asset_updates: vec![],
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition },
+        ).unwrap();
+
+        let validation_id = format!("{}-{}", env.block.height, "alice");
+        let digest = vote_digest(&env.block.chain_id, env.contract.address.as_str(), &validation_id, CURRENT_PROTOCOL_VERSION, &[7; 32], &[]);
+
+        // Node 1 rejects (its partial is not part of the aggregate), nodes 2 and 3 sign
+        for (i, valid) in [(1u8, false), (2, true), (3, true)] {
+            let partial_signature = if valid { sign_digest(&node_signing_key(i), &digest) } else { vec![] };
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(&format!("node{}", i), &[]),
+                ExecuteMsg::ValidateTransition {
+                    validation_id: validation_id.clone(),
+                    valid,
+                    partial_signature,
+                    attested_root: None,
+                },
+            ).unwrap();
+        }
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLightClientUpdate {}).unwrap();
+        let update: LightClientUpdateResponse = cosmwasm_std::from_binary(&res).unwrap();
+
+        assert_eq!(update.root, vec![7; 32]);
+        assert_eq!(update.block_height, 1);
+        assert_eq!(update.signers, vec![2, 3]);
+        assert_eq!(update.committee.len(), 2);
+        assert!(verify_light_client_update(&deps.api, &update).unwrap());
+
+        // A bundle claiming a different root must not verify
+        let mut forged = update.clone();
+        forged.root = vec![8; 32];
+        assert!(!verify_light_client_update(&deps.api, &forged).unwrap());
+    }
+
+    #[test]
+    fn test_unsupported_protocol_version_rejected() {
+        let (mut deps, env) = setup_contract(2, 3);
+
+        let mut transition = test_transition("alice", 2, 3);
+        transition.protocol_version = 1;
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: transition.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unsupported protocol version 1 (supported: 2)"));
+
+        // Only the owner may change the supported set
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate {
+                supported_protocol_versions: Some(vec![1, 2]),
+                ..Default::default()
+            }),
+        ).unwrap_err();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate {
+                supported_protocol_versions: Some(vec![1, 2]),
+                ..Default::default()
+            }),
+        ).unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition },
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_protocol_version_propagates_to_vote() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate {
+                supported_protocol_versions: Some(vec![1, 2]),
+                ..Default::default()
+            }),
+        ).unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 7, 3) },
+        ).unwrap();
+        let mut legacy = test_transition("bob", 8, 3);
+        legacy.protocol_version = 1;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: legacy },
+        ).unwrap();
+
+        let validation_id = format!("{}-{}", env.block.height, "alice");
+
+        // Nodes see the version before doing any work
+        let auth = node_auth(&mut deps, &env, 2);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetPendingForNode { node_id: 2, auth, cursor: None }).unwrap();
+        let pending: PendingForNodeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        let item = pending.items.iter().find(|i| i.validation_id == validation_id).unwrap();
+        assert_eq!(item.protocol_version, 2);
+        assert_eq!(item.share.node_id, 2);
+
+        // Votes are signed over the version, so a light client must use it too
+        let digest = vote_digest(&env.block.chain_id, env.contract.address.as_str(), &validation_id, 2, &[7; 32], &[]);
+        for i in 1..=2u8 {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(&format!("node{}", i), &[]),
+                ExecuteMsg::ValidateTransition {
+                    validation_id: validation_id.clone(),
+                    valid: true,
+                    partial_signature: sign_digest(&node_signing_key(i), &digest),
+                    attested_root: None,
+                },
+            ).unwrap();
+        }
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLightClientUpdate {}).unwrap();
+        let update: LightClientUpdateResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(update.protocol_version, 2);
+        assert!(verify_light_client_update(&deps.api, &update).unwrap());
+
+        let res = query(deps.as_ref(), env, QueryMsg::GetStats {}).unwrap();
+        let stats: StatsResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(stats.stats.submissions_by_version, vec![
+            VersionCount { version: 2, count: 1 },
+            VersionCount { version: 1, count: 1 },
+        ]);
+    }
+
+    fn conditional_transfer(
+        deps: &mut MockDeps,
+        env: &Env,
+        preimage: &[u8],
+    ) -> String {
+        let transfer = Transfer {
+            sender: "alice".to_string(),
+            recipient: "bob".to_string(),
+            sender_transition: test_transition("alice", 2, 3),
+            recipient_transition: test_transition("bob", 3, 3),
+            amount_commitment: vec![9; 32],
+        };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitConditionalTransfer {
+                transfer,
+                hashlock: Sha256::digest(preimage).to_vec(),
+                deadline: env.block.time.seconds() + 100,
+            },
+        ).unwrap();
+
+        // Both legs reach threshold but stay locked
+        for leg in ["alice", "bob"] {
+            let validation_id = format!("{}-{}", env.block.height, leg);
+            for node in 1..=2 {
+                vote(deps, env, node, &validation_id, true).unwrap();
+            }
+            let validation = PENDING_VALIDATIONS.get(&deps.storage, &validation_id).unwrap();
+            assert!(validation.threshold_reached);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::FinalizeTransition { validation_id },
+            ).unwrap_err();
+        }
+
+        format!("{}-alice-bob", env.block.height)
+    }
+
+    #[test]
+    fn test_conditional_transfer_reveal_then_finalize() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::RevealPreimage { transfer_id: transfer_id.clone(), preimage: b"secret".to_vec() },
+        ).unwrap();
+
+        for user in ["alice", "bob"] {
+            assert!(STATE_COMMITMENTS.contains(&deps.storage, &user.to_string()));
+        }
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetTransferStatus { transfer_id }).unwrap();
+        let status: TransferStatusResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(status.transfer.status, TransferStatus::Completed);
+        assert_eq!(status.transfer.preimage, Some(b"secret".to_vec()));
+    }
+
+    #[test]
+    fn test_conditional_transfer_wrong_preimage() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::RevealPreimage { transfer_id: transfer_id.clone(), preimage: b"guess".to_vec() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Preimage does not match hashlock"));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::RevealPreimage { transfer_id, preimage: vec![0; MAX_PREIMAGE_SIZE + 1] },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Preimage exceeds 256 bytes"));
+
+        assert!(!STATE_COMMITMENTS.contains(&deps.storage, &"alice".to_string()));
+    }
+
+    #[test]
+    fn test_conditional_transfer_deadline_unwind() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+
+        // Too early to unwind
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ResolveTransfer { transfer_id: transfer_id.clone() },
+        ).unwrap_err();
+
+        let mut later = env.clone();
+        later.block.time = later.block.time.plus_seconds(100);
+
+        // The preimage is useless after the deadline
+        execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::RevealPreimage { transfer_id: transfer_id.clone(), preimage: b"secret".to_vec() },
+        ).unwrap_err();
+
+        // Only the parties may unwind
+        execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("mallory", &[]),
+            ExecuteMsg::ResolveTransfer { transfer_id: transfer_id.clone() },
+        ).unwrap_err();
+        execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ResolveTransfer { transfer_id: transfer_id.clone() },
+        ).unwrap();
+
+        let res = query(deps.as_ref(), later.clone(), QueryMsg::ListPendingValidations {}).unwrap();
+        let pending: PendingValidationsResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert!(pending.validation_ids.is_empty());
+
+        let res = query(deps.as_ref(), later, QueryMsg::GetTransferStatus { transfer_id }).unwrap();
+        let status: TransferStatusResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(status.transfer.status, TransferStatus::Unwound);
+    }
+
+    #[test]
+    fn test_leaf_index_distinct_addresses() {
+        let alice = leaf_index("alice", 8);
+        let bob = leaf_index("bob", 8);
+        assert!(alice < 256 && bob < 256);
+        assert_ne!(alice, bob);
+
+        // Deterministic, and a deeper tree only appends bits
+        assert_eq!(leaf_index("alice", 8), alice);
+        assert_eq!(leaf_index("alice", 16) >> 8, alice);
+    }
+
+    #[test]
+    fn test_leaf_index_collision_rejected() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            InstantiateMsg { threshold: 1, tree_depth: Some(1), dev_mode: true, ..Default::default() },
+        ).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(1)) }).unwrap();
+
+        // With a single bit of depth, find another address sharing alice's leaf
+        let rival = (0..)
+            .map(|i| format!("user{}", i))
+            .find(|a| leaf_index(a, 1) == leaf_index("alice", 1))
+            .unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 1) },
+        ).unwrap();
+        vote(&mut deps, &env, 1, &format!("{}-alice", env.block.height), true).unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&rival, &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition(&rival, 3, 1) },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!("Leaf index {} collides with another user", leaf_index("alice", 1))));
+
+        let res = query(deps.as_ref(), env, QueryMsg::GetLeafIndex { user_address: rival }).unwrap();
+        let leaf: LeafIndexResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(leaf.owner, Some("alice".to_string()));
+    }
+
+    #[test]
+    fn test_shares_stored_in_canonical_order() {
+        let (mut deps, env) = setup_contract(2, 3);
+
+        let mut duplicated = test_transition("alice", 2, 3);
+        duplicated.encrypted_shares[1].node_id = 1;
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: duplicated },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Duplicate share for node 1"));
+
+        let mut misaligned = test_transition("alice", 2, 3);
+        misaligned.vss_commitments = vec![vec![1]];
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: misaligned },
+        ).unwrap_err();
+
+        // Shuffled shares with VSS commitments that travel with them
+        let mut shuffled = test_transition("alice", 2, 3);
+        shuffled.vss_commitments = shuffled.encrypted_shares.iter().map(|s| vec![s.node_id as u8; 32]).collect();
+        shuffled.encrypted_shares.swap(0, 2);
+        shuffled.vss_commitments.swap(0, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: shuffled },
+        ).unwrap();
+
+        let validation_id = format!("{}-alice", env.block.height);
+        let transition = PENDING_VALIDATIONS.get(&deps.storage, &validation_id).unwrap().transition;
+        assert!(crate::protocol::is_canonical(&transition));
+        for (i, share) in transition.encrypted_shares.iter().enumerate() {
+            assert_eq!(share.node_id, i as u32 + 1);
+            assert_eq!(transition.vss_commitments[i], vec![share.node_id as u8; 32]);
+        }
+
+        // Positional lookups see the canonical layout
+        let auth = node_auth(&mut deps, &env, 3);
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetSharesForNode { validation_id, node_id: 3, auth },
+        ).unwrap();
+        let shares: SharesForNodeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(shares.share, EncryptedShares { node_id: 3, encrypted_data: vec![3], encoding: ShareEncoding::Raw });
+
+        let auth = node_auth(&mut deps, &env, 1);
+        let res = query(deps.as_ref(), env, QueryMsg::GetPendingForNode { node_id: 1, auth, cursor: None }).unwrap();
+        let pending: PendingForNodeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(pending.items[0].share.encrypted_data, vec![1]);
+    }
+
+    fn attest(
+        deps: &mut MockDeps,
+        env: &Env,
+        node: u8,
+        signer: u8,
+        binary_hash: &[u8],
+    ) -> StdResult<Response> {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&format!("node{}", node), &[]),
+            signed_attestation(env.contract.address.as_str(), signer, "abc123", binary_hash),
+        )
+    }
+
+    #[test]
+    fn test_attest_build_signature_verified() {
+        let (mut deps, env) = setup_contract(2, 3);
+
+        // Signed by another node's key
+        let err = attest(&mut deps, &env, 1, 2, &[0xaa; 32]).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid attestation signature"));
+
+        attest(&mut deps, &env, 1, 1, &[0xaa; 32]).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ListNodes { include_attestations: true }).unwrap();
+        let nodes: NodesResponse = cosmwasm_std::from_binary(&res).unwrap();
+        let attestation = nodes.nodes[0].attestation.clone().unwrap();
+        assert_eq!(attestation.binary_hash, vec![0xaa; 32]);
+        assert_eq!(attestation.attested_at, env.block.time.seconds());
+        assert!(nodes.nodes[1].attestation.is_none());
+
+        let res = query(deps.as_ref(), env, QueryMsg::ListNodes { include_attestations: false }).unwrap();
+        let nodes: NodesResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert!(nodes.nodes[0].attestation.is_none());
+    }
+
+    #[test]
+    fn test_build_diversity_aggregation() {
+        let (mut deps, env) = setup_contract(2, 4);
+
+        attest(&mut deps, &env, 1, 1, &[0xaa; 32]).unwrap();
+        attest(&mut deps, &env, 2, 2, &[0xaa; 32]).unwrap();
+
+        // Node 3 attested long ago, node 4 never did
+        let mut earlier = env.clone();
+        earlier.block.time = earlier.block.time.minus_seconds(default_attestation_window() + 1);
+        attest(&mut deps, &earlier, 3, 3, &[0xbb; 32]).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetBuildDiversity {}).unwrap();
+        let diversity: BuildDiversityResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(diversity.builds, vec![BuildCount { binary_hash: vec![0xaa; 32], count: 2 }]);
+        assert_eq!(diversity.unknown, 2);
+
+        attest(&mut deps, &env, 3, 3, &[0xbb; 32]).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::GetBuildDiversity {}).unwrap();
+        let diversity: BuildDiversityResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(diversity.builds.len(), 2);
+        assert_eq!(diversity.unknown, 1);
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer() {
+        let (mut deps, env) = setup_contract(2, 0);
+
+        // Only the owner can start a transfer
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("mallory", &[]),
+            ExecuteMsg::TransferOwnership { new_owner: "mallory".to_string() },
+        ).unwrap_err();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::TransferOwnership { new_owner: "newowner".to_string() },
+        ).unwrap();
+
+        // Proposing does not hand over control yet
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: ConfigResponse = cosmwasm_std::from_binary(&res).unwrap();
+        let config = config.config.unwrap();
+        assert_eq!(config.owner, "creator");
+        assert_eq!(config.pending_owner, Some("newowner".to_string()));
+
+        // An unrelated caller cannot accept
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("mallory", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("newowner", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        ).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: ConfigResponse = cosmwasm_std::from_binary(&res).unwrap();
+        let config = config.config.unwrap();
+        assert_eq!(config.owner, "newowner");
+        assert_eq!(config.pending_owner, None);
+
+        // The previous owner lost its rights
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { attestation_window: Some(1), ..Default::default() }),
+        ).unwrap_err();
+    }
+
+    #[test]
+    fn test_compute_root_four_leaves() {
+        let deps = mock_dependencies();
+        let leaves: Vec<Vec<u8>> = (1..=4u8).map(|i| vec![i; 32]).collect();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::ComputeRoot { leaves: leaves.clone() }).unwrap();
+        let computed: ComputeRootResponse = cosmwasm_std::from_binary(&res).unwrap();
+
+        let h = |a: &[u8], b: &[u8]| Sha256::new().chain_update(a).chain_update(b).finalize().to_vec();
+        let expected = h(&h(&leaves[0], &leaves[1]), &h(&leaves[2], &leaves[3]));
+        assert_eq!(computed.root, expected);
+        assert_eq!(
+            hex::encode(&computed.root),
+            "2c0c4083be2badf7c9f9046d8730d21e034c1ce50f519c166d7605848b17b0d5",
+        );
+
+        let too_many = vec![vec![0u8; 32]; MAX_ROOT_LEAVES + 1];
+        query(deps.as_ref(), mock_env(), QueryMsg::ComputeRoot { leaves: too_many }).unwrap_err();
+        query(deps.as_ref(), mock_env(), QueryMsg::ComputeRoot { leaves: vec![] }).unwrap_err();
+    }
+
+    #[test]
+    fn test_validate_after_auto_finalize_in_same_batch() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Messages of one tx share the same deps: the second vote finalizes,
+        // the third arrives for an id that no longer is pending
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        let err = vote(&mut deps, &env, 3, &validation_id, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already finalized"));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FinalizeTransition { validation_id: validation_id.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already finalized"));
+
+        // Unknown ids still report not found
+        let err = vote(&mut deps, &env, 3, "0-nobody", true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation not found"));
+
+        let res = query(deps.as_ref(), env, QueryMsg::GetValidationOutcome { validation_id }).unwrap();
+        let outcome: ValidationOutcomeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(outcome.outcome.status, OutcomeStatus::Finalized);
+    }
+
+    #[test]
+    fn test_presence_checks_match_record_checks() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transition = test_transition("alice", 2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: transition.clone() },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Duplicate votes fail exactly as before and the record keeps one vote
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        let err = vote(&mut deps, &env, 1, &validation_id, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Already validated"));
+        let validation = PENDING_VALIDATIONS.get(&deps.storage, &validation_id).unwrap();
+        assert_eq!(validation.validations.len(), 1);
+        assert!(VOTE_PRESENCE.contains(&deps.storage, &vote_presence_key(&validation_id, 1)));
+
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+
+        // Storage audit: settlement leaves no presence keys behind
+        for node_id in 1..=3 {
+            assert!(!VOTE_PRESENCE.contains(&deps.storage, &vote_presence_key(&validation_id, node_id)));
+        }
+    }
+
+    #[test]
+    fn test_unwind_clears_vote_presence() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+
+        let mut later = env.clone();
+        later.block.time = later.block.time.plus_seconds(100);
+        execute(
+            deps.as_mut(),
+            later,
+            mock_info("bob", &[]),
+            ExecuteMsg::ResolveTransfer { transfer_id },
+        ).unwrap();
+
+        for leg in ["alice", "bob"] {
+            let validation_id = format!("{}-{}", env.block.height, leg);
+            for node_id in 1..=3 {
+                assert!(!VOTE_PRESENCE.contains(&deps.storage, &vote_presence_key(&validation_id, node_id)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_validation_details_require_observer_key() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("auditor", &[]),
+            ExecuteMsg::CreateViewingKey { entropy: "auditor entropy".to_string() },
+        ).unwrap();
+        let key: ViewingKeyResponse = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+        let auth = ViewerAuth { address: "auditor".to_string(), viewing_key: key.key };
+
+        // A valid key alone is not enough without the observer role
+        let err = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetValidation { validation_id: validation_id.clone(), auth: auth.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetObserver { address: "auditor".to_string(), authorized: true },
+        ).unwrap();
+
+        // Wrong key is rejected
+        let wrong = ViewerAuth { address: "auditor".to_string(), viewing_key: "guess".to_string() };
+        query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetValidation { validation_id: validation_id.clone(), auth: wrong },
+        ).unwrap_err();
+
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetValidation { validation_id, auth },
+        ).unwrap();
+        let validation: ValidationResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(validation.validation.transition.encrypted_shares.len(), 3);
+
+        // Global state stays public
+        query(deps.as_ref(), env, QueryMsg::GetState {}).unwrap();
+    }
+
+    #[test]
+    fn test_state_commitment_requires_viewing_key() {
+        let (mut deps, env) = setup_contract(1, 1);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 1) },
+        ).unwrap();
+        vote(&mut deps, &env, 1, &format!("{}-alice", env.block.height), true).unwrap();
+
+        let commitment_query = |key: &str| QueryMsg::GetStateCommitment {
+            user_address: "alice".to_string(),
+            viewing_key: key.to_string(),
+        };
+
+        // No key set yet: nobody can read it
+        let err = query(deps.as_ref(), env.clone(), commitment_query("")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SetViewingKey { key: "alice-key".to_string() },
+        ).unwrap();
+
+        let err = query(deps.as_ref(), env.clone(), commitment_query("wrong")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+        // Someone else's key does not unlock alice's commitment
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::SetViewingKey { key: "bob-key".to_string() },
+        ).unwrap();
+        query(deps.as_ref(), env.clone(), commitment_query("bob-key")).unwrap_err();
+
+        let res = query(deps.as_ref(), env, commitment_query("alice-key")).unwrap();
+        let commitment: StateCommitmentResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(commitment.commitment.ipfs_cid, "Qmalice2");
+    }
+
+    #[test]
+    fn test_threshold_rails_and_dev_mode() {
+        let mut deps = mock_dependencies();
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg { threshold: 0, dev_mode: true, ..Default::default() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Threshold must be at least 1"));
+
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg { threshold: 1, ..Default::default() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Threshold below 2 requires dev_mode"));
+    }
+
+    #[test]
+    fn test_single_node_dev_lifecycle() {
+        let (mut deps, env) = setup_contract(1, 1);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: ConfigResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert!(config.config.unwrap().dev_mode);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 1) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // An invalid vote from the only node must not finalize
+        let mut other = env.clone();
+        other.block.height += 1;
+        execute(
+            deps.as_mut(),
+            other.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("bob", 3, 1) },
+        ).unwrap();
+        let res = vote(&mut deps, &other, 1, &format!("{}-bob", other.block.height), false).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "threshold_reached" && a.value == "false"));
+
+        // The first valid vote finalizes
+        let res = vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "finalized" && a.value == "true"));
+        let state: StateResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetState {}).unwrap()).unwrap();
+        assert_eq!(state.state.current_state_root, vec![2; 32]);
+
+        // The flag cannot be toggled through configuration updates
+        let attempt: StdResult<ExecuteMsg> = cosmwasm_std::from_slice(br#"{"update_config":{"dev_mode":false}}"#);
+        assert!(attempt.is_err());
+    }
+
+    #[test]
+    fn test_shares_require_node_viewing_key() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        let auth = node_auth(&mut deps, &env, 2);
+        let res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetSharesForNode { validation_id: validation_id.clone(), node_id: 2, auth: auth.clone() },
+        ).unwrap();
+        let shares: SharesForNodeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(shares.share.encrypted_data, vec![2]);
+
+        // Node 3 holds a valid key but cannot read node 2's share
+        let other = node_auth(&mut deps, &env, 3);
+        let err = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetSharesForNode { validation_id: validation_id.clone(), node_id: 2, auth: other.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+        query(deps.as_ref(), env.clone(), QueryMsg::GetPendingForNode { node_id: 2, auth: other, cursor: None }).unwrap_err();
+
+        // The right address with a wrong key is rejected too
+        let wrong = ViewerAuth { address: auth.address, viewing_key: "guess".to_string() };
+        query(
+            deps.as_ref(),
+            env,
+            QueryMsg::GetSharesForNode { validation_id, node_id: 2, auth: wrong },
+        ).unwrap_err();
+    }
+
+    #[test]
+    fn test_conflicting_update_to_same_leaf_is_superseded() {
+        let (mut deps, env) = setup_contract(2, 2);
+
+        // Two transitions for alice built on the same old root
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 2) },
+        ).unwrap();
+        let first = format!("{}-alice", env.block.height);
+        let mut later = env.clone();
+        later.block.height += 1;
+        execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 3, 2) },
+        ).unwrap();
+        let second = format!("{}-alice", later.block.height);
+
+        // Both are at threshold-1 when the first finalizes
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        vote(&mut deps, &later, 1, &second, true).unwrap();
+        vote(&mut deps, &env, 2, &first, true).unwrap();
+        let res = vote(&mut deps, &later, 2, &second, true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "outcome" && a.value == "superseded"));
+
+        // Alice's leaf keeps the first update
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(commitment.state_root, vec![2; 32]);
+        let state: StateResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetState {}).unwrap()).unwrap();
+        assert_eq!(state.state.block_height, 1);
+
+        let res = query(deps.as_ref(), env, QueryMsg::GetValidationOutcome { validation_id: second.clone() }).unwrap();
+        let outcome: ValidationOutcomeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(outcome.outcome.status, OutcomeStatus::Superseded);
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &second).is_none());
+    }
+
+    #[test]
+    fn test_unanimous_operations_need_every_node() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate {
+                unanimous_operations: Some(vec![OperationKind::StateTransition]),
+                ..Default::default()
+            }),
+        ).unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Threshold alone is not enough
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        let res = vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "threshold_reached" && a.value == "false"));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FinalizeTransition { validation_id: validation_id.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Threshold not reached"));
+
+        let res = vote(&mut deps, &env, 3, &validation_id, true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "finalized" && a.value == "true"));
+    }
+
+    fn attribute_keys(res: &Response) -> Vec<&str> {
+        res.attributes.iter().map(|a| a.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_event_verbosity_levels() {
+        let levels = [
+            (
+                EventVerbosity::Minimal,
+                vec!["action", "validation_id"],
+                vec!["action", "validation_id", "outcome"],
+            ),
+            (
+                EventVerbosity::Standard,
+                vec!["action", "validation_id", "user", "old_root", "new_root", "protocol_version"],
+                vec!["action", "validation_id", "user", "valid_votes", "outcome", "new_root", "block_height", "ipfs_cid", "history_pruned", "bundle_hash"],
+            ),
+            (
+                EventVerbosity::Debug,
+                vec!["action", "validation_id", "user", "old_root", "new_root", "protocol_version", "content_hash", "created_at"],
+                vec![
+                    "action", "validation_id", "user", "valid_votes", "outcome", "new_root", "block_height",
+                    "ipfs_cid", "history_pruned", "bundle_hash", "threshold_signature", "committee_hash", "settled_at",
+                ],
+            ),
+        ];
+
+        for (verbosity, submit_keys, finalize_keys) in levels {
+            let (mut deps, env) = setup_contract(2, 2);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("creator", &[]),
+                ExecuteMsg::UpdateConfig(ConfigUpdate { event_verbosity: Some(verbosity), ..Default::default() }),
+            ).unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("alice", &[]),
+                ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 2) },
+            ).unwrap();
+            assert_eq!(attribute_keys(&res), submit_keys);
+
+            // Reach threshold without auto-finalizing so FinalizeTransition does the work
+            let validation_id = format!("{}-alice", env.block.height);
+            let mut validation = PENDING_VALIDATIONS.get(&deps.storage, &validation_id).unwrap();
+            validation.threshold_reached = true;
+            validation.validations = (1..=2).map(|node_id| NodeValidation {
+                node_id,
+                valid: true,
+                partial_signature: pending_partial(&deps, &env, node_id as u8, &validation_id),
+                attested_root: None,
+                voted_at: env.block.height,
+                carried_from: None,
+            }).collect();
+            PENDING_VALIDATIONS.insert(&mut deps.storage, &validation_id, &validation).unwrap();
+
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("anyone", &[]),
+                ExecuteMsg::FinalizeTransition { validation_id },
+            ).unwrap();
+            assert_eq!(attribute_keys(&res), finalize_keys);
+        }
+    }
+
+    #[test]
+    fn test_public_keys_stored_compressed() {
+        let (mut deps, env) = setup_contract(1, 0);
+        let verifying_key = *node_signing_key(1).verifying_key();
+        let compressed = verifying_key.to_encoded_point(true).as_bytes().to_vec();
+        let uncompressed = verifying_key.to_encoded_point(false).as_bytes().to_vec();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed.clone() },
+        ).unwrap();
+
+        // Uncompressed keys are compressed on the way in
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node2", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: uncompressed },
+        ).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.mpc_nodes[0].public_key, compressed);
+        assert_eq!(state.mpc_nodes[1].public_key, compressed);
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("node3", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: vec![4; 64] },
+        ).unwrap_err();
+        assert_eq!(
+            err,
+            StdError::generic_err("Unsupported public key format (64 bytes); expected a compressed secp256k1 or BLS key")
+        );
+    }
+
+    fn recovery_step(
+        deps: &mut MockDeps,
+        env: &Env,
+        sender: &str,
+        msg: ExecuteMsg,
+    ) -> StdResult<Response> {
+        execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), msg)
+    }
+
+    #[test]
+    fn test_admin_recovery_after_delay() {
+        // 80% of 5 nodes = 4 approvals
+        let (mut deps, env) = setup_contract(3, 5);
+        recovery_step(&mut deps, &env, "node1", ExecuteMsg::ProposeAdminRecovery { new_admin: "rescuer".to_string() }).unwrap();
+        for node in ["node2", "node3"] {
+            recovery_step(&mut deps, &env, node, ExecuteMsg::ApproveAdminRecovery {}).unwrap();
+        }
+        recovery_step(&mut deps, &env, "node3", ExecuteMsg::ApproveAdminRecovery {}).unwrap_err();
+        let res = recovery_step(&mut deps, &env, "node4", ExecuteMsg::ApproveAdminRecovery {}).unwrap();
+        let executable_at = env.block.time.seconds() + default_recovery_delay();
+        assert!(res.attributes.iter().any(|a| a.key == "executable_at" && a.value == executable_at.to_string()));
+
+        // Not before the veto window closes
+        let err = recovery_step(&mut deps, &env, "anyone", ExecuteMsg::ExecuteAdminRecovery {}).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Recovery veto period has not elapsed"));
+
+        let mut later = env.clone();
+        later.block.time = later.block.time.plus_seconds(default_recovery_delay());
+        recovery_step(&mut deps, &later, "anyone", ExecuteMsg::ExecuteAdminRecovery {}).unwrap();
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().owner, "rescuer");
+        assert!(ADMIN_RECOVERY.may_load(&deps.storage).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_admin_recovery_veto() {
+        let (mut deps, env) = setup_contract(3, 5);
+        recovery_step(&mut deps, &env, "node1", ExecuteMsg::ProposeAdminRecovery { new_admin: "rescuer".to_string() }).unwrap();
+        for node in ["node2", "node3", "node4"] {
+            recovery_step(&mut deps, &env, node, ExecuteMsg::ApproveAdminRecovery {}).unwrap();
+        }
+
+        // Only the current admin can veto
+        recovery_step(&mut deps, &env, "node5", ExecuteMsg::VetoAdminRecovery {}).unwrap_err();
+        recovery_step(&mut deps, &env, "creator", ExecuteMsg::VetoAdminRecovery {}).unwrap();
+
+        let mut later = env.clone();
+        later.block.time = later.block.time.plus_seconds(default_recovery_delay());
+        let err = recovery_step(&mut deps, &later, "anyone", ExecuteMsg::ExecuteAdminRecovery {}).unwrap_err();
+        assert_eq!(err, StdError::generic_err("No admin recovery in progress"));
+        assert_eq!(CONFIG.load(&deps.storage).unwrap().owner, "creator");
+    }
+
+    #[test]
+    fn test_admin_recovery_insufficient_quorum() {
+        let (mut deps, env) = setup_contract(3, 5);
+        recovery_step(&mut deps, &env, "mallory", ExecuteMsg::ProposeAdminRecovery { new_admin: "mallory".to_string() }).unwrap_err();
+        recovery_step(&mut deps, &env, "node1", ExecuteMsg::ProposeAdminRecovery { new_admin: "rescuer".to_string() }).unwrap();
+        for node in ["node2", "node3"] {
+            recovery_step(&mut deps, &env, node, ExecuteMsg::ApproveAdminRecovery {}).unwrap();
+        }
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetAdminRecovery {}).unwrap();
+        let recovery: AdminRecoveryResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(recovery.required_approvals, 4);
+        assert!(recovery.recovery.unwrap().quorum_reached_at.is_none());
+
+        let mut later = env;
+        later.block.time = later.block.time.plus_seconds(default_recovery_delay() * 2);
+        let err = recovery_step(&mut deps, &later, "anyone", ExecuteMsg::ExecuteAdminRecovery {}).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Recovery quorum not reached"));
+    }
+
+    fn validation_counts(deps: &MockDeps, env: &Env) -> (u64, u64, u64, u64) {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetValidationCounts {}).unwrap();
+        let counts: ValidationCountsResponse = cosmwasm_std::from_binary(&res).unwrap();
+        (counts.pending, counts.finalized, counts.rejected, counts.expired)
+    }
+
+    #[test]
+    fn test_validation_counts_track_settlements() {
+        let (mut deps, env) = setup_contract(2, 3);
+
+        // Expired: both legs of a conditional transfer unwound after the deadline
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+        assert_eq!(validation_counts(&deps, &env), (2, 0, 0, 0));
+        let mut later = env.clone();
+        later.block.time = later.block.time.plus_seconds(100);
+        later.block.height += 1;
+        execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::ResolveTransfer { transfer_id },
+        ).unwrap();
+        assert_eq!(validation_counts(&deps, &later), (0, 0, 0, 2));
+
+        // Submit and finalize
+        execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("carol", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("carol", 2, 3) },
+        ).unwrap();
+        let first = format!("{}-carol", later.block.height);
+        let mut next = later.clone();
+        next.block.height += 1;
+        execute(
+            deps.as_mut(),
+            next.clone(),
+            mock_info("carol", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("carol", 3, 3) },
+        ).unwrap();
+        let second = format!("{}-carol", next.block.height);
+        assert_eq!(validation_counts(&deps, &next), (2, 0, 0, 2));
+
+        vote(&mut deps, &later, 1, &first, true).unwrap();
+        vote(&mut deps, &later, 2, &first, true).unwrap();
+        assert_eq!(validation_counts(&deps, &next), (1, 1, 0, 2));
+
+        // Rejected: the second transition was built on the same old root
+        vote(&mut deps, &next, 1, &second, true).unwrap();
+        vote(&mut deps, &next, 2, &second, true).unwrap();
+        assert_eq!(validation_counts(&deps, &next), (0, 1, 1, 2));
+    }
+
+    fn root_at(deps: &MockDeps, env: &Env, height: u64) -> RootAtHeightResponse {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetRootAtHeight { height }).unwrap();
+        cosmwasm_std::from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn test_root_history_prunes_in_bounded_steps() {
+        let (mut deps, mut env) = setup_contract(2, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { root_history_retention: Some(3), ..Default::default() }),
+        ).unwrap();
+
+        let mut pruned = vec![];
+        for i in 1..=10u8 {
+            if i == 9 {
+                // Shrinking retention leaves a backlog that drains K at a time
+                execute(
+                    deps.as_mut(),
+                    env.clone(),
+                    mock_info("creator", &[]),
+                    ExecuteMsg::UpdateConfig(ConfigUpdate { root_history_retention: Some(1), ..Default::default() }),
+                ).unwrap();
+            }
+            let user = format!("user{}", i);
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(&user, &[]),
+                ExecuteMsg::SubmitStateTransition { transition: test_transition(&user, i, 2) },
+            ).unwrap();
+            let validation_id = format!("{}-{}", env.block.height, user);
+            vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+            let res = vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+            let count = res.attributes.iter().find(|a| a.key == "history_pruned").unwrap();
+            pruned.push(count.value.parse::<u64>().unwrap());
+            env.block.height += 1;
+        }
+
+        // The empty genesis slot goes first, then one per finalization once the
+        // window is full, then at most two per finalization while catching up
+        assert_eq!(pruned, vec![1, 0, 0, 1, 1, 1, 1, 1, 2, 2]);
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.block_height, 10);
+        assert_eq!(state.oldest_retained_height, 10);
+
+        assert_eq!(root_at(&deps, &env, 10).root, Some(vec![10; 32]));
+        assert_eq!(root_at(&deps, &env, 9).status, RootStatus::Pruned);
+        assert_eq!(root_at(&deps, &env, 1).status, RootStatus::Pruned);
+        assert_eq!(root_at(&deps, &env, 11).status, RootStatus::NotFound);
+    }
+
+    #[test]
+    fn test_unique_cids_rejects_shared_pointer() {
+        let (mut deps, env) = setup_contract(2, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 2) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+
+        // Bob points at alice's current state
+        let mut shared = test_transition("bob", 3, 2);
+        shared.new_state_ipfs = test_transition("alice", 2, 2).new_state_ipfs;
+
+        // Allowed by default for shared-storage designs
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: shared.clone() },
+        ).unwrap();
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { unique_cids: Some(true), ..Default::default() }),
+        ).unwrap();
+        let mut later = env.clone();
+        later.block.height += 1;
+        let err = execute(
+            deps.as_mut(),
+            later.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: shared },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("CID already in use by another user"));
+
+        // Alice may keep reusing her own pointer
+        let mut own = test_transition("alice", 4, 2);
+        own.old_state_root = vec![2; 32];
+        own.new_state_ipfs = test_transition("alice", 2, 2).new_state_ipfs;
+        execute(
+            deps.as_mut(),
+            later,
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: own },
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_operator_votes_for_node() {
+        let (mut deps, env) = setup_contract(2, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            ExecuteMsg::SetOperator { operator: Some("hot1".to_string()) },
+        ).unwrap();
+
+        // Operators cannot delegate further or be shared between nodes
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("hot1", &[]),
+            ExecuteMsg::SetOperator { operator: Some("hot2".to_string()) },
+        ).unwrap_err();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node2", &[]),
+            ExecuteMsg::SetOperator { operator: Some("hot1".to_string()) },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Operator already acts for another node"));
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::ListNodes { include_attestations: false }).unwrap();
+        let nodes: NodesResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(nodes.nodes[0].node.address, "node1");
+        assert_eq!(nodes.nodes[0].node.operator.as_deref(), Some("hot1"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 2) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+        let partial_signature = pending_partial(&deps, &env, 1, &validation_id);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("hot1", &[]),
+            ExecuteMsg::ValidateTransition {
+                validation_id: validation_id.clone(),
+                valid: true,
+                partial_signature,
+                attested_root: None,
+            },
+        ).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "node_id" && a.value == "1"));
+
+        // A revoked operator loses its duties
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            ExecuteMsg::SetOperator { operator: None },
+        ).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("bob", 3, 2) },
+        ).unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("hot1", &[]),
+            ExecuteMsg::ValidateTransition {
+                validation_id: format!("{}-bob", env.block.height),
+                valid: true,
+                partial_signature: vec![1; 64],
+                attested_root: None,
+            },
+        ).unwrap_err();
+        assert_eq!(err, StdError::not_found("Active MPC node"));
+    }
+
+    #[test]
+    fn test_validation_cooldown_throttles_node() {
+        let (mut deps, env) = setup_contract(2, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { validation_cooldown: Some(5), ..Default::default() }),
+        ).unwrap();
+
+        for user in ["alice", "bob"] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(user, &[]),
+                ExecuteMsg::SubmitStateTransition { transition: test_transition(user, 2, 2) },
+            ).unwrap();
+        }
+        let alice = format!("{}-alice", env.block.height);
+        let bob = format!("{}-bob", env.block.height);
+
+        vote(&mut deps, &env, 1, &alice, true).unwrap();
+        let mut soon = env.clone();
+        soon.block.height += 4;
+        let err = vote(&mut deps, &soon, 1, &bob, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Node validating too frequently"));
+
+        // Other nodes are unaffected, and the cooldown expires
+        vote(&mut deps, &soon, 2, &bob, true).unwrap();
+        let mut later = env.clone();
+        later.block.height += 5;
+        vote(&mut deps, &later, 1, &bob, true).unwrap();
+    }
+
+    fn attest_root(
+        deps: &mut MockDeps,
+        env: &Env,
+        node: u8,
+        validation_id: &str,
+        root: Vec<u8>,
+    ) -> StdResult<Response> {
+        let partial_signature = pending_partial(deps, env, node, validation_id);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&format!("node{}", node), &[]),
+            ExecuteMsg::ValidateTransition {
+                validation_id: validation_id.to_string(),
+                valid: true,
+                partial_signature,
+                attested_root: Some(root),
+            },
+        )
+    }
+
+    #[test]
+    fn test_strict_mode_blocks_on_root_disagreement() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { strict_root_agreement: Some(true), ..Default::default() }),
+        ).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Valid votes must say which root they checked
+        let err = vote(&mut deps, &env, 1, &validation_id, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Strict mode requires a 32-byte attested root"));
+
+        attest_root(&mut deps, &env, 1, &validation_id, vec![2; 32]).unwrap();
+        let res = attest_root(&mut deps, &env, 2, &validation_id, vec![9; 32]).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "blocked" && a.value == "root_disagreement"));
+
+        // A further agreeing vote would reach threshold but cannot finalize
+        let res = attest_root(&mut deps, &env, 3, &validation_id, vec![2; 32]).unwrap();
+        assert!(!res.attributes.iter().any(|a| a.key == "finalized"));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FinalizeTransition { validation_id: validation_id.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation blocked: root disagreement"));
+
+        let validation = PENDING_VALIDATIONS.get(&deps.storage, &validation_id).unwrap();
+        assert_eq!(validation.blocked, Some(BlockReason::RootDisagreement {
+            expected: vec![2; 32],
+            divergent: vec![NodeRoot { node_id: 2, root: vec![9; 32] }],
+        }));
+        let state: StateResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env, QueryMsg::GetState {}).unwrap()).unwrap();
+        assert_eq!(state.state.current_state_root, vec![0; 32]);
+    }
+
+    #[test]
+    fn test_strict_mode_finalizes_on_agreement() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { strict_root_agreement: Some(true), ..Default::default() }),
+        ).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Invalid votes need no root
+        vote(&mut deps, &env, 3, &validation_id, false).unwrap();
+        attest_root(&mut deps, &env, 1, &validation_id, vec![2; 32]).unwrap();
+        let res = attest_root(&mut deps, &env, 2, &validation_id, vec![2; 32]).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "finalized" && a.value == "true"));
+    }
+
+    #[test]
+    fn test_missing_voters() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Invalid votes count as having voted
+        vote(&mut deps, &env, 2, &validation_id, false).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetMissingVoters { validation_id: validation_id.clone() }).unwrap();
+        let missing: MissingVotersResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(missing.node_ids, vec![1, 3]);
+
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 3, &validation_id, true).unwrap();
+        let err = query(deps.as_ref(), env, QueryMsg::GetMissingVoters { validation_id }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already finalized"));
+    }
+
+    #[test]
+    fn test_voter_keys() {
+        let (mut deps, env) = setup_contract(3, 4);
+        let voter_keys = |deps: &MockDeps| -> Vec<VoterKey> {
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::GetVoterKeys { validation_id: "12345-alice".to_string() }).unwrap();
+            cosmwasm_std::from_binary::<VoterKeysResponse>(&res).unwrap().voters
+        };
+        let key_of = |node_id: u8| VoterKey { node_id: node_id as u32, public_key: compressed_public_key(&node_signing_key(node_id)) };
+        submit(&mut deps, &env, &test_transition("alice", 2, 4)).unwrap();
+        vote(&mut deps, &env, 3, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 2, "12345-alice", false).unwrap();
+        vote(&mut deps, &env, 1, "12345-alice", true).unwrap();
+        assert_eq!(voter_keys(&deps), vec![key_of(3), key_of(1)]);
+
+        // Keys that switch in later are not the ones the votes were cast with
+        for node in 1..=4 {
+            stage_key(&mut deps, &env, node, node + 10, node + 10).unwrap();
+        }
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::AdvanceEpoch {}).unwrap();
+        assert_eq!(voter_keys(&deps), vec![key_of(3), key_of(1)]);
+
+        // The finalized record answers once the validation settles
+        vote(&mut deps, &env, 4, "12345-alice", true).unwrap();
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &"12345-alice".to_string()).is_none());
+        assert_eq!(voter_keys(&deps), vec![key_of(3), key_of(1), key_of(4)]);
+    }
+
+    fn finalized_update(votes: Vec<ExecuteMsg>) -> (MockDeps, LightClientUpdateResponse) {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        submit(&mut deps, &env, &transition).unwrap();
+        for (i, msg) in votes.into_iter().enumerate() {
+            execute(deps.as_mut(), env.clone(), mock_info(&format!("node{}", i + 1), &[]), msg).unwrap();
+        }
+        let res = query(deps.as_ref(), env, QueryMsg::GetLightClientUpdate {}).unwrap();
+        (deps, cosmwasm_std::from_binary(&res).unwrap())
+    }
+
+    /// What the contract says to node2's vote on alice's transition once node1
+    /// has voted honestly
+    fn second_vote_error(msg: ExecuteMsg) -> StdError {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), signed_vote(&env, 1, &validation_id, &transition, true)).unwrap();
+        execute(deps.as_mut(), env, mock_info("node2", &[]), msg).unwrap_err()
+    }
+
+    #[test]
+    fn test_vote_signed_for_wrong_version_fails_verification() {
+        let env = mock_env();
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let validation_id = format!("{}-alice", env.block.height);
+        let mut stale = transition.clone();
+        stale.protocol_version = 1;
+
+        let err = second_vote_error(signed_vote(&env, 2, &validation_id, &stale, true));
+        assert_eq!(err, StdError::generic_err("Partial signature does not verify under the node's key"));
+    }
+
+    #[test]
+    fn test_vote_signed_for_other_deployment_fails_verification() {
+        let env = mock_env();
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let validation_id = format!("{}-alice", env.block.height);
+        let mut testnet = env.clone();
+        testnet.block.chain_id = "pulsar-3".to_string();
+
+        let err = second_vote_error(signed_vote(&testnet, 2, &validation_id, &transition, true));
+        assert_eq!(err, StdError::generic_err("Partial signature does not verify under the node's key"));
+    }
+
+    #[test]
+    fn test_vote_signed_by_other_node_fails_verification() {
+        let env = mock_env();
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let validation_id = format!("{}-alice", env.block.height);
+
+        // Node 2 submits a partial produced with node 3's key
+        let forged = ExecuteMsg::ValidateTransition {
+            validation_id: validation_id.clone(),
+            valid: true,
+            partial_signature: signed_partial(&env, 3, &validation_id, &transition),
+            attested_root: None,
+        };
+        let err = second_vote_error(forged);
+        assert_eq!(err, StdError::generic_err("Partial signature does not verify under the node's key"));
+
+        // Truncating the aggregate is rejected outright
+        let (deps, mut truncated) = finalized_update(vec![
+            signed_vote(&env, 1, &validation_id, &transition, true),
+            signed_vote(&env, 2, &validation_id, &transition, true),
+        ]);
+        truncated.threshold_signature.pop();
+        assert!(!verify_light_client_update(&deps.api, &truncated).unwrap());
+    }
+
+    #[test]
+    fn test_attestation_for_other_contract_rejected() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            signed_attestation("secret1othercontract", 1, "abc123", &[0xaa; 32]),
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid attestation signature"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            signed_attestation(env.contract.address.as_str(), 1, "abc123", &[0xaa; 32]),
+        ).unwrap();
+    }
+
+    #[test]
+    fn test_deregistration_requires_key_ownership() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let digest = deregistration_digest(
+            env.contract.address.as_str(),
+            2,
+            &compressed_public_key(&node_signing_key(2)),
+        );
+
+        // The address alone, or another key, is not enough
+        for signature in [vec![], sign_digest(&node_signing_key(3), &digest)] {
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("node2", &[]),
+                ExecuteMsg::DeregisterMPCNode { signature },
+            ).unwrap_err();
+            assert_eq!(err, StdError::generic_err("Invalid deregistration signature"));
+        }
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node2", &[]),
+            ExecuteMsg::DeregisterMPCNode { signature: sign_digest(&node_signing_key(2), &digest) },
+        ).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert!(!state.mpc_nodes[1].active);
+    }
+
+    #[test]
+    fn test_commitment_updated_at_is_monotonic() {
+        let (mut deps, env) = setup_contract(2, 2);
+        let first = test_transition("alice", 2, 2);
+        let validation_id = submit(&mut deps, &env, &first).unwrap();
+        drive_to_threshold(&mut deps, &env, &validation_id, &first, 2).unwrap();
+        let stored = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert!(!stored.clamped);
+
+        // The next update lands with a block time behind the stored one
+        let mut skewed = env.clone();
+        skewed.block.height += 1;
+        skewed.block.time = env.block.time.minus_seconds(100);
+        let mut second = test_transition("alice", 3, 2);
+        second.old_state_root = vec![2; 32];
+        let validation_id = submit(&mut deps, &skewed, &second).unwrap();
+        let res = drive_to_threshold(&mut deps, &skewed, &validation_id, &second, 2).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "warning" && a.value == "updated_at_clamped"));
+
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(commitment.updated_at, stored.updated_at + 1);
+        assert!(commitment.clamped);
+
+        // Equal timestamps are fine and not flagged
+        let mut same = skewed.clone();
+        same.block.height += 1;
+        same.block.time = cosmwasm_std::Timestamp::from_seconds(commitment.updated_at);
+        let mut third = test_transition("alice", 4, 2);
+        third.old_state_root = vec![3; 32];
+        let validation_id = submit(&mut deps, &same, &third).unwrap();
+        let res = drive_to_threshold(&mut deps, &same, &validation_id, &third, 2).unwrap();
+        assert!(!res.attributes.iter().any(|a| a.key == "warning"));
+        let latest = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(latest.updated_at, commitment.updated_at);
+        assert!(!latest.clamped);
+    }
+
+    #[test]
+    fn test_challenge_within_window_reverts() {
+        let (mut deps, env) = setup_contract(2, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { challenge_window: Some(10), ..Default::default() }),
+        ).unwrap();
+
+        let first = test_transition("alice", 2, 2);
+        let validation_id = submit(&mut deps, &env, &first).unwrap();
+        drive_to_threshold(&mut deps, &env, &validation_id, &first, 2).unwrap();
+
+        let mut next = env.clone();
+        next.block.height += 1;
+        let mut second = test_transition("alice", 3, 2);
+        second.old_state_root = vec![2; 32];
+        let validation_id = submit(&mut deps, &next, &second).unwrap();
+        drive_to_threshold(&mut deps, &next, &validation_id, &second, 2).unwrap();
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(commitment.lock_at_height, next.block.height + 10);
+
+        // Neither users nor the owner can challenge; a threshold of nodes can
+        let challenge = || ExecuteMsg::ChallengeFinalized {
+            user_address: "alice".to_string(),
+            reason: "bad balance proof".to_string(),
+        };
+        for sender in ["alice", "creator"] {
+            let err = execute(deps.as_mut(), next.clone(), mock_info(sender, &[]), challenge()).unwrap_err();
+            assert_eq!(err, StdError::generic_err("Unauthorized"));
+        }
+        let res = execute(deps.as_mut(), next.clone(), mock_info("node1", &[]), challenge()).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "reverted" && a.value == "false"));
+        let err = execute(deps.as_mut(), next.clone(), mock_info("node1", &[]), challenge()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Already challenged by this node"));
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap().state_root, vec![3; 32]);
+        assert_eq!(validation_counts(&deps, &next), (0, 2, 0, 0));
+
+        let res = execute(deps.as_mut(), next.clone(), mock_info("node2", &[]), challenge()).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "reverted" && a.value == "true"));
+        assert_eq!(validation_counts(&deps, &next), (0, 1, 0, 0));
+        assert_eq!(STATS.load(&deps.storage).unwrap().validation_counts.challenged, 1);
+
+        let restored = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(restored.state_root, vec![2; 32]);
+        assert_eq!(restored.ipfs_cid, first.new_state_ipfs);
+
+        // The restored commitment is final
+        let err = execute(deps.as_mut(), next, mock_info("node1", &[]), challenge()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Commitment is locked"));
+    }
+
+    #[test]
+    fn test_challenge_after_window_rejected() {
+        let (mut deps, env) = setup_contract(2, 2);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { challenge_window: Some(10), ..Default::default() }),
+        ).unwrap();
+        let transition = test_transition("alice", 2, 2);
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        drive_to_threshold(&mut deps, &env, &validation_id, &transition, 2).unwrap();
+
+        let mut later = env.clone();
+        later.block.height += 10;
+        let err = execute(
+            deps.as_mut(),
+            later,
+            mock_info("node1", &[]),
+            ExecuteMsg::ChallengeFinalized { user_address: "alice".to_string(), reason: "late".to_string() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Commitment is locked"));
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap().state_root, vec![2; 32]);
+    }
+
+    #[test]
+    fn test_challenge_refused_once_depended_on() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { challenge_window: Some(10), ..Default::default() }),
+        ).unwrap();
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let bob = StateTransition { depends_on: vec!["12345-alice".to_string()], ..test_transition("bob", 2, 3) };
+        submit(&mut deps, &env, &bob).unwrap();
+        for id in ["12345-alice", "12345-bob"] {
+            vote(&mut deps, &env, 1, id, true).unwrap();
+            vote(&mut deps, &env, 2, id, true).unwrap();
+        }
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"bob".to_string()).is_some());
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node1", &[]),
+            ExecuteMsg::ChallengeFinalized { user_address: "alice".to_string(), reason: "bad".to_string() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Other validations depend on this finalization"));
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap().state_root, vec![1; 32]);
+    }
+
+    fn batch_transfer(deps: &mut MockDeps, env: &Env, recipients: &[&str]) -> String {
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("payer", &[]),
+            ExecuteMsg::SubmitBatchTransfer {
+                sender_transition: test_transition("payer", 1, 3),
+                recipients: recipients.iter().map(|r| RecipientLeg {
+                    recipient: r.to_string(),
+                    transition: test_transition(r, 2, 3),
+                    amount_commitment: vec![7; 32],
+                }).collect(),
+            },
+        ).unwrap();
+        res.attributes.iter().find(|a| a.key == "group_id").unwrap().value.clone()
+    }
+
+    fn group_status(deps: &MockDeps, env: &Env, group_id: &str) -> TransferGroup {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetTransferGroupStatus { group_id: group_id.to_string() }).unwrap();
+        let status: TransferGroupStatusResponse = cosmwasm_std::from_binary(&res).unwrap();
+        status.group
+    }
+
+    #[test]
+    fn test_batch_transfer_settles_together() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let group_id = batch_transfer(&mut deps, &env, &["r1", "r2", "r3"]);
+        let group = group_status(&deps, &env, &group_id);
+        assert_eq!(group.legs.len(), 3);
+
+        // Nodes see the batch alongside their share
+        let auth = node_auth(&mut deps, &env, 1);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetPendingForNode { node_id: 1, auth, cursor: None }).unwrap();
+        let pending: PendingForNodeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(pending.items.len(), 4);
+        assert!(pending.items.iter().all(|i| i.group.as_ref().map(|g| g.group_id.as_str()) == Some(group_id.as_str())));
+
+        // Legs at threshold wait for the rest
+        let ids = group.validation_ids();
+        for validation_id in &ids[..3] {
+            vote(&mut deps, &env, 1, validation_id, true).unwrap();
+            vote(&mut deps, &env, 2, validation_id, true).unwrap();
+        }
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"payer".to_string()).is_none());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FinalizeTransition { validation_id: ids[0].clone() },
+        ).unwrap_err();
+
+        vote(&mut deps, &env, 1, &ids[3], true).unwrap();
+        let res = vote(&mut deps, &env, 2, &ids[3], true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "outcome" && a.value == "finalized"));
+
+        assert_eq!(group_status(&deps, &env, &group_id).status, TransferStatus::Completed);
+        for user in ["payer", "r1", "r2", "r3"] {
+            assert!(STATE_COMMITMENTS.get(&deps.storage, &user.to_string()).is_some());
+        }
+        assert_eq!(STATE.load(&deps.storage).unwrap().block_height, 4);
+    }
+
+    #[test]
+    fn test_batch_transfer_leg_rejection_unwinds_group() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let group_id = batch_transfer(&mut deps, &env, &["r1", "r2"]);
+        let ids = group_status(&deps, &env, &group_id).validation_ids();
+
+        vote(&mut deps, &env, 1, &ids[0], true).unwrap();
+        vote(&mut deps, &env, 2, &ids[0], true).unwrap();
+
+        // Two of three nodes reject r2's leg, so it can never reach threshold
+        vote(&mut deps, &env, 1, &ids[2], false).unwrap();
+        let res = vote(&mut deps, &env, 2, &ids[2], false).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "outcome" && a.value == "rejected"));
+
+        assert_eq!(group_status(&deps, &env, &group_id).status, TransferStatus::Unwound);
+        for validation_id in &ids {
+            assert!(PENDING_VALIDATIONS.get(&deps.storage, validation_id).is_none());
+        }
+        let outcome = VALIDATION_OUTCOMES.get(&deps.storage, &ids[0]).unwrap();
+        assert_eq!(outcome.status, OutcomeStatus::Unwound);
+        assert_eq!(VALIDATION_OUTCOMES.get(&deps.storage, &ids[2]).unwrap().status, OutcomeStatus::Rejected);
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"payer".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_batch_transfer_attributes_are_keyed_per_leg() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("payer", &[]),
+            ExecuteMsg::SubmitBatchTransfer {
+                sender_transition: test_transition("payer", 1, 3),
+                recipients: ["r1", "r2"].iter().map(|r| RecipientLeg {
+                    recipient: r.to_string(),
+                    transition: test_transition(r, 2, 3),
+                    amount_commitment: vec![7; 32],
+                }).collect(),
+            },
+        ).unwrap();
+        let values = |key: &str| -> Vec<&str> {
+            res.attributes.iter().filter(|a| a.key == key).map(|a| a.value.as_str()).collect()
+        };
+        assert_eq!(values("action"), vec!["submit_batch_transfer"]);
+        assert_eq!(values("sender_validation_id"), vec![format!("{}-payer", env.block.height)]);
+        assert_eq!(values("recipient0_validation_id"), vec![format!("{}-r1", env.block.height)]);
+        assert_eq!(values("recipient1_validation_id"), vec![format!("{}-r2", env.block.height)]);
+        assert!(values("validation_id").is_empty());
+    }
+
+    #[test]
+    fn test_batch_transfer_bounds() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let too_many: Vec<String> = (0..=MAX_BATCH_RECIPIENTS).map(|i| format!("r{}", i)).collect();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("payer", &[]),
+            ExecuteMsg::SubmitBatchTransfer {
+                sender_transition: test_transition("payer", 1, 3),
+                recipients: too_many.iter().map(|r| RecipientLeg {
+                    recipient: r.clone(),
+                    transition: test_transition(r, 2, 3),
+                    amount_commitment: vec![7; 32],
+                }).collect(),
+            },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Batch must have between 1 and 50 recipients"));
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("payer", &[]),
+            ExecuteMsg::SubmitBatchTransfer {
+                sender_transition: test_transition("payer", 1, 3),
+                recipients: ["r1", "r1"].iter().map(|r| RecipientLeg {
+                    recipient: r.to_string(),
+                    transition: test_transition(r, 2, 3),
+                    amount_commitment: vec![7; 32],
+                }).collect(),
+            },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Duplicate batch participant r1"));
+    }
+
+    #[test]
+    fn test_gateway_hint_carried_to_commitment() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let mut transition = test_transition("alice", 2, 3);
+        transition.ipfs_gateway_hint = Some("x".repeat(MAX_GATEWAY_HINT_LEN + 1));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: transition.clone() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Gateway hint must be 1 to 128 bytes"));
+
+        transition.ipfs_gateway_hint = Some("https://gateway.example.org/ipfs/".to_string());
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(commitment.ipfs_gateway_hint.as_deref(), Some("https://gateway.example.org/ipfs/"));
+    }
+
+    #[test]
+    fn test_unmigrated_instance_requires_migration() {
+        let (mut deps, env) = setup_contract(2, 3);
+        // An instance from before the schema marker existed
+        STORAGE_SCHEMA_VERSION.remove(&mut deps.storage);
+        let expected = StdError::generic_err(format!("MigrationRequired {{ have: 0, need: {} }}", CURRENT_SCHEMA_VERSION));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap_err();
+        assert_eq!(err, expected);
+        let err = query(deps.as_ref(), env.clone(), QueryMsg::GetState {}).unwrap_err();
+        assert_eq!(err, expected);
+
+        // GetConfig reports the versions without reading the old layout
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: ConfigResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(config.config, None);
+        assert_eq!((config.storage_schema_version, config.required_schema_version), (0, CURRENT_SCHEMA_VERSION));
+
+        let res = migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "from_schema" && a.value == "0"));
+        assert_eq!(STORAGE_SCHEMA_VERSION.load(&deps.storage).unwrap(), CURRENT_SCHEMA_VERSION);
+
+        submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap();
+        let config: ConfigResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert!(config.config.is_some());
+
+        // Code never runs against a newer layout than it understands
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &(CURRENT_SCHEMA_VERSION + 1)).unwrap();
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap_err();
+        query(deps.as_ref(), env, QueryMsg::GetState {}).unwrap_err();
+    }
+
+    #[test]
+    fn test_export_snapshot_pages_cover_everything() {
+        let (mut deps, env) = setup_contract(1, 2);
+        let users = ["dave", "alice", "carol", "bob", "erin"];
+        for (i, user) in users.iter().enumerate() {
+            let validation_id = submit(&mut deps, &env, &test_transition(user, i as u8 + 2, 2)).unwrap();
+            vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        }
+
+        let auth = ViewerAuth { address: "creator".to_string(), viewing_key: "creator-key".to_string() };
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetViewingKey { key: auth.viewing_key.clone() },
+        ).unwrap();
+
+        let mut exported = vec![];
+        let mut nodes = vec![];
+        let mut start_after = None;
+        loop {
+            let msg = QueryMsg::ExportSnapshot { auth: auth.clone(), start_after, limit: Some(2) };
+            let page: SnapshotResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+            assert!(page.commitments.len() <= 2);
+            exported.extend(page.commitments.into_iter().map(|c| c.user_address));
+            nodes.extend(page.nodes.into_iter().map(|n| n.node.node_id));
+            match page.next_start_after {
+                Some(cursor) => start_after = Some(cursor),
+                None => break,
+            }
+            // Removing an exported user mid-export moves nobody else
+            if exported.len() == 2 {
+                STATE_COMMITMENTS.remove(&mut deps.storage, &"dave".to_string()).unwrap();
+                unorder_commitment(&mut deps.storage, "dave").unwrap();
+            }
+        }
+        assert_eq!(exported, vec!["dave", "alice", "carol", "bob", "erin"]);
+        assert_eq!(nodes, vec![1, 2]);
+
+        // Other users cannot dump everyone's commitments
+        let node = node_auth(&mut deps, &env, 1);
+        let msg = QueryMsg::ExportSnapshot { auth: node, start_after: None, limit: None };
+        assert_eq!(query(deps.as_ref(), env, msg).unwrap_err(), StdError::generic_err("Unauthorized"));
+    }
+
+    fn da_step(
+        deps: &mut MockDeps,
+        env: &Env,
+        sender: &str,
+        msg: ExecuteMsg,
+    ) -> StdResult<Response> {
+        execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), msg)
+    }
+
+    fn bank_sends(res: &Response) -> Vec<(String, Vec<Coin>)> {
+        res.messages.iter().filter_map(|sub| match &sub.msg {
+            cosmwasm_std::CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => Some((to_address.clone(), amount.clone())),
+            _ => None,
+        }).collect()
+    }
+
+    /// setup_contract with a 7uscrt data availability challenge bond
+    fn da_fixture(threshold: u32, nodes: u8) -> (MockDeps, Env) {
+        let (mut deps, env) = setup_contract(threshold, nodes);
+        da_step(&mut deps, &env, "creator", ExecuteMsg::UpdateConfig(ConfigUpdate {
+            da_challenge_bond: Some(Coin::new(7, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        (deps, env)
+    }
+
+    fn challenge_da(deps: &mut MockDeps, env: &Env, sender: &str, validation_id: &str) -> StdResult<Response> {
+        execute(
+            deps.as_mut(), env.clone(), mock_info(sender, &cosmwasm_std::coins(7, "uscrt")),
+            ExecuteMsg::ChallengeDataAvailability { validation_id: validation_id.to_string() },
+        )
+    }
+
+    #[test]
+    fn test_data_challenge_answered_then_finalizes() {
+        let (mut deps, env) = da_fixture(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        let prove = |sample: &[u8]| ExecuteMsg::ProveAvailability {
+            validation_id: validation_id.clone(),
+            sample: Binary::from(sample),
+        };
+
+        challenge_da(&mut deps, &env, "alice", &validation_id).unwrap_err();
+        let unbonded = ExecuteMsg::ChallengeDataAvailability { validation_id: validation_id.clone() };
+        let err = da_step(&mut deps, &env, "node3", unbonded).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Challenging requires a bond of exactly 7uscrt"));
+        challenge_da(&mut deps, &env, "node3", &validation_id).unwrap();
+        let err = challenge_da(&mut deps, &env, "node1", &validation_id).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already challenged"));
+
+        // Threshold is reached but the open challenge holds finalization back
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).is_none());
+        let finalize = || ExecuteMsg::FinalizeTransition { validation_id: validation_id.clone() };
+        let err = da_step(&mut deps, &env, "anyone", finalize()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Data availability challenge open"));
+
+        let err = da_step(&mut deps, &env, "anyone", prove(b"QmOther")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Sample does not match content hash"));
+
+        // Proven available, so the bond is forfeited to the user
+        let res = da_step(&mut deps, &env, "anyone", prove(b"Qmalice2")).unwrap();
+        assert_eq!(bank_sends(&res), vec![("alice".to_string(), cosmwasm_std::coins(7, "uscrt"))]);
+        assert!(CHALLENGE_BONDS.get(&deps.storage, &validation_id).is_none());
+
+        da_step(&mut deps, &env, "anyone", finalize()).unwrap();
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_designated_finalizer_wins_within_window() {
+        let (mut deps, mut env) = da_fixture(2, 3);
+        da_step(&mut deps, &env, "creator", ExecuteMsg::UpdateConfig(ConfigUpdate {
+            finalizer_window: Some(10),
+            ..Default::default()
+        })).unwrap();
+
+        // Challenged validations reach threshold without finalizing
+        let mut ids = vec![];
+        for (user, root) in [("alice", 2), ("bob", 4)] {
+            let validation_id = submit(&mut deps, &env, &test_transition(user, root, 3)).unwrap();
+            challenge_da(&mut deps, &env, "node1", &validation_id).unwrap();
+            vote(&mut deps, &env, 3, &validation_id, true).unwrap();
+            let res = vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+            assert!(res.attributes.iter().any(|a| a.key == "finalizer" && a.value == "2"));
+            let sample = format!("Qm{}{}", user, root);
+            da_step(&mut deps, &env, "anyone", ExecuteMsg::ProveAvailability {
+                validation_id: validation_id.clone(),
+                sample: Binary::from(sample.as_bytes()),
+            }).unwrap();
+            ids.push(validation_id);
+        }
+        let finalize = |validation_id: &str| ExecuteMsg::FinalizeTransition { validation_id: validation_id.to_string() };
+        let committed = |deps: &MockDeps, user: &str| STATE_COMMITMENTS.get(&deps.storage, &user.to_string()).is_some();
+
+        // Only node2, the lowest valid voter, acts within the window
+        for sender in ["anyone", "node1", "node3"] {
+            let res = da_step(&mut deps, &env, sender, finalize(&ids[0])).unwrap();
+            assert!(res.attributes.iter().any(|a| a.key == "result" && a.value == "already_finalized"));
+            assert!(!committed(&deps, "alice"));
+            assert!(PENDING_VALIDATIONS.get(&deps.storage, &ids[0]).is_some());
+        }
+        let res = da_step(&mut deps, &env, "node2", finalize(&ids[0])).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "outcome" && a.value == "finalized"));
+        assert!(committed(&deps, "alice"));
+
+        // Once the window passes, anyone may finalize
+        env.block.height += 10;
+        da_step(&mut deps, &env, "anyone", finalize(&ids[1])).unwrap();
+        assert!(committed(&deps, "bob"));
+    }
+
+    #[test]
+    fn test_unanswered_data_challenge_refunds_deposit_and_bond() {
+        use cosmwasm_std::coins;
+        let (mut deps, mut env) = da_fixture(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            expiry_bounty: Some(Coin::new(5, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        let validation_id = derive_validation_id(&deps.storage, &env, "alice").unwrap();
+        execute(
+            deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "uscrt")),
+            ExecuteMsg::SubmitStateTransition { transition: test_transition("alice", 2, 3) },
+        ).unwrap();
+        challenge_da(&mut deps, &env, "node3", &validation_id).unwrap();
+
+        // The user's deposit never goes to the challenger
+        env.block.height += DA_RESPONSE_WINDOW;
+        let res = da_step(&mut deps, &env, "anyone", ExecuteMsg::ExpireDataChallenge { validation_id: validation_id.clone() }).unwrap();
+        assert_eq!(bank_sends(&res), vec![
+            ("alice".to_string(), coins(5, "uscrt")),
+            ("node3".to_string(), coins(7, "uscrt")),
+        ]);
+        assert!(CHALLENGE_BONDS.get(&deps.storage, &validation_id).is_none());
+        let receipt = WITHDRAWAL_RECEIPTS.get(&deps.storage, &format!("{}/bond", validation_id)).unwrap();
+        assert_eq!(receipt.recipient, "node3");
+    }
+
+    #[test]
+    fn test_data_challenges_need_a_configured_bond() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        let err = challenge_da(&mut deps, &env, "node3", &validation_id).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Data availability challenges need a configured bond"));
+    }
+
+    #[test]
+    fn test_data_challenge_expiry_rejects_validation() {
+        let (mut deps, mut env) = da_fixture(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        challenge_da(&mut deps, &env, "node3", &validation_id).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+
+        let expire = || ExecuteMsg::ExpireDataChallenge { validation_id: validation_id.clone() };
+        let err = da_step(&mut deps, &env, "anyone", expire()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Data availability challenge still open"));
+
+        env.block.height += DA_RESPONSE_WINDOW;
+        let late = ExecuteMsg::ProveAvailability { validation_id: validation_id.clone(), sample: Binary::from(b"Qmalice2") };
+        let err = da_step(&mut deps, &env, "alice", late).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Data availability challenge expired"));
+
+        let res = da_step(&mut deps, &env, "anyone", expire()).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "outcome" && a.value == "data_unavailable"));
+        assert_eq!(VALIDATION_OUTCOMES.get(&deps.storage, &validation_id).unwrap().status, OutcomeStatus::DataUnavailable);
+        assert_eq!(validation_counts(&deps, &env), (0, 0, 1, 0));
+
+        let err = vote(&mut deps, &env, 2, &validation_id, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already data_unavailable"));
+    }
+
+    #[test]
+    fn test_data_challenge_after_threshold() {
+        let (mut deps, env) = da_fixture(2, 3);
+
+        // Too late once the validation finalized
+        let validation_id = submit(&mut deps, &env, &test_transition("carol", 2, 3)).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        let err = challenge_da(&mut deps, &env, "node3", &validation_id).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already finalized"));
+
+        // A hashlocked leg at threshold is still pending, so it can be challenged
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+        let sender_leg = TRANSFERS.get(&deps.storage, &transfer_id).unwrap().sender_validation_id;
+        challenge_da(&mut deps, &env, "node3", &sender_leg).unwrap();
+
+        let reveal = || ExecuteMsg::RevealPreimage { transfer_id: transfer_id.clone(), preimage: b"secret".to_vec() };
+        let err = da_step(&mut deps, &env, "bob", reveal()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Data availability challenge open"));
+
+        let proof = ExecuteMsg::ProveAvailability { validation_id: sender_leg, sample: Binary::from(b"Qmalice2") };
+        da_step(&mut deps, &env, "alice", proof).unwrap();
+        da_step(&mut deps, &env, "bob", reveal()).unwrap();
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_request_revalidation_targets_node() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        let request = |node_id| ExecuteMsg::RequestRevalidation { validation_id: validation_id.clone(), node_id };
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), request(3)).unwrap();
+        let attr = |key: &str| res.attributes.iter().find(|a| a.key == key).map(|a| a.value.clone());
+        assert_eq!(attr("action").as_deref(), Some("request_revalidation"));
+        assert_eq!(attr("validation_id"), Some(validation_id.clone()));
+        assert_eq!(attr("node_id").as_deref(), Some("3"));
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), request(2)).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), request(3)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), request(1)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Already validated"));
+        let err = execute(deps.as_mut(), env, mock_info("alice", &[]), request(9)).unwrap_err();
+        assert_eq!(err, StdError::not_found("MPC node 9"));
+    }
+
+    fn finalized_at(deps: &MockDeps, env: &Env, height: u64) -> FinalizedResponse {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFinalized { height }).unwrap();
+        cosmwasm_std::from_binary(&res).unwrap()
+    }
+
+    fn set_partials(deps: &mut MockDeps, env: &Env, update: ConfigUpdate) {
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(update)).unwrap();
+    }
+
+    #[test]
+    fn test_finalized_record_retains_partials_by_mode() {
+        for (mode, expected) in [
+            (RetainPartials::None, vec![]),
+            (RetainPartials::All, vec![1, 2, 3]),
+            (RetainPartials::FirstK(2), vec![1, 2]),
+        ] {
+            let (mut deps, env) = setup_contract(3, 4);
+            set_partials(&mut deps, &env, ConfigUpdate { retain_partials: Some(mode), ..Default::default() });
+
+            let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 4)).unwrap();
+            for node in [3, 1, 2] {
+                vote(&mut deps, &env, node, &validation_id, true).unwrap();
+            }
+
+            let finalized = finalized_at(&deps, &env, 1);
+            assert_eq!(finalized.status, RootStatus::Found);
+            let record = finalized.record.unwrap();
+            assert_eq!(record.validation_id, validation_id);
+            assert!(!record.threshold_signature.is_empty());
+            let partials: Vec<u32> = record.partials.iter().map(|p| p.node_id).collect();
+            assert_eq!(partials, expected);
+            assert!(record.partials.iter().all(|p| p.partial_signature == signed_partial(&env, p.node_id as u8, &validation_id, &test_transition("alice", 2, 4))));
+        }
+    }
+
+    #[test]
+    fn test_partials_pruned_before_records() {
+        let (mut deps, mut env) = setup_contract(2, 2);
+        set_partials(&mut deps, &env, ConfigUpdate {
+            retain_partials: Some(RetainPartials::All),
+            root_history_retention: Some(4),
+            partial_retention: Some(2),
+            ..Default::default()
+        });
+
+        for i in 1..=6u8 {
+            let validation_id = submit(&mut deps, &env, &test_transition(&format!("user{}", i), i, 2)).unwrap();
+            vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+            vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+            env.block.height += 1;
+        }
+
+        // Oldest records are gone entirely, the next ones keep only the aggregate
+        for height in 1..=2 {
+            assert_eq!(finalized_at(&deps, &env, height).status, RootStatus::Pruned);
+        }
+        for height in 3..=4 {
+            let record = finalized_at(&deps, &env, height).record.unwrap();
+            assert!(record.partials.is_empty());
+            assert!(!record.threshold_signature.is_empty());
+        }
+        for height in 5..=6 {
+            assert_eq!(finalized_at(&deps, &env, height).record.unwrap().partials.len(), 2);
+        }
+        assert_eq!(finalized_at(&deps, &env, 7).status, RootStatus::NotFound);
+    }
+
+    #[test]
+    fn test_submissions_wait_for_committee() {
+        let (mut deps, env) = setup_contract(2, 1);
+        assert!(STATE.load(&deps.storage).unwrap().pending_committee);
+
+        let err = submit(&mut deps, &env, &test_transition("alice", 2, 1)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Committee not ready: 1 of 2 nodes registered"));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node2", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(2)) },
+        ).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "committee_ready" && a.value == "true"));
+        assert!(!STATE.load(&deps.storage).unwrap().pending_committee);
+
+        submit(&mut deps, &env, &test_transition("alice", 2, 2)).unwrap();
+    }
+
+    fn env_on_chain(chain_id: &str) -> Env {
+        let mut env = mock_env();
+        env.block.chain_id = chain_id.to_string();
+        env
+    }
+
+    #[test]
+    fn test_root_attestation_bound_to_deployment() {
+        let mut updates = vec![];
+        let mut committees = vec![];
+        for chain_id in ["secret-4", "pulsar-3"] {
+            let (mut deps, env) = setup_contract_with_env(env_on_chain(chain_id), 2, 3);
+            let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+            vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+            vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::GetDeploymentIdentity {}).unwrap();
+            let identity: DeploymentIdentityResponse = cosmwasm_std::from_binary(&res).unwrap();
+            assert_eq!(identity.identity.chain_id, chain_id);
+            assert_eq!(identity.identity.contract_address, env.contract.address.as_str());
+            assert_eq!(identity.identity.genesis_time, env.block.time.seconds());
+
+            let res = query(deps.as_ref(), env, QueryMsg::GetLightClientUpdate {}).unwrap();
+            updates.push(cosmwasm_std::from_binary::<LightClientUpdateResponse>(&res).unwrap());
+            committees.push(committee_hash(&deps.storage, &STATE.load(&deps.storage).unwrap()).unwrap());
+        }
+
+        // Same root and committee, different deployment: never interchangeable
+        assert_eq!(updates[0].root, updates[1].root);
+        assert_ne!(updates[0].root_attestation, updates[1].root_attestation);
+        assert_ne!(committees[0], committees[1]);
+        assert_eq!(updates[0].chain_id, "secret-4");
+
+        // Relabelling a genuine update for another chain fails verification
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let env = mock_env();
+        let validation_id = format!("{}-alice", env.block.height);
+        let (deps, mut update) = finalized_update(vec![
+            signed_vote(&env, 1, &validation_id, &transition, true),
+            signed_vote(&env, 2, &validation_id, &transition, true),
+        ]);
+        assert!(verify_light_client_update(&deps.api, &update).unwrap());
+        update.chain_id = "pulsar-3".to_string();
+        assert!(!verify_light_client_update(&deps.api, &update).unwrap());
+    }
+
+    #[test]
+    fn test_migrate_refuses_state_from_other_chain() {
+        let (mut deps, env) = setup_contract_with_env(env_on_chain("secret-4"), 2, 3);
+
+        let err = migrate(deps.as_mut(), env_on_chain("pulsar-3"), MigrateMsg::Execute { owner: None }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("State belongs to chain secret-4, not pulsar-3"));
+
+        let mut moved = env.clone();
+        moved.contract.address = cosmwasm_std::Addr::unchecked("secret1elsewhere");
+        migrate(deps.as_mut(), moved, MigrateMsg::Execute { owner: None }).unwrap_err();
+
+        migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: None }).unwrap();
+    }
+
+    #[test]
+    fn test_verify_inclusion_query() {
+        let (deps, env) = setup_contract(2, 2);
+        let leaves: Vec<Vec<u8>> = (1..=4u8).map(|i| vec![i; 32]).collect();
+        let root = compute_merkle_root(&leaves);
+        let sibling = |hash: Vec<u8>, is_left| MerkleProofElement { hash, is_left };
+        let verify = |leaf: &[u8], proof: Vec<MerkleProofElement>, expected_root: Option<Vec<u8>>| {
+            let msg = QueryMsg::VerifyInclusion { leaf: leaf.to_vec(), proof, expected_root };
+            cosmwasm_std::from_binary::<VerifyInclusionResponse>(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+
+        // Leaf 2 pairs with leaf 3 on its right, then with hash(0, 1) on its left
+        let proof = vec![sibling(leaves[3].clone(), false), sibling(hash_pair(&leaves[0], &leaves[1]), true)];
+        assert!(verify(&leaves[2], proof.clone(), Some(root.clone())).valid);
+
+        assert!(!verify(&leaves[1], proof.clone(), Some(root.clone())).valid);
+        let flipped = vec![sibling(leaves[3].clone(), true), proof[1].clone()];
+        assert!(!verify(&leaves[2], flipped, Some(root)).valid);
+
+        // Without an expected root the current (genesis) root is used
+        let current = verify(&leaves[2], proof, None);
+        assert!(!current.valid);
+        assert_eq!(current.root, vec![0; 32]);
+    }
+
+    #[test]
+    fn test_validate_batch_reports_each_vote() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let ids: Vec<String> = ["alice", "bob", "carol"].iter()
+            .map(|user| submit(&mut deps, &env, &test_transition(user, 2, 3)).unwrap())
+            .collect();
+
+        // A single vote reports its result the same way
+        let res = vote(&mut deps, &env, 2, &ids[1], true).unwrap();
+        let single: VoteResultEntry = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!((single.status, single.finalized), (VoteStatus::Accepted, false));
+
+        let batch_vote = |validation_id: &str, partial_signature: Vec<u8>| BatchVote {
+            validation_id: validation_id.to_string(),
+            valid: true,
+            partial_signature,
+            attested_root: None,
+        };
+        let votes = vec![
+            batch_vote(&ids[0], pending_partial(&deps, &env, 1, &ids[0])),
+            batch_vote(&ids[0], pending_partial(&deps, &env, 1, &ids[0])),
+            batch_vote(&ids[1], pending_partial(&deps, &env, 1, &ids[1])),
+            batch_vote(&ids[1], pending_partial(&deps, &env, 3, &ids[1])),
+            batch_vote("missing", vec![1; 64]),
+            batch_vote(&ids[2], vec![1; 10]),
+            batch_vote(&ids[2], vec![1; 64]),
+        ];
+        let res = execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::ValidateBatch { votes, max_items: None, cursor: None }).unwrap();
+
+        let batch: BatchVoteResult = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+        let statuses: Vec<VoteStatus> = batch.results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, vec![
+            VoteStatus::Accepted,
+            VoteStatus::Duplicate,
+            VoteStatus::Finalized,
+            VoteStatus::AlreadySettled,
+            VoteStatus::ValidationMissing,
+            VoteStatus::SignatureRejected,
+            VoteStatus::SignatureRejected,
+        ]);
+        let ids_out: Vec<&str> = batch.results.iter().map(|r| r.validation_id.as_str()).collect();
+        assert_eq!(ids_out, vec![ids[0].as_str(), &ids[0], &ids[1], &ids[1], "missing", &ids[2], &ids[2]]);
+        assert!(batch.results[2].finalized);
+        assert_eq!(batch.results[3].error.as_deref(), Some("Validation already finalized"));
+        assert_eq!(batch.results[4].error.as_deref(), Some("Validation not found"));
+        assert_eq!(batch.results[5].error.as_deref(), Some("Partial signature must be 64 bytes"));
+        assert_eq!(batch.results[6].error.as_deref(), Some("Partial signature does not verify under the node's key"));
+        assert!(batch.results[0].error.is_none());
+
+        // Attributes only summarize
+        for status in ["accepted", "duplicate", "finalized", "already_settled", "validation_missing"] {
+            assert!(res.attributes.iter().any(|a| a.key == status && a.value == "1"));
+        }
+        assert!(res.attributes.iter().any(|a| a.key == "signature_rejected" && a.value == "2"));
+        assert!(!res.attributes.iter().any(|a| a.key == "validation_id"));
+
+        // Skipped votes left nothing behind
+        let carol = PENDING_VALIDATIONS.get(&deps.storage, &ids[2]).unwrap();
+        assert!(carol.validations.is_empty());
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &"bob".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_each_batch_vote_counts_against_the_cooldown() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            validation_cooldown: Some(5),
+            ..Default::default()
+        })).unwrap();
+        let ids: Vec<String> = ["alice", "bob", "carol", "dave"].iter()
+            .map(|user| submit(&mut deps, &env, &test_transition(user, 2, 3)).unwrap())
+            .collect();
+        let batch = |deps: &mut MockDeps, env: &Env, ids: &[String]| {
+            let votes = ids.iter().map(|id| BatchVote {
+                validation_id: id.clone(),
+                valid: true,
+                partial_signature: pending_partial(deps, env, 1, id),
+                attested_root: None,
+            }).collect();
+            execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::ValidateBatch { votes, max_items: None, cursor: None })
+        };
+
+        let statuses = |res: Response| -> Vec<VoteStatus> {
+            let result: BatchVoteResult = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+            result.results.iter().map(|r| r.status).collect()
+        };
+
+        // Batching does not get around the cooldown: only the first vote lands
+        let res = batch(&mut deps, &env, &ids[..3]).unwrap();
+        assert_eq!(statuses(res), vec![VoteStatus::Accepted, VoteStatus::Throttled, VoteStatus::Throttled]);
+
+        let mut soon = env.clone();
+        soon.block.height += 4;
+        let res = batch(&mut deps, &soon, &ids[1..]).unwrap();
+        assert_eq!(statuses(res), vec![VoteStatus::Throttled; 3]);
+        assert_eq!(vote(&mut deps, &soon, 1, &ids[1], true).unwrap_err(), StdError::generic_err("Node validating too frequently"));
+        soon.block.height += 1;
+        let res = batch(&mut deps, &soon, &ids[1..]).unwrap();
+        assert_eq!(statuses(res), vec![VoteStatus::Accepted, VoteStatus::Throttled, VoteStatus::Throttled]);
+    }
+
+    #[test]
+    fn test_oversized_transition_rejected() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let mut transition = test_transition("alice", 2, 3);
+        transition.vss_proof_polynomial = vec!["ab".repeat(1024); 64];
+        let err = submit(&mut deps, &env, &transition).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Transition too large"));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { max_transition_size: Some(1024 * 1024), ..Default::default() }),
+        ).unwrap();
+        submit(&mut deps, &env, &transition).unwrap();
+    }
+
+    fn opt_into_carryover(deps: &mut MockDeps, env: &Env, node: u8) {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&format!("node{}", node), &[]),
+            ExecuteMsg::UpdateNodeMetadata { auto_carryover_votes: Some(true) },
+        ).unwrap();
+    }
+
+    fn cancel(deps: &mut MockDeps, env: &Env, sender: &str, validation_id: &str) -> StdResult<Response> {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(sender, &[]),
+            ExecuteMsg::CancelTransition { validation_id: validation_id.to_string() },
+        )
+    }
+
+    #[test]
+    fn test_cancelled_votes_carry_over_to_resubmission() {
+        let (mut deps, mut env) = setup_contract(3, 3);
+        opt_into_carryover(&mut deps, &env, 1);
+        opt_into_carryover(&mut deps, &env, 2);
+
+        let transition = test_transition("alice", 2, 3);
+        let first = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        vote(&mut deps, &env, 2, &first, true).unwrap();
+
+        assert_eq!(cancel(&mut deps, &env, "mallory", &first).unwrap_err(), StdError::generic_err("Unauthorized"));
+        cancel(&mut deps, &env, "alice", &first).unwrap();
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &first).is_none());
+
+        env.block.height += 10;
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("alice", &[]),
+            ExecuteMsg::SubmitStateTransition { transition: transition.clone() },
+        ).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "carried_votes" && a.value == "2"));
+        let second = format!("{}-alice", env.block.height);
+        assert_ne!(first, second);
+
+        let pending = PENDING_VALIDATIONS.get(&deps.storage, &second).unwrap();
+        assert!(pending.validations.iter().all(|v| v.carried_from.as_deref() == Some(first.as_str())));
+        assert!(pending.validations.iter().all(|v| v.voted_at == env.block.height));
+
+        // Carried votes count as cast
+        assert_eq!(vote(&mut deps, &env, 1, &second, true).unwrap_err(), StdError::generic_err("Already validated"));
+        vote(&mut deps, &env, 3, &second, true).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::GetLightClientUpdate {}).unwrap();
+        let update: LightClientUpdateResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(update.signed_validation_ids, vec![first.clone(), first, second]);
+    }
+
+    #[test]
+    fn test_carryover_requires_opt_in_and_identical_content() {
+        let (mut deps, mut env) = setup_contract(3, 3);
+        opt_into_carryover(&mut deps, &env, 1);
+
+        let transition = test_transition("alice", 2, 3);
+        let first = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        vote(&mut deps, &env, 2, &first, true).unwrap();
+        cancel(&mut deps, &env, "alice", &first).unwrap();
+
+        // Different content finds nothing to inherit
+        env.block.height += 1;
+        let changed = submit(&mut deps, &env, &test_transition("alice", 3, 3)).unwrap();
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &changed).unwrap().validations.is_empty());
+
+        env.block.height += 1;
+        let second = submit(&mut deps, &env, &transition).unwrap();
+        let carried: Vec<u32> = PENDING_VALIDATIONS.get(&deps.storage, &second).unwrap()
+            .validations.iter().map(|v| v.node_id).collect();
+        assert_eq!(carried, vec![1]);
+
+        // The cancelled entry is consumed
+        cancel(&mut deps, &env, "alice", &second).unwrap();
+        env.block.height += CARRYOVER_WINDOW + 1;
+        let late = submit(&mut deps, &env, &transition).unwrap();
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &late).unwrap().validations.is_empty());
+    }
+
+    #[test]
+    fn test_finalizers_for_historical_commitment() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let finalizers = |deps: &MockDeps, nonce: u64| {
+            query(deps.as_ref(), env.clone(), QueryMsg::GetFinalizersForUser { user_address: "alice".to_string(), nonce })
+                .map(|res| cosmwasm_std::from_binary::<FinalizersResponse>(&res).unwrap().node_ids)
+        };
+
+        let first = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        vote(&mut deps, &env, 2, &first, true).unwrap();
+
+        let mut later = env.clone();
+        later.block.height += 1;
+        let mut next = test_transition("alice", 3, 3);
+        next.old_state_root = vec![2; 32];
+        let second = submit(&mut deps, &later, &next).unwrap();
+        vote(&mut deps, &later, 3, &second, true).unwrap();
+        vote(&mut deps, &later, 1, &second, true).unwrap();
+
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap().nonce, 1);
+        assert_eq!(finalizers(&deps, 0).unwrap(), vec![1, 2]);
+        assert_eq!(finalizers(&deps, 1).unwrap(), vec![3, 1]);
+        assert_eq!(
+            finalizers(&deps, 2).unwrap_err(),
+            StdError::generic_err("No finalized commitment with that nonce"),
+        );
+    }
+
+    fn storage_used(deps: &MockDeps, env: &Env, user: &str) -> u64 {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetStorageUsage { user: user.to_string() }).unwrap();
+        cosmwasm_std::from_binary::<StorageUsageResponse>(&res).unwrap().bytes
+    }
+
+    #[test]
+    fn test_storage_quota_reached_through_history_growth() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        let transition_for = |round: u8| {
+            let mut transition = test_transition("alice", round + 1, 3);
+            transition.old_state_root = vec![round; 32];
+            transition
+        };
+
+        let first = submit(&mut deps, &env, &transition_for(1)).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        vote(&mut deps, &env, 2, &first, true).unwrap();
+        let after_one = storage_used(&deps, &env, "alice");
+        assert!(after_one > 0);
+
+        // Room for exactly one more pending transition on top of current history
+        let pending = to_vec(&transition_for(2)).unwrap().len() as u64;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { max_user_storage: Some(after_one + pending), ..Default::default() }),
+        ).unwrap();
+
+        env.block.height += 1;
+        let second = submit(&mut deps, &env, &transition_for(2)).unwrap();
+        assert_eq!(storage_used(&deps, &env, "alice"), after_one + pending);
+        vote(&mut deps, &env, 1, &second, true).unwrap();
+        vote(&mut deps, &env, 2, &second, true).unwrap();
+
+        // One more finalizer record pushes the next submission over
+        let after_two = storage_used(&deps, &env, "alice");
+        assert!(after_two > after_one);
+        env.block.height += 1;
+        let err = submit(&mut deps, &env, &transition_for(3)).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!(
+            "StorageQuotaExceeded {{ used: {}, quota: {} }}", after_two, after_one + pending
+        )));
+
+        // Other users are unaffected
+        submit(&mut deps, &env, &test_transition("bob", 9, 3)).unwrap();
+    }
+
+    #[test]
+    fn test_storage_released_when_pending_closes() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        assert!(storage_used(&deps, &env, "alice") > 0);
+
+        cancel(&mut deps, &env, "alice", &validation_id).unwrap();
+        assert_eq!(storage_used(&deps, &env, "alice"), 0);
+    }
+
+    #[test]
+    fn test_owner_bulk_deactivates_nodes() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let set_active = |deps: &mut MockDeps, sender: &str, active: bool| {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::SetNodesActive { node_ids: vec![1, 2], active },
+            )
+        };
+        assert_eq!(set_active(&mut deps, "node1", false).unwrap_err(), StdError::generic_err("Unauthorized"));
+
+        let res = set_active(&mut deps, "creator", false).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "can_finalize" && a.value == "false"));
+        assert!(res.attributes.iter().any(|a| a.key == "warning" && a.value == "threshold_unreachable"));
+        let state = STATE.load(&deps.storage).unwrap();
+        let active: Vec<u32> = state.mpc_nodes.iter().filter(|n| n.active).map(|n| n.node_id).collect();
+        assert_eq!(active, vec![3]);
+
+        let res = set_active(&mut deps, "creator", true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "can_finalize" && a.value == "true"));
+        assert!(!res.attributes.iter().any(|a| a.key == "warning"));
+    }
+
+    #[test]
+    fn test_migration_dry_run_catches_corrupt_record() {
+        let (mut deps, env) = setup_contract(2, 3);
+        submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        submit(&mut deps, &env, &test_transition("bob", 3, 3)).unwrap();
+
+        // A legacy record under the commitments namespace that no longer decodes
+        let legacy: Keymap<String, String> = Keymap::new(b"state_commitments");
+        legacy.insert(&mut deps.storage, &"carol".to_string(), &"v0 commitment".to_string()).unwrap();
+
+        let report = dry_run(&deps, &env, None, None, None);
+        assert_eq!(report.failed_keys, vec!["state_commitments/carol".to_string()]);
+        assert_eq!(report.total_failures, 1);
+        assert_eq!(report.next_start_after, None);
+        let count = |store: &str| report.counts.iter().find(|c| c.store == store).unwrap().records;
+        assert_eq!(count("state_commitments"), 1);
+        assert_eq!(count("pending_validations"), 2);
+        assert_eq!(count("state"), 1);
+
+        // Map records are decoded a page at a time, in store order; pending
+        // validations come first, then commitments
+        let first = dry_run(&deps, &env, None, None, Some(2));
+        assert_eq!((first.total_failures, first.next_start_after), (0, Some(2)));
+        let second = dry_run(&deps, &env, None, first.next_start_after, Some(2));
+        assert_eq!(second.failed_keys, vec!["state_commitments/carol".to_string()]);
+        assert_eq!(second.counts, report.counts);
+
+        // Nothing was written
+        assert!(legacy.get(&deps.storage, &"carol".to_string()).is_some());
+        assert_eq!(STORAGE_SCHEMA_VERSION.load(&deps.storage).unwrap(), CURRENT_SCHEMA_VERSION);
+
+        legacy.remove(&mut deps.storage, &"carol".to_string()).unwrap();
+        assert_eq!(dry_run(&deps, &env, None, None, None).total_failures, 0);
+        migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: None }).unwrap();
+    }
+
+    fn dry_run(deps: &MockDeps, env: &Env, owner: Option<&str>, start_after: Option<u64>, limit: Option<u32>) -> MigrationDryRunResponse {
+        let msg = QueryMsg::MigrationDryRun { owner: owner.map(|owner| owner.to_string()), start_after, limit };
+        cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_submit_transfer_namespaces_leg_attributes() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer = Transfer {
+            sender: "alice".to_string(),
+            recipient: "bob".to_string(),
+            sender_transition: test_transition("alice", 2, 3),
+            recipient_transition: test_transition("bob", 3, 3),
+            amount_commitment: vec![9; 32],
+        };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SubmitTransfer { transfer }).unwrap();
+
+        let value = |key: &str| {
+            let matches: Vec<&str> = res.attributes.iter().filter(|a| a.key == key).map(|a| a.value.as_str()).collect();
+            assert_eq!(matches.len(), 1, "{} appears {} times", key, matches.len());
+            matches[0].to_string()
+        };
+        assert_eq!(value("sender_validation_id"), format!("{}-alice", env.block.height));
+        assert_eq!(value("recipient_validation_id"), format!("{}-bob", env.block.height));
+        assert_eq!(value("action"), "submit_transfer");
+        for key in ["validation_id", "new_root"] {
+            assert!(!res.attributes.iter().any(|a| a.key == key));
+        }
+    }
+
+    fn health_of(deps: &MockDeps, env: &Env) -> HealthResponse {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetHealth {}).unwrap();
+        cosmwasm_std::from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn test_health_reports_each_degraded_condition() {
+        let (mut deps, env) = setup_contract(2, 3);
+        assert_eq!(health_of(&deps, &env), HealthResponse { status: HealthStatus::Healthy, reasons: vec![] });
+
+        // Backlog over the watermark
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { pending_watermark: Some(1), ..Default::default() }),
+        ).unwrap();
+        submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        assert_eq!(health_of(&deps, &env).status, HealthStatus::Healthy);
+        submit(&mut deps, &env, &test_transition("bob", 3, 3)).unwrap();
+        assert_eq!(health_of(&deps, &env).reasons, vec![HealthReason::PendingBacklog]);
+
+        // Frozen nodes leave the threshold unreachable
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetNodesActive { node_ids: vec![1, 2], active: false },
+        ).unwrap();
+        let health = health_of(&deps, &env);
+        assert_eq!(health.status, HealthStatus::Degraded);
+        assert_eq!(health.reasons, vec![HealthReason::BelowThreshold, HealthReason::PendingBacklog]);
+
+        // Failed sends parked past the watermark
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { outbound_failure_watermark: Some(1), ..Default::default() }),
+        ).unwrap();
+        for id in [1, 2] {
+            OUTBOUND_IN_FLIGHT.insert(&mut deps.storage, &id, &OutboundSend {
+                recipient: "alice".to_string(),
+                amount: Coin::new(5, "uscrt"),
+                origin: format!("v{}", id),
+                attempts: 1,
+                last_error: None,
+            }).unwrap();
+            reply(deps.as_mut(), env.clone(), Reply {
+                id: OUTBOUND_REPLY_BASE + id,
+                result: SubMsgResult::Err("recipient blocked".to_string()),
+            }).unwrap();
+            assert_eq!(health_of(&deps, &env).reasons.contains(&HealthReason::OutboundFailures), id > 1);
+        }
+
+        // Un-migrated storage short-circuits everything else
+        STORAGE_SCHEMA_VERSION.remove(&mut deps.storage);
+        assert_eq!(health_of(&deps, &env), HealthResponse {
+            status: HealthStatus::MigrationRequired,
+            reasons: vec![HealthReason::MigrationRequired],
+        });
+    }
+
+    #[test]
+    fn test_health_reports_pause() {
+        let (mut deps, env) = setup_contract(2, 3);
+        set_paused(&mut deps, &env, true);
+        assert_eq!(health_of(&deps, &env), HealthResponse { status: HealthStatus::Paused, reasons: vec![HealthReason::Paused] });
+
+        // Pause outranks whatever else is degraded
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetNodesActive { node_ids: vec![1, 2], active: false },
+        ).unwrap();
+        let health = health_of(&deps, &env);
+        assert_eq!(health.status, HealthStatus::Paused);
+        assert_eq!(health.reasons, vec![HealthReason::Paused, HealthReason::BelowThreshold]);
+
+        set_paused(&mut deps, &env, false);
+        assert_eq!(health_of(&deps, &env).status, HealthStatus::Degraded);
+    }
+
+    #[test]
+    fn test_health_before_committee_is_ready() {
+        let (deps, env) = setup_contract(2, 1);
+        assert_eq!(health_of(&deps, &env).reasons, vec![HealthReason::CommitteeNotReady, HealthReason::BelowThreshold]);
+    }
+
+    #[test]
+    fn test_validation_expiry_query() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetValidationExpiry { validation_id }).unwrap();
+        let expiry: ValidationExpiryResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(expiry.created_at, env.block.time.seconds());
+        assert_eq!(expiry.timeout, default_validation_timeout());
+        assert_eq!(expiry.expires_at, expiry.created_at + expiry.timeout);
+
+        let err = query(deps.as_ref(), env, QueryMsg::GetValidationExpiry { validation_id: "missing".to_string() }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation not found"));
+    }
+
+    fn stage_key(deps: &mut MockDeps, env: &Env, node: u8, new_key: u8, signer: u8) -> StdResult<Response> {
+        let public_key = compressed_public_key(&node_signing_key(new_key));
+        let next_epoch = STATE.load(&deps.storage)?.epoch + 1;
+        let digest = key_possession_digest(env.contract.address.as_str(), node as u32, next_epoch, &public_key);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&format!("node{}", node), &[]),
+            ExecuteMsg::StageNextEpochKey { public_key, proof_of_possession: sign_digest(&node_signing_key(signer), &digest) },
+        )
+    }
+
+    #[test]
+    fn test_stage_next_epoch_key_requires_possession() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let err = stage_key(&mut deps, &env, 1, 11, 1).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid proof of possession"));
+
+        stage_key(&mut deps, &env, 1, 11, 11).unwrap();
+        let res = query(deps.as_ref(), env, QueryMsg::ListNodes { include_attestations: false }).unwrap();
+        let nodes: NodesResponse = cosmwasm_std::from_binary(&res).unwrap();
+        let node1 = &nodes.nodes[0].node;
+        assert_eq!(node1.public_key, compressed_public_key(&node_signing_key(1)));
+        assert_eq!(node1.staged_public_key, Some(compressed_public_key(&node_signing_key(11))));
+    }
+
+    #[test]
+    fn test_re_registering_cannot_swap_keys() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let register = |deps: &mut MockDeps, seed: u8| execute(deps.as_mut(), env.clone(), mock_info("node1", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(seed)) });
+        let refused = StdError::generic_err("Registered nodes change keys with StageNextEpochKey");
+
+        // An active node waits for AdvanceEpoch even once its key is staged
+        assert_eq!(register(&mut deps, 11).unwrap_err(), refused);
+        stage_key(&mut deps, &env, 1, 11, 11).unwrap();
+        assert_eq!(register(&mut deps, 11).unwrap_err(), refused);
+
+        // Rejoining takes the staged key, and no other
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SetNodesActive { node_ids: vec![1], active: false }).unwrap();
+        assert_eq!(register(&mut deps, 12).unwrap_err(), refused);
+        register(&mut deps, 11).unwrap();
+        let node1 = get_node(&STATE.load(&deps.storage).unwrap(), 1).unwrap().clone();
+        assert!(node1.active);
+        assert_eq!(node1.public_key, compressed_public_key(&node_signing_key(11)));
+        assert_eq!(node1.staged_public_key, None);
+    }
+
+    #[test]
+    fn test_advance_epoch_switches_staged_keys_atomically() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let next_epoch = STATE.load(&deps.storage).unwrap().epoch + 1;
+        stage_key(&mut deps, &env, 1, 11, 11).unwrap();
+        stage_key(&mut deps, &env, 2, 12, 12).unwrap();
+
+        let advance = |deps: &mut MockDeps, sender: &str| {
+            execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), ExecuteMsg::AdvanceEpoch {})
+        };
+        assert_eq!(advance(&mut deps, "node1").unwrap_err(), StdError::generic_err("Unauthorized"));
+        let res = advance(&mut deps, "creator").unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "deactivated" && a.value == "3"));
+        assert!(res.attributes.iter().any(|a| a.key == "can_finalize" && a.value == "true"));
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.epoch, next_epoch);
+        for (node, key) in [(0, 11), (1, 12)] {
+            assert_eq!(state.mpc_nodes[node].public_key, compressed_public_key(&node_signing_key(key)));
+            assert!(state.mpc_nodes[node].active);
+            assert_eq!(state.mpc_nodes[node].staged_public_key, None);
+        }
+        assert!(!state.mpc_nodes[2].active);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetCommitteeSnapshot { epoch: next_epoch }).unwrap();
+        let snapshot: CommitteeSnapshotResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(snapshot.current_epoch, next_epoch);
+        let members: Vec<u32> = snapshot.snapshot.members.iter().map(|m| m.node_id).collect();
+        assert_eq!(members, vec![1, 2]);
+
+        // Nobody staged for the epoch after: the whole committee sits it out
+        let res = advance(&mut deps, "creator").unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "warning" && a.value == "threshold_unreachable"));
+    }
+
+    #[test]
+    fn test_commitment_hidden_until_confirmed() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { commitment_confirmations: Some(5), ..Default::default() }),
+        ).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetViewingKey { key: "alice-key".to_string() }).unwrap();
+
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 2, 3)).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+
+        let commitment_query = || QueryMsg::GetStateCommitment {
+            user_address: "alice".to_string(),
+            viewing_key: "alice-key".to_string(),
+        };
+        let err = query(deps.as_ref(), env.clone(), commitment_query()).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Commitment not yet confirmed"));
+
+        env.block.height += 5;
+        let res = query(deps.as_ref(), env, commitment_query()).unwrap();
+        let commitment: StateCommitmentResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(commitment.commitment.state_root, vec![2; 32]);
+    }
+
+    /// Drives the payer leg of a one-recipient batch to where node2's batched
+    /// vote both reaches threshold and leaves threshold unreachable: node1's
+    /// valid vote still counts after node1 is frozen, node3 voted invalid
+    fn simultaneous_crossing(precedence: VotePrecedence) -> (MockDeps, Env, String, Response) {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { vote_precedence: Some(precedence), ..Default::default() }),
+        ).unwrap();
+        let group_id = batch_transfer(&mut deps, &env, &["r1"]);
+        let payer_leg = format!("{}-payer", env.block.height);
+        vote(&mut deps, &env, 3, &payer_leg, false).unwrap();
+        vote(&mut deps, &env, 1, &payer_leg, true).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetNodesActive { node_ids: vec![1], active: false },
+        ).unwrap();
+
+        let partial_signature = pending_partial(&deps, &env, 2, &payer_leg);
+        let votes = vec![BatchVote { validation_id: payer_leg, valid: true, partial_signature, attested_root: None }];
+        let res = execute(deps.as_mut(), env.clone(), mock_info("node2", &[]), ExecuteMsg::ValidateBatch { votes, max_items: None, cursor: None }).unwrap();
+        (deps, env, group_id, res)
+    }
+
+    fn outcome_of(deps: &MockDeps, validation_id: &str) -> ValidationOutcome {
+        VALIDATION_OUTCOMES.get(&deps.storage, &validation_id.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_simultaneous_crossing_reject_wins() {
+        let (deps, env, group_id, res) = simultaneous_crossing(VotePrecedence::RejectWins);
+        assert_eq!(group_status(&deps, &env, &group_id).status, TransferStatus::Unwound);
+        let payer_leg = format!("{}-payer", env.block.height);
+        assert!(res.attributes.iter().any(|a| a.key == "contested" && a.value == payer_leg));
+        let batch: BatchVoteResult = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+        assert!(batch.results[0].contested);
+        let outcome = outcome_of(&deps, &payer_leg);
+        assert_eq!(outcome.status, OutcomeStatus::Rejected);
+        assert!(outcome.contested);
+        assert_eq!(outcome.precedence, Some(VotePrecedence::RejectWins));
+        assert_eq!(outcome_of(&deps, &format!("{}-r1", env.block.height)).status, OutcomeStatus::Unwound);
+    }
+
+    #[test]
+    fn test_simultaneous_crossing_accept_wins() {
+        let (mut deps, env, group_id, _) = simultaneous_crossing(VotePrecedence::AcceptWins);
+        let payer_leg = format!("{}-payer", env.block.height);
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &payer_leg).unwrap().threshold_reached);
+
+        let recipient_leg = format!("{}-r1", env.block.height);
+        vote(&mut deps, &env, 2, &recipient_leg, true).unwrap();
+        vote(&mut deps, &env, 3, &recipient_leg, true).unwrap();
+        assert_eq!(group_status(&deps, &env, &group_id).status, TransferStatus::Completed);
+
+        let outcome = outcome_of(&deps, &payer_leg);
+        assert_eq!(outcome.status, OutcomeStatus::Finalized);
+        assert_eq!(outcome.precedence, Some(VotePrecedence::AcceptWins));
+        assert_eq!(outcome_of(&deps, &recipient_leg).precedence, None);
+    }
+
+    #[test]
+    fn test_coordinator_metadata_passthrough() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let mut transition = test_transition("alice", 2, 3);
+        transition.coordinator_metadata = Some(Binary::from(vec![0xab; MAX_COORDINATOR_METADATA + 1]));
+        let err = submit(&mut deps, &env, &transition).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!("Coordinator metadata exceeds {} bytes", MAX_COORDINATOR_METADATA)));
+
+        transition.coordinator_metadata = Some(Binary::from(b"round=7;batch=42".to_vec()));
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("auditor", &[]),
+            ExecuteMsg::CreateViewingKey { entropy: "auditor entropy".to_string() },
+        ).unwrap();
+        let key: ViewingKeyResponse = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetObserver { address: "auditor".to_string(), authorized: true },
+        ).unwrap();
+
+        let auth = ViewerAuth { address: "auditor".to_string(), viewing_key: key.key };
+        let res = query(deps.as_ref(), env, QueryMsg::GetValidation { validation_id, auth }).unwrap();
+        let validation: ValidationResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(validation.validation.coordinator_metadata, Some(Binary::from(b"round=7;batch=42".to_vec())));
+        // Kept off the transition, so replay detection ignores it
+        assert_eq!(validation.validation.transition.coordinator_metadata, None);
+    }
+
+    fn budgeted_batch(
+        deps: &mut MockDeps,
+        env: &Env,
+        votes: &[BatchVote],
+        max_items: Option<u32>,
+        cursor: Option<u32>,
+    ) -> BatchVoteResult {
+        let msg = ExecuteMsg::ValidateBatch { votes: votes.to_vec(), max_items, cursor };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), msg).unwrap();
+        cosmwasm_std::from_binary(&res.data.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_budgeted_batch_resumes_from_cursor() {
+        let users = ["alice", "bob", "carol", "dave", "erin"];
+        let setup = || {
+            let (mut deps, env) = setup_contract(2, 3);
+            for (i, user) in users.iter().chain(&["frank"]).enumerate() {
+                submit(&mut deps, &env, &test_transition(user, i as u8 + 2, 3)).unwrap();
+            }
+            (deps, env)
+        };
+        let mut votes: Vec<BatchVote> = users.iter().enumerate().map(|(i, user)| {
+            let validation_id = format!("12345-{}", user);
+            let partial_signature = signed_partial(&mock_env(), 1, &validation_id, &test_transition(user, i as u8 + 2, 3));
+            BatchVote { validation_id, valid: true, partial_signature, attested_root: None }
+        }).collect();
+        votes.push(votes[0].clone());
+
+        let (mut deps, env) = setup();
+        let whole = budgeted_batch(&mut deps, &env, &votes, None, None);
+        assert_eq!(whole.resume_cursor, None);
+
+        let (mut deps, env) = setup();
+        let first = budgeted_batch(&mut deps, &env, &votes, Some(4), None);
+        assert_eq!(first.resume_cursor, Some(4));
+
+        // An unrelated settlement between calls leaves the cursor meaningful
+        vote(&mut deps, &env, 2, "12345-frank", true).unwrap();
+        vote(&mut deps, &env, 3, "12345-frank", true).unwrap();
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &"12345-frank".to_string()).is_none());
+
+        let second = budgeted_batch(&mut deps, &env, &votes, Some(4), first.resume_cursor);
+        assert_eq!(second.resume_cursor, None);
+
+        let key = |r: &VoteResultEntry| (r.validation_id.clone(), r.status);
+        let resumed: Vec<_> = first.results.iter().chain(&second.results).map(key).collect();
+        assert_eq!(resumed, whole.results.iter().map(key).collect::<Vec<_>>());
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("node1", &[]),
+            ExecuteMsg::ValidateBatch { votes: votes.clone(), max_items: Some(4), cursor: Some(votes.len() as u32) },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Cursor is past the end of the batch"));
+    }
+
+    #[test]
+    fn test_slash_node_requires_verifiable_fault() {
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let env = mock_env();
+        let (mut deps, _) = finalized_update(vec![
+            signed_vote(&env, 1, "12345-alice", &transition, true),
+            signed_vote(&env, 2, "12345-alice", &transition, true),
+        ]);
+        let height = STATE.load(&deps.storage).unwrap().block_height;
+
+        // node3 signed a valid vote for a root the validation never reached
+        let forged_root = vec![6; 32];
+        let evidence = |node: u8, root: &[u8]| FaultEvidence {
+            validation_id: "12345-alice".to_string(),
+            height,
+            node_id: 3,
+            protocol_version: transition.protocol_version,
+            signed_root: root.to_vec(),
+            partial_signature: sign_digest(&node_signing_key(node), &vote_digest(&env.block.chain_id, env.contract.address.as_str(), "12345-alice", transition.protocol_version, root, &[])),
+        };
+        let slash = |deps: &mut MockDeps, evidence: FaultEvidence| {
+            execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SlashNode { evidence })
+        };
+
+        let err = slash(&mut deps, evidence(3, &[5; 32])).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Evidence agrees with the finalized root"));
+        let err = slash(&mut deps, evidence(1, &forged_root)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Evidence signature does not verify under the node's key"));
+        let err = slash(&mut deps, FaultEvidence { height: height + 1, ..evidence(3, &forged_root) }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("No finalized record for that validation at that height"));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::SlashNode { evidence: evidence(3, &forged_root) }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+        assert!(STATE.load(&deps.storage).unwrap().mpc_nodes[2].active);
+
+        slash(&mut deps, evidence(3, &forged_root)).unwrap();
+        let node3 = STATE.load(&deps.storage).unwrap().mpc_nodes[2].clone();
+        assert!(node3.slashed && !node3.active);
+
+        // Slashed nodes stay out
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("node3", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(3)) },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Node was slashed"));
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            ExecuteMsg::SetNodesActive { node_ids: vec![3], active: true },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Node 3 was slashed"));
+    }
+
+    #[test]
+    fn test_reward_split_follows_effort() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let mut alice = test_transition("alice", 1, 3);
+        alice.encrypted_shares[0].encrypted_data = vec![7; 1000];
+        submit(&mut deps, &env, &alice).unwrap();
+        submit(&mut deps, &env, &test_transition("bob", 2, 3)).unwrap();
+
+        // node1 verifies a large share and votes twice; node2 once; node3 never
+        vote(&mut deps, &env, 1, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 2, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 1, "12345-bob", true).unwrap();
+
+        let epoch = STATE.load(&deps.storage).unwrap().epoch;
+        let stats = |deps: &MockDeps, node_id| -> NodeStatsResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetNodeStats { node_id, epoch: None }).unwrap()).unwrap()
+        };
+        assert_eq!(stats(&deps, 1), NodeStatsResponse { node_id: 1, epoch, votes: 2, effort_units: 5 });
+        assert_eq!(stats(&deps, 2), NodeStatsResponse { node_id: 2, epoch, votes: 1, effort_units: 1 });
+        assert_eq!(stats(&deps, 3).votes, 0);
+
+        let split = |deps: &MockDeps| -> RewardSplitResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetRewardSplit { epoch, amount: Uint128::new(100) }).unwrap()).unwrap()
+        };
+        let amounts = |res: &RewardSplitResponse| res.payouts.iter().map(|p| (p.node_id, p.amount.u128())).collect::<Vec<_>>();
+
+        let equal = split(&deps);
+        assert_eq!(equal.policy, RewardPolicy::Equal);
+        assert_eq!(amounts(&equal), vec![(1, 50), (2, 50)]);
+        assert_eq!(equal.remainder, Uint128::zero());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { reward_policy: Some(RewardPolicy::EffortWeighted), ..Default::default() }),
+        ).unwrap();
+        let weighted = split(&deps);
+        assert_eq!(amounts(&weighted), vec![(1, 83), (2, 16)]);
+        assert_eq!(weighted.remainder, Uint128::new(1));
+
+        // Nothing recorded for an epoch nobody voted in
+        let err = query(deps.as_ref(), env.clone(), QueryMsg::GetNodeStats { node_id: 9, epoch: None }).unwrap_err();
+        assert_eq!(err, StdError::not_found("MPC node 9"));
+        let res: RewardSplitResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env, QueryMsg::GetRewardSplit { epoch: 1, amount: Uint128::new(100) }).unwrap()).unwrap();
+        assert!(res.payouts.is_empty());
+        assert_eq!(res.remainder, Uint128::new(100));
+    }
+
+    #[test]
+    fn test_reject_threshold_rejects_early() {
+        let (mut deps, env) = setup_contract(2, 5);
+        for out_of_range in [1, 6] {
+            let err = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]),
+                ExecuteMsg::UpdateConfig(ConfigUpdate { reject_threshold: Some(out_of_range), ..Default::default() })).unwrap_err();
+            assert_eq!(err, StdError::generic_err("Reject threshold must be 0 or between the threshold (2) and the active node count (5)"));
+        }
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { reject_threshold: Some(2), ..Default::default() }),
+        ).unwrap();
+        submit(&mut deps, &env, &test_transition("alice", 1, 5)).unwrap();
+
+        // Three nodes could still bring it to threshold after two invalid votes
+        let res = vote(&mut deps, &env, 1, "12345-alice", false).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "action" && a.value == "validate_transition"));
+        let res = vote(&mut deps, &env, 2, "12345-alice", false).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "action" && a.value == "validate_and_reject"));
+        assert!(res.attributes.iter().any(|a| a.key == "outcome" && a.value == "rejected"));
+
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &"12345-alice".to_string()).is_none());
+        assert_eq!(VALIDATION_OUTCOMES.get(&deps.storage, &"12345-alice".to_string()).unwrap().status, OutcomeStatus::Rejected);
+        let err = vote(&mut deps, &env, 3, "12345-alice", true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation already rejected"));
+    }
+
+    fn validation_status(deps: &MockDeps, env: &Env, validation_id: &str) -> ValidationStatusResponse {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetValidationStatus { validation_id: validation_id.to_string() }).unwrap();
+        cosmwasm_std::from_binary(&res).unwrap()
+    }
+
+    #[test]
+    fn test_dependent_transition_finalizes_after_dependency() {
+        let (mut deps, env) = setup_contract(2, 3);
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let bob = StateTransition { depends_on: vec!["12345-alice".to_string()], ..test_transition("bob", 2, 3) };
+        submit(&mut deps, &env, &bob).unwrap();
+
+        // bob reaches threshold first but waits on alice
+        vote(&mut deps, &env, 1, "12345-bob", true).unwrap();
+        let res = vote(&mut deps, &env, 2, "12345-bob", true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "awaiting_dependencies" && a.value == "true"));
+        let status = validation_status(&deps, &env, "12345-bob");
+        assert!(status.threshold_reached && status.outcome.is_none());
+        assert_eq!(status.unmet_dependencies, vec!["12345-alice".to_string()]);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::FinalizeTransition { validation_id: "12345-bob".to_string() },
+        ).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Dependencies not finalized"));
+
+        // Finalizing alice releases bob in the same transaction
+        vote(&mut deps, &env, 1, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 2, "12345-alice", true).unwrap();
+        let status = validation_status(&deps, &env, "12345-bob");
+        assert_eq!(status.outcome, Some(OutcomeStatus::Finalized));
+        assert!(status.unmet_dependencies.is_empty());
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"bob".to_string()).unwrap().state_root, vec![2; 32]);
+        assert_eq!(STATE.load(&deps.storage).unwrap().current_state_root, vec![2; 32]);
+    }
+
+    #[test]
+    fn test_dependency_rejection_cascades() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { reject_threshold: Some(2), ..Default::default() }),
+        ).unwrap();
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let bob = StateTransition { depends_on: vec!["12345-alice".to_string()], ..test_transition("bob", 2, 3) };
+        submit(&mut deps, &env, &bob).unwrap();
+        let carol = StateTransition { depends_on: vec!["12345-bob".to_string()], ..test_transition("carol", 3, 3) };
+        submit(&mut deps, &env, &carol).unwrap();
+
+        let err = submit(&mut deps, &env, &StateTransition {
+            depends_on: vec!["12345-nobody".to_string()],
+            ..test_transition("dave", 4, 3)
+        }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Dependency 12345-nobody not found"));
+
+        vote(&mut deps, &env, 1, "12345-alice", false).unwrap();
+        vote(&mut deps, &env, 2, "12345-alice", false).unwrap();
+        for id in ["12345-bob", "12345-carol"] {
+            assert_eq!(validation_status(&deps, &env, id).outcome, Some(OutcomeStatus::DependencyFailed));
+            assert!(PENDING_VALIDATIONS.get(&deps.storage, &id.to_string()).is_none());
+        }
+
+        let err = submit(&mut deps, &env, &StateTransition {
+            depends_on: vec!["12345-alice".to_string()],
+            ..test_transition("dave", 4, 3)
+        }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Dependency 12345-alice already rejected"));
+    }
+
+    #[test]
+    fn test_dependents_per_validation_are_capped() {
+        let (mut deps, env) = setup_contract(2, 3);
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let dependent = |user: &str| StateTransition {
+            depends_on: vec!["12345-alice".to_string()],
+            ..test_transition(user, 2, 3)
+        };
+        for i in 0..MAX_DEPENDENTS {
+            submit(&mut deps, &env, &dependent(&format!("user{}", i))).unwrap();
+        }
+        let err = submit(&mut deps, &env, &dependent("late")).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!("Dependency 12345-alice already has {} dependents", MAX_DEPENDENTS)));
+
+        // A dependent that settled without finalizing frees its slot
+        cancel(&mut deps, &env, "user0", "12345-user0").unwrap();
+        submit(&mut deps, &env, &dependent("late")).unwrap();
+        let dependents = DEPENDENTS.get(&deps.storage, &"12345-alice".to_string()).unwrap();
+        assert_eq!(dependents.len(), MAX_DEPENDENTS);
+        assert!(!dependents.contains(&"12345-user0".to_string()));
+    }
+
+    #[test]
+    fn test_dependency_cycle_refused() {
+        let (mut deps, env) = setup_contract(2, 3);
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+
+        // A second submission in the same block would take the id it depends on
+        let err = submit(&mut deps, &env, &StateTransition {
+            depends_on: vec!["12345-alice".to_string()],
+            ..test_transition("alice", 2, 3)
+        }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Dependency cycle"));
+    }
+
+    #[test]
+    fn test_late_reject_leaves_an_accepted_leg_accepted() {
+        let (mut deps, env) = setup_contract(2, 4);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { reject_threshold: Some(2), ..Default::default() }),
+        ).unwrap();
+        let group_id = batch_transfer(&mut deps, &env, &["r1"]);
+        let payer_leg = format!("{}-payer", env.block.height);
+        vote(&mut deps, &env, 1, &payer_leg, true).unwrap();
+        vote(&mut deps, &env, 2, &payer_leg, true).unwrap();
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &payer_leg).unwrap().threshold_reached);
+
+        // node4's batched invalid vote meets the reject threshold only after
+        // an earlier vote met the accept threshold, so nothing is contested
+        vote(&mut deps, &env, 3, &payer_leg, false).unwrap();
+        let votes = vec![BatchVote { validation_id: payer_leg.clone(), valid: false, partial_signature: vec![], attested_root: None }];
+        let res = execute(deps.as_mut(), env.clone(), mock_info("node4", &[]), ExecuteMsg::ValidateBatch { votes, max_items: None, cursor: None }).unwrap();
+        assert!(!res.attributes.iter().any(|a| a.key == "contested"));
+        let batch: BatchVoteResult = cosmwasm_std::from_binary(&res.data.unwrap()).unwrap();
+        assert!(!batch.results[0].contested);
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &payer_leg).unwrap().precedence_fired.is_none());
+
+        let recipient_leg = format!("{}-r1", env.block.height);
+        vote(&mut deps, &env, 1, &recipient_leg, true).unwrap();
+        vote(&mut deps, &env, 2, &recipient_leg, true).unwrap();
+        assert_eq!(group_status(&deps, &env, &group_id).status, TransferStatus::Completed);
+        let outcome = outcome_of(&deps, &payer_leg);
+        assert_eq!(outcome.status, OutcomeStatus::Finalized);
+        assert!(!outcome.contested);
+    }
+
+    fn at(env: &Env, seconds: u64) -> Env {
+        let mut later = env.clone();
+        later.block.time = env.block.time.plus_seconds(seconds);
+        later
+    }
+
+    fn set_paused(deps: &mut MockDeps, env: &Env, paused: bool) {
+        let msg = if paused { ExecuteMsg::Pause {} } else { ExecuteMsg::Unpause {} };
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), msg).unwrap();
+    }
+
+    #[test]
+    fn test_pause_extends_validation_expiry() {
+        let (mut deps, env) = setup_contract(2, 3);
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let expiry = |deps: &MockDeps, env: &Env| -> ValidationExpiryResponse {
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::GetValidationExpiry { validation_id: "12345-alice".to_string() }).unwrap();
+            cosmwasm_std::from_binary(&res).unwrap()
+        };
+        let original = expiry(&deps, &env).expires_at;
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::Pause {}).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+        set_paused(&mut deps, &at(&env, 10), true);
+        let err = vote(&mut deps, &at(&env, 20), 1, "12345-alice", true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Contract paused"));
+        let err = submit(&mut deps, &at(&env, 20), &test_transition("bob", 2, 3)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Contract paused"));
+
+        // Still paused: the expiry keeps moving with block time
+        assert_eq!(expiry(&deps, &at(&env, 110)).expires_at, original + 100);
+
+        // Paused across the original expiry; it survives by the paused amount
+        let resumed = at(&env, default_validation_timeout() + 500);
+        set_paused(&mut deps, &resumed, false);
+        let paused_for = default_validation_timeout() + 490;
+        assert_eq!(expiry(&deps, &at(&env, default_validation_timeout() + 600)).expires_at, original + paused_for);
+        vote(&mut deps, &resumed, 1, "12345-alice", true).unwrap();
+
+        let res = query(deps.as_ref(), resumed, QueryMsg::GetPauseLedger {}).unwrap();
+        let ledger: PauseLedgerResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert!(!ledger.paused);
+        assert_eq!(ledger.windows[0].paused_for, paused_for);
+    }
+
+    #[test]
+    fn test_pause_extends_transfer_deadline() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer_id = conditional_transfer(&mut deps, &env, b"secret");
+        let resolve = |deps: &mut MockDeps, env: &Env| {
+            execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::ResolveTransfer { transfer_id: transfer_id.clone() })
+        };
+
+        // 200 seconds paused across the 100 second deadline
+        set_paused(&mut deps, &at(&env, 50), true);
+        set_paused(&mut deps, &at(&env, 250), false);
+
+        // Effective time is 60 here, so the preimage still counts
+        let err = resolve(&mut deps, &at(&env, 260)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Transfer deadline not reached"));
+        let err = resolve(&mut deps, &at(&env, 299)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Transfer deadline not reached"));
+        resolve(&mut deps, &at(&env, 300)).unwrap();
+    }
+
+    #[test]
+    fn test_deadlines_stop_while_paused() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let mut later = at(&env, 50);
+        later.block.height = env.block.height + 5;
+        set_paused(&mut deps, &later, true);
+
+        // Still paused: both clocks stopped at the pause
+        let mut now = at(&env, 500);
+        now.block.height = env.block.height + 50;
+        let by_time = Deadline { clock: Clock::Time, since: env.block.time.seconds(), at: env.block.time.seconds() + 100 };
+        let by_height = Deadline { clock: Clock::Height, since: env.block.height, at: env.block.height + 10 };
+        assert!(!by_time.passed(&deps.storage, &now).unwrap());
+        assert!(!by_height.passed(&deps.storage, &now).unwrap());
+        assert_eq!(by_time.adjusted(&deps.storage, now.block.time.seconds()).unwrap(), by_time.at + 450);
+
+        // Resumed, they run on from where they stopped
+        set_paused(&mut deps, &now, false);
+        now = at(&now, 49);
+        now.block.height += 4;
+        assert!(!by_time.passed(&deps.storage, &now).unwrap());
+        assert!(!by_height.passed(&deps.storage, &now).unwrap());
+        now = at(&now, 1);
+        now.block.height += 1;
+        assert!(by_time.passed(&deps.storage, &now).unwrap());
+        assert!(by_height.passed(&deps.storage, &now).unwrap());
+    }
+
+    #[test]
+    fn test_pause_ledger_merges_oldest_windows() {
+        let (mut deps, env) = setup_contract(2, 3);
+        for i in 0..=MAX_PAUSE_WINDOWS as u64 {
+            set_paused(&mut deps, &at(&env, i * 100), true);
+            set_paused(&mut deps, &at(&env, i * 100 + 10), false);
+        }
+        let ledger = PAUSE_LEDGER.load(&deps.storage).unwrap();
+        assert_eq!(ledger.len(), MAX_PAUSE_WINDOWS);
+        assert_eq!(ledger[0], PauseWindow {
+            paused_at: env.block.time.seconds(),
+            resumed_at: Some(env.block.time.seconds() + 110),
+            paused_for: 20,
+            paused_at_height: env.block.height,
+            resumed_at_height: Some(env.block.height),
+            paused_blocks: 0,
+        });
+
+        // Total paused time since before the first window is unchanged
+        let now = env.block.time.seconds() + 10_000;
+        let total = paused_since(&deps.storage, Clock::Time, 0, now).unwrap();
+        assert_eq!(total, 10 * (MAX_PAUSE_WINDOWS as u64 + 1));
+
+        let err = execute(deps.as_mut(), env, mock_info("creator", &[]), ExecuteMsg::Unpause {}).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Not paused"));
+    }
+
+    /// alice finalizes twice with a 5-block challenge window, and bob once
+    fn purge_fixture() -> (MockDeps, Env) {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { challenge_window: Some(5), ..Default::default() }),
+        ).unwrap();
+        submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        submit(&mut deps, &env, &test_transition("bob", 2, 3)).unwrap();
+        for id in ["12345-alice", "12345-bob"] {
+            vote(&mut deps, &env, 1, id, true).unwrap();
+            vote(&mut deps, &env, 2, id, true).unwrap();
+        }
+
+        let mut next = env.clone();
+        next.block.height += 1;
+        let second = StateTransition { old_state_root: vec![1; 32], ..test_transition("alice", 3, 3) };
+        submit(&mut deps, &next, &second).unwrap();
+        vote(&mut deps, &next, 1, "12346-alice", true).unwrap();
+        vote(&mut deps, &next, 2, "12346-alice", true).unwrap();
+        (deps, next)
+    }
+
+    /// Registers `user`'s key if needed and signs a purge at their next nonce
+    fn signed_purge(deps: &mut MockDeps, env: &Env, user: &str, keep_current: bool) -> ExecuteMsg {
+        if USER_KEYS.get(&deps.storage, &user.to_string()).is_none() {
+            register_user_key(deps, env, user).unwrap();
+        }
+        let nonce = PURGE_NONCES.get(&deps.storage, &user.to_string()).unwrap_or(0);
+        let digest = purge_digest(&env.block.chain_id, env.contract.address.as_str(), user, keep_current, nonce);
+        ExecuteMsg::PurgeMyHistory { keep_current, nonce, signature: sign_digest(&user_signing_key(user), &digest) }
+    }
+
+    fn purge(deps: &mut MockDeps, env: &Env, user: &str, keep_current: bool) -> StdResult<Response> {
+        let msg = signed_purge(deps, env, user, keep_current);
+        execute(deps.as_mut(), env.clone(), mock_info(user, &[]), msg)
+    }
+
+    #[test]
+    fn test_purge_my_history() {
+        let (mut deps, env) = purge_fixture();
+        let usage_before = USER_STORAGE.get(&deps.storage, &"alice".to_string()).unwrap();
+        let mut later = env.clone();
+        later.block.height += 5;
+
+        let res = purge(&mut deps, &later, "alice", true).unwrap();
+        for (key, value) in [("previous_commitments", "1"), ("finalizer_records", "1"), ("outcomes", "2")] {
+            assert!(res.attributes.iter().any(|a| a.key == key && a.value == value), "expected {}={}", key, value);
+        }
+
+        // The current commitment, its finalizers and leaf remain
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!((commitment.nonce, commitment.state_root), (1, vec![3; 32]));
+        assert!(LEAF_OWNERS.contains(&deps.storage, &commitment.leaf_index));
+        assert!(PREVIOUS_COMMITMENTS.get(&deps.storage, &"alice".to_string()).is_none());
+        assert!(COMMITMENT_FINALIZERS.get(&deps.storage, &nonce_key("alice", 1)).is_some());
+        assert!(COMMITMENT_FINALIZERS.get(&deps.storage, &nonce_key("alice", 0)).is_none());
+        assert!(VALIDATION_OUTCOMES.get(&deps.storage, &"12345-alice".to_string()).is_none());
+        assert!(USER_STORAGE.get(&deps.storage, &"alice".to_string()).unwrap() < usage_before);
+
+        // Other users are untouched
+        assert!(VALIDATION_OUTCOMES.get(&deps.storage, &"12345-bob".to_string()).is_some());
+        assert!(COMMITMENT_FINALIZERS.get(&deps.storage, &nonce_key("bob", 0)).is_some());
+
+        let res = purge(&mut deps, &later, "alice", false).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "finalizer_records" && a.value == "1"));
+        assert!(COMMITMENT_FINALIZERS.get(&deps.storage, &nonce_key("alice", 1)).is_none());
+    }
+
+    #[test]
+    fn test_purge_requires_signed_fresh_nonce() {
+        let (mut deps, env) = purge_fixture();
+        let mut later = env.clone();
+        later.block.height += 5;
+        let info = mock_info("alice", &[]);
+
+        // Signed by alice, but sent by bob
+        let msg = signed_purge(&mut deps, &later, "alice", true);
+        let ExecuteMsg::PurgeMyHistory { signature, .. } = &msg else { unreachable!() };
+        let forged = ExecuteMsg::PurgeMyHistory { keep_current: true, nonce: 0, signature: signature.clone() };
+        register_user_key(&mut deps, &later, "bob").unwrap();
+        let err = execute(deps.as_mut(), later.clone(), mock_info("bob", &[]), forged).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid purge signature"));
+
+        // A signature over the other keep_current does not carry over
+        let ExecuteMsg::PurgeMyHistory { signature, .. } = signed_purge(&mut deps, &later, "alice", false) else { unreachable!() };
+        let swapped = ExecuteMsg::PurgeMyHistory { keep_current: true, nonce: 0, signature };
+        let err = execute(deps.as_mut(), later.clone(), info.clone(), swapped).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid purge signature"));
+
+        execute(deps.as_mut(), later.clone(), info.clone(), msg).unwrap();
+        assert_eq!(PURGE_NONCES.get(&deps.storage, &"alice".to_string()), Some(1));
+        assert!(USER_OUTCOMES.add_suffix(b"alice").iter(&deps.storage).unwrap().next().is_none());
+        assert!(USER_OUTCOMES.add_suffix(b"bob").contains(&deps.storage, &"12345-bob".to_string()));
+
+        // The spent nonce cannot be replayed
+        let replay = signed_purge(&mut deps, &later, "alice", true);
+        let ExecuteMsg::PurgeMyHistory { signature, .. } = replay else { unreachable!() };
+        let stale = ExecuteMsg::PurgeMyHistory { keep_current: true, nonce: 0, signature };
+        let err = execute(deps.as_mut(), later, info, stale).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Purge nonce must be 1"));
+    }
+
+    #[test]
+    fn test_user_key_changes_are_signed_by_the_current_key() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let info = mock_info("alice", &[]);
+        let alice = user_signing_key("alice");
+        let alice_key = compressed_public_key(&alice);
+        let thief = user_signing_key("mallory");
+        let thief_key = compressed_public_key(&thief);
+
+        // A first key must sign its own registration
+        let unproven = signed_user_key(&env, "alice", &alice_key, &thief, 0);
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), unproven).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Key must sign its own registration"));
+        execute(deps.as_mut(), env.clone(), info.clone(), signed_user_key(&env, "alice", &alice_key, &alice, 0)).unwrap();
+
+        // Whoever holds the sender account cannot swap in a key of their own
+        let hijack = signed_user_key(&env, "alice", &thief_key, &thief, 1);
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), hijack).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Key change must be signed by the current key"));
+
+        // A rotation signed by the current key is applied and recorded
+        let rotation = signed_user_key(&env, "alice", &thief_key, &alice, 1);
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), rotation).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "rotated" && a.value == "true"));
+        assert_eq!(USER_KEYS.get(&deps.storage, &"alice".to_string()), Some(thief_key.clone()));
+
+        let account = overview(&deps, &env, "alice", None).unwrap();
+        assert_eq!(account.key_nonce, 2);
+        assert_eq!(account.key_rotations, vec![
+            KeyRotation { previous_key: None, public_key: alice_key, height: env.block.height },
+            KeyRotation { previous_key: Some(compressed_public_key(&alice)), public_key: thief_key, height: env.block.height },
+        ]);
+
+        // A signed rotation applies once
+        let replay = signed_user_key(&env, "alice", &compressed_public_key(&thief), &alice, 1);
+        let err = execute(deps.as_mut(), env, info, replay).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Key nonce must be 2"));
+    }
+
+    #[test]
+    fn test_purge_refused_while_challengeable() {
+        let (mut deps, env) = purge_fixture();
+        let err = purge(&mut deps, &env, "alice", true).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!("Commitment open to challenge until height {}", env.block.height + 5)));
+        assert!(PREVIOUS_COMMITMENTS.get(&deps.storage, &"alice".to_string()).is_some());
+        assert!(VALIDATION_OUTCOMES.get(&deps.storage, &"12345-alice".to_string()).is_some());
+    }
+
+    #[test]
+    fn test_update_transfer_cids() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transfer = Transfer {
+            sender: "alice".to_string(),
+            recipient: "bob".to_string(),
+            sender_transition: test_transition("alice", 2, 3),
+            recipient_transition: test_transition("bob", 3, 3),
+            amount_commitment: vec![9; 32],
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SubmitTransfer { transfer }).unwrap();
+        vote(&mut deps, &env, 1, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 1, "12345-bob", true).unwrap();
+
+        let update = |sender_validation_id: &str, recipient_validation_id: &str| ExecuteMsg::UpdateTransferCids {
+            sender_validation_id: sender_validation_id.to_string(),
+            recipient_validation_id: recipient_validation_id.to_string(),
+            sender_ipfs: "QmAliceRepinned".to_string(),
+            recipient_ipfs: "QmBobRepinned".to_string(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), update("12345-alice", "12345-bob")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), update("12345-bob", "12345-alice")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validations are not the legs of one transfer"));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), update("12345-alice", "12345-bob")).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "recipient_votes_reset" && a.value == "true"));
+
+        // The sender's vote still counts; bob never agreed to his new CID, so
+        // his leg is validated afresh
+        assert_eq!(load_pending(&deps.storage, "12345-alice").unwrap().validations.len(), 1);
+        assert!(load_pending(&deps.storage, "12345-bob").unwrap().validations.is_empty());
+        vote(&mut deps, &env, 2, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 1, "12345-bob", true).unwrap();
+        vote(&mut deps, &env, 2, "12345-bob", true).unwrap();
+        for (user, cid) in [("alice", "QmAliceRepinned"), ("bob", "QmBobRepinned")] {
+            let commitment = STATE_COMMITMENTS.get(&deps.storage, &user.to_string()).unwrap();
+            assert_eq!(commitment.ipfs_cid, cid);
+            assert_eq!(CID_OWNERS.get(&deps.storage, &cid.to_string()).unwrap(), user);
+        }
+    }
+
+    #[test]
+    fn test_transfer_cids_follow_the_submitter_not_the_claimed_sender() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        let transfer = Transfer {
+            sender: "alice".to_string(),
+            recipient: "bob".to_string(),
+            sender_transition: test_transition("alice", 2, 3),
+            recipient_transition: test_transition("bob", 3, 3),
+            amount_commitment: vec![9; 32],
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("relayer", &[]), ExecuteMsg::SubmitTransfer { transfer }).unwrap();
+        let record = TRANSFERS.get(&deps.storage, &"12345-alice-bob".to_string()).unwrap();
+        assert_eq!(record.submitter, "relayer");
+
+        let update = |recipient_ipfs: &str| ExecuteMsg::UpdateTransferCids {
+            sender_validation_id: "12345-alice".to_string(),
+            recipient_validation_id: "12345-bob".to_string(),
+            sender_ipfs: "QmAliceRepinned".to_string(),
+            recipient_ipfs: recipient_ipfs.to_string(),
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), update("QmBobRepinned")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+
+        // Leaving bob's CID alone keeps his votes; alice never agreed to her
+        // new CID, so her leg is validated afresh
+        vote(&mut deps, &env, 1, "12345-alice", true).unwrap();
+        vote(&mut deps, &env, 1, "12345-bob", true).unwrap();
+        let bob_ipfs = test_transition("bob", 3, 3).new_state_ipfs;
+        let res = execute(deps.as_mut(), env.clone(), mock_info("relayer", &[]), update(&bob_ipfs)).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "sender_votes_reset" && a.value == "true"));
+        assert!(res.attributes.iter().any(|a| a.key == "recipient_votes_reset" && a.value == "false"));
+        assert!(load_pending(&deps.storage, "12345-alice").unwrap().validations.is_empty());
+        assert_eq!(load_pending(&deps.storage, "12345-bob").unwrap().validations.len(), 1);
+        vote(&mut deps, &env, 1, "12345-alice", true).unwrap();
+
+        // Past threshold, only bob could have agreed to a new CID for his leg
+        let mut bob_leg = load_pending(&deps.storage, "12345-bob").unwrap();
+        bob_leg.threshold_reached = true;
+        PENDING_VALIDATIONS.insert(&mut deps.storage, &bob_leg.validation_id, &bob_leg).unwrap();
+        env.block.height += 1;
+        let err = execute(deps.as_mut(), env.clone(), mock_info("relayer", &[]), update("QmBobRepinned")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Only the recipient can repoint a leg past threshold"));
+
+        // Likewise alice for hers
+        let mut alice_leg = load_pending(&deps.storage, "12345-alice").unwrap();
+        alice_leg.threshold_reached = true;
+        PENDING_VALIDATIONS.insert(&mut deps.storage, &alice_leg.validation_id, &alice_leg).unwrap();
+        let repoint_sender = ExecuteMsg::UpdateTransferCids {
+            sender_validation_id: "12345-alice".to_string(),
+            recipient_validation_id: "12345-bob".to_string(),
+            sender_ipfs: "QmAliceRepinnedAgain".to_string(),
+            recipient_ipfs: bob_ipfs,
+        };
+        let err = execute(deps.as_mut(), env, mock_info("relayer", &[]), repoint_sender).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Only the sender can repoint a leg past threshold"));
+        assert_eq!(load_pending(&deps.storage, "12345-alice").unwrap().validations.len(), 1);
+    }
+
+    fn salted_contract(salt: &[u8]) -> (MockDeps, Env) {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            InstantiateMsg { threshold: 2, dev_mode: true, deployment_salt: salt.to_vec(), ..Default::default() },
+        ).unwrap();
+        for i in 1..=3 {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(&format!("node{}", i), &[]),
+                ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(i)) },
+            ).unwrap();
+        }
+        (deps, env)
+    }
+
+    #[test]
+    fn test_deployment_salt_separates_ids() {
+        let (mut first, env) = salted_contract(b"instance-a");
+        let (mut second, _) = salted_contract(b"instance-b");
+        let (mut unsalted, _) = salted_contract(b"");
+
+        let transition = test_transition("alice", 1, 3);
+        let ids: Vec<String> = [&mut first, &mut second, &mut unsalted].into_iter()
+            .map(|deps| submit(deps, &env, &transition).unwrap())
+            .collect();
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[0], format!("{}:12345-alice", salt_tag(b"instance-a")));
+        assert_eq!(ids[2], "12345-alice");
+
+        let roots: Vec<Vec<u8>> = [&first, &second, &unsalted].into_iter()
+            .map(|deps| STATE.load(&deps.storage).unwrap().current_state_root)
+            .collect();
+        assert_ne!(roots[0], roots[1]);
+        assert_eq!(roots[2], vec![0; 32]);
+
+        // Salted ids work like any other
+        vote(&mut first, &env, 1, &ids[0], true).unwrap();
+        vote(&mut first, &env, 2, &ids[0], true).unwrap();
+        assert_eq!(VALIDATION_OUTCOMES.get(&first.storage, &ids[0]).unwrap().status, OutcomeStatus::Finalized);
+    }
+
+    #[test]
+    fn test_share_encodings_are_checked_and_exposed() {
+        let (mut deps, env) = setup_contract(2, 3);
+
+        // Compressed shares are accepted and handed to nodes with their tag
+        let mut transition = test_transition("alice", 1, 3);
+        transition.encrypted_shares[0].encoding = ShareEncoding::Zstd;
+        transition.encrypted_shares[1].encoding = ShareEncoding::Deflate;
+        let id = submit(&mut deps, &env, &transition).unwrap();
+        let auth = node_auth(&mut deps, &env, 1);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetPendingForNode { node_id: 1, auth, cursor: None }).unwrap();
+        let pending: PendingForNodeResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(pending.items[0].validation_id, id);
+        assert_eq!(pending.items[0].share.encoding, ShareEncoding::Zstd);
+
+        // The configured limit applies to the stored (compressed) bytes
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            max_share_len: Some(16),
+            ..Default::default()
+        })).unwrap();
+        let mut transition = test_transition("bob", 1, 3);
+        transition.encrypted_shares[2].encrypted_data = vec![0; 17];
+        let err = submit(&mut deps, &env, &transition).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Share for node 3 exceeds 16 bytes"));
+        let mut transition = test_transition("bob", 1, 3);
+        transition.encrypted_shares[2].encrypted_data = vec![0; 16];
+        submit(&mut deps, &env, &transition).unwrap();
+
+        // Unknown encodings never make it past message parsing
+        let mut json = cosmwasm_std::to_vec(&ExecuteMsg::SubmitStateTransition { transition: test_transition("carol", 1, 3) }).unwrap();
+        json = String::from_utf8(json).unwrap().replacen("\"raw\"", "\"brotli\"", 1).into_bytes();
+        assert!(cosmwasm_std::from_slice::<ExecuteMsg>(&json).is_err());
+    }
+
+    #[test]
+    fn test_share_encoding_digest_is_stable() {
+        let raw = test_transition("alice", 1, 3);
+        let hash = transition_content_hash(&raw).unwrap();
+        assert_eq!(hash, transition_content_hash(&raw.clone()).unwrap());
+
+        // Payloads from clients that predate the tag hash as raw
+        let json = String::from_utf8(cosmwasm_std::to_vec(&raw).unwrap()).unwrap();
+        assert!(json.contains(",\"encoding\":\"raw\""));
+        let legacy: StateTransition = cosmwasm_std::from_slice(json.replace(",\"encoding\":\"raw\"", "").as_bytes()).unwrap();
+        assert_eq!(transition_content_hash(&legacy).unwrap(), hash);
+
+        // Re-tagging a share changes what signers commit to
+        let mut zstd = raw;
+        zstd.encrypted_shares[0].encoding = ShareEncoding::Zstd;
+        assert_ne!(transition_content_hash(&zstd).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_last_finalized_tracks_the_latest_finalization() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        let last = |deps: &MockDeps, env: &Env| -> Option<LastFinalized> {
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLastFinalized {}).unwrap();
+            cosmwasm_std::from_binary::<LastFinalizedResponse>(&res).unwrap().last
+        };
+        assert_eq!(last(&deps, &env), None);
+
+        let first = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        vote(&mut deps, &env, 2, &first, true).unwrap();
+        assert_eq!(last(&deps, &env).unwrap().validation_id, first);
+
+        env.block.height += 5;
+        let second = submit(&mut deps, &env, &test_transition("bob", 2, 3)).unwrap();
+        vote(&mut deps, &env, 3, &second, true).unwrap();
+        vote(&mut deps, &env, 2, &second, true).unwrap();
+
+        let record = last(&deps, &env).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(record.validation_id, second);
+        assert_eq!(record.user_address, "bob");
+        assert_eq!(record.new_root, vec![2; 32]);
+        assert_eq!((record.block_height, record.finalized_at_height), (2, env.block.height));
+        assert_eq!(record.threshold_signature, state.last_threshold_signature);
+        assert_eq!(record.signers, vec![3, 2]);
+    }
+
+    fn diagnose(deps: &MockDeps, env: &Env, validation_id: &str) -> DiagnosisResponse {
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::DiagnoseValidation { validation_id: validation_id.to_string() }).unwrap();
+        cosmwasm_std::from_binary(&res).unwrap()
+    }
+
+    fn failing(diagnosis: &DiagnosisResponse) -> Vec<&str> {
+        diagnosis.checks.iter().filter(|c| !c.passed).map(|c| c.check.as_str()).collect()
+    }
+
+    fn finalize_error(deps: &mut MockDeps, env: &Env, validation_id: &str) -> StdError {
+        execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::FinalizeTransition {
+            validation_id: validation_id.to_string(),
+        }).unwrap_err()
+    }
+
+    #[test]
+    fn test_diagnose_validation_reports_what_finalize_refuses() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+
+        // Below threshold and past the deadline
+        let id = submit(&mut deps, &env, &test_transition("carol", 4, 3)).unwrap();
+        vote(&mut deps, &env, 1, &id, true).unwrap();
+        let expires_at = validation_expires_at(&deps.storage, &PENDING_VALIDATIONS.get(&deps.storage, &id).unwrap(), 0).unwrap();
+        env.block.time = cosmwasm_std::Timestamp::from_seconds(expires_at);
+        let diagnosis = diagnose(&deps, &env, &id);
+        assert_eq!(failing(&diagnosis), vec!["threshold_met", "deadline_ok"]);
+        assert_eq!(diagnosis.checks[1].detail, "1/2 valid votes");
+        assert_eq!(finalize_error(&mut deps, &env, &id), StdError::generic_err("Threshold not reached"));
+
+        let transfer = Transfer {
+            sender: "alice".to_string(),
+            recipient: "bob".to_string(),
+            sender_transition: test_transition("alice", 2, 3),
+            recipient_transition: test_transition("bob", 3, 3),
+            amount_commitment: vec![9; 32],
+        };
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SubmitConditionalTransfer {
+            transfer: transfer.clone(),
+            hashlock: Sha256::digest(b"secret").to_vec(),
+            deadline: env.block.time.seconds() + 100,
+        }).unwrap();
+        let (sender_leg, recipient_leg) = (format!("{}-alice", env.block.height), format!("{}-bob", env.block.height));
+        for node in 1..=2 {
+            execute(deps.as_mut(), env.clone(), mock_info(&format!("node{}", node), &[]),
+                signed_vote(&env, node, &sender_leg, &transfer.sender_transition, true)).unwrap();
+        }
+
+        // At threshold with verifiable partials, only the hashlock holds it
+        let diagnosis = diagnose(&deps, &env, &sender_leg);
+        assert!(!diagnosis.finalizable);
+        assert_eq!(failing(&diagnosis), vec!["lock_consistent"]);
+        assert_eq!(diagnosis.checks[0].detail, "hashlock");
+        assert_eq!(finalize_error(&mut deps, &env, &sender_leg), StdError::generic_err("Transfer locked until preimage is revealed"));
+
+        // A compromised signer's key is listed first, as finalize refuses it first
+        report(&mut deps, &env, "creator", 2, &[]).unwrap();
+        let diagnosis = diagnose(&deps, &env, &sender_leg);
+        assert_eq!(failing(&diagnosis), vec!["lock_consistent"]);
+        assert_eq!(diagnosis.checks[0].detail, "key_compromised,hashlock");
+        assert_eq!(finalize_error(&mut deps, &env, &sender_leg), StdError::generic_err("Validation blocked: key of node 2 compromised"));
+
+        // Nothing was mutated by diagnosing
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &sender_leg).unwrap().blocked.is_none());
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &recipient_leg).is_some());
+
+        query(deps.as_ref(), env, QueryMsg::DiagnoseValidation { validation_id: "missing".to_string() }).unwrap_err();
+    }
+
+    #[test]
+    fn test_node_lookups_return_not_found() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env, mock_info("creator", &[]), ExecuteMsg::SetNodesActive { node_ids: vec![3], active: false }).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+
+        assert_eq!(get_active_node(&state, "node2").unwrap().node_id, 2);
+        assert_eq!(get_active_node(&state, "stranger").unwrap_err(), StdError::not_found("Active MPC node"));
+        assert_eq!(get_active_node(&state, "node3").unwrap_err(), StdError::not_found("Active MPC node"));
+
+        assert_eq!(get_node(&state, 3).unwrap().address, "node3");
+        assert_eq!(get_node(&state, 0).unwrap_err(), StdError::not_found("MPC node 0"));
+        assert_eq!(get_node(&state, u32::MAX).unwrap_err(), StdError::not_found(format!("MPC node {}", u32::MAX)));
+    }
+
+    fn bootstrap(nodes: u8, accept_submissions_at: Option<cosmwasm_std::Timestamp>) -> StdResult<(MockDeps, Env)> {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let initial_nodes = (1..=nodes)
+            .map(|i| InitialNode {
+                address: format!("node{}", i),
+                public_key: compressed_public_key(&node_signing_key(i)),
+                key_type: Some(KeyType::Secp256k1),
+            })
+            .collect();
+        instantiate(deps.as_mut(), env.clone(), mock_info("creator", &[]), InstantiateMsg {
+            threshold: 2,
+            dev_mode: true,
+            initial_nodes,
+            accept_submissions_at,
+            ..Default::default()
+        })?;
+        Ok((deps, env))
+    }
+
+    #[test]
+    fn test_instantiate_bootstraps_committee_behind_submission_gate() {
+        let opens = mock_env().block.time.plus_seconds(60);
+        let (mut deps, mut env) = bootstrap(3, Some(opens)).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert!(!state.pending_committee);
+        assert_eq!(state.mpc_nodes.iter().map(|n| n.node_id).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        // Ready to vote, but not yet open to users
+        let transition = test_transition("alice", 1, 3);
+        let err = submit(&mut deps, &env, &transition).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!("Submissions open at {}", opens.seconds())));
+        env.block.time = opens;
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 3, &validation_id, true).unwrap();
+        assert_eq!(outcome_of(&deps, &validation_id).status, OutcomeStatus::Finalized);
+
+        // A seeded address re-registers in place, and only with its own key
+        let err = execute(deps.as_mut(), env.clone(), mock_info("node2", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(9)) }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Registered nodes change keys with StageNextEpochKey"));
+        let public_key = compressed_public_key(&node_signing_key(2));
+        execute(deps.as_mut(), env, mock_info("node2", &[]), ExecuteMsg::RegisterMPCNode { public_key: public_key.clone() }).unwrap();
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!(state.mpc_nodes.len(), 3);
+        assert_eq!(state.mpc_nodes[1].public_key, public_key);
+    }
+
+    #[test]
+    fn test_instantiate_rejects_inconsistent_initial_committee() {
+        let err = bootstrap(1, None).err().unwrap();
+        assert_eq!(err, StdError::generic_err("Initial committee of 1 cannot meet threshold 2"));
+
+        let node = |address: &str, public_key: Vec<u8>, key_type| InitialNode { address: address.to_string(), public_key, key_type };
+        let key = compressed_public_key(&node_signing_key(1));
+        for (nodes, expected) in [
+            (vec![node("node1", key.clone(), None), node("node1", key.clone(), None)], "Duplicate initial node node1"),
+            (vec![node("node1", key.clone(), Some(KeyType::Bls))], "Key of initial node node1 is not Bls"),
+            (vec![node("node1", vec![7; 20], None)], "Unsupported public key format (20 bytes); expected a compressed secp256k1 or BLS key"),
+        ] {
+            let err = instantiate(mock_dependencies().as_mut(), mock_env(), mock_info("creator", &[]), InstantiateMsg {
+                threshold: 2,
+                dev_mode: true,
+                initial_nodes: nodes,
+                ..Default::default()
+            }).unwrap_err();
+            assert_eq!(err, StdError::generic_err(expected));
+        }
+    }
+
+    #[test]
+    fn test_empty_merkle_proof_only_for_genesis_users() {
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            require_nonempty_merkle_proof: Some(true),
+            ..Default::default()
+        })).unwrap();
+
+        // A first-time user starting from the empty root needs no proof
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+
+        let mut unrooted = test_transition("bob", 1, 3);
+        unrooted.old_state_root = vec![7; 32];
+        let err = submit(&mut deps, &env, &unrooted).unwrap_err();
+        assert_eq!(err, StdError::generic_err("An empty merkle proof requires the empty old state root"));
+
+        // Once the user has a commitment, a proof must be attached; nodes,
+        // not the contract, check that it is sound
+        let mut next = test_transition("alice", 2, 3);
+        next.old_state_root = vec![1; 32];
+        let err = submit(&mut deps, &env, &next).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Merkle proof required"));
+        next.merkle_proof = vec![MerkleProofElement { hash: vec![5; 32], is_left: true }];
+        submit(&mut deps, &env, &next).unwrap();
+    }
+
+    /// alice with a finalized commitment (root [1; 32], nonce 0) and a user key
+    fn repoint_fixture() -> (MockDeps, Env) {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        register_user_key(&mut deps, &env, "alice").unwrap();
+        (deps, env)
+    }
+
+    fn repoint_msg(env: &Env, signer: &k256::ecdsa::SigningKey, cosign: Option<NodeCosign>) -> ExecuteMsg {
+        let digest = repoint_digest(env.contract.address.as_str(), &[1; 32], "QmRepinned", &[4; 32], 0);
+        ExecuteMsg::RepointStorage {
+            new_ipfs_cid: "QmRepinned".to_string(),
+            content_hash: vec![4; 32],
+            signature: sign_digest(signer, &digest),
+            cosign,
+        }
+    }
+
+    #[test]
+    fn test_repoint_storage_replaces_only_the_pointer() {
+        let (mut deps, mut env) = repoint_fixture();
+        let before = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        env.block.time = env.block.time.plus_seconds(30);
+
+        // Someone else's key cannot move alice's pointer
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), repoint_msg(&env, &user_signing_key("bob"), None)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid repoint signature"));
+
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), repoint_msg(&env, &user_signing_key("alice"), None)).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "action" && a.value == "repoint_storage"));
+        let after = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!((after.state_root.clone(), after.nonce), (before.state_root.clone(), before.nonce));
+        assert_eq!((after.ipfs_cid.as_str(), after.content_hash.clone()), ("QmRepinned", vec![4; 32]));
+        assert_eq!(after.updated_at, env.block.time.seconds());
+        assert_eq!(CID_OWNERS.get(&deps.storage, &"QmRepinned".to_string()).unwrap(), "alice");
+        assert!(CID_OWNERS.get(&deps.storage, &before.ipfs_cid).is_none());
+
+        // A user without a commitment has nothing to repoint
+        let err = execute(deps.as_mut(), env.clone(), mock_info("bob", &[]), repoint_msg(&env, &user_signing_key("bob"), None)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Commitment not found"));
+    }
+
+    #[test]
+    fn test_repoint_storage_cosign_mode() {
+        let (mut deps, env) = repoint_fixture();
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            repoint_cosign: Some(true),
+            ..Default::default()
+        })).unwrap();
+        let alice = user_signing_key("alice");
+        let digest = repoint_digest(env.contract.address.as_str(), &[1; 32], "QmRepinned", &[4; 32], 0);
+
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), repoint_msg(&env, &alice, None)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Repoint requires a node co-signature"));
+
+        // Signed by node3 but claimed as node2
+        let forged = NodeCosign { node_id: 2, signature: sign_digest(&node_signing_key(3), &digest) };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), repoint_msg(&env, &alice, Some(forged))).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid node co-signature"));
+
+        let cosign = NodeCosign { node_id: 2, signature: sign_digest(&node_signing_key(2), &digest) };
+        let res = execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), repoint_msg(&env, &alice, Some(cosign))).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "cosigned_by" && a.value == "2"));
+    }
+
+    #[test]
+    fn test_threshold_signatures_use_the_submission_epoch_key() {
+        let (mut deps, mut env) = setup_contract(3, 3);
+        let rotate = |deps: &mut MockDeps, env: &Env, seed: u8| {
+            let public_key = compressed_public_key(&node_signing_key(seed));
+            execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::RotateThresholdKey { public_key })
+        };
+        let verify = |deps: &MockDeps, env: &Env, validation_id: &str, seed: u8| -> ThresholdSignatureResponse {
+            let validation = PENDING_VALIDATIONS.get(&deps.storage, &validation_id.to_string()).unwrap();
+            let digest = vote_digest(&env.block.chain_id, env.contract.address.as_str(), validation_id, validation.protocol_version, &validation.transition.new_state_root, &[]);
+            let signature = sign_digest(&node_signing_key(seed), &digest);
+            let res = query(deps.as_ref(), env.clone(), QueryMsg::VerifyThresholdSignature { validation_id: validation_id.to_string(), signature }).unwrap();
+            cosmwasm_std::from_binary(&res).unwrap()
+        };
+
+        let committee_epoch = STATE.load(&deps.storage).unwrap().epoch;
+        let unkeyed = submit(&mut deps, &env, &test_transition("carol", 1, 3)).unwrap();
+        rotate(&mut deps, &env, 7).unwrap();
+        let before = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+
+        // The committee changes and produces a new key
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::AdvanceEpoch {}).unwrap();
+        env.block.height += 1;
+        assert_eq!(rotate(&mut deps, &env, 7).unwrap_err(), StdError::generic_err("Threshold key unchanged"));
+        let res = rotate(&mut deps, &env, 8).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "key_epoch" && a.value == "2"));
+        let after = submit(&mut deps, &env, &test_transition("bob", 1, 3)).unwrap();
+
+        assert_eq!(verify(&deps, &env, &before, 7), ThresholdSignatureResponse { key_epoch: 1, valid: true });
+        assert_eq!(verify(&deps, &env, &before, 8), ThresholdSignatureResponse { key_epoch: 1, valid: false });
+        assert_eq!(verify(&deps, &env, &after, 8), ThresholdSignatureResponse { key_epoch: 2, valid: true });
+        assert_eq!(verify(&deps, &env, &after, 7), ThresholdSignatureResponse { key_epoch: 2, valid: false });
+        query(deps.as_ref(), env.clone(), QueryMsg::VerifyThresholdSignature { validation_id: unkeyed, signature: vec![0; 64] }).unwrap_err();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetThresholdKey { key_epoch: Some(1) }).unwrap();
+        let key: ThresholdKeyResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!((key.current_key_epoch, key.key.committee_epoch), (2, committee_epoch));
+        let err = execute(deps.as_mut(), env, mock_info("node1", &[]), ExecuteMsg::RotateThresholdKey { public_key: vec![2; 33] }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+    }
+
+    #[test]
+    fn test_threshold_signatures_verify_after_finalization() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let rotate = |deps: &mut MockDeps, seed: u8| {
+            let public_key = compressed_public_key(&node_signing_key(seed));
+            execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::RotateThresholdKey { public_key }).unwrap();
+        };
+        let transition = test_transition("alice", 2, 3);
+        rotate(&mut deps, 7);
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        assert_eq!(outcome_of(&deps, &validation_id).status, OutcomeStatus::Finalized);
+        rotate(&mut deps, 8);
+
+        // The record's key_epoch still names the key it was submitted under
+        let verify = |deps: &MockDeps, validation_id: &str, seed: u8| {
+            let signature = signed_partial(&env, seed, validation_id, &transition);
+            let msg = QueryMsg::VerifyThresholdSignature { validation_id: validation_id.to_string(), signature };
+            query(deps.as_ref(), env.clone(), msg).and_then(|res| cosmwasm_std::from_binary::<ThresholdSignatureResponse>(&res))
+        };
+        assert_eq!(verify(&deps, &validation_id, 7).unwrap(), ThresholdSignatureResponse { key_epoch: 1, valid: true });
+        assert_eq!(verify(&deps, &validation_id, 8).unwrap(), ThresholdSignatureResponse { key_epoch: 1, valid: false });
+        assert_eq!(verify(&deps, "missing", 7).unwrap_err(), StdError::generic_err("Validation not found"));
+    }
+
+    #[test]
+    fn test_pending_for_node_pages_oldest_deadline_first() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        let start = env.block.time;
+        let mut expected = vec![];
+        for i in 0..25u64 {
+            let offset = (i * 7) % 25;
+            env.block.time = start.plus_seconds(offset);
+            let validation_id = submit(&mut deps, &env, &test_transition(&format!("user{:02}", i), 1, 3)).unwrap();
+            expected.push((offset, validation_id));
+        }
+        expected.sort();
+        let expected: Vec<String> = expected.into_iter().map(|(_, id)| id).collect();
+
+        let auth = node_auth(&mut deps, &env, 2);
+        let page = |deps: &MockDeps, cursor: Option<String>| -> PendingForNodeResponse {
+            let msg = QueryMsg::GetPendingForNode { node_id: 2, auth: auth.clone(), cursor };
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+
+        let first = page(&deps, None);
+        assert_eq!((first.items.len(), first.stubs.len()), (10, 15));
+        assert_eq!(first.items.iter().map(|i| &i.validation_id).collect::<Vec<_>>(), expected[..10].iter().collect::<Vec<_>>());
+        assert_eq!(first.stubs.iter().map(|s| &s.validation_id).collect::<Vec<_>>(), expected[10..].iter().collect::<Vec<_>>());
+        assert!(first.items.windows(2).all(|w| w[0].expires_at <= w[1].expires_at));
+
+        // A stub's payload matches its digest
+        let stub = &first.stubs[0];
+        let msg = QueryMsg::GetAssignedPayload { validation_id: stub.validation_id.clone(), node_id: 2, auth: auth.clone() };
+        let payload: AssignedPayloadResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(Sha256::digest(cosmwasm_std::to_vec(&payload.item.share).unwrap()).to_vec(), stub.share_digest);
+        assert_eq!(payload.item.expires_at, stub.expires_at);
+
+        let second = page(&deps, first.next_cursor);
+        assert_eq!((second.items.len(), second.stubs.len()), (10, 5));
+        assert_eq!(second.items[0].validation_id, expected[10]);
+        let third = page(&deps, second.next_cursor);
+        assert_eq!((third.items.len(), third.stubs.len(), third.next_cursor), (5, 0, None));
+        assert_eq!(third.items.last().unwrap().validation_id, expected[24]);
+
+        let msg = QueryMsg::GetPendingForNode { node_id: 2, auth, cursor: Some("garbage".to_string()) };
+        assert_eq!(query(deps.as_ref(), env.clone(), msg).unwrap_err(), StdError::generic_err("Invalid cursor"));
+    }
+
+    #[test]
+    fn test_pending_for_node_cursor_survives_settlements() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            max_validation_blocks: Some(5),
+            max_work_payloads: Some(2),
+            ..Default::default()
+        })).unwrap();
+        let mut ids = vec![];
+        for user in ["ann", "ben", "cal", "dee", "eve"] {
+            ids.push(submit(&mut deps, &env, &test_transition(user, 1, 3)).unwrap());
+            env.block.time = env.block.time.plus_seconds(1);
+        }
+        let auth = node_auth(&mut deps, &env, 1);
+        let page = |deps: &MockDeps, cursor: Option<String>| -> PendingForNodeResponse {
+            let msg = QueryMsg::GetPendingForNode { node_id: 1, auth: auth.clone(), cursor };
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+
+        let first = page(&deps, None);
+        assert_eq!(first.items.iter().map(|i| &i.validation_id).collect::<Vec<_>>(), vec![&ids[0], &ids[1]]);
+        let window_end = env.block.height + 5 + 1;
+        assert_eq!(first.items[0].expires_at_height, Some(window_end));
+        assert_eq!(first.stubs[0].expires_at_height, Some(window_end));
+
+        // Settling work on either side of the cursor neither skips nor repeats any
+        for (user, validation_id) in [("ann", &ids[0]), ("dee", &ids[3])] {
+            execute(deps.as_mut(), env.clone(), mock_info(user, &[]), ExecuteMsg::CancelTransition { validation_id: validation_id.clone() }).unwrap();
+        }
+        let second = page(&deps, first.next_cursor);
+        assert_eq!(second.items.iter().map(|i| &i.validation_id).collect::<Vec<_>>(), vec![&ids[2], &ids[4]]);
+        assert_eq!(second.next_cursor, None);
+    }
+
+    #[test]
+    fn test_validation_verifies_under_its_submission_epoch() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 0, 3);
+        let epoch = STATE.load(&deps.storage).unwrap().epoch;
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), signed_vote(&env, 1, &validation_id, &transition, true)).unwrap();
+
+        // Every node rotates its key while the validation is still pending
+        for node in 1..=3 {
+            stage_key(&mut deps, &env, node, node + 10, node + 10).unwrap();
+        }
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::AdvanceEpoch {}).unwrap();
+        let pending = PENDING_VALIDATIONS.get(&deps.storage, &validation_id).unwrap();
+        assert_eq!(pending.epoch, epoch);
+
+        // node2 signs with the old key the validation is bound to
+        execute(deps.as_mut(), env.clone(), mock_info("node2", &[]), signed_vote(&env, 2, &validation_id, &transition, true)).unwrap();
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap();
+        assert_eq!(commitment.epoch, epoch);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLightClientUpdate {}).unwrap();
+        let update: LightClientUpdateResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(update.epoch, epoch);
+        assert_eq!(update.committee[0].public_key, compressed_public_key(&node_signing_key(1)));
+        assert!(verify_light_client_update(&deps.api, &update).unwrap());
+
+        // Validations submitted now belong to the new epoch
+        let next = submit(&mut deps, &env, &test_transition("bob", 1, 3)).unwrap();
+        assert_eq!(PENDING_VALIDATIONS.get(&deps.storage, &next).unwrap().epoch, epoch + 1);
+    }
+
+    #[test]
+    fn test_committee_changes_start_epochs() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        let epoch = |deps: &MockDeps| STATE.load(&deps.storage).unwrap().epoch;
+        let members = |deps: &MockDeps, epoch: u64| -> Vec<u32> {
+            COMMITTEE_SNAPSHOTS.get(&deps.storage, &epoch).unwrap().members.iter().map(|m| m.node_id).collect()
+        };
+        assert_eq!(epoch(&deps), 3);
+        assert_eq!(members(&deps, 0), Vec::<u32>::new());
+        assert_eq!(members(&deps, 3), vec![1, 2, 3]);
+        let transition = test_transition("alice", 2, 4);
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+
+        // A node joining later is not in the committee the validation was submitted to
+        env.block.height += 1;
+        execute(deps.as_mut(), env.clone(), mock_info("node4", &[]), ExecuteMsg::RegisterMPCNode { public_key: vec![0x80; 48] }).unwrap();
+        assert_eq!(epoch(&deps), 4);
+        assert_eq!(COMMITTEE_SNAPSHOTS.get(&deps.storage, &4).unwrap().started_at, env.block.height);
+        assert_eq!(vote(&mut deps, &env, 4, &validation_id, true).unwrap_err(),
+            StdError::generic_err("Node 4 was not in the committee of epoch 3"));
+
+        // Nor is a key taken on rejoining: the snapshot keeps the one it was submitted under
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SetNodesActive { node_ids: vec![1], active: false }).unwrap();
+        stage_key(&mut deps, &env, 1, 11, 11).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("node1", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(11)) }).unwrap();
+        let stale = execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::ValidateTransition {
+            validation_id: validation_id.clone(),
+            valid: true,
+            partial_signature: signed_partial(&env, 11, &validation_id, &transition),
+            attested_root: None,
+        });
+        assert_eq!(stale.unwrap_err(), StdError::generic_err("Partial signature does not verify under the node's key"));
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+
+        // A BLS partial cannot be checked on chain, so it is taken as cast
+        let bls = submit(&mut deps, &env, &test_transition("bob", 3, 4)).unwrap();
+        vote(&mut deps, &env, 4, &bls, true).unwrap();
+
+        // Deactivating a node and reporting a key each start one too
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SetNodesActive { node_ids: vec![2], active: false }).unwrap();
+        assert_eq!(members(&deps, 7), vec![1, 3, 4]);
+        report(&mut deps, &env, "creator", 3, &[]).unwrap();
+        assert_eq!(members(&deps, 8), vec![1, 4]);
+        assert_eq!(epoch(&deps), 8);
+        assert_eq!(members(&deps, 3), vec![1, 2, 3]);
+    }
+
+    fn report(deps: &mut MockDeps, env: &Env, sender: &str, node_id: u32, cosigners: &[u8]) -> StdResult<Response> {
+        let public_key = compressed_public_key(&node_signing_key(node_id as u8));
+        let digest = compromise_digest(&env.block.chain_id, env.contract.address.as_str(), node_id, &public_key);
+        let cosigns = cosigners.iter()
+            .map(|node| NodeCosign { node_id: *node as u32, signature: sign_digest(&node_signing_key(*node), &digest) })
+            .collect();
+        execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), ExecuteMsg::ReportKeyCompromise {
+            node_id,
+            signature_by_compromised_key: None,
+            cosigns,
+        })
+    }
+
+    #[test]
+    fn test_key_compromise_reporters() {
+        let (mut deps, env) = setup_contract(2, 6);
+
+        // The node's own owner and the contract owner need nothing more
+        let res = report(&mut deps, &env, "node6", 6, &[]).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "reporter" && a.value == "node_owner"));
+        let res = report(&mut deps, &env, "creator", 5, &[]).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "reporter" && a.value == "admin"));
+        let err = report(&mut deps, &env, "creator", 5, &[]).err().unwrap();
+        assert_eq!(err, StdError::generic_err("Key was already reported compromised"));
+
+        // Another node needs two cosigns from distinct active nodes other than either party
+        let err = report(&mut deps, &env, "node1", 3, &[2]).err().unwrap();
+        assert_eq!(err, StdError::generic_err("Reporting another node needs 2 co-signatures"));
+        let err = report(&mut deps, &env, "node1", 3, &[2, 1]).err().unwrap();
+        assert_eq!(err, StdError::generic_err("Cosigns must come from distinct other nodes"));
+        let err = report(&mut deps, &env, "node1", 3, &[2, 5]).err().unwrap();
+        assert_eq!(err, StdError::generic_err("Invalid node co-signature"));
+        let mut testnet = env.clone();
+        testnet.block.chain_id = "pulsar-3".to_string();
+        let err = report(&mut deps, &testnet, "node1", 3, &[2, 4]).err().unwrap();
+        assert_eq!(err, StdError::generic_err("Invalid node co-signature"));
+        let err = report(&mut deps, &env, "stranger", 3, &[2, 4]).err().unwrap();
+        assert_eq!(err, StdError::not_found("Active MPC node"));
+
+        // A signature by the reported key must verify when given
+        let bad_proof = ExecuteMsg::ReportKeyCompromise {
+            node_id: 3,
+            signature_by_compromised_key: Some(vec![1; 64]),
+            cosigns: vec![],
+        };
+        let err = execute(deps.as_mut(), env.clone(), mock_info("node3", &[]), bad_proof).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Invalid compromise signature"));
+
+        let res = report(&mut deps, &env, "node1", 3, &[2, 4]).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "reporter" && a.value == "node"));
+        let state = STATE.load(&deps.storage).unwrap();
+        let active: Vec<u32> = state.mpc_nodes.iter().filter(|n| n.active).map(|n| n.node_id).collect();
+        assert_eq!(active, vec![1, 2, 4]);
+
+        // The key is banned for good, on every path back in
+        let key = compressed_public_key(&node_signing_key(3));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("node3", &[]), ExecuteMsg::RegisterMPCNode { public_key: key.clone() }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Key was reported compromised"));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("node9", &[]), ExecuteMsg::RegisterMPCNode { public_key: key }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Key was reported compromised"));
+        let err = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SetNodesActive { node_ids: vec![3], active: true }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Key of node 3 was reported compromised"));
+
+        // A fresh, staged key brings the node back
+        stage_key(&mut deps, &env, 3, 33, 33).unwrap();
+        let fresh = compressed_public_key(&node_signing_key(33));
+        execute(deps.as_mut(), env.clone(), mock_info("node3", &[]), ExecuteMsg::RegisterMPCNode { public_key: fresh }).unwrap();
+        assert!(get_node(&STATE.load(&deps.storage).unwrap(), 3).unwrap().active);
+    }
+
+    #[test]
+    fn test_key_compromise_quarantines_shares() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let affected = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let unaffected = submit(&mut deps, &env, &test_transition("bob", 2, 2)).unwrap();
+        vote(&mut deps, &env, 1, &affected, true).unwrap();
+
+        // The report itself leaves pending validations as they are
+        let res = report(&mut deps, &env, "node3", 3, &[]).unwrap();
+        assert!(res.events.is_empty());
+        assert_eq!(PENDING_VALIDATIONS.get(&deps.storage, &affected).unwrap().blocked, None);
+        let pending = PENDING_VALIDATIONS.get(&deps.storage, &affected).unwrap();
+        assert_eq!(block_reason(&deps.storage, &pending).unwrap(), Some(BlockReason::KeyCompromised { node_id: 3 }));
+
+        // The next vote blocks it and tells the user to cancel; asking to finalize fails too
+        let res = vote(&mut deps, &env, 2, &affected, true).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "blocked" && a.value == "key_compromised"));
+        let event = res.events.iter().find(|e| e.ty == "key_compromise_quarantine").unwrap();
+        assert!(event.attributes.iter().any(|a| a.key == "validation_id" && a.value == affected));
+        assert!(event.attributes.iter().any(|a| a.key == "required_action" && a.value == "cancel"));
+        assert!(PENDING_VALIDATIONS.get(&deps.storage, &affected).unwrap().blocked.is_some());
+        let err = execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::FinalizeTransition {
+            validation_id: affected.clone(),
+        }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation blocked: key of node 3 compromised"));
+
+        // Shares encrypted to other nodes only are left alone
+        vote(&mut deps, &env, 1, &unaffected, true).unwrap();
+        vote(&mut deps, &env, 2, &unaffected, true).unwrap();
+        assert_eq!(outcome_of(&deps, &unaffected).status, OutcomeStatus::Finalized);
+    }
+
+    #[test]
+    fn test_list_epochs_pages_every_threshold_key() {
+        let (mut deps, mut env) = setup_contract(2, 2);
+        let rotate = |deps: &mut MockDeps, env: &Env, seed: u8| {
+            let public_key = compressed_public_key(&node_signing_key(seed));
+            execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::RotateThresholdKey { public_key }).unwrap();
+        };
+        let advance = |deps: &mut MockDeps, env: &Env| {
+            execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::AdvanceEpoch {}).unwrap();
+        };
+        let list = |deps: &MockDeps, env: &Env, start_after: Option<u64>| -> EpochsResponse {
+            let msg = QueryMsg::ListEpochs { start_after, limit: Some(2) };
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        let listed = |res: &EpochsResponse| -> Vec<(u64, u64, Vec<u8>)> {
+            res.keys.iter().map(|k| (k.key_epoch, k.committee_epoch, k.public_key.clone())).collect()
+        };
+        let key = |seed: u8| compressed_public_key(&node_signing_key(seed));
+
+        // No key yet, nothing to list
+        assert_eq!(list(&deps, &env, None).keys, vec![]);
+
+        // Two rotations within one committee epoch both stay listed
+        rotate(&mut deps, &env, 7);
+        env.block.height += 1;
+        rotate(&mut deps, &env, 8);
+        advance(&mut deps, &env);
+        rotate(&mut deps, &env, 9);
+
+        // Registering the two nodes started epochs 1 and 2
+        let first = list(&deps, &env, None);
+        assert_eq!((first.current_epoch, first.current_key_epoch), (3, 3));
+        assert_eq!(listed(&first), vec![(1, 2, key(7)), (2, 2, key(8))]);
+        assert_eq!(first.next_start_after, Some(2));
+        let second = list(&deps, &env, first.next_start_after);
+        assert_eq!(listed(&second), vec![(3, 3, key(9))]);
+        assert_eq!(second.next_start_after, None);
+    }
+
+    fn overview(deps: &MockDeps, env: &Env, user: &str, viewing_key: Option<&str>) -> StdResult<AccountOverviewResponse> {
+        let msg = QueryMsg::GetAccountOverview { user_address: user.to_string(), viewing_key: viewing_key.map(String::from) };
+        cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg)?)
+    }
+
+    #[test]
+    fn test_account_overview_joins_every_subsystem() {
+        let (mut deps, env) = repoint_fixture();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetViewingKey { key: "alice-key".to_string() }).unwrap();
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[1; 32], &[2; 32], 1, 3);
+        let pending_id = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &pending_id, true).unwrap();
+        report(&mut deps, &env, "node3", 3, &[]).unwrap();
+
+        // Without the key only public fields
+        let public = overview(&deps, &env, "alice", None).unwrap();
+        let commitment = public.commitment.unwrap();
+        assert_eq!((commitment.state_root, commitment.nonce, commitment.confirmed), (vec![1; 32], 0, true));
+        assert_eq!(commitment.private, None);
+        assert_eq!(public.user_key, Some(compressed_public_key(&user_signing_key("alice"))));
+        assert_eq!(public.pending_total, 1);
+        assert_eq!(public.pending[0].validation_id, pending_id);
+        assert_eq!((public.pending[0].valid_votes, public.pending[0].blocked.as_deref()), (1, Some("key_compromised")));
+        assert!(public.storage.bytes > 0);
+
+        let private = overview(&deps, &env, "alice", Some("alice-key")).unwrap().commitment.unwrap().private.unwrap();
+        assert_eq!(private.ipfs_cid, "Qmalice1");
+        let err = overview(&deps, &env, "alice", Some("guess")).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unauthorized"));
+    }
+
+    #[test]
+    fn test_account_overview_of_a_new_user() {
+        let (deps, env) = setup_contract(2, 3);
+        let res = overview(&deps, &env, "nobody", None).unwrap();
+        assert_eq!(res.commitment, None);
+        assert_eq!(res.user_key, None);
+        assert_eq!((res.pending, res.pending_total), (vec![], 0));
+        assert_eq!(res.storage.bytes, 0);
+    }
+
+    #[test]
+    fn test_nonce_commitments_gate_partials() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            require_nonce_commitments: Some(true),
+            ..Default::default()
+        })).unwrap();
+        let transition = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[1; 32], 1, 3);
+        let id = submit(&mut deps, &env, &transition).unwrap();
+
+        // Signing is deterministic, so a node knows its nonce point, and the
+        // r it commits to, before it signs
+        let partial_of = |env: &Env, node: u8| {
+            let ExecuteMsg::ValidateTransition { partial_signature, .. } = signed_vote(env, node, &id, &transition, true) else {
+                unreachable!()
+            };
+            partial_signature
+        };
+        let commit_to = |deps: &mut MockDeps, env: &Env, node: u8, nonce_commitment: Vec<u8>| {
+            let msg = ExecuteMsg::CommitNonce { validation_id: id.clone(), nonce_commitment };
+            execute(deps.as_mut(), env.clone(), mock_info(&format!("node{}", node), &[]), msg)
+        };
+        let commit = |deps: &mut MockDeps, env: &Env, node: u8| commit_to(deps, env, node, partial_of(env, node)[..32].to_vec());
+        let partial = |deps: &mut MockDeps, env: &Env, node: u8| {
+            let msg = ExecuteMsg::SubmitPartial { validation_id: id.clone(), partial_signature: partial_of(env, node), attested_root: None };
+            execute(deps.as_mut(), env.clone(), mock_info(&format!("node{}", node), &[]), msg)
+        };
+
+        // Nobody signs before committing, nor before the signer set has
+        assert_eq!(partial(&mut deps, &env, 1).unwrap_err(), StdError::generic_err("Nonce not committed"));
+        assert_eq!(commit_to(&mut deps, &env, 1, vec![1; 66]).unwrap_err(), StdError::generic_err("Nonce commitment must be 32 bytes"));
+        commit(&mut deps, &env, 1).unwrap();
+        assert_eq!(commit(&mut deps, &env, 1).unwrap_err(), StdError::generic_err("Nonce already committed"));
+        assert_eq!(partial(&mut deps, &env, 1).unwrap_err(), StdError::generic_err("Nonce round open: 1/2 committed"));
+        let res = execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), signed_vote(&env, 1, &id, &transition, true)).unwrap_err();
+        assert_eq!(res, StdError::generic_err("Nonce round open: 1/2 committed"));
+
+        // node2 commits to a nonce it does not sign with
+        let res = commit_to(&mut deps, &env, 2, vec![9; 32]).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "round_closed" && a.value == "true"));
+        assert_eq!(commit(&mut deps, &env, 3).unwrap_err(), StdError::generic_err("Nonce round closed"));
+        assert_eq!(partial(&mut deps, &env, 3).unwrap_err(), StdError::generic_err("Nonce not committed"));
+        assert_eq!(partial(&mut deps, &env, 2).unwrap_err(), StdError::generic_err("Partial not signed with the committed nonce"));
+        partial(&mut deps, &env, 1).unwrap();
+
+        // Once the round has timed out, node3 takes the silent node's seat
+        env.block.height += NONCE_ROUND_TIMEOUT - 1;
+        assert_eq!(commit(&mut deps, &env, 3).unwrap_err(), StdError::generic_err("Nonce round closed"));
+        env.block.height += 1;
+        let res = commit(&mut deps, &env, 3).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "evicted" && a.value == "2"));
+        let stored = PENDING_VALIDATIONS.get(&deps.storage, &id).unwrap().nonce_commitments;
+        assert_eq!(stored.iter().map(|c| c.node_id).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(partial(&mut deps, &env, 2).unwrap_err(), StdError::generic_err("Nonce not committed"));
+
+        // The committed signers finalize, and their partials aggregate into a valid update
+        partial(&mut deps, &env, 3).unwrap();
+        assert_eq!(outcome_of(&deps, &id).status, OutcomeStatus::Finalized);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetLightClientUpdate {}).unwrap();
+        let update: LightClientUpdateResponse = cosmwasm_std::from_binary(&res).unwrap();
+        assert_eq!(update.signers, vec![1, 3]);
+        assert!(verify_light_client_update(&deps.api, &update).unwrap());
+    }
+
+    #[test]
+    fn test_registrations_are_rate_limited() {
+        let (mut deps, mut env) = setup_contract(2, 2);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            max_registrations_per_window: Some(2),
+            ..Default::default()
+        })).unwrap_err();
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            registration_cooldown: Some(10),
+            registration_window: Some(100),
+            max_registrations_per_window: Some(2),
+            ..Default::default()
+        })).unwrap();
+        let register = |deps: &mut MockDeps, env: &Env, node: u8, seed: u8| {
+            let public_key = compressed_public_key(&node_signing_key(seed));
+            execute(deps.as_mut(), env.clone(), mock_info(&format!("node{}", node), &[]), ExecuteMsg::RegisterMPCNode { public_key })
+        };
+        env.block.height = 1_000;
+
+        // Per address: re-registering waits out the cooldown
+        register(&mut deps, &env, 3, 3).unwrap();
+        env.block.height += 9;
+        assert_eq!(register(&mut deps, &env, 3, 3).unwrap_err(), StdError::generic_err("Registering too frequently"));
+        env.block.height += 1;
+        register(&mut deps, &env, 3, 3).unwrap();
+
+        // Overall: two per window, whoever registers
+        assert_eq!(register(&mut deps, &env, 4, 4).unwrap_err(), StdError::generic_err("Registration limit reached for this window"));
+        env.block.height = 1_100;
+        register(&mut deps, &env, 4, 4).unwrap();
+    }
+
+    #[test]
+    fn test_roots_must_be_32_bytes() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let mut transition = test_transition("alice", 1, 3);
+        transition.new_state_root = vec![1; 16];
+        assert_eq!(submit(&mut deps, &env, &transition).unwrap_err(), StdError::generic_err("Invalid root length"));
+
+        let mut transition = test_transition("alice", 1, 3);
+        transition.old_state_root = vec![];
+        assert_eq!(submit(&mut deps, &env, &transition).unwrap_err(), StdError::generic_err("Invalid root length"));
+    }
+
+    /// alice finalized on schema 2, before commitments kept their metadata:
+    /// her record is rewritten in the schema 2 layout, then migrated
+    fn legacy_commitment() -> (MockDeps, Env) {
+        use crate::state::{LegacyStateCommitmentV2, LEGACY_STATE_COMMITMENTS_V2};
+
+        let (mut deps, env) = repoint_fixture();
+        let alice = "alice".to_string();
+        let commitment = STATE_COMMITMENTS.get(&deps.storage, &alice).unwrap();
+        assert!(commitment.metadata_complete());
+        STATE_COMMITMENTS.remove(&mut deps.storage, &alice).unwrap();
+        LEGACY_STATE_COMMITMENTS_V2.insert(&mut deps.storage, &alice, &LegacyStateCommitmentV2 {
+            user_address: alice.clone(),
+            state_root: commitment.state_root.clone(),
+            ipfs_cid: commitment.ipfs_cid.clone(),
+            merkle_proof: commitment.merkle_proof.clone(),
+            nonce: commitment.nonce,
+            updated_at: commitment.updated_at,
+            leaf_index: commitment.leaf_index,
+            clamped: commitment.clamped,
+            lock_at_height: commitment.lock_at_height,
+            ipfs_gateway_hint: Some("gw.example".to_string()),
+        }).unwrap();
+        // Newer code cannot read the old bytes until the migration runs
+        assert!(STATE_COMMITMENTS.get(&deps.storage, &alice).is_none());
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &2).unwrap();
+
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap();
+        let migrated = STATE_COMMITMENTS.get(&deps.storage, &alice).unwrap();
+        assert_eq!(migrated.state_root, commitment.state_root);
+        assert_eq!((migrated.leaf_index, migrated.ipfs_gateway_hint.as_deref()), (commitment.leaf_index, Some("gw.example")));
+        assert!(!migrated.metadata_complete());
+        (deps, env)
+    }
+
+    fn backfill(deps: &mut MockDeps, env: &Env, sender: &str, state_root: Vec<u8>) -> StdResult<Response> {
+        execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), ExecuteMsg::BackfillCommitment {
+            user_address: "alice".to_string(),
+            metadata: CommitmentMetadata {
+                state_root,
+                finalized_at_height: 100,
+                committee_hash: vec![7; 32],
+                threshold_signature: vec![8; 128],
+            },
+        })
+    }
+
+    #[test]
+    fn test_backfill_fills_legacy_commitment_metadata() {
+        let (mut deps, env) = legacy_commitment();
+        let until = STATE.load(&deps.storage).unwrap().backfill_until.unwrap();
+        assert_eq!(until, env.block.height + BACKFILL_WINDOW);
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetViewingKey { key: "alice-key".to_string() }).unwrap();
+        let read = |deps: &MockDeps| -> StateCommitmentResponse {
+            let msg = QueryMsg::GetStateCommitment { user_address: "alice".to_string(), viewing_key: "alice-key".to_string() };
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        assert!(!read(&deps).metadata_complete);
+
+        assert_eq!(backfill(&mut deps, &env, "alice", vec![1; 32]).unwrap_err(), StdError::generic_err("Unauthorized"));
+        assert_eq!(
+            backfill(&mut deps, &env, "creator", vec![2; 32]).unwrap_err(),
+            StdError::generic_err("Root does not match the stored commitment"),
+        );
+        backfill(&mut deps, &env, "creator", vec![1; 32]).unwrap();
+        let res = read(&deps);
+        assert!(res.metadata_complete);
+        assert_eq!((res.commitment.finalized_at_height, res.commitment.committee_hash), (100, vec![7; 32]));
+        assert_eq!(
+            backfill(&mut deps, &env, "creator", vec![1; 32]).unwrap_err(),
+            StdError::generic_err("Commitment metadata already complete"),
+        );
+    }
+
+    #[test]
+    fn test_backfill_refused_outside_its_window() {
+        // Instances that never held legacy commitments have no window at all
+        let (mut deps, env) = repoint_fixture();
+        assert_eq!(backfill(&mut deps, &env, "creator", vec![1; 32]).unwrap_err(), StdError::generic_err("No backfill window was opened"));
+
+        let (mut deps, mut env) = legacy_commitment();
+        env.block.height += BACKFILL_WINDOW;
+        assert_eq!(backfill(&mut deps, &env, "creator", vec![1; 32]).unwrap_err(), StdError::generic_err("Backfill window closed"));
+
+        // Migrating again does not reopen it
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap();
+        assert_eq!(backfill(&mut deps, &env, "creator", vec![1; 32]).unwrap_err(), StdError::generic_err("Backfill window closed"));
+    }
+
+    #[test]
+    fn test_commitment_at_height_reads_history() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetViewingKey { key: "alice-key".to_string() }).unwrap();
+        let at = |deps: &MockDeps, env: &Env, height: u64| -> StdResult<Option<StateCommitment>> {
+            let msg = QueryMsg::GetCommitmentAtHeight { user_address: "alice".to_string(), height, viewing_key: "alice-key".to_string() };
+            let res: CommitmentAtHeightResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg)?)?;
+            Ok(res.commitment)
+        };
+        let alice = user_signing_key("alice");
+
+        env.block.height = 1;
+        let first = signed_transition("alice", &alice, &[0; 32], &[1; 32], 1, 3);
+        let id = submit(&mut deps, &env, &first).unwrap();
+        drive_to_threshold(&mut deps, &env, &id, &first, 2).unwrap();
+        env.block.height = 3;
+        let second = signed_transition("alice", &alice, &[1; 32], &[3; 32], 3, 3);
+        let id = submit(&mut deps, &env, &second).unwrap();
+        drive_to_threshold(&mut deps, &env, &id, &second, 2).unwrap();
+
+        assert_eq!(at(&deps, &env, 0).unwrap(), None);
+        let commitment = at(&deps, &env, 2).unwrap().unwrap();
+        assert_eq!((commitment.state_root, commitment.nonce, commitment.finalized_at_height), (vec![1; 32], 0, 1));
+        assert_eq!(at(&deps, &env, 3).unwrap().unwrap().state_root, vec![3; 32]);
+        assert_eq!(at(&deps, &env, 99).unwrap().unwrap().nonce, 1);
+
+        // A commitment only counts once it is confirmed, as GetStateCommitment shows it
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig(ConfigUpdate { commitment_confirmations: Some(5), ..Default::default() }),
+        ).unwrap();
+        assert_eq!(at(&deps, &env, 99).unwrap(), None);
+        env.block.height = 6;
+        assert_eq!(at(&deps, &env, 99).unwrap().unwrap().nonce, 0);
+        env.block.height = 8;
+        assert_eq!(at(&deps, &env, 7).unwrap().unwrap().nonce, 0);
+        assert_eq!(at(&deps, &env, 8).unwrap().unwrap().nonce, 1);
+
+        // Purged history is reported as gone, not as absent
+        env.block.height = 10;
+        purge(&mut deps, &env, "alice", true).unwrap();
+        assert_eq!(at(&deps, &env, 2).unwrap_err(), StdError::generic_err("Commitment history no longer kept for that height"));
+    }
+
+    #[test]
+    fn test_protocol_info_follows_enforcement() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let info = |deps: &MockDeps| -> ProtocolInfoResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetProtocolInfo {}).unwrap()).unwrap()
+        };
+        let before = info(&deps);
+        assert_eq!(before.protocol_versions, vec![CURRENT_PROTOCOL_VERSION]);
+        assert_eq!(before.node_key_types, vec![KeyType::Secp256k1, KeyType::Bls]);
+        assert_eq!(before.limits.partial_signature_len, PARTIAL_SIGNATURE_LEN as u32);
+        assert!(before.domain_tags.contains(&"repoint".to_string()));
+        assert!(before.domain_tags.contains(&VOTE_TAG.to_string()));
+
+        // Pinning a deployment back to v1 changes what is accepted and what is reported
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            supported_protocol_versions: Some(vec![1]),
+            ..Default::default()
+        })).unwrap();
+        assert_eq!(info(&deps).protocol_versions, vec![1]);
+
+        let err = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Unsupported protocol version 2 (supported: 1)"));
+        let mut transition = test_transition("alice", 1, 3);
+        transition.protocol_version = 1;
+        submit(&mut deps, &env, &transition).unwrap();
+    }
+
+    #[test]
+    fn test_circuit_breaker_pauses_on_rejection_burst() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            reject_threshold: Some(2),
+            breaker_window: Some(10),
+            breaker_max_rejections: Some(2),
+            ..Default::default()
+        })).unwrap();
+        let reject = |deps: &mut MockDeps, env: &Env, id: &str| {
+            vote(deps, env, 2, id, false).unwrap();
+            vote(deps, env, 1, id, false)
+        };
+
+        // Withdrawals are not verdicts and never count
+        for user in ["gina", "hank", "ivan"] {
+            let id = submit(&mut deps, &env, &test_transition(user, 7, 3)).unwrap();
+            execute(deps.as_mut(), env.clone(), mock_info(user, &[]), ExecuteMsg::CancelTransition { validation_id: id }).unwrap();
+        }
+        assert!(RECENT_REJECTIONS.may_load(&deps.storage).unwrap().is_none());
+
+        // Two rejections, then one that has aged out of the window: no trip
+        for (i, user) in ["alice", "bob"].iter().enumerate() {
+            let id = submit(&mut deps, &env, &test_transition(user, i as u8 + 1, 3)).unwrap();
+            let res = reject(&mut deps, &env, &id).unwrap();
+            assert!(res.events.is_empty());
+        }
+        env.block.height += 10;
+        let id = submit(&mut deps, &env, &test_transition("carol", 3, 3)).unwrap();
+        assert!(reject(&mut deps, &env, &id).unwrap().events.is_empty());
+
+        // Repeat rejections of one user count once
+        env.block.height += 1;
+        for nonce in [11, 12, 13] {
+            let id = submit(&mut deps, &env, &test_transition("carol", nonce, 3)).unwrap();
+            assert!(reject(&mut deps, &env, &id).unwrap().events.is_empty());
+        }
+
+        // Two more users inside the window make three
+        let id = submit(&mut deps, &env, &test_transition("dave", 4, 3)).unwrap();
+        assert!(reject(&mut deps, &env, &id).unwrap().events.is_empty());
+        let id = submit(&mut deps, &env, &test_transition("erin", 5, 3)).unwrap();
+        let res = reject(&mut deps, &env, &id).unwrap();
+        let alert = res.events.iter().find(|e| e.ty == "circuit_breaker_tripped").unwrap();
+        assert!(alert.attributes.iter().any(|a| a.key == "rejections" && a.value == "3"));
+
+        let err = submit(&mut deps, &env, &test_transition("frank", 6, 3)).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Contract paused"));
+
+        // Only the owner resumes, and the count starts over
+        execute(deps.as_mut(), env.clone(), mock_info("node1", &[]), ExecuteMsg::Unpause {}).unwrap_err();
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::Unpause {}).unwrap();
+        let id = submit(&mut deps, &env, &test_transition("frank", 6, 3)).unwrap();
+        assert!(reject(&mut deps, &env, &id).unwrap().events.is_empty());
+    }
+
+    #[test]
+    fn test_subscribers_are_notified_on_settlement() {
+        let (mut deps, env) = setup_contract(2, 3);
+        use cosmwasm_std::{Coin, CosmosMsg, ReplyOn};
+        let code_hash = "ab".repeat(32);
+        let subscribe = |deps: &mut MockDeps, sender: &str, validation_id: &str, funds: &[Coin]| execute(
+            deps.as_mut(), env.clone(), mock_info(sender, funds),
+            ExecuteMsg::SubscribeValidation {
+                validation_id: validation_id.to_string(),
+                subscriber: "indexer".to_string(),
+                code_hash: code_hash.clone(),
+            },
+        );
+        let notified = |res: &Response| -> Vec<(String, OutcomeStatus)> {
+            res.messages.iter().filter_map(|sub| match &sub.msg {
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+                    assert_eq!((sub.id, sub.reply_on.clone(), sub.gas_limit), (SUBSCRIBER_REPLY_ID, ReplyOn::Error, Some(SUBSCRIBER_GAS_LIMIT)));
+                    let SubscriberMsg::ValidationSettled { outcome } = cosmwasm_std::from_binary(msg).unwrap();
+                    Some((contract_addr.clone(), outcome.status))
+                }
+                _ => None,
+            }).collect()
+        };
+
+        let accepted = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let rejected = submit(&mut deps, &env, &test_transition("bob", 2, 3)).unwrap();
+        let err = subscribe(&mut deps, "alice", &accepted, &[]).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Subscriptions need a configured fee"));
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            reject_threshold: Some(2),
+            subscription_fee: Some(Coin::new(10, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+
+        let err = subscribe(&mut deps, "alice", &accepted, &[Coin::new(9, "uscrt")]).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Subscribing costs exactly 10uscrt"));
+        let err = subscribe(&mut deps, "broker", &accepted, &[Coin::new(10, "uscrt")]).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Only the validation's parties may subscribe"));
+        subscribe(&mut deps, "alice", &accepted, &[Coin::new(10, "uscrt")]).unwrap();
+        subscribe(&mut deps, "bob", &rejected, &[Coin::new(10, "uscrt")]).unwrap();
+        let err = subscribe(&mut deps, "bob", &rejected, &[Coin::new(10, "uscrt")]).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Already subscribed"));
+        assert_eq!(validation_status(&deps, &env, &accepted).subscribers[0].address, "indexer");
+
+        // Votes that do not settle send nothing; the one that does is paid
+        // the fee for running the callback
+        assert!(vote(&mut deps, &env, 1, &accepted, true).unwrap().messages.is_empty());
+        let res = vote(&mut deps, &env, 2, &accepted, true).unwrap();
+        assert_eq!(notified(&res), vec![("indexer".to_string(), OutcomeStatus::Finalized)]);
+        assert_eq!(bank_sends(&res), vec![("node2".to_string(), vec![Coin::new(10, "uscrt")])]);
+        assert!(vote(&mut deps, &env, 2, &rejected, false).unwrap().messages.is_empty());
+        let res = vote(&mut deps, &env, 1, &rejected, false).unwrap();
+        assert_eq!(notified(&res), vec![("indexer".to_string(), OutcomeStatus::Rejected)]);
+        assert_eq!(bank_sends(&res), vec![("node1".to_string(), vec![Coin::new(10, "uscrt")])]);
+
+        // Gone with the pending entry, and a failing subscriber is only noted
+        assert!(validation_status(&deps, &env, &accepted).subscribers.is_empty());
+        assert!(SUBSCRIPTION_FEES.get(&deps.storage, &accepted).is_none());
+        assert!(SETTLED_VALIDATIONS.may_load(&deps.storage).unwrap().is_none());
+        let res = reply(deps.as_mut(), env.clone(), Reply { id: SUBSCRIBER_REPLY_ID, result: SubMsgResult::Err("out of gas".to_string()) }).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "action" && a.value == "subscriber_failed"));
+    }
+
+    #[test]
+    fn test_lifetime_node_count_counts_addresses_once() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let count = |deps: &MockDeps| -> LifetimeNodeCountResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::GetLifetimeNodeCount {}).unwrap()).unwrap()
+        };
+        assert_eq!(count(&deps), LifetimeNodeCountResponse { registered: 3, active: 3 });
+
+        let key = compressed_public_key(&node_signing_key(3));
+        let digest = deregistration_digest(env.contract.address.as_str(), 3, &key);
+        execute(deps.as_mut(), env.clone(), mock_info("node3", &[]),
+            ExecuteMsg::DeregisterMPCNode { signature: sign_digest(&node_signing_key(3), &digest) }).unwrap();
+        assert_eq!(count(&deps), LifetimeNodeCountResponse { registered: 3, active: 2 });
+
+        // Coming back, even with a staged key, is not a new registration
+        stage_key(&mut deps, &env, 3, 9, 9).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("node3", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(9)) }).unwrap();
+        assert_eq!(count(&deps), LifetimeNodeCountResponse { registered: 3, active: 3 });
+
+        execute(deps.as_mut(), env.clone(), mock_info("node4", &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(4)) }).unwrap();
+        assert_eq!(count(&deps), LifetimeNodeCountResponse { registered: 4, active: 4 });
+    }
+
+    #[test]
+    fn test_is_node_reports_registration_and_activity() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let is_node = |deps: &MockDeps, address: &str| -> IsNodeResponse {
+            let msg = QueryMsg::IsNode { address: address.to_string() };
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(is_node(&deps, "node2"), IsNodeResponse { is_registered: true, is_active: true, node_id: Some(2) });
+        assert_eq!(is_node(&deps, "stranger"), IsNodeResponse { is_registered: false, is_active: false, node_id: None });
+
+        let key = compressed_public_key(&node_signing_key(3));
+        let digest = deregistration_digest(env.contract.address.as_str(), 3, &key);
+        execute(deps.as_mut(), env.clone(), mock_info("node3", &[]),
+            ExecuteMsg::DeregisterMPCNode { signature: sign_digest(&node_signing_key(3), &digest) }).unwrap();
+        assert_eq!(is_node(&deps, "node3"), IsNodeResponse { is_registered: true, is_active: false, node_id: Some(3) });
+    }
+
+    #[test]
+    fn test_contradicting_votes_on_resubmitted_content_are_equivocation() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            equivocation_limit: Some(2),
+            ..Default::default()
+        })).unwrap();
+        let equivocations = |deps: &MockDeps| -> EquivocationsResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::ListEquivocations { node_id: 1 }).unwrap()).unwrap()
+        };
+
+        // Voting the same way again on identical content is fine
+        let transition = test_transition("alice", 1, 3);
+        let first = submit(&mut deps, &env, &transition).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        cancel(&mut deps, &env, "alice", &first).unwrap();
+        env.block.height += 1;
+        let second = submit(&mut deps, &env, &transition).unwrap();
+        assert!(vote(&mut deps, &env, 1, &second, true).unwrap().events.is_empty());
+        cancel(&mut deps, &env, "alice", &second).unwrap();
+
+        // Changing its mind on the same bytes is recorded
+        env.block.height += 1;
+        let third = submit(&mut deps, &env, &transition).unwrap();
+        let res = vote(&mut deps, &env, 1, &third, false).unwrap();
+        let event = res.events.iter().find(|e| e.ty == "equivocation").unwrap();
+        assert!(event.attributes.iter().any(|a| a.key == "first_validation_id" && a.value == second));
+        assert!(event.attributes.iter().any(|a| a.key == "deactivated" && a.value == "false"));
+        let listed = equivocations(&deps);
+        assert_eq!(listed.offenses, 1);
+        assert_eq!(listed.equivocations[0].first.validation_id, second);
+        assert_eq!((listed.equivocations[0].second.validation_id.clone(), listed.equivocations[0].second.valid), (third.clone(), false));
+
+        // Other content does not count; a second offense reaches the limit
+        let other = submit(&mut deps, &env, &test_transition("bob", 2, 3)).unwrap();
+        assert!(vote(&mut deps, &env, 1, &other, false).unwrap().events.is_empty());
+        cancel(&mut deps, &env, "alice", &third).unwrap();
+        env.block.height += 1;
+        let fourth = submit(&mut deps, &env, &transition).unwrap();
+        let res = vote(&mut deps, &env, 1, &fourth, true).unwrap();
+        let event = res.events.iter().find(|e| e.ty == "equivocation").unwrap();
+        assert!(event.attributes.iter().any(|a| a.key == "deactivated" && a.value == "true"));
+        assert_eq!(equivocations(&deps).offenses, 2);
+        assert!(!get_node(&STATE.load(&deps.storage).unwrap(), 1).unwrap().active);
+    }
+
+    #[test]
+    fn test_stale_resubmission_after_the_user_moved_is_not_equivocation() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            equivocation_limit: Some(1),
+            ..Default::default()
+        })).unwrap();
+
+        let stale = test_transition("alice", 1, 3);
+        let first = submit(&mut deps, &env, &stale).unwrap();
+        vote(&mut deps, &env, 1, &first, true).unwrap();
+        cancel(&mut deps, &env, "alice", &first).unwrap();
+
+        // alice's root moves on, then the old transition comes back
+        env.block.height += 1;
+        let moved = test_transition("alice", 2, 3);
+        let id = submit(&mut deps, &env, &moved).unwrap();
+        drive_to_threshold(&mut deps, &env, &id, &moved, 2).unwrap();
+        env.block.height += 1;
+        let resubmitted = submit(&mut deps, &env, &stale).unwrap();
+        assert!(vote(&mut deps, &env, 1, &resubmitted, false).unwrap().events.is_empty());
+        let node = get_node(&STATE.load(&deps.storage).unwrap(), 1).unwrap().clone();
+        assert_eq!((node.offenses, node.active), (0, true));
+    }
+
+    #[test]
+    fn test_schema_5_migration_keeps_equivocation_evidence() {
+        use crate::state::LegacyEquivocationEvidence;
+        let (mut deps, env) = setup_contract(2, 3);
+        let legacy = LegacyContentVote { content_hash: vec![1; 32], validation_id: "v1".to_string(), valid: true, voted_at: 5 };
+        LEGACY_EQUIVOCATIONS.insert(&mut deps.storage, &1, &vec![LegacyEquivocationEvidence {
+            node_id: 1,
+            content_hash: vec![1; 32],
+            first: legacy.clone(),
+            second: LegacyContentVote { validation_id: "v2".to_string(), valid: false, voted_at: 6, ..legacy },
+        }]).unwrap();
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &4).unwrap();
+        migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: None }).unwrap();
+
+        let evidence = EQUIVOCATIONS.get(&deps.storage, &1).unwrap();
+        assert_eq!((evidence[0].first.validation_id.as_str(), evidence[0].second.valid), ("v1", false));
+        assert!(evidence[0].second.committed_root.is_empty());
+    }
+
+    #[test]
+    fn test_expiring_a_stale_validation_pays_its_bounty() {
+        use cosmwasm_std::coins;
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            expiry_bounty: Some(Coin::new(5, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        let submit_paying = |deps: &mut MockDeps, env: &Env, transition: StateTransition, funds: &[Coin]| execute(
+            deps.as_mut(), env.clone(), mock_info(&transition.user_address.clone(), funds),
+            ExecuteMsg::SubmitStateTransition { transition },
+        );
+        for funds in [coins(4, "uscrt"), coins(6, "uscrt"), vec![Coin::new(5, "uscrt"), Coin::new(1, "uatom")]] {
+            let err = submit_paying(&mut deps, &env, test_transition("alice", 1, 3), &funds).unwrap_err();
+            assert_eq!(err, StdError::generic_err("Submitting requires a 5uscrt expiry bounty per validation, 5uscrt in all"));
         }
-        QueryMsg::GetStateCommitment { user_address } => {
-            let commitment = STATE_COMMITMENTS.get(deps.storage, &user_address)
-                .ok_or_else(|| StdError::generic_err("Commitment not found"))?;
-            to_binary(&StateCommitmentResponse { commitment })
+        submit_paying(&mut deps, &env, test_transition("alice", 1, 3), &coins(5, "uscrt")).unwrap();
+        let stale = derive_validation_id(&deps.storage, &env, "alice").unwrap();
+        submit_paying(&mut deps, &env, test_transition("bob", 2, 3), &coins(5, "uscrt")).unwrap();
+        let settled = derive_validation_id(&deps.storage, &env, "bob").unwrap();
+
+        let expire = |deps: &mut MockDeps, env: &Env| execute(
+            deps.as_mut(), env.clone(), mock_info("keeper", &[]),
+            ExecuteMsg::ExpireValidation { validation_id: stale.clone() },
+        );
+        let deadline = env.block.time.seconds() + CONFIG.load(&deps.storage).unwrap().validation_timeout;
+        assert_eq!(expire(&mut deps, &env).unwrap_err(), StdError::generic_err(format!("Validation expires at {}", deadline)));
+
+        // Settling normally hands the deposit back to the submitter
+        vote(&mut deps, &env, 1, &settled, true).unwrap();
+        let res = vote(&mut deps, &env, 2, &settled, true).unwrap();
+        assert_eq!(bank_sends(&res), vec![("bob".to_string(), coins(5, "uscrt"))]);
+
+        // Past the deadline anyone may prune it, and only they are paid
+        env.block.time = env.block.time.plus_seconds(deadline - env.block.time.seconds());
+        let res = expire(&mut deps, &env).unwrap();
+        assert_eq!(bank_sends(&res), vec![("keeper".to_string(), coins(5, "uscrt"))]);
+        assert_eq!(outcome_of(&deps, &stale).status, OutcomeStatus::Expired);
+        assert_eq!(expire(&mut deps, &env).unwrap_err(), StdError::generic_err("Validation already expired"));
+    }
+
+    #[test]
+    fn test_every_transfer_leg_holds_an_expiry_bounty() {
+        use cosmwasm_std::coins;
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            expiry_bounty: Some(Coin::new(5, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        let transfer = || Transfer {
+            sender: "alice".to_string(),
+            recipient: "bob".to_string(),
+            sender_transition: test_transition("alice", 2, 3),
+            recipient_transition: test_transition("bob", 3, 3),
+            amount_commitment: vec![9; 32],
+        };
+        let send = |deps: &mut MockDeps, funds: &[Coin]| execute(deps.as_mut(), env.clone(), mock_info("alice", funds), ExecuteMsg::SubmitTransfer {
+            transfer: transfer(),
+        });
+        assert_eq!(
+            send(&mut deps, &coins(5, "uscrt")).unwrap_err(),
+            StdError::generic_err("Submitting requires a 5uscrt expiry bounty per validation, 10uscrt in all"),
+        );
+        send(&mut deps, &coins(10, "uscrt")).unwrap();
+        for user in ["alice", "bob"] {
+            let validation_id = derive_validation_id(&deps.storage, &env, user).unwrap();
+            assert_eq!(load_pending(&deps.storage, &validation_id).unwrap().bounty, Some(Coin::new(5, "uscrt")));
         }
-        QueryMsg::GetValidation { validation_id } => {
-            let validation = PENDING_VALIDATIONS.get(deps.storage, &validation_id)
-                .ok_or_else(|| StdError::generic_err("Not found"))?;
-            to_binary(&ValidationResponse { validation })
+
+        // Without a bounty configured, funds sent along would be stranded
+        let (mut deps, env) = setup_contract(2, 3);
+        let err = execute(deps.as_mut(), env, mock_info("alice", &coins(5, "uscrt")), ExecuteMsg::SubmitStateTransition {
+            transition: test_transition("alice", 2, 3),
+        }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Submitting takes no funds"));
+    }
+
+    #[test]
+    fn test_failed_bounty_send_is_parked_for_retry() {
+        use cosmwasm_std::{coins, ReplyOn, SubMsgResponse};
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            expiry_bounty: Some(Coin::new(5, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        for user in ["alice", "bob"] {
+            execute(deps.as_mut(), env.clone(), mock_info(user, &coins(5, "uscrt")), ExecuteMsg::SubmitStateTransition {
+                transition: test_transition(user, 1, 3),
+            }).unwrap();
         }
-        QueryMsg::GetCurrentRoot {} => {
-            let state = STATE.load(deps.storage)?;
-            to_binary(&CurrentRootResponse { root: state.current_state_root })
+        let alice = derive_validation_id(&deps.storage, &env, "alice").unwrap();
+        let bob = derive_validation_id(&deps.storage, &env, "bob").unwrap();
+
+        // The refund replies whatever happens, so its failure cannot revert the finalization
+        vote(&mut deps, &env, 1, &alice, true).unwrap();
+        let res = vote(&mut deps, &env, 2, &alice, true).unwrap();
+        let refund = res.messages.iter().find(|sub| sub.id > OUTBOUND_REPLY_BASE).unwrap();
+        assert_eq!(refund.reply_on, ReplyOn::Always);
+        let res = reply(deps.as_mut(), env.clone(), Reply {
+            id: refund.id,
+            result: SubMsgResult::Err("recipient blocked".to_string()),
+        }).unwrap();
+        assert!(res.attributes.iter().any(|a| a.key == "action" && a.value == "outbound_failed"));
+        let parked = OUTBOUND_FAILURES.get(&deps.storage, &(refund.id - OUTBOUND_REPLY_BASE)).unwrap();
+        assert_eq!((parked.recipient.as_str(), parked.origin.as_str(), parked.attempts), ("alice", alice.as_str(), 1));
+        assert_eq!(parked.last_error.as_deref(), Some("recipient blocked"));
+        assert_eq!(outcome_of(&deps, &alice).status, OutcomeStatus::Finalized);
+
+        // A delivered payout leaves nothing behind
+        env.block.time = env.block.time.plus_seconds(CONFIG.load(&deps.storage).unwrap().validation_timeout);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), ExecuteMsg::ExpireValidation {
+            validation_id: bob,
+        }).unwrap();
+        let payout = &res.messages[0];
+        assert_eq!(payout.reply_on, ReplyOn::Always);
+        reply(deps.as_mut(), env, Reply {
+            id: payout.id,
+            result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }),
+        }).unwrap();
+        let id = payout.id - OUTBOUND_REPLY_BASE;
+        assert!(OUTBOUND_IN_FLIGHT.get(&deps.storage, &id).is_none());
+        assert!(OUTBOUND_FAILURES.get(&deps.storage, &id).is_none());
+    }
+
+    #[test]
+    fn test_failed_sends_retry_until_capped_then_redirect() {
+        use cosmwasm_std::{coins, BankMsg, CosmosMsg, SubMsgResponse};
+        let (mut deps, env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            expiry_bounty: Some(Coin::new(5, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &coins(5, "uscrt")), ExecuteMsg::SubmitStateTransition {
+            transition: test_transition("alice", 1, 3),
+        }).unwrap();
+        let validation_id = derive_validation_id(&deps.storage, &env, "alice").unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        let res = vote(&mut deps, &env, 2, &validation_id, true).unwrap();
+        let reply_id = res.messages.iter().find(|sub| sub.id > OUTBOUND_REPLY_BASE).unwrap().id;
+        let id = reply_id - OUTBOUND_REPLY_BASE;
+        let fail = |deps: &mut MockDeps| reply(deps.as_mut(), env.clone(), Reply {
+            id: reply_id,
+            result: SubMsgResult::Err("recipient blocked".to_string()),
+        }).unwrap();
+        let retry = |deps: &mut MockDeps| execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::RetryOutbound { id });
+        let listed = |deps: &MockDeps| -> OutboundFailuresResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::ListOutboundFailures {
+                start_after: None,
+                limit: None,
+            }).unwrap()).unwrap()
+        };
+        fail(&mut deps);
+        assert_eq!(listed(&deps).failures.iter().map(|f| (f.id, f.send.attempts)).collect::<Vec<_>>(), vec![(id, 1)]);
+
+        // Anyone may resend it under the same id until the cap
+        for attempt in 2..=MAX_SEND_ATTEMPTS {
+            let res = retry(&mut deps).unwrap();
+            assert_eq!(res.messages[0].id, reply_id);
+            assert!(listed(&deps).failures.is_empty());
+            fail(&mut deps);
+            assert_eq!(listed(&deps).failures[0].send.attempts, attempt);
         }
-        QueryMsg::ListPendingValidations {} => {
-            // Iterate through all pending validations
-            let validation_ids: Vec<String> = PENDING_VALIDATIONS
-                .iter(deps.storage)?
-                .map(|item| {
-                    let (key, _) = item?;
-                    Ok(key)
-                })
-                .collect::<StdResult<Vec<String>>>()?;
-            to_binary(&PendingValidationsResponse { validation_ids })
+        assert_eq!(
+            retry(&mut deps).unwrap_err(),
+            StdError::generic_err(format!("Send {} failed {} times; only RedirectOutbound can release it", id, MAX_SEND_ATTEMPTS)),
+        );
+
+        // Only the owner may send it elsewhere; delivery drains the queue
+        let redirect = |deps: &mut MockDeps, sender: &str| execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), ExecuteMsg::RedirectOutbound {
+            id,
+            new_recipient: "treasury".to_string(),
+        });
+        assert_eq!(redirect(&mut deps, "alice").unwrap_err(), StdError::generic_err("Unauthorized"));
+        let res = redirect(&mut deps, "creator").unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(5, "uscrt"),
+        }));
+        reply(deps.as_mut(), env.clone(), Reply {
+            id: reply_id,
+            result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }),
+        }).unwrap();
+        assert!(listed(&deps).failures.is_empty());
+        assert_eq!(retry(&mut deps).unwrap_err(), StdError::generic_err(format!("No failed send {}", id)));
+    }
+
+    #[test]
+    fn test_parked_sends_page_in_id_order_up_to_the_cap() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let fail = |deps: &mut MockDeps, id: u64| {
+            OUTBOUND_IN_FLIGHT.insert(&mut deps.storage, &id, &OutboundSend {
+                recipient: "alice".to_string(),
+                amount: Coin::new(5, "uscrt"),
+                origin: format!("v{}", id),
+                attempts: 1,
+                last_error: None,
+            }).unwrap();
+            reply(deps.as_mut(), env.clone(), Reply {
+                id: OUTBOUND_REPLY_BASE + id,
+                result: SubMsgResult::Err("recipient blocked".to_string()),
+            })
+        };
+        let page = |deps: &MockDeps, start_after: Option<u64>| -> OutboundFailuresResponse {
+            cosmwasm_std::from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::ListOutboundFailures {
+                start_after,
+                limit: Some(2),
+            }).unwrap()).unwrap()
+        };
+
+        for id in [4, 2, 9, 1] {
+            fail(&mut deps, id).unwrap();
+        }
+        execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::RetryOutbound { id: 2 }).unwrap();
+        let first = page(&deps, None);
+        assert_eq!(first.failures.iter().map(|f| f.id).collect::<Vec<_>>(), vec![1, 4]);
+        let second = page(&deps, first.next_start_after);
+        assert_eq!((second.failures.iter().map(|f| f.id).collect::<Vec<_>>(), second.next_start_after), (vec![9], None));
+
+        for id in 100..(100 + MAX_OUTBOUND_FAILURES as u64 - 3) {
+            fail(&mut deps, id).unwrap();
         }
+        assert_eq!(
+            fail(&mut deps, 1_000).unwrap_err(),
+            StdError::generic_err(format!("{} failed sends are parked; retry or redirect them first", MAX_OUTBOUND_FAILURES)),
+        );
     }
-}
 
-// ============================================================================
-// HELPER FUNCTIONS
-// ============================================================================
+    #[test]
+    fn test_bounty_sends_leave_receipts() {
+        use cosmwasm_std::{coins, SubMsgResponse};
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            expiry_bounty: Some(Coin::new(5, "uscrt")),
+            ..Default::default()
+        })).unwrap();
+        for user in ["alice", "bob"] {
+            execute(deps.as_mut(), env.clone(), mock_info(user, &coins(5, "uscrt")), ExecuteMsg::SubmitStateTransition {
+                transition: test_transition(user, 1, 3),
+            }).unwrap();
+        }
+        let alice = derive_validation_id(&deps.storage, &env, "alice").unwrap();
+        let bob = derive_validation_id(&deps.storage, &env, "bob").unwrap();
+        for user in ["alice", "keeper"] {
+            execute(deps.as_mut(), env.clone(), mock_info(user, &[]), ExecuteMsg::SetViewingKey { key: format!("{}-key", user) }).unwrap();
+        }
+        let query_env = env.clone();
+        let lookup = |deps: &MockDeps, viewer: &str, validation_id: &str| -> Option<WithdrawalReceipt> {
+            let auth = ViewerAuth { address: viewer.to_string(), viewing_key: format!("{}-key", viewer) };
+            let res = query(deps.as_ref(), query_env.clone(), QueryMsg::GetWithdrawalReceipt { auth, validation_id: validation_id.to_string() }).unwrap();
+            cosmwasm_std::from_binary::<WithdrawalReceiptResponse>(&res).unwrap().receipt
+        };
+        let send_reply = |deps: &mut MockDeps, env: &Env, id: u64, result: SubMsgResult| {
+            reply(deps.as_mut(), env.clone(), Reply { id, result }).unwrap();
+        };
 
-fn verify_user_signature(transition: &StateTransition) -> bool {
-    // In production: verify ECDSA/EdDSA signature
-    // For POC: simplified check
-    !transition.user_signature.is_empty()
-}
+        // A failed refund points at the queue entry holding the funds
+        vote(&mut deps, &env, 1, &alice, true).unwrap();
+        let res = vote(&mut deps, &env, 2, &alice, true).unwrap();
+        let reply_id = res.messages.iter().find(|sub| sub.id > OUTBOUND_REPLY_BASE).unwrap().id;
+        send_reply(&mut deps, &env, reply_id, SubMsgResult::Err("recipient blocked".to_string()));
+        let failed = lookup(&deps, "alice", &alice).unwrap();
+        assert_eq!(lookup(&deps, "keeper", &alice), None);
+        assert_eq!(failed, WithdrawalReceipt {
+            validation_id: alice.clone(),
+            recipient: "alice".to_string(),
+            amount: Coin::new(5, "uscrt"),
+            send_id: reply_id - OUTBOUND_REPLY_BASE,
+            status: SendStatus::Failed,
+            error: Some("recipient blocked".to_string()),
+        });
+        assert!(OUTBOUND_FAILURES.get(&deps.storage, &failed.send_id).is_some());
 
-fn aggregate_signatures(validations: &[NodeValidation]) -> Vec<u8> {
-    // Aggregate TSS partial signatures into threshold signature
-    // In production: proper BLS aggregation
-    // For POC: concatenate
-    validations.iter()
-        .flat_map(|v| v.partial_signature.clone())
-        .collect()
-}
+        // Once a retry goes through, the same receipt reads delivered
+        execute(deps.as_mut(), env.clone(), mock_info("anyone", &[]), ExecuteMsg::RetryOutbound { id: failed.send_id }).unwrap();
+        send_reply(&mut deps, &env, reply_id, SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }));
+        assert_eq!(lookup(&deps, "alice", &alice).unwrap().status, SendStatus::Delivered);
 
-fn serialize_merkle_proof(proof: &[MerkleProofElement]) -> Vec<u8> {
-    // Serialize proof for storage
-    proof.iter()
-        .flat_map(|e| e.hash.clone())
-        .collect()
-}
+        // A payout is receipted to whoever collected it, and listed only to them
+        env.block.time = env.block.time.plus_seconds(CONFIG.load(&deps.storage).unwrap().validation_timeout);
+        let res = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), ExecuteMsg::ExpireValidation {
+            validation_id: bob.clone(),
+        }).unwrap();
+        send_reply(&mut deps, &env, res.messages[0].id, SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }));
+        let paid = lookup(&deps, "keeper", &bob).unwrap();
+        assert_eq!((paid.recipient.as_str(), paid.status, paid.error), ("keeper", SendStatus::Delivered, None));
 
-// ============================================================================
-// MPC VALIDATION LOGIC (Reference for off-chain nodes)
-// ============================================================================
+        let list = |deps: &MockDeps, viewing_key: &str| query(deps.as_ref(), env.clone(), QueryMsg::ListWithdrawalReceipts {
+            auth: ViewerAuth { address: "keeper".to_string(), viewing_key: viewing_key.to_string() },
+            start_after: None,
+            limit: None,
+        });
+        let listed: WithdrawalReceiptsResponse = cosmwasm_std::from_binary(&list(&deps, "keeper-key").unwrap()).unwrap();
+        assert_eq!(listed.receipts.iter().map(|r| r.validation_id.clone()).collect::<Vec<_>>(), vec![bob]);
+        list(&deps, "guess").unwrap_err();
+    }
 
-/// This runs OFF-CHAIN on each MPC node when they receive validation request
-#[allow(dead_code)]
-pub fn hash_shares(balance: i64, nonce: i64) -> Vec<u8> {
-    let mut hasher = Sha256::new();
-    hasher.update(balance.to_le_bytes());
-    hasher.update(nonce.to_le_bytes());
-    hasher.finalize().to_vec()
-}
+    /// Every hashed field set to something distinct. Never edit it: the
+    /// frozen hash below is what clients were told to reproduce.
+    fn content_hash_fixture() -> StateTransition {
+        StateTransition {
+            user_address: "secret1alice".to_string(),
+            old_state_root: vec![0x11; 32],
+            new_state_root: vec![0x22; 32],
+            merkle_proof: vec![MerkleProofElement { hash: vec![0x33; 32], is_left: true }],
+            new_state_ipfs: "QmFixture".to_string(),
+            ipfs_gateway_hint: Some("https://ipfs.io".to_string()),
+            content_hash: vec![0x44; 32],
+            user_signature: vec![0x55; 64],
+            encrypted_shares: vec![
+                EncryptedShares { node_id: 1, encrypted_data: vec![0x61, 0x62], encoding: ShareEncoding::Raw },
+                EncryptedShares { node_id: 2, encrypted_data: vec![0x63], encoding: ShareEncoding::Zstd },
+            ],
+            vss_commitments: vec![vec![0x71], vec![0x72]],
+            vss_proof_polynomial: vec!["0a".to_string(), "0b".to_string()],
+            protocol_version: 1,
+            coordinator_metadata: None,
+            depends_on: vec!["7-bob".to_string()],
+            asset_updates: vec![],
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::testing::*;
-    use crate::state::EncryptedShares;
+    #[test]
+    fn test_content_hash_matches_frozen_fixture() {
+        let fixture = content_hash_fixture();
+        let bytes = crate::protocol::canonical_transition_bytes(&fixture).unwrap();
+        // Tag first, length-prefixed with a little-endian u64
+        assert_eq!(&bytes[..8], &(crate::protocol::TRANSITION_TAG.len() as u64).to_le_bytes());
+        assert_eq!(&bytes[8..8 + crate::protocol::TRANSITION_TAG.len()], crate::protocol::TRANSITION_TAG.as_bytes());
+        assert_eq!(hex::encode(transition_content_hash(&fixture).unwrap()), "09262feb31cb7318a97bb4dbde506501a08e6a0136a12f8e7824848af2862cec");
+    }
 
     #[test]
-    fn test_complete_mpc_flow() {
-        let mut deps = mock_dependencies();
-        let env = mock_env();
+    fn test_content_hash_covers_every_field_but_the_signature() {
+        let fixture = content_hash_fixture();
+        let hash = transition_content_hash(&fixture).unwrap();
+        type Change = fn(&mut StateTransition);
+        let changes: Vec<(&str, Change)> = vec![
+            ("user_address", |t| t.user_address.push('x')),
+            ("old_state_root", |t| t.old_state_root[0] ^= 1),
+            ("new_state_root", |t| t.new_state_root[31] ^= 1),
+            ("merkle_proof hash", |t| t.merkle_proof[0].hash[0] ^= 1),
+            ("merkle_proof side", |t| t.merkle_proof[0].is_left = false),
+            ("merkle_proof length", |t| t.merkle_proof.clear()),
+            ("new_state_ipfs", |t| t.new_state_ipfs.push('x')),
+            ("ipfs_gateway_hint", |t| t.ipfs_gateway_hint = None),
+            ("ipfs_gateway_hint empty", |t| t.ipfs_gateway_hint = Some(String::new())),
+            ("content_hash", |t| t.content_hash[0] ^= 1),
+            ("share data", |t| t.encrypted_shares[0].encrypted_data.push(0)),
+            ("share node", |t| t.encrypted_shares[1].node_id = 3),
+            ("share encoding", |t| t.encrypted_shares[1].encoding = ShareEncoding::Deflate),
+            ("vss_commitments", |t| t.vss_commitments[1][0] ^= 1),
+            ("vss_proof_polynomial", |t| t.vss_proof_polynomial.pop().map(|_| ()).unwrap()),
+            ("protocol_version", |t| t.protocol_version = 2),
+            ("depends_on", |t| t.depends_on.clear()),
+            ("asset_updates", |t| t.asset_updates.push(AssetUpdate { asset_id: "usd".to_string(), old_leaf: vec![], new_leaf: vec![0x81; 32] })),
+            // Length prefixes keep neighbouring fields from trading bytes
+            ("share boundary", |t| {
+                t.encrypted_shares[0].encrypted_data = vec![0x61];
+                t.encrypted_shares[1].encrypted_data = vec![0x62, 0x63];
+            }),
+            ("list boundary", |t| t.vss_proof_polynomial = vec!["0a0b".to_string()]),
+        ];
+        for (field, change) in changes {
+            let mut changed = fixture.clone();
+            change(&mut changed);
+            assert_ne!(transition_content_hash(&changed).unwrap(), hash, "{} is not covered", field);
+        }
 
-        // 1. Instantiate
-        instantiate(
+        let mut resigned = fixture.clone();
+        resigned.user_signature = vec![0x99; 64];
+        resigned.coordinator_metadata = Some(Binary::from(vec![1, 2, 3]));
+        assert_eq!(transition_content_hash(&resigned).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_content_hash_is_independent_of_share_order() {
+        let fixture = content_hash_fixture();
+        let mut shuffled = fixture.clone();
+        shuffled.encrypted_shares.reverse();
+        shuffled.vss_commitments.reverse();
+        assert_eq!(transition_content_hash(&shuffled).unwrap(), transition_content_hash(&fixture).unwrap());
+
+        // Swapping only the commitments is different content
+        let mut misaligned = fixture.clone();
+        misaligned.vss_commitments.reverse();
+        assert_ne!(transition_content_hash(&misaligned).unwrap(), transition_content_hash(&fixture).unwrap());
+
+        let mut duplicated = fixture;
+        duplicated.encrypted_shares[1].node_id = 1;
+        assert_eq!(transition_content_hash(&duplicated).unwrap_err(), StdError::generic_err("Duplicate share for node 1"));
+    }
+
+    #[test]
+    fn test_purge_stale_accounts_spares_active_ones() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        let purge = |deps: &mut MockDeps, env: &Env, limit: Option<u32>| execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::PurgeStaleAccounts { limit },
+        );
+        let purged = |res: &Response| -> Vec<String> {
+            res.events.iter()
+                .filter(|e| e.ty == "account_purged")
+                .flat_map(|e| e.attributes.iter().filter(|a| a.key == "user").map(|a| a.value.clone()))
+                .collect()
+        };
+        assert_eq!(purge(&mut deps, &env, None).unwrap_err(), StdError::generic_err("Account purging is disabled"));
+        execute(
             deps.as_mut(),
             env.clone(),
             mock_info("creator", &[]),
-            InstantiateMsg { threshold: 2 }
+            ExecuteMsg::UpdateConfig(ConfigUpdate { account_ttl_seconds: Some(100), ..Default::default() }),
         ).unwrap();
 
-        // 2. Register MPC nodes
-        for i in 1..=3 {
-            execute(
-                deps.as_mut(),
-                env.clone(),
-                mock_info(&format!("node{}", i), &[]),
-                ExecuteMsg::RegisterMPCNode {
-                    public_key: vec![i; 32],
-                }
-            ).unwrap();
+        // carol and dave never submit; alice has a commitment, bob a pending validation
+        for user in ["alice", "bob", "carol", "dave"] {
+            register_user_key(&mut deps, &env, user).unwrap();
         }
+        let alice = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        vote(&mut deps, &env, 1, &alice, true).unwrap();
+        vote(&mut deps, &env, 2, &alice, true).unwrap();
+        submit(&mut deps, &env, &test_transition("bob", 2, 3)).unwrap();
 
-        // 3. User submits state transition
-        let transition = StateTransition {
-            user_address: "alice".to_string(),
+        // A leaf left behind by dave's reverted first commitment
+        let dave_leaf = leaf_index("dave", CONFIG.load(&deps.storage).unwrap().tree_depth);
+        LEAF_OWNERS.insert(&mut deps.storage, &dave_leaf, &"dave".to_string()).unwrap();
+
+        // Too early for anyone, and erin registers later
+        env.block.time = env.block.time.plus_seconds(60);
+        assert!(purged(&purge(&mut deps, &env, None).unwrap()).is_empty());
+        register_user_key(&mut deps, &env, "erin").unwrap();
+
+        // Bounded per call, then the rest
+        env.block.time = env.block.time.plus_seconds(40);
+        let first = purged(&purge(&mut deps, &env, Some(1)).unwrap());
+        assert_eq!(first.len(), 1);
+        let second = purged(&purge(&mut deps, &env, None).unwrap());
+        let mut all = [first, second].concat();
+        all.sort();
+        assert_eq!(all, vec!["carol".to_string(), "dave".to_string()]);
+        assert!(purged(&purge(&mut deps, &env, None).unwrap()).is_empty());
+
+        assert!(USER_KEYS.get(&deps.storage, &"carol".to_string()).is_none());
+        assert!(!LEAF_OWNERS.contains(&deps.storage, &dave_leaf));
+        for user in ["alice", "bob", "erin"] {
+            assert!(USER_KEYS.get(&deps.storage, &user.to_string()).is_some());
+        }
+
+        // Active accounts stay however long they sit idle
+        env.block.time = env.block.time.plus_seconds(1_000);
+        assert_eq!(purged(&purge(&mut deps, &env, None).unwrap()), vec!["erin".to_string()]);
+
+        // A purged user simply registers again and can claim the freed leaf
+        register_user_key(&mut deps, &env, "dave").unwrap();
+        assert!(ACCOUNT_REGISTRATIONS.contains(&deps.storage, &"dave".to_string()));
+        let dave = submit(&mut deps, &env, &test_transition("dave", 4, 3)).unwrap();
+        vote(&mut deps, &env, 1, &dave, true).unwrap();
+        vote(&mut deps, &env, 2, &dave, true).unwrap();
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"dave".to_string()).unwrap().leaf_index, dave_leaf);
+    }
+
+    #[test]
+    fn test_validation_closes_after_its_block_window() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            max_validation_blocks: Some(5),
+            ..Default::default()
+        })).unwrap();
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let submitted_at = env.block.height;
+        assert_eq!(load_pending(&deps.storage, &validation_id).unwrap().submission_height, submitted_at);
+        let expire = |deps: &mut MockDeps, env: &Env| execute(
+            deps.as_mut(), env.clone(), mock_info("keeper", &[]),
+            ExecuteMsg::ExpireValidation { validation_id: validation_id.clone() },
+        );
+
+        // The last block of the window still takes votes, however little time passed
+        env.block.height = submitted_at + 5;
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        assert!(expire(&mut deps, &env).is_err());
+
+        env.block.height += 1;
+        let err = vote(&mut deps, &env, 2, &validation_id, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation window closed"));
+        expire(&mut deps, &env).unwrap();
+        assert_eq!(outcome_of(&deps, &validation_id).status, OutcomeStatus::Expired);
+    }
+
+    #[test]
+    fn test_blocks_spent_paused_do_not_close_the_block_window() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            max_validation_blocks: Some(5),
+            ..Default::default()
+        })).unwrap();
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        let submitted_at = env.block.height;
+
+        // Paused for 10 blocks across the end of the window
+        env.block.height = submitted_at + 3;
+        set_paused(&mut deps, &env, true);
+        env.block.height = submitted_at + 13;
+        set_paused(&mut deps, &env, false);
+        assert_eq!(PAUSE_LEDGER.load(&deps.storage).unwrap()[0].paused_blocks, 10);
+
+        env.block.height = submitted_at + 15;
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]),
+            ExecuteMsg::ExpireValidation { validation_id: validation_id.clone() }).unwrap_err();
+        assert!(err.to_string().contains("Validation expires at"));
+
+        env.block.height += 1;
+        let err = vote(&mut deps, &env, 2, &validation_id, true).unwrap_err();
+        assert_eq!(err, StdError::generic_err("Validation window closed"));
+    }
+
+    #[test]
+    fn test_schema_6_migration_keeps_pause_windows() {
+        use crate::state::LegacyPauseWindow;
+        let (mut deps, env) = setup_contract(2, 3);
+        let closed = LegacyPauseWindow { paused_at: 10, resumed_at: Some(20), paused_for: 10 };
+        let open = LegacyPauseWindow { paused_at: 30, resumed_at: None, paused_for: 0 };
+        LEGACY_PAUSE_LEDGER.save(&mut deps.storage, &vec![closed, open]).unwrap();
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &5).unwrap();
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap();
+
+        let ledger = PAUSE_LEDGER.load(&deps.storage).unwrap();
+        assert_eq!((ledger[0].paused_for, ledger[0].resumed_at_height, ledger[0].paused_blocks), (10, Some(0), 0));
+        assert_eq!((ledger[1].paused_at, ledger[1].paused_at_height, ledger[1].resumed_at_height), (30, env.block.height, None));
+    }
+
+    #[test]
+    fn test_schema_7_migration_moves_config_to_json() {
+        use crate::state::LegacyConfig;
+        let (mut deps, env) = setup_contract(2, 3);
+        let config = CONFIG.load(&deps.storage).unwrap();
+        // Schema 6 still called the proof flag require_merkle_proof
+        let json = String::from_utf8(to_vec(&config).unwrap()).unwrap().replace("require_nonempty_merkle_proof", "require_merkle_proof");
+        let legacy: LegacyConfig = cosmwasm_std::from_slice(json.as_bytes()).unwrap();
+        LEGACY_CONFIG.save(&mut deps.storage, &legacy).unwrap();
+        assert!(CONFIG.load(&deps.storage).is_err());
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &6).unwrap();
+        migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: None }).unwrap();
+
+        let migrated = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(migrated, config);
+        assert_eq!(migrated.da_challenge_bond, None);
+    }
+
+    #[test]
+    fn test_schema_9_migration_indexes_parked_sends() {
+        let (mut deps, env) = setup_contract(2, 3);
+        for id in [7u64, 3] {
+            OUTBOUND_FAILURES.insert(&mut deps.storage, &id, &OutboundSend {
+                recipient: "alice".to_string(),
+                amount: Coin::new(5, "uscrt"),
+                origin: format!("v{}", id),
+                attempts: 1,
+                last_error: None,
+            }).unwrap();
+        }
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &8).unwrap();
+        migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: None }).unwrap();
+        assert_eq!(OUTBOUND_FAILURE_IDS.load(&deps.storage).unwrap(), vec![3, 7]);
+    }
+
+    #[test]
+    fn test_schema_10_migration_files_node_work() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        for node_id in 1..=3 {
+            NODE_WORK.remove(&mut deps.storage, &node_id).unwrap();
+        }
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &9).unwrap();
+        migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: None }).unwrap();
+        let work = NODE_WORK.get(&deps.storage, &2).unwrap();
+        assert_eq!(work.iter().map(|(_, id)| id).collect::<Vec<_>>(), vec![&validation_id]);
+    }
+
+    #[test]
+    fn test_schema_11_migration_indexes_pending_by_user() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let validation_id = submit(&mut deps, &env, &test_transition("alice", 1, 3)).unwrap();
+        USER_PENDING.add_suffix(b"alice").remove(&mut deps.storage, &validation_id).unwrap();
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &10).unwrap();
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap();
+        let res = overview(&deps, &env, "alice", None).unwrap();
+        assert_eq!((res.pending_total, res.pending[0].validation_id.clone()), (1, validation_id));
+    }
+
+    #[test]
+    fn test_schema_12_migration_gives_commitments_export_slots() {
+        let (mut deps, env) = setup_contract(1, 2);
+        for (i, user) in ["bob", "alice"].iter().enumerate() {
+            let validation_id = submit(&mut deps, &env, &test_transition(user, i as u8 + 2, 2)).unwrap();
+            vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        }
+        for user in ["bob", "alice"] {
+            unorder_commitment(&mut deps.storage, user).unwrap();
+        }
+        COMMITMENT_SEQ.remove(&mut deps.storage);
+        STORAGE_SCHEMA_VERSION.save(&mut deps.storage, &11).unwrap();
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap();
+
+        // Existing users are slotted by address, later ones after them
+        let validation_id = submit(&mut deps, &env, &test_transition("aaron", 4, 2)).unwrap();
+        vote(&mut deps, &env, 1, &validation_id, true).unwrap();
+        let auth = ViewerAuth { address: "creator".to_string(), viewing_key: "creator-key".to_string() };
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::SetViewingKey { key: auth.viewing_key.clone() }).unwrap();
+        let msg = QueryMsg::ExportSnapshot { auth, start_after: None, limit: None };
+        let page: SnapshotResponse = cosmwasm_std::from_binary(&query(deps.as_ref(), env, msg).unwrap()).unwrap();
+        assert_eq!(page.commitments.iter().map(|c| c.user_address.as_str()).collect::<Vec<_>>(), vec!["alice", "bob", "aaron"]);
+        assert_eq!(page.next_start_after, None);
+    }
+
+    #[test]
+    fn test_asset_updates_move_together_or_not_at_all() {
+        let (mut deps, mut env) = setup_contract(2, 3);
+        execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), ExecuteMsg::UpdateConfig(ConfigUpdate {
+            reject_threshold: Some(2),
+            ..Default::default()
+        })).unwrap();
+        let update = |asset_id: &str, old_leaf: Option<u8>, new_leaf: u8| AssetUpdate {
+            asset_id: asset_id.to_string(),
+            old_leaf: old_leaf.map(|b| vec![b; 32]).unwrap_or_default(),
+            new_leaf: vec![new_leaf; 32],
+        };
+        let leaves = |deps: &MockDeps| -> Vec<(String, u8)> {
+            STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap()
+                .asset_leaves.iter().map(|a| (a.asset_id.clone(), a.leaf[0])).collect()
+        };
+        let locked = |deps: &MockDeps, asset_id: &str| ASSET_LOCKS.get(&deps.storage, &asset_key("alice", asset_id));
+
+        // A swap of usd for eur, listed out of canonical order
+        let swap = StateTransition {
+            asset_updates: vec![update("usd", None, 1), update("eur", None, 2)],
+            ..test_transition("alice", 1, 3)
+        };
+        let swap_id = submit(&mut deps, &env, &swap).unwrap();
+        let pending = load_pending(&deps.storage, &swap_id).unwrap();
+        assert_eq!(pending.transition.asset_updates.iter().map(|u| u.asset_id.as_str()).collect::<Vec<_>>(), vec!["eur", "usd"]);
+
+        // One locked asset refuses the whole transition, taking no other lock
+        env.block.height += 1;
+        let rival = StateTransition {
+            asset_updates: vec![update("gbp", None, 5), update("usd", None, 5)],
+            ..test_transition("alice", 5, 3)
+        };
+        let err = submit(&mut deps, &env, &rival).unwrap_err();
+        assert_eq!(err, StdError::generic_err(format!("Asset usd is locked by validation {}", swap_id)));
+        assert!(locked(&deps, "gbp").is_none());
+
+        // Votes bind to the asset list, and both assets move in one finalization
+        let other_assets = vote_digest(&env.block.chain_id, env.contract.address.as_str(), &swap_id, CURRENT_PROTOCOL_VERSION, &swap.new_state_root, &asset_updates_digest(&[update("usd", None, 1)]));
+        assert_ne!(other_assets, vote_digest(&env.block.chain_id, env.contract.address.as_str(), &swap_id, CURRENT_PROTOCOL_VERSION, &swap.new_state_root, &asset_updates_digest(&swap.asset_updates)));
+        vote(&mut deps, &env, 1, &swap_id, true).unwrap();
+        vote(&mut deps, &env, 2, &swap_id, true).unwrap();
+        assert_eq!(outcome_of(&deps, &swap_id).status, OutcomeStatus::Finalized);
+        assert_eq!(leaves(&deps), vec![("eur".to_string(), 2), ("usd".to_string(), 1)]);
+        assert!(locked(&deps, "usd").is_none() && locked(&deps, "eur").is_none());
+
+        // A rejected swap back moves neither asset and frees both
+        env.block.height += 1;
+        let back = StateTransition {
             old_state_root: vec![1; 32],
-            new_state_root: vec![2; 32],
-            merkle_proof: vec![],
-            new_state_ipfs: "QmABC123".to_string(),
-            user_signature: vec![1, 2, 3],
-            encrypted_shares: vec![
-                EncryptedShares {
-                    node_id: 1,
-                    encrypted_data: vec![],
-                },
-                EncryptedShares {
-                    node_id: 2,
-                    encrypted_data: vec![],
-                },
-                EncryptedShares {
-                    node_id: 3,
-                    encrypted_data: vec![],
-                },
-            ],
+            asset_updates: vec![update("usd", Some(1), 3), update("eur", Some(2), 4)],
+            ..test_transition("alice", 3, 3)
         };
+        let back_id = submit(&mut deps, &env, &back).unwrap();
+        assert_eq!(locked(&deps, "eur"), Some(back_id.clone()));
+        vote(&mut deps, &env, 1, &back_id, false).unwrap();
+        vote(&mut deps, &env, 2, &back_id, false).unwrap();
+        assert_eq!(outcome_of(&deps, &back_id).status, OutcomeStatus::Rejected);
+        assert_eq!(leaves(&deps), vec![("eur".to_string(), 2), ("usd".to_string(), 1)]);
+        assert!(locked(&deps, "usd").is_none() && locked(&deps, "eur").is_none());
 
-        execute(
-            deps.as_mut(),
-            env.clone(),
-            mock_info("alice", &[]),
-            ExecuteMsg::SubmitStateTransition { transition }
-        ).unwrap();
+        // One stale old leaf supersedes the lot
+        env.block.height += 1;
+        let stale = StateTransition {
+            old_state_root: vec![1; 32],
+            asset_updates: vec![update("usd", Some(1), 3), update("eur", Some(9), 4)],
+            ..test_transition("alice", 3, 3)
+        };
+        let stale_id = submit(&mut deps, &env, &stale).unwrap();
+        vote(&mut deps, &env, 1, &stale_id, true).unwrap();
+        vote(&mut deps, &env, 2, &stale_id, true).unwrap();
+        assert_eq!(outcome_of(&deps, &stale_id).status, OutcomeStatus::Superseded);
+        assert_eq!(leaves(&deps), vec![("eur".to_string(), 2), ("usd".to_string(), 1)]);
+    }
 
-        let validation_id = format!("{}-{}", env.block.height, "alice");
+    #[test]
+    fn test_audit_bundle_verifies_a_past_finalization_offline() {
+        let (mut deps, env) = setup_contract(2, 3);
+        let alice = signed_transition("alice", &user_signing_key("alice"), &[0; 32], &[5; 32], 1, 3);
+        let alice_id = submit(&mut deps, &env, &alice).unwrap();
+        let res = drive_to_threshold(&mut deps, &env, &alice_id, &alice, 2).unwrap();
+        let emitted = res.attributes.iter().find(|a| a.key == "bundle_hash").unwrap().value.clone();
 
-        // 4. MPC nodes validate
-        for i in 1..=2 {
-            execute(
-                deps.as_mut(),
-                env.clone(),
-                mock_info(&format!("node{}", i), &[]),
-                ExecuteMsg::ValidateTransition {
-                    validation_id: validation_id.clone(),
-                    valid: true,
-                    partial_signature: vec![i; 32],
-                }
-            ).unwrap();
+        let bob = signed_transition("bob", &user_signing_key("bob"), &[0; 32], &[6; 32], 1, 3);
+        let bob_id = submit(&mut deps, &env, &bob).unwrap();
+        drive_to_threshold(&mut deps, &env, &bob_id, &bob, 2).unwrap();
+
+        execute(deps.as_mut(), env.clone(), mock_info("alice", &[]), ExecuteMsg::SetViewingKey { key: "alice-key".to_string() }).unwrap();
+        let bundle_for = |viewing_key: Option<&str>| query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::GetAuditBundle { validation_id: alice_id.clone(), viewing_key: viewing_key.map(str::to_string) },
+        ).and_then(|bin| cosmwasm_std::from_binary::<AuditBundleResponse>(&bin));
+        let bundle = bundle_for(None).unwrap();
+        assert_eq!(bundle.private, None);
+        assert_eq!(bundle.transition_hash, transition_content_hash(&alice).unwrap());
+        assert_eq!((bundle.root_before.clone(), bundle.root_after.clone()), (vec![0; 32], vec![5; 32]));
+        assert_eq!(bundle.signers, vec![1, 2]);
+
+        // Each partial verifies under the frozen key against its signed message
+        for (i, partial) in bundle.threshold_signature.chunks(PARTIAL_SIGNATURE_LEN).enumerate() {
+            let key = &bundle.signer_keys[i];
+            assert_eq!(key.public_key, compressed_public_key(&node_signing_key(bundle.signers[i] as u8)));
+            assert_eq!(bundle.signed_messages[i], vote_digest(&env.block.chain_id, env.contract.address.as_str(), &alice_id, CURRENT_PROTOCOL_VERSION, &alice.new_state_root, &[]));
+            assert!(deps.api.secp256k1_verify(&bundle.signed_messages[i], partial, &key.public_key).unwrap());
         }
+        assert_eq!(audit_bundle_hash(&bundle), bundle.bundle_hash);
+        assert_eq!(hex::encode(&bundle.bundle_hash), emitted);
+
+        // Only alice's viewing key reveals her address, roots and CID, and
+        // the hash is the same either way
+        assert_eq!(bundle_for(Some("guess")).unwrap_err(), StdError::generic_err("Unauthorized"));
+        let private = bundle_for(Some("alice-key")).unwrap();
+        assert_eq!(private.private, Some(AuditPrivate {
+            user_address: "alice".to_string(),
+            old_state_root: vec![0; 32],
+            new_state_ipfs: alice.new_state_ipfs.clone(),
+            content_hash: alice.content_hash.clone(),
+        }));
+        assert_eq!(audit_bundle_hash(&private), bundle.bundle_hash);
+
+        let carol = QueryMsg::GetAuditBundle { validation_id: "12345-carol".to_string(), viewing_key: None };
+        let err = query(deps.as_ref(), env, carol).unwrap_err();
+        assert_eq!(err, StdError::generic_err("No retained finalization for 12345-carol"));
+    }
+
+    #[test]
+    fn test_first_release_below_production_threshold_is_refused() {
+        use crate::state::LegacyMPCNode;
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        LEGACY_STATE.save(&mut deps.storage, &LegacyState {
+            mpc_nodes: vec![LegacyMPCNode {
+                address: "node1".to_string(),
+                node_id: 1,
+                public_key: compressed_public_key(&node_signing_key(1)),
+                active: true,
+            }],
+            threshold: 1,
+            current_state_root: vec![0; 32],
+            block_height: 0,
+        }).unwrap();
+
+        let err = migrate(deps.as_mut(), env, MigrateMsg::Execute { owner: Some("admin".to_string()) }).unwrap_err();
+        assert_eq!(err, StdError::generic_err("1 records fail to migrate, first: state/threshold 1 below 2"));
+        assert!(CONFIG.may_load(&deps.storage).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_migrate_first_release_instance() {
+        use crate::state::{LegacyMPCNode, LegacyStateCommitment};
+
+        // All the first release ever stored: a bare State and commitments
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        LEGACY_STATE.save(&mut deps.storage, &LegacyState {
+            mpc_nodes: (1..=3u8).map(|i| LegacyMPCNode {
+                address: format!("node{}", i),
+                node_id: i as u32,
+                public_key: compressed_public_key(&node_signing_key(i)),
+                active: true,
+            }).collect(),
+            threshold: 2,
+            current_state_root: vec![0; 32],
+            block_height: 4,
+        }).unwrap();
+        LEGACY_STATE_COMMITMENTS.insert(&mut deps.storage, &"bob".to_string(), &LegacyStateCommitment {
+            user_address: "bob".to_string(),
+            state_root: vec![7; 32],
+            ipfs_cid: "Qmbob".to_string(),
+            merkle_proof: vec![],
+            nonce: 3,
+            updated_at: 100,
+        }).unwrap();
+
+        // There is no config to take an owner from, so one must be named
+        let report = dry_run(&deps, &env, None, None, None);
+        assert_eq!(report.failed_keys, vec!["config/<missing>".to_string()]);
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: None }).unwrap_err();
+
+        assert_eq!(dry_run(&deps, &env, Some("admin"), None, None).total_failures, 0);
+        migrate(deps.as_mut(), env.clone(), MigrateMsg::Execute { owner: Some("admin".to_string()) }).unwrap();
+
+        let state = STATE.load(&deps.storage).unwrap();
+        assert_eq!((state.threshold, state.block_height, state.mpc_nodes.len()), (2, 4, 3));
+        assert!(!state.pending_committee);
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.owner, "admin");
+        assert!(!config.dev_mode);
+        assert_eq!(STATS.load(&deps.storage).unwrap(), Stats::default());
+        let bob = STATE_COMMITMENTS.get(&deps.storage, &"bob".to_string()).unwrap();
+        assert_eq!((bob.nonce, bob.state_root.clone()), (3, vec![7; 32]));
+        assert_eq!(LEAF_OWNERS.get(&deps.storage, &bob.leaf_index), Some("bob".to_string()));
 
-        // 5. Finalize
+        // Executes, queries and viewing keys all work on the migrated instance
+        let transition = test_transition("alice", 2, 3);
+        let validation_id = submit(&mut deps, &env, &transition).unwrap();
+        drive_to_threshold(&mut deps, &env, &validation_id, &transition, 2).unwrap();
+        assert_eq!(STATE_COMMITMENTS.get(&deps.storage, &"alice".to_string()).unwrap().state_root, vec![2; 32]);
+        query(deps.as_ref(), env.clone(), QueryMsg::GetState {}).unwrap();
         execute(
             deps.as_mut(),
-            env.clone(),
-            mock_info("anyone", &[]),
-            ExecuteMsg::FinalizeTransition {
-                validation_id: validation_id.clone(),
-            }
-        ).unwrap();
-
-        // 6. Query state commitment
-        let res = query(
-            deps.as_ref(),
-            env.clone(),
-            QueryMsg::GetStateCommitment {
-                user_address: "alice".to_string(),
-            }
+            env,
+            mock_info("alice", &[]),
+            ExecuteMsg::CreateViewingKey { entropy: "e".to_string() },
         ).unwrap();
-
-        let commitment: StateCommitmentResponse = cosmwasm_std::from_binary(&res).unwrap();
-        assert_eq!(commitment.commitment.ipfs_cid, "QmABC123");
     }
 }
@@ -1,4 +1,11 @@
+// secret-toolkit storage types carry internal caches; they are declared as
+// consts on purpose so every use starts from a fresh instance
+#![allow(clippy::borrow_interior_mutable_const, clippy::declare_interior_mutable_const)]
+
 pub mod contract;
 pub mod msg;
+pub mod protocol;
 pub mod state;
+#[cfg(test)]
+pub mod testing;
 
@@ -1,20 +1,84 @@
+use cosmwasm_std::{Binary, Coin, Timestamp, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use crate::state::{State, StateCommitment, StateTransition, Transfer, PendingValidation};
+use crate::state::{
+    State, Config, Stats, MPCNode, StateCommitment, StateTransition, Transfer, TransferRecord, TransferGroup,
+    PendingValidation, EncryptedShares, BuildAttestation, ValidationOutcome, AdminRecovery, OperationKind, EventVerbosity,
+    RetainPartials, VotePrecedence, RewardPolicy, OutcomeStatus, PauseWindow, FinalizedRecord, LastFinalized, DeploymentIdentity, MerkleProofElement, CommitteeSnapshot, ThresholdKey, CommitmentMetadata,
+    ShareEncoding, Subscriber, EquivocationEvidence, AssetUpdate, EpochMember, RetainedPartial, OutboundSend, WithdrawalReceipt, KeyRotation,
+};
 
 // ============================================================================
 // MESSAGES
 // ============================================================================
 
 #[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrateMsg {
+    /// `owner` is required only to migrate an instance of the first release,
+    /// which had no config and so no owner. Stored maps are not decoded here;
+    /// page through `QueryMsg::MigrationDryRun` under the new code against a
+    /// copy of the state first.
+    Execute {
+        #[serde(default)]
+        owner: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Default, JsonSchema)]
 pub struct InstantiateMsg {
     pub threshold: u32,
+    #[serde(default)]
+    pub supported_protocol_versions: Option<Vec<u16>>,
+    #[serde(default)]
+    pub tree_depth: Option<u8>,
+    // Allows a threshold of 1 for local development; cannot be changed later
+    #[serde(default)]
+    pub dev_mode: bool,
+    // Admin recovery parameters; immutable once instantiated
+    #[serde(default)]
+    pub recovery_supermajority: Option<u8>,
+    #[serde(default)]
+    pub recovery_delay: Option<u64>,
+    // Separates instances on one chain: mixed into validation ids and the genesis root
+    #[serde(default)]
+    pub deployment_salt: Vec<u8>,
+    // Committee registered in the same transaction, under RegisterMPCNode's rules
+    #[serde(default)]
+    pub initial_nodes: Vec<InitialNode>,
+    // Submissions are refused before this block time
+    #[serde(default)]
+    pub accept_submissions_at: Option<Timestamp>,
+}
+
+/// An active node's signature over the digest of the message carrying it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodeCosign {
+    pub node_id: u32,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InitialNode {
+    pub address: String,
+    pub public_key: Vec<u8>,
+    #[serde(default)]
+    pub key_type: Option<KeyType>,  // Checked against the key's encoding when given
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyType {
+    Secp256k1,
+    Bls,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
 pub enum ExecuteMsg {
-    // MPC node management
+    // MPC node management. A registered node sends its current key to rejoin
+    // the committee; it changes keys through StageNextEpochKey.
     RegisterMPCNode {
         public_key: Vec<u8>,
     },
@@ -24,14 +88,92 @@ pub enum ExecuteMsg {
         transition: StateTransition,
     },
 
+    // First signing round when config.require_nonce_commitments is on: a
+    // node commits to the x coordinate of its ECDSA nonce point before anyone
+    // signs, and its partial must then carry that value as r
+    CommitNonce {
+        validation_id: String,
+        nonce_commitment: Vec<u8>,
+    },
+
+    // Second round: a valid vote, accepted once the signer set's nonces are
+    // all committed. Invalid votes still go through ValidateTransition.
+    SubmitPartial {
+        validation_id: String,
+        partial_signature: Vec<u8>,
+        #[serde(default)]
+        attested_root: Option<Vec<u8>>,
+    },
+
     // MPC node validates transition (receives their share)
     ValidateTransition {
         validation_id: String,
         valid: bool,
         partial_signature: Vec<u8>,
+        // Required for valid votes when strict_root_agreement is on
+        #[serde(default)]
+        attested_root: Option<Vec<u8>>,
     },
 
-    // Finalize after threshold reached
+    // Several votes from one node in one transaction. Per-item outcomes come
+    // back as BatchVoteResult in the response data. With `max_items`, votes
+    // are processed in input order from `cursor` (an index into `votes`) and
+    // the call stops early; resend the same list with the returned cursor.
+    ValidateBatch {
+        votes: Vec<BatchVote>,
+        #[serde(default)]
+        max_items: Option<u32>,
+        #[serde(default)]
+        cursor: Option<u32>,
+    },
+
+    // Submitting user withdraws a plain (non-transfer) pending transition
+    CancelTransition {
+        validation_id: String,
+    },
+
+    // Sender deletes their settled history. The current commitment and leaf
+    // always stay; keep_current also keeps the current commitment's finalizers.
+    // Signed by the sender's user key over protocol::purge_digest.
+    PurgeMyHistory {
+        keep_current: bool,
+        nonce: u64,
+        signature: Vec<u8>,
+    },
+
+    // Sender's secp256k1 key for signing storage repoints and purges.
+    // `signature` is over protocol::user_key_digest at the sender's next key
+    // nonce, by the current key, or by `public_key` itself for a first key.
+    SetUserKey {
+        public_key: Vec<u8>,
+        nonce: u64,
+        signature: Vec<u8>,
+    },
+
+    // Sender moves their commitment to re-uploaded content; root and nonce
+    // stay. `signature` is by the user key over protocol::repoint_digest.
+    RepointStorage {
+        new_ipfs_cid: String,
+        content_hash: Vec<u8>,
+        signature: Vec<u8>,
+        // Required when config.repoint_cosign is on, ignored otherwise
+        #[serde(default)]
+        cosign: Option<NodeCosign>,
+    },
+
+    // Owner-only incident control: freeze or unfreeze several nodes at once
+    SetNodesActive {
+        node_ids: Vec<u32>,
+        active: bool,
+    },
+
+    // Node-published settings (owner or operator)
+    UpdateNodeMetadata {
+        auto_carryover_votes: Option<bool>,
+    },
+
+    // Finalize after threshold reached; within config.finalizer_window only
+    // the designated finalizer's call acts, others return already_finalized
     FinalizeTransition {
         validation_id: String,
     },
@@ -40,16 +182,472 @@ pub enum ExecuteMsg {
     SubmitTransfer {
         transfer: Transfer,
     },
+
+    // Transfer sender re-points both pending legs at re-pinned content
+    UpdateTransferCids {
+        sender_validation_id: String,
+        recipient_validation_id: String,
+        sender_ipfs: String,
+        recipient_ipfs: String,
+    },
+
+    // Transfer whose legs only finalize once the hashlock preimage is revealed
+    SubmitConditionalTransfer {
+        transfer: Transfer,
+        hashlock: Vec<u8>,
+        deadline: u64,  // Unix seconds
+    },
+
+    // One sender transition debiting the total, plus one transition per
+    // recipient; all legs finalize together or not at all
+    SubmitBatchTransfer {
+        sender_transition: StateTransition,
+        recipients: Vec<RecipientLeg>,
+    },
+
+    // Reveal the preimage, finalizing both legs of a conditional transfer
+    RevealPreimage {
+        transfer_id: String,
+        preimage: Vec<u8>,
+    },
+
+    // Unwind a conditional transfer after its deadline (sender or recipient)
+    ResolveTransfer {
+        transfer_id: String,
+    },
+
+    // Node attests the software build it runs (signed by its registered key)
+    AttestBuild {
+        git_commit: String,
+        binary_hash: Vec<u8>,
+        signature: Vec<u8>,
+    },
+
+    // Node owner stages the key it will use from the next epoch on.
+    // `proof_of_possession` signs protocol::key_possession_digest with it.
+    StageNextEpochKey {
+        public_key: Vec<u8>,
+        proof_of_possession: Vec<u8>,
+    },
+
+    // Owner-only: staged keys take over, nodes without one go inactive
+    AdvanceEpoch {},
+
+    // Owner-only: records the committee's new aggregate public key under the
+    // next key epoch; later submissions are verified against it
+    RotateThresholdKey {
+        public_key: Vec<u8>,
+    },
+
+    // Owner-only: stop votes and submissions; deadlines stand still meanwhile
+    Pause {},
+    Unpause {},
+
+    // Owner-only, but only with evidence the contract can check itself
+    SlashNode {
+        evidence: FaultEvidence,
+    },
+
+    // Leave the committee; signed over protocol::deregistration_digest by the
+    // node's registered key so the address alone is not enough
+    DeregisterMPCNode {
+        signature: Vec<u8>,
+    },
+
+    // Deactivates the node and bans its key. Pending validations holding a
+    // share for it are blocked when next voted on or finalized; their users
+    // can only cancel them. Sent by the node's owner, the
+    // contract owner, or another active node with two more nodes' cosigns.
+    // The optional signature, by the reported key over
+    // protocol::compromise_digest, proves possession; cosigns sign the same
+    // digest with their own keys.
+    ReportKeyCompromise {
+        node_id: u32,
+        signature_by_compromised_key: Option<Vec<u8>>,
+        #[serde(default)]
+        cosigns: Vec<NodeCosign>,
+    },
+
+    // Viewing keys for permissioned queries
+    CreateViewingKey {
+        entropy: String,
+    },
+    SetViewingKey {
+        key: String,
+    },
+
+    // Owner grants or revokes read access to private validation data
+    SetObserver {
+        address: String,
+        authorized: bool,
+    },
+
+    // Owner-only configuration update (unset fields are left unchanged)
+    UpdateConfig(ConfigUpdate),
+
+    // Two-step ownership handover: owner proposes, new owner accepts
+    TransferOwnership {
+        new_owner: String,
+    },
+    AcceptOwnership {},
+
+    // Revert a user's latest commitment while it is inside the challenge window.
+    // Takes effect once `threshold` active nodes have challenged it, and is
+    // refused once another validation depends on it.
+    ChallengeFinalized {
+        user_address: String,
+        reason: String,
+    },
+
+    // Data availability: an active node claims new_state_ipfs is unreachable,
+    // posting exactly config.da_challenge_bond. Anyone answers with content
+    // hashing to the transition's content_hash before the deadline, which
+    // forfeits the bond to the user, or anyone settles the validation as
+    // rejected after it. The user's expiry_bounty deposit is refunded as for
+    // any other failure, and the bond returned once the validation settles.
+    ChallengeDataAvailability {
+        validation_id: String,
+    },
+    ProveAvailability {
+        validation_id: String,
+        sample: Binary,
+    },
+    ExpireDataChallenge {
+        validation_id: String,
+    },
+
+    // Nudge one node that has not voted yet (owner or the submitting user).
+    // Only emits an event for that node's off-chain process.
+    RequestRevalidation {
+        validation_id: String,
+        node_id: u32,
+    },
+
+    // Node owner delegates validator duties to a hot operator key (None revokes)
+    SetOperator {
+        operator: Option<String>,
+    },
+
+    // Committee recovery of a lost admin key: an active node proposes, others
+    // approve, and after a supermajority plus the veto delay anyone executes
+    ProposeAdminRecovery {
+        new_admin: String,
+    },
+    ApproveAdminRecovery {},
+    VetoAdminRecovery {},
+    ExecuteAdminRecovery {},
+
+    // Owner-only, while state.backfill_until is open: fills the finalization
+    // metadata a pre-schema-3 commitment was stored without
+    BackfillCommitment {
+        user_address: String,
+        metadata: CommitmentMetadata,
+    },
+
+    // Anyone may remove up to `limit` accounts that registered a key, never
+    // submitted, and have been idle for config.account_ttl_seconds
+    PurgeStaleAccounts {
+        limit: Option<u32>,
+    },
+
+    // Anyone may settle a validation past its deadline and collect the
+    // bounty its submitter deposited
+    ExpireValidation {
+        validation_id: String,
+    },
+
+    // Asks for `subscriber` (a contract) to be sent a SubscriberMsg when the
+    // pending validation settles. Only the validation's parties may ask, and
+    // each pays exactly config.subscription_fee, which goes to the sender of
+    // the message that settles it and so pays for the callback
+    SubscribeValidation {
+        validation_id: String,
+        subscriber: String,
+        code_hash: String,
+    },
+
+    // Anyone may resend a failed bounty send, up to its attempt cap
+    RetryOutbound {
+        id: u64,
+    },
+
+    // Owner sends a failed bounty to another address instead, for
+    // recipients that can never accept it; resets the attempt count
+    RedirectOutbound {
+        id: u64,
+        new_recipient: String,
+    },
+}
+
+/// Execute message a subscribed contract receives; its failure is ignored
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberMsg {
+    ValidationSettled { outcome: ValidationOutcome },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RecipientLeg {
+    pub recipient: String,
+    pub transition: StateTransition,
+    pub amount_commitment: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigUpdate {
+    pub supported_protocol_versions: Option<Vec<u16>>,
+    pub attestation_window: Option<u64>,
+    pub unanimous_operations: Option<Vec<OperationKind>>,
+    pub event_verbosity: Option<EventVerbosity>,
+    pub root_history_retention: Option<u64>,
+    pub unique_cids: Option<bool>,
+    pub validation_cooldown: Option<u64>,
+    pub strict_root_agreement: Option<bool>,
+    pub challenge_window: Option<u64>,
+    pub retain_partials: Option<RetainPartials>,
+    pub partial_retention: Option<u64>,
+    pub max_transition_size: Option<u64>,
+    pub max_share_len: Option<u32>,
+    pub max_user_storage: Option<u64>,
+    pub pending_watermark: Option<u64>,
+    pub validation_timeout: Option<u64>,
+    pub commitment_confirmations: Option<u64>,
+    pub vote_precedence: Option<VotePrecedence>,
+    pub reward_policy: Option<RewardPolicy>,
+    pub reject_threshold: Option<u32>,
+    pub require_nonempty_merkle_proof: Option<bool>,
+    pub repoint_cosign: Option<bool>,
+    pub max_work_payloads: Option<u32>,
+    pub require_nonce_commitments: Option<bool>,
+    pub registration_cooldown: Option<u64>,
+    pub registration_window: Option<u64>,
+    pub max_registrations_per_window: Option<u32>,
+    pub breaker_window: Option<u64>,
+    pub breaker_max_rejections: Option<u32>,
+    pub subscription_fee: Option<Coin>,  // A zero amount disables subscriptions
+    pub equivocation_limit: Option<u32>,
+    pub expiry_bounty: Option<Coin>,  // A zero amount stops requiring a deposit
+    pub finalizer_window: Option<u64>,
+    pub account_ttl_seconds: Option<u64>,
+    pub max_validation_blocks: Option<u64>,  // Zero removes the block limit
+    pub da_challenge_bond: Option<Coin>,  // A zero amount disables challenges
+    pub outbound_failure_watermark: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetState {},
-    GetStateCommitment { user_address: String },
-    GetValidation { validation_id: String },
+    GetStateCommitment { user_address: String, viewing_key: String },
+    GetValidation { validation_id: String, auth: ViewerAuth },
     GetCurrentRoot {},
     ListPendingValidations {},
+    GetLightClientUpdate {},
+    GetConfig {},
+    GetStats {},
+    /// Cheap liveness probe; answers even when a migration is required
+    GetHealth {},
+    /// Distinct addresses that ever registered a node, beside the active count
+    GetLifetimeNodeCount {},
+    /// Whether `address` runs a node (as its owner or operator), and whether
+    /// ValidateTransition would currently accept it
+    IsNode { address: String },
+    /// Everything needed to check one retained finalization offline; fails
+    /// when the bundle would be larger than the contract's cap. The user's
+    /// address, roots and CID are filled in only with their viewing key.
+    GetAuditBundle {
+        validation_id: String,
+        #[serde(default)]
+        viewing_key: Option<String>,
+    },
+    /// Failed bounty sends still held by the contract, by ascending id
+    ListOutboundFailures {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// How the bounty of a settled validation left the contract, if it had
+    /// one; answered only to the receipt's recipient
+    GetWithdrawalReceipt { auth: ViewerAuth, validation_id: String },
+    /// Receipts of the bounties sent to `auth.address`, by validation id
+    ListWithdrawalReceipts {
+        auth: ViewerAuth,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Everything a submission or registration currently has to match
+    GetProtocolInfo {},
+    /// Oldest deadline first: up to config.max_work_payloads full items, then
+    /// stubs for what follows. Pass `next_cursor` back for the next page.
+    GetPendingForNode {
+        node_id: u32,
+        auth: ViewerAuth,
+        #[serde(default)]
+        cursor: Option<String>,
+    },
+    /// Full work item for one validation, typically one listed as a stub
+    GetAssignedPayload { validation_id: String, node_id: u32, auth: ViewerAuth },
+    GetTransferStatus { transfer_id: String },
+    GetTransferGroupStatus { group_id: String },
+    GetLeafIndex { user_address: String },
+    GetSharesForNode { validation_id: String, node_id: u32, auth: ViewerAuth },
+    ListNodes {
+        #[serde(default)]
+        include_attestations: bool,
+    },
+    GetBuildDiversity {},
+    ComputeRoot { leaves: Vec<Vec<u8>> },
+    GetValidationExpiry { validation_id: String },
+    GetCommitteeSnapshot { epoch: u64 },
+    /// The key for `key_epoch`, or the current one if omitted
+    GetThresholdKey { key_epoch: Option<u64> },
+    /// Threshold keys by ascending key_epoch, each with the committee epoch it
+    /// was rotated in. Page through with `next_start_after`.
+    ListEpochs {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// Checks an aggregate signature over a pending or finalized validation's
+    /// vote digest against the threshold key it was submitted under
+    /// (key_epoch); finalized records are found through their stored key_epoch
+    VerifyThresholdSignature { validation_id: String, signature: Vec<u8> },
+    /// Effort a node put in during `epoch` (the current one if omitted)
+    GetNodeStats { node_id: u32, epoch: Option<u64> },
+    /// Contradicting votes recorded against a node, oldest first
+    ListEquivocations { node_id: u32 },
+    /// How `amount` would be paid out for `epoch` under config.reward_policy
+    GetRewardSplit { epoch: u64, amount: Uint128 },
+    GetValidationOutcome { validation_id: String },
+    GetPauseLedger {},
+    /// Pending or settled, plus the dependencies still holding it back
+    GetValidationStatus { validation_id: String },
+    GetAdminRecovery {},
+    GetValidationCounts {},
+    GetRootAtHeight { height: u64 },
+    GetFinalized { height: u64 },
+    GetLastFinalized {},
+    /// Runs every finalization precondition for a pending validation without
+    /// changing anything, reporting each check separately
+    DiagnoseValidation { validation_id: String },
+    /// Nodes that finalized `user_address`'s commitment with `nonce`
+    GetFinalizersForUser { user_address: String, nonce: u64 },
+    /// The user's confirmed commitment as it stood at block `height`
+    GetCommitmentAtHeight { user_address: String, height: u64, viewing_key: String },
+    GetStorageUsage { user: String },
+    /// What a wallet shows for one account. Private fields (CID, proof,
+    /// gateway hint) are filled in only with the user's viewing key.
+    GetAccountOverview {
+        user_address: String,
+        #[serde(default)]
+        viewing_key: Option<String>,
+    },
+    GetDeploymentIdentity {},
+    /// Checks `proof` from `leaf` up to `expected_root`, or the current root
+    VerifyInclusion {
+        leaf: Vec<u8>,
+        proof: Vec<MerkleProofElement>,
+        expected_root: Option<Vec<u8>>,
+    },
+    GetMissingVoters { validation_id: String },
+    /// Keys the nodes that voted valid held in the validation's committee, in
+    /// vote order; pending or finalized
+    GetVoterKeys { validation_id: String },
+    /// Owner-only, since it includes every user's commitment, in the order
+    /// users first committed. Page through with `next_start_after` (an
+    /// export slot) until it comes back empty; a page may hold fewer than
+    /// `limit` commitments where users were removed.
+    ExportSnapshot {
+        auth: ViewerAuth,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// What `MigrateMsg::Execute` would do to this storage under the code
+    /// answering, without writing. Stored maps are decoded a page of records
+    /// at a time; page through with `next_start_after`.
+    MigrationDryRun {
+        #[serde(default)]
+        owner: Option<String>,
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+/// A node's valid-vote signature over a root other than the one its
+/// validation finalized with. `height` locates the FinalizedRecord.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FaultEvidence {
+    pub validation_id: String,
+    pub height: u64,
+    pub node_id: u32,
+    pub protocol_version: u16,
+    pub signed_root: Vec<u8>,
+    pub partial_signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchVote {
+    pub validation_id: String,
+    pub valid: bool,
+    pub partial_signature: Vec<u8>,
+    #[serde(default)]
+    pub attested_root: Option<Vec<u8>>,
+}
+
+/// What happened to one vote; also the response data of ValidateTransition
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VoteStatus {
+    Accepted,
+    Duplicate,
+    Finalized,  // This vote finalized the validation
+    ValidationMissing,
+    AlreadySettled,  // The validation was settled before this vote; `error` names how
+    Throttled,  // An earlier vote of the node is still inside validation_cooldown
+    SignatureRejected,  // Malformed, off the committed nonce, or not verifying under the node's key
+}
+
+impl VoteStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VoteStatus::Accepted => "accepted",
+            VoteStatus::Duplicate => "duplicate",
+            VoteStatus::Finalized => "finalized",
+            VoteStatus::ValidationMissing => "validation_missing",
+            VoteStatus::AlreadySettled => "already_settled",
+            VoteStatus::Throttled => "throttled",
+            VoteStatus::SignatureRejected => "signature_rejected",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoteResultEntry {
+    pub validation_id: String,
+    pub status: VoteStatus,
+    pub finalized: bool,
+    pub error: Option<String>,
+    #[serde(default)]
+    pub contested: bool,  // This vote left the validation at both accept and reject
+}
+
+/// Response data of ValidateBatch, in input order
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BatchVoteResult {
+    pub results: Vec<VoteResultEntry>,
+    // Where the next call continues, when the budget stopped this one early
+    #[serde(default)]
+    pub resume_cursor: Option<u32>,
+}
+
+/// Credentials for permissioned queries
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ViewerAuth {
+    pub address: String,
+    pub viewing_key: String,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -60,6 +658,7 @@ pub struct StateResponse {
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct StateCommitmentResponse {
     pub commitment: StateCommitment,
+    pub metadata_complete: bool,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -76,3 +675,521 @@ pub struct CurrentRootResponse {
 pub struct PendingValidationsResponse {
     pub validation_ids: Vec<String>,
 }
+
+/// Public key of a committee member, as needed to verify its partial signature
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommitteeKey {
+    pub node_id: u32,
+    pub public_key: Vec<u8>,
+}
+
+/// Everything a light client needs to verify the latest root in one response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LightClientUpdateResponse {
+    pub root: Vec<u8>,
+    pub block_height: u64,
+    pub validation_id: String,
+    pub threshold_signature: Vec<u8>,  // Concatenated partials, ordered as `signers`
+    pub protocol_version: u16,
+    pub signers: Vec<u32>,
+    // Validation id each signer's partial covers (differs for carried-over votes)
+    #[serde(default)]
+    pub signed_validation_ids: Vec<String>,
+    pub threshold: u32,
+    #[serde(default)]
+    pub epoch: u64,  // Committee epoch whose keys `committee` lists
+    pub committee: Vec<CommitteeKey>,
+    // Asset list the partials also cover; empty unless the last transition moved assets
+    #[serde(default)]
+    pub asset_digest: Vec<u8>,
+    // Deployment the root belongs to, and root_attestation_digest over it
+    pub chain_id: String,
+    pub contract_address: String,
+    pub root_attestation: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ConfigResponse {
+    // Omitted while a migration is outstanding, since the stored layout may
+    // no longer deserialize
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<Config>,
+    pub storage_schema_version: u16,
+    pub required_schema_version: u16,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct StatsResponse {
+    pub stats: Stats,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidationCountsResponse {
+    pub pending: u64,
+    pub finalized: u64,
+    pub rejected: u64,
+    pub expired: u64,
+    pub challenged: u64,
+}
+
+/// Work item for a node: its share of a pending validation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodeWorkItem {
+    pub validation_id: String,
+    pub protocol_version: u16,
+    pub created_at: u64,
+    #[serde(default)]
+    pub expires_at: u64,
+    #[serde(default)]
+    pub expires_at_height: Option<u64>,  // Where config.max_validation_blocks closes it first by height
+    pub share: EncryptedShares,
+    pub group: Option<TransferGroup>,  // Batch context for checking the sum across legs
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PendingForNodeResponse {
+    pub items: Vec<NodeWorkItem>,
+    pub stubs: Vec<NodeWorkStub>,
+    pub next_cursor: Option<String>,  // Set while anything follows `items`
+}
+
+/// A work item without its share; fetch it with GetAssignedPayload
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodeWorkStub {
+    pub validation_id: String,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub expires_at_height: Option<u64>,
+    pub share_digest: Vec<u8>,  // SHA-256 of the JSON-encoded share
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AssignedPayloadResponse {
+    pub item: NodeWorkItem,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TransferStatusResponse {
+    pub transfer: TransferRecord,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TransferGroupStatusResponse {
+    pub group: TransferGroup,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct LeafIndexResponse {
+    pub leaf_index: u64,
+    pub owner: Option<String>,  // Address currently holding the leaf, if any
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SharesForNodeResponse {
+    pub share: EncryptedShares,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodeInfo {
+    pub node: MPCNode,
+    pub attestation: Option<BuildAttestation>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct NodesResponse {
+    pub nodes: Vec<NodeInfo>,
+}
+
+/// One page of an export. Nodes are only sent on the first page; commitments
+/// are ordered by user address.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SnapshotResponse {
+    pub block_height: u64,
+    pub current_state_root: Vec<u8>,
+    pub nodes: Vec<NodeInfo>,
+    pub commitments: Vec<StateCommitment>,
+    pub next_start_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BuildCount {
+    pub binary_hash: Vec<u8>,
+    pub count: u32,
+}
+
+/// Active nodes grouped by attested build; missing or stale attestations are unknown
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BuildDiversityResponse {
+    pub builds: Vec<BuildCount>,
+    pub unknown: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ComputeRootResponse {
+    pub root: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ValidationOutcomeResponse {
+    pub outcome: ValidationOutcome,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseLedgerResponse {
+    pub paused: bool,
+    pub windows: Vec<PauseWindow>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidationStatusResponse {
+    pub validation_id: String,
+    pub outcome: Option<OutcomeStatus>,  // None while pending
+    pub threshold_reached: bool,
+    pub unmet_dependencies: Vec<String>,  // Declared dependencies not yet finalized
+    pub subscribers: Vec<Subscriber>,  // Empty once settled; notifications have been sent
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EquivocationsResponse {
+    pub node_id: u32,
+    pub offenses: u32,  // Every equivocation, including ones no longer listed
+    pub equivocations: Vec<EquivocationEvidence>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodeStatsResponse {
+    pub node_id: u32,
+    pub epoch: u64,
+    pub votes: u64,
+    pub effort_units: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardSplitResponse {
+    pub policy: RewardPolicy,
+    pub payouts: Vec<NodePayout>,  // Nodes that voted in the epoch, by node_id
+    pub remainder: Uint128,        // Left over by integer division
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodePayout {
+    pub node_id: u32,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EpochsResponse {
+    pub current_epoch: u64,
+    pub current_key_epoch: u64,
+    pub keys: Vec<ThresholdKey>,  // Ascending by key_epoch
+    pub next_start_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ThresholdKeyResponse {
+    pub current_key_epoch: u64,
+    pub key: ThresholdKey,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ThresholdSignatureResponse {
+    pub key_epoch: u64,
+    pub valid: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommitteeSnapshotResponse {
+    pub current_epoch: u64,
+    pub snapshot: CommitteeSnapshot,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidationExpiryResponse {
+    pub created_at: u64,  // Unix seconds
+    pub timeout: u64,     // config.validation_timeout
+    pub expires_at: u64,  // created_at + timeout, or a conditional transfer's earlier deadline
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ViewingKeyResponse {
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminRecoveryResponse {
+    pub recovery: Option<AdminRecovery>,
+    pub required_approvals: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RootStatus {
+    Found,
+    Pruned,  // Existed but fell out of the retention window
+    NotFound,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,  // Still serving, see reasons
+    Paused,    // Owner paused the contract; submissions and votes are refused
+    MigrationRequired,
+}
+
+/// Why the contract is not healthy
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthReason {
+    MigrationRequired,  // Stored schema differs from the code's; only GetConfig is answerable
+    Paused,             // Paused by the owner until Unpause
+    CommitteeNotReady,  // Too few nodes have registered to accept submissions
+    BelowThreshold,     // Active nodes cannot meet the threshold, nothing can finalize
+    PendingBacklog,     // More pending validations than config.pending_watermark
+    OutboundFailures,   // More parked failed sends than config.outbound_failure_watermark
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LifetimeNodeCountResponse {
+    pub registered: u32,
+    pub active: u32,
+}
+
+/// One finalization, checkable offline: signed_messages[i] is the vote
+/// digest signers[i] signed (see protocol::vote_digest) and verifies under
+/// signer_keys[i] against the i-th 64-byte chunk of threshold_signature.
+/// bundle_hash (protocol::audit_bundle_hash) matches the `bundle_hash`
+/// attribute emitted when the validation finalized; it covers only the
+/// public fields, so anyone can check it without `private`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuditBundleResponse {
+    pub validation_id: String,
+    pub block_height: u64,
+    pub new_state_root: Vec<u8>,
+    pub protocol_version: u16,
+    pub transition_hash: Vec<u8>,
+    pub asset_updates: Vec<AssetUpdate>,
+    pub epoch: u64,
+    pub key_epoch: u64,
+    pub signers: Vec<u32>,
+    pub signer_keys: Vec<EpochMember>,
+    pub signed_validation_ids: Vec<String>,
+    pub signed_messages: Vec<Vec<u8>>,
+    pub threshold_signature: Vec<u8>,
+    pub partials: Vec<RetainedPartial>,  // As retained by config.retain_partials; may be pruned
+    pub root_before: Vec<u8>,
+    pub root_after: Vec<u8>,
+    pub committee: Option<CommitteeSnapshot>,  // Full committee of `epoch`, once snapshotted
+    pub private: Option<AuditPrivate>,  // With the user's viewing key
+    pub bundle_hash: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuditPrivate {
+    pub user_address: String,
+    pub old_state_root: Vec<u8>,
+    pub new_state_ipfs: String,
+    pub content_hash: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OutboundFailure {
+    pub id: u64,
+    pub send: OutboundSend,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OutboundFailuresResponse {
+    pub failures: Vec<OutboundFailure>,
+    pub next_start_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawalReceiptResponse {
+    pub receipt: Option<WithdrawalReceipt>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawalReceiptsResponse {
+    pub receipts: Vec<WithdrawalReceipt>,
+    pub next_start_after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct IsNodeResponse {
+    pub is_registered: bool,  // Ever registered; deregistered nodes keep their entry
+    pub is_active: bool,
+    pub node_id: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolInfoResponse {
+    pub protocol_versions: Vec<u16>,
+    pub share_encodings: Vec<ShareEncoding>,
+    pub user_key_types: Vec<KeyType>,
+    pub node_key_types: Vec<KeyType>,
+    /// Digest used for roots, content hashes and every signed challenge
+    pub hash_scheme: String,
+    pub domain_tags: Vec<String>,
+    pub limits: ProtocolLimits,
+}
+
+/// Sizes in bytes unless named otherwise
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolLimits {
+    pub digest_len: u32,
+    pub partial_signature_len: u32,
+    pub max_transition_size: u64,
+    pub max_share_len: u32,
+    pub max_coordinator_metadata: u32,
+    pub max_gateway_hint_len: u32,
+    pub nonce_commitment_len: u32,
+    pub max_dependencies: u32,
+    pub max_dependents: u32,
+    pub max_batch_votes: u32,
+    pub max_asset_updates: u32,
+    pub max_asset_id_len: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    pub reasons: Vec<HealthReason>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RootAtHeightResponse {
+    pub status: RootStatus,
+    pub root: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VerifyInclusionResponse {
+    pub valid: bool,
+    pub root: Vec<u8>,  // Root the proof was checked against
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DeploymentIdentityResponse {
+    pub identity: DeploymentIdentity,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinalizedResponse {
+    pub status: RootStatus,
+    pub record: Option<FinalizedRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LastFinalizedResponse {
+    pub last: Option<LastFinalized>,  // None until the first finalization
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DiagnosisResponse {
+    pub validation_id: String,
+    pub finalizable: bool,  // Every check passed
+    pub checks: Vec<FinalizationCheck>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinalizationCheck {
+    pub check: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrationDryRunResponse {
+    pub from_schema: u16,
+    pub to_schema: u16,
+    pub counts: Vec<RecordCount>,
+    pub failed_keys: Vec<String>,  // "<store>/<key>", at most MAX_REPORTED_FAILURES
+    pub total_failures: u64,       // Items on every page, map records on this page only
+    pub next_start_after: Option<u64>,  // None once every map record was decoded
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RecordCount {
+    pub store: String,
+    pub records: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommitmentAtHeightResponse {
+    pub height: u64,
+    pub commitment: Option<StateCommitment>,  // None before the user's first finalization
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinalizersResponse {
+    pub node_ids: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccountOverviewResponse {
+    pub user_address: String,
+    pub commitment: Option<AccountCommitment>,  // None before the first finalization
+    pub user_key: Option<Vec<u8>>,  // Key registered with SetUserKey
+    pub key_nonce: u64,  // Nonce the next SetUserKey signs
+    pub key_rotations: Vec<KeyRotation>,  // Oldest first, at most MAX_KEY_ROTATIONS
+    pub purge_nonce: u64,  // Nonce the next PurgeMyHistory signs
+    pub pending: Vec<AccountPending>,  // Oldest first, at most MAX_OVERVIEW_PENDING
+    pub pending_total: u32,
+    pub storage: StorageUsageResponse,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccountCommitment {
+    pub state_root: Vec<u8>,
+    pub nonce: u64,
+    pub leaf_index: u64,
+    pub updated_at: u64,
+    pub finalized_at_height: u64,
+    pub confirmed: bool,  // Past config.commitment_confirmations
+    pub epoch: u64,
+    pub metadata_complete: bool,
+    pub private: Option<AccountPrivate>,  // With a valid viewing key, once confirmed
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccountPrivate {
+    pub ipfs_cid: String,
+    pub content_hash: Vec<u8>,
+    pub merkle_proof: Vec<u8>,
+    pub ipfs_gateway_hint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AccountPending {
+    pub validation_id: String,
+    pub created_at: u64,
+    pub valid_votes: u32,
+    pub threshold_reached: bool,
+    pub blocked: Option<String>,  // BlockReason, e.g. "key_compromised"
+    pub unmet_dependencies: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StorageUsageResponse {
+    pub user: String,
+    pub bytes: u64,  // Approximate, tracks growth and release of the user's entries
+    pub quota: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MissingVotersResponse {
+    pub node_ids: Vec<u32>,  // Active nodes that have not voted yet
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoterKeysResponse {
+    pub voters: Vec<VoterKey>,  // Valid voters with the keys they voted under
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoterKey {
+    pub node_id: u32,
+    pub public_key: Vec<u8>,
+}
@@ -0,0 +1,511 @@
+//! Wire-level conventions shared by the contract, MPC nodes and clients.
+//!
+//! Everything here is what an off-chain implementation has to reproduce
+//! byte-for-byte: the digests nodes sign and the canonical layout of a
+//! transition's per-node data.
+//!
+//! # Share ordering
+//!
+//! `encrypted_shares` is sorted ascending by `node_id` with no duplicates, and
+//! `vss_commitments[i]` (when present) is the commitment for
+//! `encrypted_shares[i]`. Clients may submit in any order; the contract
+//! canonicalizes on submit and stores only the canonical form, so every
+//! positional consumer (node share lookups, VSS checks, digests) can index
+//! both arrays directly. Clients should run [`canonicalize_shares`] before
+//! hashing or signing anything derived from a transition.
+//!
+//! # Content hash
+//!
+//! [`transition_content_hash`] is what users sign and what replay checks,
+//! vote carry-over and equivocation records key on. It is SHA-256 over
+//! [`canonical_transition_bytes`]: [`TRANSITION_TAG`], then the fields in
+//! declaration order, with
+//!
+//! - byte strings and strings as a u64 little-endian length and the bytes
+//! - integers widened to u64 little-endian
+//! - lists as a u64 little-endian count and their items
+//! - booleans as one byte, 0 or 1
+//! - options as one byte, 0 if absent or 1 followed by the value
+//! - share encodings as integers: raw 0, zstd 1, deflate 2
+//!
+//! Shares and their VSS commitments are encoded in canonical order, so
+//! submission order does not matter. `user_signature` (which signs the hash)
+//! and `coordinator_metadata` (opaque to the contract) are left out.
+//! `asset_updates` is encoded, in canonical order, only when non-empty, so
+//! plain transitions hash as they did before assets existed.
+//!
+//! # Asset updates
+//!
+//! A transition may also move up to [`MAX_ASSET_UPDATES`] of the user's
+//! asset leaves, all or none. The canonical order is ascending `asset_id`
+//! with no duplicates; [`canonicalize_asset_updates`] sorts and checks them.
+//! Votes on such a transition sign [`vote_digest`] with the
+//! [`asset_updates_digest`] of the list appended, so a partial cannot be
+//! reused for the same root with different assets.
+//!
+//! # Share encodings
+//!
+//! Each share declares its [`ShareEncoding`]; the tag is part of
+//! [`transition_content_hash`]. [`SUPPORTED_SHARE_ENCODINGS`] lists every
+//! variant for clients; a tag outside it fails to parse. Shares whose
+//! `encrypted_data` (the compressed, encrypted form) exceeds
+//! `config.max_share_len` are rejected at submit.
+//!
+//! # Node keys
+//!
+//! Node public keys are stored compressed: 33-byte SEC1 secp256k1 keys or
+//! 48-byte BLS12-381 G1 keys. Uncompressed 65-byte secp256k1 keys are
+//! compressed on registration; anything else is rejected. A key staged for
+//! the next epoch must sign [`key_possession_digest`], so only secp256k1
+//! keys can be staged.
+//!
+//! # Deployment binding
+//!
+//! Testnet and mainnet run identical code, so outputs that identify a root or
+//! committee start with [`deployment_prefix`]: the chain id and contract
+//! address recorded at instantiate, each prefixed with its length as a
+//! big-endian u32.
+//!
+//! # Audit bundles
+//!
+//! [`audit_bundle_hash`] is SHA-256 over [`AUDIT_BUNDLE_TAG`] and the
+//! bundle's fields in declaration order, encoded as for the content hash.
+//! `committee` (a snapshot may be written after the finalization),
+//! `partials` (pruned over time, and already inside `threshold_signature`)
+//! and `bundle_hash` itself are left out, so the hash a finalization emits
+//! stays valid for as long as the bundle can be queried. `private` is left
+//! out too, so the hash checks without the user's viewing key. Finalizations
+//! from before `audit_bundle_v2` emitted a hash over the private fields,
+//! which the public bundle cannot reproduce.
+//!
+//! # Compatibility
+//!
+//! `GetProtocolInfo` reports what a submission has to match: the protocol
+//! versions in config and the constants below. Submit and registration
+//! checks read the same values, so the query cannot drift from enforcement.
+
+use cosmwasm_std::{StdError, StdResult};
+use sha2::{Digest, Sha256};
+
+use crate::msg::{AuditBundleResponse, KeyType};
+use crate::state::{AssetUpdate, ShareEncoding, StateTransition};
+
+pub const SECP256K1_COMPRESSED_LEN: usize = 33;
+pub const SECP256K1_UNCOMPRESSED_LEN: usize = 65;
+pub const BLS_G1_COMPRESSED_LEN: usize = 48;
+
+/// Key types each party may register; only secp256k1 keys can sign the
+/// digests the contract verifies itself
+pub const USER_KEY_TYPES: [KeyType; 1] = [KeyType::Secp256k1];
+pub const NODE_KEY_TYPES: [KeyType; 2] = [KeyType::Secp256k1, KeyType::Bls];
+
+/// Length of one secp256k1 partial signature (compact r || s)
+pub const PARTIAL_SIGNATURE_LEN: usize = 64;
+
+/// Every digest here, state roots and content hashes are SHA-256
+pub const HASH_SCHEME: &str = "sha256";
+pub const DIGEST_LEN: usize = 32;
+
+/// Prefixes separating the signed challenges below from one another
+pub const DEREGISTER_TAG: &str = "deregister";
+pub const STAGE_EPOCH_KEY_TAG: &str = "stage_epoch_key";
+pub const REPOINT_TAG: &str = "repoint";
+pub const KEY_COMPROMISE_TAG: &str = "key_compromise";
+pub const TRANSITION_TAG: &str = "state_transition_v1";
+pub const ASSET_UPDATES_TAG: &str = "asset_updates_v1";
+pub const AUDIT_BUNDLE_TAG: &str = "audit_bundle_v2";
+pub const PURGE_HISTORY_TAG: &str = "purge_history";
+pub const USER_KEY_TAG: &str = "user_key";
+pub const VOTE_TAG: &str = "vote_v2";
+pub const ATTESTATION_TAG: &str = "build_attestation";
+pub const DOMAIN_TAGS: [&str; 11] = [
+    DEREGISTER_TAG, STAGE_EPOCH_KEY_TAG, REPOINT_TAG, KEY_COMPROMISE_TAG, TRANSITION_TAG, ASSET_UPDATES_TAG, AUDIT_BUNDLE_TAG,
+    PURGE_HISTORY_TAG, USER_KEY_TAG, VOTE_TAG, ATTESTATION_TAG,
+];
+
+/// Protocol version new deployments accept. Votes on transitions of
+/// VOTE_TAG_VERSION or later sign a [`VOTE_TAG`]-prefixed digest; older
+/// ones, accepted only where config still lists them, sign it untagged.
+pub const CURRENT_PROTOCOL_VERSION: u16 = 2;
+pub const VOTE_TAG_VERSION: u16 = 2;
+
+/// Returns the compressed form of a node public key, compressing uncompressed
+/// secp256k1 keys and rejecting unknown formats.
+pub fn normalize_public_key(public_key: &[u8]) -> StdResult<Vec<u8>> {
+    match public_key.len() {
+        SECP256K1_COMPRESSED_LEN if matches!(public_key[0], 0x02 | 0x03) => Ok(public_key.to_vec()),
+        SECP256K1_UNCOMPRESSED_LEN if public_key[0] == 0x04 => {
+            // Prefix encodes the parity of y; x follows unchanged
+            let mut compressed = Vec::with_capacity(SECP256K1_COMPRESSED_LEN);
+            compressed.push(0x02 | (public_key[64] & 1));
+            compressed.extend_from_slice(&public_key[1..33]);
+            Ok(compressed)
+        }
+        // Compressed G1 points always carry the compression flag bit
+        BLS_G1_COMPRESSED_LEN if public_key[0] & 0x80 != 0 => Ok(public_key.to_vec()),
+        len => Err(StdError::generic_err(format!(
+            "Unsupported public key format ({} bytes); expected a compressed secp256k1 or BLS key",
+            len
+        ))),
+    }
+}
+
+/// Type of a key already in the compressed form [`normalize_public_key`] returns
+pub fn key_type(public_key: &[u8]) -> KeyType {
+    match public_key.len() {
+        BLS_G1_COMPRESSED_LEN => KeyType::Bls,
+        _ => KeyType::Secp256k1,
+    }
+}
+
+/// Sorts `encrypted_shares` by node_id and applies the same permutation to
+/// `vss_commitments`, rejecting duplicate node_ids and misaligned VSS data.
+pub fn canonicalize_shares(transition: &mut StateTransition) -> StdResult<()> {
+    let count = transition.encrypted_shares.len();
+    if !transition.vss_commitments.is_empty() && transition.vss_commitments.len() != count {
+        return Err(StdError::generic_err(format!(
+            "VSS commitments do not align with shares ({} commitments, {} shares)",
+            transition.vss_commitments.len(),
+            count
+        )));
+    }
+
+    let mut order: Vec<usize> = (0..count).collect();
+    order.sort_by_key(|&i| transition.encrypted_shares[i].node_id);
+
+    if let Some(pair) = order.windows(2).find(|w| {
+        transition.encrypted_shares[w[0]].node_id == transition.encrypted_shares[w[1]].node_id
+    }) {
+        return Err(StdError::generic_err(format!(
+            "Duplicate share for node {}",
+            transition.encrypted_shares[pair[0]].node_id
+        )));
+    }
+
+    transition.encrypted_shares = order.iter()
+        .map(|&i| transition.encrypted_shares[i].clone())
+        .collect();
+    if !transition.vss_commitments.is_empty() {
+        transition.vss_commitments = order.iter()
+            .map(|&i| transition.vss_commitments[i].clone())
+            .collect();
+    }
+
+    Ok(())
+}
+
+pub const SUPPORTED_SHARE_ENCODINGS: [ShareEncoding; 3] = [ShareEncoding::Raw, ShareEncoding::Zstd, ShareEncoding::Deflate];
+
+/// Checks every share's stored (compressed) size against `max_share_len`
+pub fn validate_shares(transition: &StateTransition, max_share_len: u32) -> StdResult<()> {
+    for share in &transition.encrypted_shares {
+        if share.encrypted_data.len() > max_share_len as usize {
+            return Err(StdError::generic_err(format!(
+                "Share for node {} exceeds {} bytes",
+                share.node_id, max_share_len
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub const MAX_ASSET_UPDATES: usize = 8;
+pub const MAX_ASSET_ID_LEN: usize = 64;
+
+/// Sorts `asset_updates` by asset_id and checks each entry: a non-empty id,
+/// a new leaf of DIGEST_LEN bytes and an old leaf that is either that long or
+/// empty (an asset the user has never held).
+pub fn canonicalize_asset_updates(transition: &mut StateTransition) -> StdResult<()> {
+    let updates = &mut transition.asset_updates;
+    if updates.len() > MAX_ASSET_UPDATES {
+        return Err(StdError::generic_err(format!("At most {} asset updates per transition", MAX_ASSET_UPDATES)));
+    }
+    for update in updates.iter() {
+        if update.asset_id.is_empty() || update.asset_id.len() > MAX_ASSET_ID_LEN {
+            return Err(StdError::generic_err(format!("Asset ids must be 1 to {} bytes", MAX_ASSET_ID_LEN)));
+        }
+        if update.new_leaf.len() != DIGEST_LEN || !(update.old_leaf.is_empty() || update.old_leaf.len() == DIGEST_LEN) {
+            return Err(StdError::generic_err(format!("Invalid leaf length for asset {}", update.asset_id)));
+        }
+    }
+
+    updates.sort_by(|a, b| a.asset_id.cmp(&b.asset_id));
+    if let Some(pair) = updates.windows(2).find(|w| w[0].asset_id == w[1].asset_id) {
+        return Err(StdError::generic_err(format!("Duplicate update for asset {}", pair[0].asset_id)));
+    }
+    Ok(())
+}
+
+/// Digest of an asset list, appended to the vote digest; empty for no
+/// assets. Order-independent: the list is hashed in canonical order.
+pub fn asset_updates_digest(updates: &[AssetUpdate]) -> Vec<u8> {
+    if updates.is_empty() {
+        return vec![];
+    }
+    let mut sorted: Vec<&AssetUpdate> = updates.iter().collect();
+    sorted.sort_by(|a, b| a.asset_id.cmp(&b.asset_id));
+
+    let mut out = CanonicalWriter::default();
+    out.bytes(ASSET_UPDATES_TAG.as_bytes());
+    write_asset_updates(&mut out, &sorted);
+    Sha256::digest(out.0).to_vec()
+}
+
+fn write_asset_updates(out: &mut CanonicalWriter, updates: &[&AssetUpdate]) {
+    out.int(updates.len() as u64);
+    for update in updates {
+        out.bytes(update.asset_id.as_bytes());
+        out.bytes(&update.old_leaf);
+        out.bytes(&update.new_leaf);
+    }
+}
+
+/// True if shares are already in canonical order (what the contract stores)
+pub fn is_canonical(transition: &StateTransition) -> bool {
+    transition.encrypted_shares.windows(2).all(|w| w[0].node_id < w[1].node_id)
+}
+
+/// Digest each node signs (secp256k1) when voting a transition valid, bound
+/// to one deployment so a partial cannot be replayed on another chain or
+/// instance. `asset_digest` is [`asset_updates_digest`] of its assets, empty
+/// for none. Starts with [`VOTE_TAG`] from [`VOTE_TAG_VERSION`] on.
+pub fn vote_digest(
+    chain_id: &str,
+    contract_address: &str,
+    validation_id: &str,
+    protocol_version: u16,
+    new_state_root: &[u8],
+    asset_digest: &[u8],
+) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    if protocol_version >= VOTE_TAG_VERSION {
+        hasher.update(VOTE_TAG);
+    }
+    hasher.update(deployment_prefix(chain_id, contract_address));
+    hasher.update(validation_id.as_bytes());
+    hasher.update(protocol_version.to_le_bytes());
+    hasher.update(new_state_root);
+    hasher.update(asset_digest);
+    hasher.finalize().to_vec()
+}
+
+/// Digest a node signs to attest its build, bound to this contract instance
+pub fn attestation_digest(contract_address: &str, git_commit: &str, binary_hash: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(ATTESTATION_TAG);
+    hasher.update(contract_address.as_bytes());
+    hasher.update(git_commit.as_bytes());
+    hasher.update(binary_hash);
+    hasher.finalize().to_vec()
+}
+
+/// Length-prefixed chain id and contract address, prepended to every
+/// deployment-bound digest
+pub fn deployment_prefix(chain_id: &str, contract_address: &str) -> Vec<u8> {
+    let mut prefix = Vec::with_capacity(8 + chain_id.len() + contract_address.len());
+    for part in [chain_id, contract_address] {
+        prefix.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        prefix.extend_from_slice(part.as_bytes());
+    }
+    prefix
+}
+
+/// Digest identifying a finalized root on one deployment, as returned with
+/// light client updates
+pub fn root_attestation_digest(chain_id: &str, contract_address: &str, block_height: u64, root: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(deployment_prefix(chain_id, contract_address));
+    hasher.update(block_height.to_be_bytes());
+    hasher.update(root);
+    hasher.finalize().to_vec()
+}
+
+/// Challenge a node signs with its registered key to leave the committee.
+/// Covering the key means a signature stops working once the key is rotated.
+pub fn deregistration_digest(contract_address: &str, node_id: u32, public_key: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(DEREGISTER_TAG);
+    hasher.update(contract_address.as_bytes());
+    hasher.update(node_id.to_be_bytes());
+    hasher.update(public_key);
+    hasher.finalize().to_vec()
+}
+
+/// Proof of possession for a key staged for `epoch`: signed by the staged
+/// key itself, so nobody can stage a key they cannot sign with
+pub fn key_possession_digest(contract_address: &str, node_id: u32, epoch: u64, public_key: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(STAGE_EPOCH_KEY_TAG);
+    hasher.update(contract_address.as_bytes());
+    hasher.update(node_id.to_be_bytes());
+    hasher.update(epoch.to_be_bytes());
+    hasher.update(public_key);
+    hasher.finalize().to_vec()
+}
+
+/// Digest a user's registered key (and, when required, one node) signs to
+/// move the user's commitment to `new_ipfs_cid` without a transition
+pub fn repoint_digest(contract_address: &str, old_root: &[u8], new_ipfs_cid: &str, content_hash: &[u8], nonce: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(REPOINT_TAG);
+    hasher.update(contract_address.as_bytes());
+    hasher.update(old_root);
+    hasher.update((new_ipfs_cid.len() as u32).to_be_bytes());
+    hasher.update(new_ipfs_cid.as_bytes());
+    hasher.update(content_hash);
+    hasher.update(nonce.to_be_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Digest a user's registered key signs to purge their history. `nonce` is
+/// the user's next purge nonce, so each signature purges once.
+pub fn purge_digest(chain_id: &str, contract_address: &str, user_address: &str, keep_current: bool, nonce: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(PURGE_HISTORY_TAG);
+    hasher.update(deployment_prefix(chain_id, contract_address));
+    hasher.update((user_address.len() as u32).to_be_bytes());
+    hasher.update(user_address.as_bytes());
+    hasher.update([keep_current as u8]);
+    hasher.update(nonce.to_be_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Digest that registers `public_key` as a user's key. A rotation is signed
+/// by the current key; a first key signs for itself, proving possession.
+/// `nonce` is the user's next key nonce, so each signature applies once.
+pub fn user_key_digest(chain_id: &str, contract_address: &str, user_address: &str, public_key: &[u8], nonce: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(USER_KEY_TAG);
+    hasher.update(deployment_prefix(chain_id, contract_address));
+    hasher.update((user_address.len() as u32).to_be_bytes());
+    hasher.update(user_address.as_bytes());
+    hasher.update((public_key.len() as u32).to_be_bytes());
+    hasher.update(public_key);
+    hasher.update(nonce.to_be_bytes());
+    hasher.finalize().to_vec()
+}
+
+/// Digest binding a compromise report to the reported node's current key on
+/// one deployment
+pub fn compromise_digest(chain_id: &str, contract_address: &str, node_id: u32, public_key: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(KEY_COMPROMISE_TAG);
+    hasher.update(deployment_prefix(chain_id, contract_address));
+    hasher.update(node_id.to_be_bytes());
+    hasher.update(public_key);
+    hasher.finalize().to_vec()
+}
+
+/// Hash identifying a transition's content; see the module docs for the
+/// encoding it covers
+pub fn transition_content_hash(transition: &StateTransition) -> StdResult<Vec<u8>> {
+    Ok(Sha256::digest(canonical_transition_bytes(transition)?).to_vec())
+}
+
+/// Canonical encoding of everything [`transition_content_hash`] covers.
+/// Fails when the shares cannot be put in canonical order.
+pub fn canonical_transition_bytes(transition: &StateTransition) -> StdResult<Vec<u8>> {
+    let mut canonical = transition.clone();
+    canonicalize_shares(&mut canonical)?;
+    let t = &canonical;
+
+    let mut out = CanonicalWriter::default();
+    out.bytes(TRANSITION_TAG.as_bytes());
+    out.bytes(t.user_address.as_bytes());
+    out.bytes(&t.old_state_root);
+    out.bytes(&t.new_state_root);
+    out.int(t.merkle_proof.len() as u64);
+    for element in &t.merkle_proof {
+        out.bytes(&element.hash);
+        out.flag(element.is_left);
+    }
+    out.bytes(t.new_state_ipfs.as_bytes());
+    out.flag(t.ipfs_gateway_hint.is_some());
+    if let Some(hint) = &t.ipfs_gateway_hint {
+        out.bytes(hint.as_bytes());
+    }
+    out.bytes(&t.content_hash);
+    out.int(t.encrypted_shares.len() as u64);
+    for share in &t.encrypted_shares {
+        out.int(u64::from(share.node_id));
+        out.bytes(&share.encrypted_data);
+        out.int(match share.encoding {
+            ShareEncoding::Raw => 0,
+            ShareEncoding::Zstd => 1,
+            ShareEncoding::Deflate => 2,
+        });
+    }
+    out.int(t.vss_commitments.len() as u64);
+    for commitment in &t.vss_commitments {
+        out.bytes(commitment);
+    }
+    out.int(t.vss_proof_polynomial.len() as u64);
+    for coefficient in &t.vss_proof_polynomial {
+        out.bytes(coefficient.as_bytes());
+    }
+    out.int(u64::from(t.protocol_version));
+    out.int(t.depends_on.len() as u64);
+    for dependency in &t.depends_on {
+        out.bytes(dependency.as_bytes());
+    }
+    if !t.asset_updates.is_empty() {
+        let mut updates: Vec<&AssetUpdate> = t.asset_updates.iter().collect();
+        updates.sort_by(|a, b| a.asset_id.cmp(&b.asset_id));
+        write_asset_updates(&mut out, &updates);
+    }
+    Ok(out.0)
+}
+
+/// Hash of an audit bundle; see the module docs for what it covers
+pub fn audit_bundle_hash(bundle: &AuditBundleResponse) -> Vec<u8> {
+    let b = bundle;
+    let mut out = CanonicalWriter::default();
+    out.bytes(AUDIT_BUNDLE_TAG.as_bytes());
+    out.bytes(b.validation_id.as_bytes());
+    out.int(b.block_height);
+    out.bytes(&b.new_state_root);
+    out.int(u64::from(b.protocol_version));
+    out.bytes(&b.transition_hash);
+    write_asset_updates(&mut out, &b.asset_updates.iter().collect::<Vec<_>>());
+    out.int(b.epoch);
+    out.int(b.key_epoch);
+    out.int(b.signers.len() as u64);
+    for signer in &b.signers {
+        out.int(u64::from(*signer));
+    }
+    out.int(b.signer_keys.len() as u64);
+    for key in &b.signer_keys {
+        out.int(u64::from(key.node_id));
+        out.bytes(&key.public_key);
+    }
+    out.int(b.signed_validation_ids.len() as u64);
+    for validation_id in &b.signed_validation_ids {
+        out.bytes(validation_id.as_bytes());
+    }
+    out.int(b.signed_messages.len() as u64);
+    for message in &b.signed_messages {
+        out.bytes(message);
+    }
+    out.bytes(&b.threshold_signature);
+    out.bytes(&b.root_before);
+    out.bytes(&b.root_after);
+    Sha256::digest(out.0).to_vec()
+}
+
+#[derive(Default)]
+struct CanonicalWriter(Vec<u8>);
+
+impl CanonicalWriter {
+    fn int(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn bytes(&mut self, value: &[u8]) {
+        self.int(value.len() as u64);
+        self.0.extend_from_slice(value);
+    }
+
+    fn flag(&mut self, value: bool) {
+        self.0.push(u8::from(value));
+    }
+}
@@ -1,6 +1,8 @@
+use cosmwasm_std::{Binary, Coin, Timestamp};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use secret_toolkit::storage::{Item, Keymap};
+use secret_toolkit::serialization::{Bincode2, Json};
+use secret_toolkit::storage::{Item, Keymap, KeymapBuilder, Keyset, KeysetBuilder, WithoutIter};
 
 // ============================================================================
 // STATE STRUCTURES
@@ -13,18 +15,371 @@ pub struct State {
     pub threshold: u32,
     pub current_state_root: Vec<u8>,
     pub block_height: u64,
+
+    // Last finalization (for light client updates)
+    #[serde(default)]
+    pub last_validation_id: String,
+    #[serde(default)]
+    pub last_threshold_signature: Vec<u8>,
+    #[serde(default)]
+    pub last_signers: Vec<u32>,  // node_ids whose partials form the aggregate
+    #[serde(default)]
+    pub last_signed_ids: Vec<String>,  // Validation id each signer's partial covers
+    #[serde(default)]
+    pub epoch: u64,  // Committee epoch, bumped by every change to the active set or its keys
+    #[serde(default = "default_protocol_version")]
+    pub last_protocol_version: u16,
+
+    // Lowest block_height still kept in ROOT_HISTORY; everything below is
+    // pruned. Starts at 0 for states written before history existed, where
+    // pruning the missing entries is a harmless no-op.
+    #[serde(default)]
+    pub oldest_retained_height: u64,
+    // Lowest block_height whose finalized record may still hold partials
+    #[serde(default)]
+    pub oldest_partials_height: u64,
+
+    // Set at instantiate and cleared once `threshold` nodes are active;
+    // submissions are rejected until then
+    #[serde(default)]
+    pub pending_committee: bool,
+
+    // Fixed at instantiate; empty keeps the unsalted ids and zero genesis root
+    #[serde(default)]
+    pub deployment_salt: Vec<u8>,
+
+    // `epoch` and `key_epoch` count different things and move independently.
+    // `epoch` numbers committees: a validation keeps the one it was submitted
+    // in, and its partials verify against that committee's snapshot.
+    // `key_epoch` numbers threshold keys and is bumped only by
+    // RotateThresholdKey (0 until the first key is set): a validation and its
+    // finalized record keep the one their aggregate verifies under. Several
+    // committee epochs can share a key; THRESHOLD_KEYS records the committee
+    // epoch each key was rotated in.
+    #[serde(default)]
+    pub key_epoch: u64,
+    // Committee `epoch` of the last finalized validation, whose keys verify
+    // the light client update built from it
+    #[serde(default)]
+    pub last_epoch: u64,
+    // Asset list the last finalization's votes covered (see protocol::asset_updates_digest)
+    #[serde(default)]
+    pub last_asset_digest: Vec<u8>,
+    // BackfillCommitment is accepted below this height; opened once, by the
+    // migration to schema 3
+    #[serde(default)]
+    pub backfill_until: Option<u64>,
+}
+
+/// Which chain and contract this state belongs to, fixed at instantiate
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DeploymentIdentity {
+    pub chain_id: String,
+    pub contract_address: String,
+    pub genesis_time: u64,  // Block time (seconds) of instantiation
+}
+
+/// Admin-controlled contract configuration
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    // Development deployment (single node / threshold 1); fixed at instantiate
+    #[serde(default)]
+    pub dev_mode: bool,
+    pub owner: String,
+    #[serde(default)]
+    pub pending_owner: Option<String>,  // Must call AcceptOwnership to take over
+    pub supported_protocol_versions: Vec<u16>,
+    #[serde(default = "default_tree_depth")]
+    pub tree_depth: u8,  // Depth of the sparse Merkle tree (fixed at instantiate)
+    #[serde(default = "default_attestation_window")]
+    pub attestation_window: u64,  // Seconds a build attestation stays current
+    #[serde(default)]
+    pub unanimous_operations: Vec<OperationKind>,  // Need every active node, not just threshold
+    #[serde(default)]
+    pub event_verbosity: EventVerbosity,
+    // Committee-driven admin recovery (fixed at instantiate)
+    #[serde(default = "default_recovery_supermajority")]
+    pub recovery_supermajority: u8,  // Percent of active nodes that must approve
+    #[serde(default = "default_recovery_delay")]
+    pub recovery_delay: u64,  // Seconds the owner has to veto after quorum
+    #[serde(default = "default_root_history_retention")]
+    pub root_history_retention: u64,  // Finalized roots kept for GetRootAtHeight
+    #[serde(default)]
+    pub unique_cids: bool,  // Reject a new_state_ipfs another user currently points at
+    #[serde(default)]
+    pub validation_cooldown: u64,  // Minimum blocks between a node's votes (0 = off)
+    #[serde(default)]
+    pub strict_root_agreement: bool,  // Valid votes must attest the exact new root bytes
+    #[serde(default)]
+    pub challenge_window: u64,  // Blocks a finalized commitment stays revertible (0 = never)
+    #[serde(default)]
+    pub commitment_confirmations: u64,  // Blocks after finalization before GetStateCommitment shows it
+    #[serde(default)]
+    pub vote_precedence: VotePrecedence,  // Which decision applies when one vote makes both
+    #[serde(default)]
+    pub reward_policy: RewardPolicy,  // How GetRewardSplit divides an epoch's rewards
+    #[serde(default)]
+    pub reject_threshold: u32,  // Invalid votes that reject a transition outright (0 = off, else threshold..=active nodes)
+    #[serde(default)]
+    pub retain_partials: RetainPartials,  // Partials copied into finalized records
+    #[serde(default = "default_partial_retention")]
+    pub partial_retention: u64,  // Finalizations partials are kept for (records last root_history_retention)
+    #[serde(default = "default_max_transition_size")]
+    pub max_transition_size: u64,  // Bytes of serialized transition accepted per submission
+    #[serde(default = "default_max_share_len")]
+    pub max_share_len: u32,  // Bytes of encrypted_data (the compressed form) accepted per share
+    #[serde(default = "default_max_user_storage")]
+    pub max_user_storage: u64,  // Approximate bytes a user's keys may occupy before submissions fail
+    #[serde(default = "default_pending_watermark")]
+    pub pending_watermark: u64,  // Pending validations above this report the contract as degraded
+    #[serde(default = "default_validation_timeout")]
+    pub validation_timeout: u64,  // Seconds a validation is expected to settle in; clients resubmit after
+    #[serde(default)]
+    pub accept_submissions_at: Option<Timestamp>,  // Set at instantiate; submissions before it are refused
+    // Users with a commitment must attach a non-empty proof, and only a
+    // first-time user starting from the empty root may omit one. The proof is
+    // not checked here: no global tree is kept on chain, so nodes verify it.
+    #[serde(default, alias = "require_merkle_proof")]
+    pub require_nonempty_merkle_proof: bool,
+    #[serde(default)]
+    pub repoint_cosign: bool,  // RepointStorage also needs one active node's signature
+    #[serde(default = "default_max_work_payloads")]
+    pub max_work_payloads: u32,  // Full work items per GetPendingForNode page; the rest are stubs
+    #[serde(default)]
+    pub require_nonce_commitments: bool,  // Two-round signing: CommitNonce, then SubmitPartial
+    #[serde(default)]
+    pub registration_cooldown: u64,  // Minimum blocks between RegisterMPCNode calls per address (0 = off)
+    #[serde(default)]
+    pub registration_window: u64,  // Blocks per registration-cap window
+    #[serde(default)]
+    pub max_registrations_per_window: u32,  // RegisterMPCNode calls allowed per window (0 = off)
+    #[serde(default)]
+    pub breaker_window: u64,  // Blocks the circuit breaker looks back over
+    #[serde(default)]
+    pub breaker_max_rejections: u32,  // Users with a vote-threshold rejection tolerated within the window before auto-pause (0 = off)
+    #[serde(default)]
+    pub subscription_fee: Option<Coin>,  // Paid per SubscribeValidation to whoever settles it; None = subscriptions disabled
+    #[serde(default)]
+    pub equivocation_limit: u32,  // Offenses that deactivate a node (0 = record only)
+    #[serde(default)]
+    pub expiry_bounty: Option<Coin>,  // Deposit per validation created, paid exactly with the submission and to whoever expires it; None = none
+    #[serde(default)]
+    pub finalizer_window: u64,  // Blocks only the designated finalizer's FinalizeTransition acts (0 = anyone)
+    #[serde(default)]
+    pub account_ttl_seconds: u64,  // Idle time after SetUserKey before a never-active account is purgeable (0 = never)
+    #[serde(default)]
+    pub max_validation_blocks: u64,  // Blocks after submission a validation may still be voted on or finalized (0 = no limit)
+    #[serde(default)]
+    pub da_challenge_bond: Option<Coin>,  // Posted per ChallengeDataAvailability; None = challenges disabled
+    #[serde(default)]
+    pub outbound_failure_watermark: u32,  // Parked failed sends above this report the contract as degraded
+}
+
+/// RegisterMPCNode calls in the current fixed window of
+/// config.registration_window blocks
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct RegistrationWindow {
+    pub started_at: u64,  // Block height, a multiple of the window length
+    pub count: u32,
+}
+
+/// The latest committee rejection of one user's validations, as the
+/// circuit breaker counts it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RecentRejection {
+    pub user_address: String,
+    pub height: u64,
+}
+
+/// Which contributing partial signatures a finalized record keeps
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RetainPartials {
+    #[default]
+    None,
+    All,
+    FirstK(u32),  // Lowest node ids first
+}
+
+/// Decides a contested vote: one that leaves a transition both at threshold,
+/// which it was not before, and rejected, because config.reject_threshold
+/// invalid votes are in or because a batch leg can no longer reach threshold
+/// (votes of since-deactivated nodes still count as valid, while only active
+/// nodes can add more). By default the rejection wins.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VotePrecedence {
+    #[default]
+    RejectWins,
+    AcceptWins,
+}
+
+impl VotePrecedence {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VotePrecedence::RejectWins => "reject_wins",
+            VotePrecedence::AcceptWins => "accept_wins",
+        }
+    }
+}
+
+/// How an epoch's reward amount is divided among the nodes that voted in it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RewardPolicy {
+    #[default]
+    Equal,
+    EffortWeighted,  // Proportional to effort units
+}
+
+pub fn default_partial_retention() -> u64 {
+    10_000
+}
+
+pub fn default_max_transition_size() -> u64 {
+    64 * 1024
+}
+
+pub fn default_max_share_len() -> u32 {
+    4096
+}
+
+pub fn default_max_user_storage() -> u64 {
+    1024 * 1024
+}
+
+pub fn default_pending_watermark() -> u64 {
+    1_000
+}
+
+pub fn default_validation_timeout() -> u64 {
+    24 * 60 * 60
+}
+
+pub fn default_max_work_payloads() -> u32 {
+    10
+}
+
+pub fn default_root_history_retention() -> u64 {
+    100_000
+}
+
+pub fn default_recovery_supermajority() -> u8 {
+    80
+}
+
+pub fn default_recovery_delay() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+/// How much detail handlers put in emitted events (ordered by detail)
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, PartialOrd, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventVerbosity {
+    // Action, validation id and terminal outcomes only
+    Minimal,
+    // Adds vote counts, roots and user addresses
+    #[default]
+    Standard,
+    // Adds digests, committee hashes and timing
+    Debug,
+}
+
+/// Classification of a validation for per-operation approval rules
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    StateTransition,
+    Transfer,
+}
+
+pub fn default_tree_depth() -> u8 {
+    32
+}
+
+pub fn default_attestation_window() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+/// Usage counters for dashboards and deprecation planning
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Stats {
+    pub submissions_by_version: Vec<VersionCount>,
+    #[serde(default)]
+    pub validation_counts: ValidationCounts,
+}
+
+/// Validations by status, maintained as they are created and settled
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct ValidationCounts {
+    pub pending: u64,
+    pub finalized: u64,
+    pub rejected: u64,  // Rejected, Superseded, DataUnavailable or DependencyFailed
+    pub expired: u64,   // Expired, Cancelled or Unwound
+    #[serde(default)]
+    pub challenged: u64,  // Finalized, then reverted by ChallengeFinalized; no longer in `finalized`
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VersionCount {
+    pub version: u16,
+    pub count: u64,
 }
 
 /// MPC committee member
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MPCNode {
-    pub address: String,
+    pub address: String,  // Owner key: registers, rotates, sets the operator
     pub node_id: u32,
     pub public_key: Vec<u8>,
     pub active: bool,
+    #[serde(default)]
+    pub operator: Option<String>,  // Hot key allowed to vote and read shares
+    #[serde(default)]
+    pub last_active_height: Option<u64>,  // Block of the node's last accepted vote
+    #[serde(default)]
+    pub staged_public_key: Option<Vec<u8>>,  // Becomes public_key at the next AdvanceEpoch
+    #[serde(default)]
+    pub slashed: bool,  // Proven faulty by SlashNode; cannot reactivate
+    #[serde(default)]
+    pub offenses: u32,  // Equivocations recorded against the node
+}
+
+impl MPCNode {
+    /// True if `sender` may run this node's validator duties
+    pub fn is_operated_by(&self, sender: &str) -> bool {
+        self.address == sender || self.operator.as_deref() == Some(sender)
+    }
+}
+
+/// Permanent record of a key reported by ReportKeyCompromise
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KeyCompromise {
+    pub node_id: u32,
+    pub reported_by: String,
+    pub reported_at: u64,  // Block height
+    pub proven: bool,      // Report carried a signature by the key itself
+    pub quarantined: Vec<String>,  // Filled only by releases that blocked validations at report time
+}
+
+/// Off-chain facts a node publishes about itself
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct NodeMetadata {
+    pub attestation: Option<BuildAttestation>,
+    #[serde(default)]
+    pub auto_carryover_votes: bool,  // Reuse this node's vote when a cancelled transition is resubmitted
 }
 
-/// User's state commitment (stored on-chain)
+/// Signed statement of the software build a node is running
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BuildAttestation {
+    pub git_commit: String,
+    pub binary_hash: Vec<u8>,
+    pub attested_at: u64,
+}
+
+/// User's state commitment (stored on-chain). Stored in Bincode, so a new
+/// field needs a schema step; the serde defaults only serve JSON readers.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct StateCommitment {
     pub user_address: String,
@@ -33,6 +388,51 @@ pub struct StateCommitment {
     pub merkle_proof: Vec<u8>,     // Proof in global tree
     pub nonce: u64,
     pub updated_at: u64,
+    #[serde(default)]
+    pub leaf_index: u64,           // Position of the user's leaf in the global tree
+    #[serde(default)]
+    pub clamped: bool,             // updated_at was raised past a lower block time
+    #[serde(default)]
+    pub lock_at_height: u64,       // ChallengeFinalized can revert it before this height
+    #[serde(default)]
+    pub ipfs_gateway_hint: Option<String>,  // Where to fetch ipfs_cid if the default gateway is down
+    #[serde(default)]
+    pub finalized_at_height: u64,
+    #[serde(default)]
+    pub content_hash: Vec<u8>,     // Hash of the content at ipfs_cid; replaced by RepointStorage
+    #[serde(default)]
+    pub epoch: u64,                // Committee epoch the finalizing validation was submitted in
+    #[serde(default)]
+    pub committee_hash: Vec<u8>,   // Active committee when it finalized (see contract::committee_hash)
+    #[serde(default)]
+    pub threshold_signature: Vec<u8>,
+    #[serde(default)]
+    pub asset_leaves: Vec<AssetLeaf>,  // Every asset a transition has moved, sorted by asset_id
+}
+
+/// Current leaf of one of a user's assets
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetLeaf {
+    pub asset_id: String,
+    pub leaf: Vec<u8>,
+}
+
+impl StateCommitment {
+    /// False for commitments from before schema 3 until BackfillCommitment
+    /// fills in what they were finalized with
+    pub fn metadata_complete(&self) -> bool {
+        self.finalized_at_height > 0 && !self.committee_hash.is_empty() && !self.threshold_signature.is_empty()
+    }
+}
+
+/// Finalization facts of a pre-schema-3 commitment, taken from archived
+/// transaction events
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommitmentMetadata {
+    pub state_root: Vec<u8>,  // Must match the stored commitment
+    pub finalized_at_height: u64,
+    pub committee_hash: Vec<u8>,
+    pub threshold_signature: Vec<u8>,
 }
 
 /// Secret shares sent to MPC nodes for validation
@@ -62,8 +462,13 @@ pub struct StateTransition {
     // Merkle proof
     pub merkle_proof: Vec<MerkleProofElement>,
 
-    // IPFS pointer
+    // IPFS pointer, plus an optional gateway to fetch it from
     pub new_state_ipfs: String,
+    #[serde(default)]
+    pub ipfs_gateway_hint: Option<String>,
+    // SHA-256 of the content behind new_state_ipfs, checked by ProveAvailability
+    #[serde(default)]
+    pub content_hash: Vec<u8>,
 
     // User signature
     pub user_signature: Vec<u8>,
@@ -76,12 +481,58 @@ pub struct StateTransition {
     pub vss_commitments: Vec<Vec<u8>>,  // c_i = H(v_i || R(i) || γ_i) for each node
     #[serde(default)]
     pub vss_proof_polynomial: Vec<String>, // Z(X) polynomial coefficients as hex strings
+
+    // Protocol version the client built this transition with
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u16,
+
+    // Opaque routing hints for off-chain coordinators. Moved onto the
+    // PendingValidation on submit; not part of the content hash and never
+    // reaches nodes' share data.
+    #[serde(default)]
+    pub coordinator_metadata: Option<Binary>,
+
+    // Validations that must finalize before this one may. Standalone
+    // submissions only; this one fails if any of them does.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    // Asset leaves moved atomically with the root; empty for a plain
+    // transition. Sorted by asset_id on submit.
+    #[serde(default)]
+    pub asset_updates: Vec<AssetUpdate>,
+}
+
+/// One asset leaf a transition moves from `old_leaf` (empty if the user has
+/// never held the asset) to `new_leaf`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AssetUpdate {
+    pub asset_id: String,
+    pub old_leaf: Vec<u8>,
+    pub new_leaf: Vec<u8>,
+}
+
+pub fn default_protocol_version() -> u16 {
+    1
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct EncryptedShares {
     pub node_id: u32,
     pub encrypted_data: Vec<u8>,  // Encrypted SecretShares for this node
+    #[serde(default)]
+    pub encoding: ShareEncoding,  // How the node unwraps encrypted_data after decrypting
+}
+
+/// Compression applied to a share before encryption. The contract cannot
+/// decompress; it only checks the compressed size against config.max_share_len.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareEncoding {
+    #[default]
+    Raw,
+    Zstd,
+    Deflate,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -106,6 +557,71 @@ pub struct Transfer {
     pub amount_commitment: Vec<u8>,
 }
 
+/// Links the two legs of a submitted transfer
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferRecord {
+    pub transfer_id: String,
+    pub sender: String,
+    pub recipient: String,
+    pub sender_validation_id: String,
+    pub recipient_validation_id: String,
+    pub amount_commitment: Vec<u8>,
+    pub sender_finalized: bool,
+    pub recipient_finalized: bool,
+    pub status: TransferStatus,
+
+    // Conditional transfers: legs only finalize once SHA-256(preimage) == hashlock
+    pub hashlock: Option<Vec<u8>>,
+    pub deadline: Option<u64>,
+    pub preimage: Option<Vec<u8>>,
+    #[serde(default)]
+    pub created_at: u64,  // Block time of submission; pauses after it extend the deadline
+    #[serde(default)]
+    pub submitter: String,  // Account that sent the transfer; `sender` is only its claim
+}
+
+impl TransferRecord {
+    /// A hashlocked transfer holds its legs until the preimage is revealed
+    pub fn is_locked(&self) -> bool {
+        self.hashlock.is_some() && self.preimage.is_none()
+    }
+}
+
+/// One sender leg fanned out to many recipients, settled as a unit: every leg
+/// must reach threshold before any finalizes, and one rejection unwinds all
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TransferGroup {
+    pub group_id: String,
+    pub sender: String,
+    pub sender_validation_id: String,
+    pub legs: Vec<GroupLeg>,
+    pub status: TransferStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GroupLeg {
+    pub recipient: String,
+    pub validation_id: String,
+    pub amount_commitment: Vec<u8>,
+}
+
+impl TransferGroup {
+    /// Sender leg first, then recipients in submission order
+    pub fn validation_ids(&self) -> Vec<String> {
+        std::iter::once(self.sender_validation_id.clone())
+            .chain(self.legs.iter().map(|l| l.validation_id.clone()))
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferStatus {
+    Pending,
+    Completed,
+    Unwound,
+}
+
 /// Pending validation (waiting for threshold MPC signatures)
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PendingValidation {
@@ -114,6 +630,278 @@ pub struct PendingValidation {
     pub validations: Vec<NodeValidation>,
     pub threshold_reached: bool,
     pub created_at: u64,
+    #[serde(default = "default_protocol_version")]
+    pub protocol_version: u16,
+    #[serde(default)]
+    pub transfer_id: Option<String>,  // Set when this is one leg of a transfer
+    #[serde(default)]
+    pub blocked: Option<BlockReason>,  // Never finalizes once set
+    #[serde(default)]
+    pub group_id: Option<String>,  // Set when this is a leg of a batch transfer
+    #[serde(default)]
+    pub da_challenge: Option<DataChallenge>,  // At most one per validation
+    #[serde(default)]
+    pub precedence_fired: Option<VotePrecedence>,  // Accepted over a simultaneous rejection
+    #[serde(default)]
+    pub coordinator_metadata: Option<Binary>,  // Submitter's opaque hints, returned as-is
+    #[serde(default)]
+    pub key_epoch: u64,  // Threshold key its aggregate is verified against
+    #[serde(default)]
+    pub epoch: u64,  // Committee epoch at submission; votes verify against its keys
+    #[serde(default)]
+    pub nonce_commitments: Vec<NonceCommitment>,  // First signing round, when required
+    #[serde(default)]
+    pub subscribers: Vec<Subscriber>,  // Notified once when the validation settles
+    #[serde(default)]
+    pub bounty: Option<Coin>,  // Escrowed deposit: the expirer's reward, else refunded to the user
+    #[serde(default)]
+    pub finalizer: Option<DesignatedFinalizer>,  // Chosen when threshold is reached while locked
+    #[serde(default)]
+    pub submission_height: u64,  // Block height at submission; 0 for validations that predate it
+}
+
+/// The node expected to call FinalizeTransition for one validation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DesignatedFinalizer {
+    pub node_id: u32,  // Lowest-id valid voter at threshold
+    pub until: u64,    // Block height before which other callers are no-ops
+}
+
+/// What one settled validation owes outside the contract
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SettledValidation {
+    pub validation_id: String,
+    pub subscribers: Vec<Subscriber>,
+    pub refund: Option<(String, Coin)>,  // Recipient and escrowed bounty
+}
+
+/// Bounty on its way out of the contract, kept from dispatch until its bank
+/// send is known to have gone through
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OutboundSend {
+    pub recipient: String,
+    pub amount: Coin,
+    pub origin: String,  // Validation whose bounty it is; a challenge bond's ends in "/bond"
+    pub attempts: u32,   // Sends dispatched so far
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// Bounty the contract sent out for one validation, kept so a disputed
+/// payment can be traced to its bank send
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawalReceipt {
+    pub validation_id: String,
+    pub recipient: String,
+    pub amount: Coin,
+    pub send_id: u64,  // Key of its OUTBOUND_FAILURES entry while it is Failed
+    pub status: SendStatus,
+    pub error: Option<String>,  // From the last failed attempt
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SendStatus {
+    InFlight,
+    Delivered,
+    Failed,
+}
+
+/// Contract that receives a `SubscriberMsg` when one validation settles
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Subscriber {
+    pub address: String,
+    pub code_hash: String,
+}
+
+/// A node's commitment to its signing nonce for one validation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NonceCommitment {
+    pub node_id: u32,
+    pub commitment: Vec<u8>,  // x coordinate of the nonce point R; the partial's r must equal it
+    pub committed_at: u64,  // Block height
+}
+
+/// One change of a user's key, kept so a disputed signature can be matched
+/// to the key in force when it was made
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KeyRotation {
+    pub previous_key: Option<Vec<u8>>,  // None for the first key
+    pub public_key: Vec<u8>,
+    pub height: u64,
+}
+
+/// Bond a node posted with a data availability challenge: forfeited to the
+/// user if availability is proven, returned once the validation settles
+/// otherwise
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChallengeBond {
+    pub challenger: String,
+    pub amount: Coin,
+}
+
+/// A node's claim that a transition's encrypted state cannot be fetched
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DataChallenge {
+    pub node_id: u32,
+    pub opened_at: u64,  // Block height
+    pub deadline: u64,   // Unanswered at this height, the validation is rejected
+    pub answered: bool,
+}
+
+impl DataChallenge {
+    pub fn is_open(&self) -> bool {
+        !self.answered
+    }
+}
+
+/// Why a pending validation can no longer finalize
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockReason {
+    // Strict mode: valid voters attested roots other than the transition's
+    RootDisagreement {
+        expected: Vec<u8>,
+        divergent: Vec<NodeRoot>,
+    },
+    // A share was encrypted to a key reported compromised
+    KeyCompromised {
+        node_id: u32,
+    },
+}
+
+impl BlockReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockReason::RootDisagreement { .. } => "root_disagreement",
+            BlockReason::KeyCompromised { .. } => "key_compromised",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NodeRoot {
+    pub node_id: u32,
+    pub root: Vec<u8>,
+}
+
+/// What a finalization produced, kept for GetFinalized while its root is
+/// retained. Partials are dropped earlier than the rest.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FinalizedRecord {
+    pub validation_id: String,
+    pub user_address: String,
+    pub new_root: Vec<u8>,
+    pub threshold_signature: Vec<u8>,
+    pub signers: Vec<u32>,
+    pub partials: Vec<RetainedPartial>,
+    #[serde(default)]
+    pub key_epoch: u64,
+    #[serde(default)]
+    pub asset_digest: Vec<u8>,  // Asset list the signers' votes covered; empty for plain transitions
+    #[serde(default)]
+    pub audit: Option<AuditTrail>,  // None for records written before audit bundles
+}
+
+/// What GetAuditBundle needs beyond the record itself, frozen at finalization
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AuditTrail {
+    pub old_state_root: Vec<u8>,
+    pub new_state_ipfs: String,
+    pub content_hash: Vec<u8>,
+    pub protocol_version: u16,
+    pub transition_hash: Vec<u8>,  // transition_content_hash of the finalized transition
+    pub asset_updates: Vec<AssetUpdate>,
+    pub epoch: u64,
+    pub signed_validation_ids: Vec<String>,  // Aligned with the record's signers
+    pub signer_keys: Vec<EpochMember>,       // Aligned with the record's signers
+    pub root_before: Vec<u8>,
+}
+
+/// The most recent finalization, kept regardless of root history retention
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LastFinalized {
+    pub validation_id: String,
+    pub user_address: String,
+    pub new_root: Vec<u8>,
+    pub block_height: u64,         // Contract height the root was recorded at
+    pub finalized_at_height: u64,  // Chain height
+    pub threshold_signature: Vec<u8>,
+    pub signers: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RetainedPartial {
+    pub node_id: u32,
+    pub partial_signature: Vec<u8>,
+}
+
+/// How a validation left the pending set (kept after the pending entry is gone)
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ValidationOutcome {
+    pub validation_id: String,
+    pub status: OutcomeStatus,
+    pub settled_at: u64,
+    #[serde(default)]
+    pub precedence: Option<VotePrecedence>,  // Set when the outcome was a tie decided by config.vote_precedence
+    #[serde(default)]
+    pub contested: bool,  // Accept and reject were both reached; `precedence` says which won
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Finalized,
+    Unwound,
+    // A conflicting update to the same leaf finalized first
+    Superseded,
+    // Could no longer reach threshold (batch legs), or enough invalid votes
+    Rejected,
+    // Rejected: a data availability challenge went unanswered
+    DataUnavailable,
+    // Withdrawn by the submitting user
+    Cancelled,
+    // A validation it depended on settled without finalizing
+    DependencyFailed,
+    // Outlived its deadline and was settled by ExpireValidation
+    Expired,
+}
+
+impl OutcomeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutcomeStatus::Finalized => "finalized",
+            OutcomeStatus::Unwound => "unwound",
+            OutcomeStatus::Superseded => "superseded",
+            OutcomeStatus::Rejected => "rejected",
+            OutcomeStatus::DataUnavailable => "data_unavailable",
+            OutcomeStatus::Cancelled => "cancelled",
+            OutcomeStatus::DependencyFailed => "dependency_failed",
+            OutcomeStatus::Expired => "expired",
+        }
+    }
+}
+
+/// A period the owner paused voting and submissions for. Deadlines are
+/// checked against block time minus the paused time since they started, and
+/// block windows against height minus the paused blocks.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseWindow {
+    pub paused_at: u64,
+    pub resumed_at: Option<u64>,  // None while paused
+    pub paused_for: u64,  // Seconds paused within the window, below its span once merged
+    pub paused_at_height: u64,
+    pub resumed_at_height: Option<u64>,
+    pub paused_blocks: u64,  // Blocks paused within the window, like paused_for
+}
+
+/// In-flight proposal by the committee to replace a lost admin key
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminRecovery {
+    pub new_admin: String,
+    pub approvals: Vec<u32>,  // Node ids, proposer first
+    pub proposed_at: u64,
+    pub quorum_reached_at: Option<u64>,  // Veto window starts here
 }
 
 /// Individual node's validation
@@ -122,12 +910,392 @@ pub struct NodeValidation {
     pub node_id: u32,
     pub valid: bool,
     pub partial_signature: Vec<u8>,  // TSS partial signature
+    #[serde(default)]
+    pub attested_root: Option<Vec<u8>>,  // New root the node checked (strict mode)
+    #[serde(default)]
+    pub voted_at: u64,  // Block height the vote was recorded (or carried over)
+    #[serde(default)]
+    pub carried_from: Option<String>,  // Validation id the partial was signed for, if carried over
+}
+
+impl NodeValidation {
+    /// Validation id whose vote digest `partial_signature` covers
+    pub fn signed_validation_id<'a>(&'a self, validation_id: &'a str) -> &'a str {
+        self.carried_from.as_deref().unwrap_or(validation_id)
+    }
+}
+
+/// Committee public key for aggregate signatures, set by the owner once the
+/// committee has produced new key material
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ThresholdKey {
+    pub key_epoch: u64,
+    pub public_key: Vec<u8>,
+    pub committee_epoch: u64,  // state.epoch when it was recorded
+    pub rotated_at: u64,       // Block height
+}
+
+/// Committee as it stood when an epoch began
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CommitteeSnapshot {
+    pub epoch: u64,
+    pub started_at: u64,  // Block height
+    pub threshold: u32,
+    pub members: Vec<EpochMember>,  // Active nodes only
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EpochMember {
+    pub node_id: u32,
+    pub public_key: Vec<u8>,
+}
+
+/// How a node voted on one transition's content, kept to spot a contradicting
+/// vote on a byte-identical resubmission against the same user state
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContentVote {
+    pub content_hash: Vec<u8>,  // transition_content_hash of the transition
+    pub validation_id: String,
+    pub valid: bool,
+    pub voted_at: u64,  // Block height
+    pub committed_root: Vec<u8>,  // User's committed root when the vote was cast; empty if none
+    pub committed_nonce: u64,
+}
+
+/// Two votes by one node that disagree about the same transition content
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EquivocationEvidence {
+    pub node_id: u32,
+    pub content_hash: Vec<u8>,
+    pub first: ContentVote,
+    pub second: ContentVote,
+}
+
+/// A node's on-chain work in one epoch. Each vote costs one unit plus one
+/// per EFFORT_BYTES_PER_UNIT of share data the node had to verify.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct NodeEffort {
+    pub votes: u64,
+    pub effort_units: u64,
+}
+
+/// Votes of a transition its user cancelled, kept by content hash so an
+/// identical resubmission can carry them over
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CancelledVotes {
+    pub validation_id: String,
+    pub cancelled_at: u64,  // Block height
+    pub votes: Vec<NodeValidation>,
+}
+
+/// State as the first release stored it, before the schema marker; only the
+/// schema 0 migration reads it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyState {
+    pub mpc_nodes: Vec<LegacyMPCNode>,
+    pub threshold: u32,
+    pub current_state_root: Vec<u8>,
+    pub block_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyMPCNode {
+    pub address: String,
+    pub node_id: u32,
+    pub public_key: Vec<u8>,
+    pub active: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyStateCommitment {
+    pub user_address: String,
+    pub state_root: Vec<u8>,
+    pub ipfs_cid: String,
+    pub merkle_proof: Vec<u8>,
+    pub nonce: u64,
+    pub updated_at: u64,
+}
+
+/// Commitment as schemas 1 and 2 stored it, before the finalization
+/// metadata and asset leaves; only the schema 3 migration reads it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyStateCommitmentV2 {
+    pub user_address: String,
+    pub state_root: Vec<u8>,
+    pub ipfs_cid: String,
+    pub merkle_proof: Vec<u8>,
+    pub nonce: u64,
+    pub updated_at: u64,
+    pub leaf_index: u64,
+    pub clamped: bool,
+    pub lock_at_height: u64,
+    pub ipfs_gateway_hint: Option<String>,
+}
+
+/// Config as schema 6 and earlier stored it, in Bincode, where missing
+/// fields do not fall back to their defaults; only the schema 7 migration
+/// reads it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyConfig {
+    pub dev_mode: bool,
+    pub owner: String,
+    pub pending_owner: Option<String>,
+    pub supported_protocol_versions: Vec<u16>,
+    pub tree_depth: u8,
+    pub attestation_window: u64,
+    pub unanimous_operations: Vec<OperationKind>,
+    pub event_verbosity: EventVerbosity,
+    pub recovery_supermajority: u8,
+    pub recovery_delay: u64,
+    pub root_history_retention: u64,
+    pub unique_cids: bool,
+    pub validation_cooldown: u64,
+    pub strict_root_agreement: bool,
+    pub challenge_window: u64,
+    pub commitment_confirmations: u64,
+    pub vote_precedence: VotePrecedence,
+    pub reward_policy: RewardPolicy,
+    pub reject_threshold: u32,
+    pub retain_partials: RetainPartials,
+    pub partial_retention: u64,
+    pub max_transition_size: u64,
+    pub max_user_storage: u64,
+    pub pending_watermark: u64,
+    pub validation_timeout: u64,
+    pub accept_submissions_at: Option<Timestamp>,
+    pub require_merkle_proof: bool,
+    pub repoint_cosign: bool,
+    pub max_work_payloads: u32,
+    pub require_nonce_commitments: bool,
+    pub registration_cooldown: u64,
+    pub registration_window: u64,
+    pub max_registrations_per_window: u32,
+    pub breaker_window: u64,
+    pub breaker_max_rejections: u32,
+    pub subscription_fee: Option<Coin>,
+    pub equivocation_limit: u32,
+    pub expiry_bounty: Option<Coin>,
+    pub finalizer_window: u64,
+    pub account_ttl_seconds: u64,
+    pub max_validation_blocks: u64,
+}
+
+/// Pause window as schema 5 stored it, before windows recorded heights;
+/// only the schema 6 migration reads it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyPauseWindow {
+    pub paused_at: u64,
+    pub resumed_at: Option<u64>,
+    pub paused_for: u64,
+}
+
+/// Equivocation evidence as schema 4 stored it, before content votes recorded
+/// the user's committed state; only the schema 5 migration reads it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyEquivocationEvidence {
+    pub node_id: u32,
+    pub content_hash: Vec<u8>,
+    pub first: LegacyContentVote,
+    pub second: LegacyContentVote,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyContentVote {
+    pub content_hash: Vec<u8>,
+    pub validation_id: String,
+    pub valid: bool,
+    pub voted_at: u64,
 }
 
 // ============================================================================
 // STORAGE
 // ============================================================================
 
+/// Layout version of everything below. Absent on instances that predate it,
+/// which reads as schema 0.
+pub const STORAGE_SCHEMA_VERSION: Item<u16> = Item::new(b"storage_schema_version");
+// Kept apart from State so migrate can check it whatever the schema
+pub const DEPLOYMENT: Item<DeploymentIdentity> = Item::new(b"deployment");
 pub const STATE: Item<State> = Item::new(b"state");
+// Json, so fields added with a serde default read from older records
+pub const CONFIG: Item<Config, Json> = Item::new(b"config");
+pub const STATS: Item<Stats> = Item::new(b"stats");
+pub const ADMIN_RECOVERY: Item<AdminRecovery> = Item::new(b"admin_recovery");
+// Append-only; the oldest windows are merged once it outgrows MAX_PAUSE_WINDOWS
+pub const PAUSE_LEDGER: Item<Vec<PauseWindow>> = Item::new(b"pause_ledger");
+// The same key read in the schema 5 layout, for the schema 6 migration
+pub const LEGACY_PAUSE_LEDGER: Item<Vec<LegacyPauseWindow>> = Item::new(b"pause_ledger");
+pub const LAST_FINALIZED: Item<LastFinalized> = Item::new(b"last_finalized");
 pub const PENDING_VALIDATIONS: Keymap<String, PendingValidation> = Keymap::new(b"pending_validations");
+// Pending validations holding a share for each node, by node id, as
+// (deadline when filed, validation id) in ascending order
+pub const NODE_WORK: Keymap<u32, Vec<(u64, String)>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"node_work").without_iter().build();
 pub const STATE_COMMITMENTS: Keymap<String, StateCommitment> = Keymap::new(b"state_commitments");
+// Users holding a commitment by export slot, the order their first commitment
+// landed in. A removed commitment leaves its slot empty, so slots never shift.
+pub const COMMITMENT_ORDER: Keymap<u64, String, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"commitment_order").without_iter().build();
+pub const COMMITMENT_SLOTS: Keymap<String, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"commitment_slots").without_iter().build();
+// Slots handed out so far
+pub const COMMITMENT_SEQ: Item<u64> = Item::new(b"commitment_seq");
+pub const TRANSFERS: Keymap<String, TransferRecord> = Keymap::new(b"transfers");
+pub const TRANSFER_GROUPS: Keymap<String, TransferGroup> = Keymap::new(b"transfer_groups");
+pub const LEAF_OWNERS: Keymap<u64, String> = Keymap::new(b"leaf_owners");
+pub const NODE_METADATA: Keymap<u32, NodeMetadata> = Keymap::new(b"node_metadata");
+pub const VALIDATION_OUTCOMES: Keymap<String, ValidationOutcome> = Keymap::new(b"validation_outcomes");
+// The same keys read in the first release's layout, for the schema 0 migration
+pub const LEGACY_STATE: Item<LegacyState> = Item::new(b"state");
+pub const LEGACY_STATE_COMMITMENTS: Keymap<String, LegacyStateCommitment> = Keymap::new(b"state_commitments");
+// Commitments in the schema 2 layout, for the schema 3 migration
+pub const LEGACY_STATE_COMMITMENTS_V2: Keymap<String, LegacyStateCommitmentV2> = Keymap::new(b"state_commitments");
+// Config in its Bincode layout, for the schema 7 migration
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new(b"config");
+
+// Global root after each finalization, by block_height (pruned in order)
+pub const ROOT_HISTORY: Keymap<u64, Vec<u8>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"root_history").without_iter().build();
+
+// Finalized record per block_height, pruned together with ROOT_HISTORY
+pub const FINALIZED_RECORDS: Keymap<u64, FinalizedRecord, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"finalized_records").without_iter().build();
+// Block height of each retained finalized record, by validation id
+pub const FINALIZED_HEIGHTS: Keymap<String, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"finalized_heights").without_iter().build();
+
+// Every threshold key by key epoch, 1..=state.key_epoch; validations keep the
+// key epoch they were submitted in
+pub const THRESHOLD_KEYS: Keymap<u64, ThresholdKey, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"threshold_keys").without_iter().build();
+// Committee at the start of each epoch after the first
+pub const COMMITTEE_SNAPSHOTS: Keymap<u64, CommitteeSnapshot, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"committee_snapshots").without_iter().build();
+
+// Each node's most recent content votes, oldest first
+pub const CONTENT_VOTES: Keymap<u32, Vec<ContentVote>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"content_votes").without_iter().build();
+
+// Each node's most recent equivocations, oldest first
+pub const EQUIVOCATIONS: Keymap<u32, Vec<EquivocationEvidence>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"equivocations").without_iter().build();
+// The same key read in the schema 4 layout, for the schema 5 migration
+pub const LEGACY_EQUIVOCATIONS: Keymap<u32, Vec<LegacyEquivocationEvidence>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"equivocations").without_iter().build();
+
+// Effort per "<epoch>/<node_id>"
+pub const NODE_EFFORT: Keymap<String, NodeEffort, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"node_effort").without_iter().build();
+
+// Validations that declared a dependency on a validation id, registered at
+// submit whether or not it had settled; taken when it fails, kept when it
+// finalizes so ChallengeFinalized can see what was built on it. Failed
+// dependents are dropped when the next one registers.
+pub const DEPENDENTS: Keymap<String, Vec<String>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"dependents").without_iter().build();
+// Validation that produced each user's current commitment
+pub const FINALIZED_BY: Keymap<String, String, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"finalized_by").without_iter().build();
+// Nodes that have challenged a finalization, by validation id, until quorum
+pub const FINALIZED_CHALLENGES: Keymap<String, Vec<u32>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"finalized_challenges").without_iter().build();
+// Settled validation ids of each user (suffixed by user address), so a
+// purge visits only that user's outcomes
+pub const USER_OUTCOMES: Keyset<String> = Keyset::new(b"user_outcomes");
+// Pending validation ids of each user (suffixed by user address); the
+// user's storage quota bounds how many there are
+pub const USER_PENDING: Keyset<String> = Keyset::new(b"user_pending");
+// Next nonce each user's PurgeMyHistory signature must cover
+pub const PURGE_NONCES: Keymap<String, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"purge_nonces").without_iter().build();
+
+// Votes of cancelled transitions by content hash, consumed by a resubmission
+pub const CANCELLED_CONTENT: Keymap<Vec<u8>, CancelledVotes, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"cancelled_content").without_iter().build();
+
+// Nodes whose partials finalized each commitment, by "<user_address>/<nonce>"
+pub const COMMITMENT_FINALIZERS: Keymap<String, Vec<u32>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"commitment_finalizers").without_iter().build();
+
+// Approximate bytes stored under each user's keys (see contract::charge_storage)
+pub const USER_STORAGE: Keymap<String, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"user_storage").without_iter().build();
+
+// Commitment each user had before their latest one, kept while it is challengeable
+pub const PREVIOUS_COMMITMENTS: Keymap<String, StateCommitment, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"previous_commitments").without_iter().build();
+
+// Commitments each user has since replaced, by "<user_address>/<nonce>"
+// (the last contract::MAX_COMMITMENT_HISTORY)
+pub const COMMITMENT_HISTORY: Keymap<String, StateCommitment, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"commitment_history").without_iter().build();
+
+// Pending validation holding each of a user's assets, by "user/asset_id";
+// taken on submit and released when the validation settles
+pub const ASSET_LOCKS: Keymap<String, String, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"asset_locks").without_iter().build();
+
+// Secp256k1 key each user signs storage repoints with
+pub const USER_KEYS: Keymap<String, Vec<u8>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"user_keys").without_iter().build();
+// Next nonce each user's SetUserKey signature must cover
+pub const USER_KEY_NONCES: Keymap<String, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"user_key_nonces").without_iter().build();
+// Each user's most recent key changes, oldest first
+pub const KEY_ROTATIONS: Keymap<String, Vec<KeyRotation>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"key_rotations").without_iter().build();
+
+// Accounts that registered a key but have not submitted yet, by address
+// (block time of registration); submitting removes the entry for good
+pub const ACCOUNT_REGISTRATIONS: Keymap<String, u64> = Keymap::new(b"account_registrations");
+
+// Block height of each address's last RegisterMPCNode
+pub const REGISTRATION_HEIGHTS: Keymap<String, u64, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"registration_heights").without_iter().build();
+pub const REGISTRATION_WINDOW: Item<RegistrationWindow> = Item::new(b"registration_window");
+
+// Users with a rejection inside the last config.breaker_window blocks, one
+// entry each, oldest first; at most config.breaker_max_rejections + 1 entries
+pub const RECENT_REJECTIONS: Item<Vec<RecentRejection>> = Item::new(b"recent_rejections");
+
+// Validations settled by the message being executed that owe notifications
+// or refunds; drained into messages before it returns, so empty between
+// transactions
+pub const SETTLED_VALIDATIONS: Item<Vec<SettledValidation>> = Item::new(b"settled_validations");
+// Data availability challenge bonds held, by validation id
+pub const CHALLENGE_BONDS: Keymap<String, ChallengeBond, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"challenge_bonds").without_iter().build();
+// Subscription fees held, by validation id, aligned with its subscribers
+pub const SUBSCRIPTION_FEES: Keymap<String, Vec<Coin>, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"subscription_fees").without_iter().build();
+
+// Last outbound send id handed out
+pub const OUTBOUND_SEQ: Item<u64> = Item::new(b"outbound_seq");
+// Sends dispatched by the message being executed, by id; each is removed by
+// its reply
+pub const OUTBOUND_IN_FLIGHT: Keymap<u64, OutboundSend, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"outbound_in_flight").without_iter().build();
+// Sends whose bank message failed, by id, with the funds still held here
+pub const OUTBOUND_FAILURES: Keymap<u64, OutboundSend> = Keymap::new(b"outbound_failures");
+// Keys of OUTBOUND_FAILURES in ascending order, for paging
+pub const OUTBOUND_FAILURE_IDS: Item<Vec<u64>> = Item::new(b"outbound_failure_ids");
+// Receipt of each validation's bounty send, by validation id, and the ids
+// of each recipient's receipts (suffixed by recipient address)
+pub const WITHDRAWAL_RECEIPTS: Keymap<String, WithdrawalReceipt, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"withdrawal_receipts").without_iter().build();
+pub const RECIPIENT_RECEIPTS: Keyset<String> = Keyset::new(b"recipient_receipts");
+
+// Keys reported compromised, by public key; never removed, never re-registrable
+pub const COMPROMISED_KEYS: Keymap<Vec<u8>, KeyCompromise, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"compromised_keys").without_iter().build();
+
+// Reverse index of each user's current state CID (cid -> user address)
+pub const CID_OWNERS: Keymap<String, String, Bincode2, WithoutIter> =
+    KeymapBuilder::new(b"cid_owners").without_iter().build();
+
+// Presence-only set for the duplicate-vote check (no value to deserialize)
+pub const VOTE_PRESENCE: Keyset<String, Bincode2, WithoutIter> =
+    KeysetBuilder::new(b"vote_presence").without_iter().build();  // "<validation_id>/<node_id>"
+
+// Addresses allowed to read full (share-carrying) validation data
+pub const OBSERVERS: Keyset<String> = Keyset::new(b"observers");
@@ -0,0 +1,257 @@
+//! Deterministic fixtures for unit tests.
+//!
+//! Keys are derived from fixed seeds so signatures are reproducible, and every
+//! signed payload is built from the same digests in [`crate::protocol`] that
+//! the contract and light clients verify against. Nothing here hand-rolls
+//! signature bytes.
+
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{Env, OwnedDeps, Response, StdError, StdResult};
+use k256::ecdsa::{signature::hazmat::PrehashSigner, Signature, SigningKey};
+use sha2::{Digest, Sha256};
+
+use crate::contract::{execute, instantiate, MIN_PRODUCTION_THRESHOLD};
+use crate::msg::{ExecuteMsg, InstantiateMsg, ViewerAuth};
+use crate::protocol::{asset_updates_digest, attestation_digest, transition_content_hash, user_key_digest, vote_digest, CURRENT_PROTOCOL_VERSION};
+use crate::state::{EncryptedShares, ShareEncoding, StateTransition, PENDING_VALIDATIONS, USER_KEY_NONCES};
+
+pub type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+/// Secret key of `node{i}` (the scalar `[i; 32]`)
+pub fn node_signing_key(i: u8) -> SigningKey {
+    SigningKey::from_slice(&[i; 32]).unwrap()
+}
+
+/// Secret key of a user, derived from their address
+pub fn user_signing_key(user: &str) -> SigningKey {
+    SigningKey::from_slice(&Sha256::digest(user.as_bytes())).unwrap()
+}
+
+/// 33-byte SEC1 public key, the form nodes register with
+pub fn compressed_public_key(key: &SigningKey) -> Vec<u8> {
+    key.verifying_key().to_encoded_point(true).as_bytes().to_vec()
+}
+
+/// 64-byte compact secp256k1 signature over a 32-byte digest
+pub fn sign_digest(key: &SigningKey, digest: &[u8]) -> Vec<u8> {
+    let signature: Signature = key.sign_prehash(digest).unwrap();
+    signature.to_bytes().to_vec()
+}
+
+/// Instantiates as "creator" and registers `node1..=nodes` with their
+/// deterministic keys. Thresholds below the production minimum get dev_mode.
+pub fn setup_contract(threshold: u32, nodes: u8) -> (MockDeps, Env) {
+    setup_contract_with_env(mock_env(), threshold, nodes)
+}
+
+/// [`setup_contract`] on a specific chain or contract address
+pub fn setup_contract_with_env(env: Env, threshold: u32, nodes: u8) -> (MockDeps, Env) {
+    let mut deps = mock_dependencies();
+
+    instantiate(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        InstantiateMsg { threshold, dev_mode: threshold < MIN_PRODUCTION_THRESHOLD, ..Default::default() },
+    ).unwrap();
+
+    for i in 1..=nodes {
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&format!("node{}", i), &[]),
+            ExecuteMsg::RegisterMPCNode { public_key: compressed_public_key(&node_signing_key(i)) },
+        ).unwrap();
+    }
+
+    (deps, env)
+}
+
+/// Canonical transition with one share per node, signed by `key` over its
+/// content hash. `nonce` becomes
+/// part of the IPFS pointer so otherwise identical transitions stay distinct.
+///
+/// The contract only checks that a user signature is present today; the
+/// signature is real so tests keep working once it is verified.
+pub fn signed_transition(
+    user: &str,
+    key: &SigningKey,
+    old_root: &[u8],
+    new_root: &[u8],
+    nonce: u64,
+    nodes: u32,
+) -> StateTransition {
+    let mut transition = StateTransition {
+        user_address: user.to_string(),
+        old_state_root: old_root.to_vec(),
+        new_state_root: new_root.to_vec(),
+        merkle_proof: vec![],
+        new_state_ipfs: format!("Qm{}{}", user, nonce),
+        ipfs_gateway_hint: None,
+        content_hash: Sha256::digest(format!("Qm{}{}", user, nonce)).to_vec(),
+        user_signature: vec![],
+        encrypted_shares: (1..=nodes)
+            .map(|node_id| EncryptedShares { node_id, encrypted_data: vec![node_id as u8], encoding: ShareEncoding::Raw })
+            .collect(),
+        vss_commitments: vec![],
+        vss_proof_polynomial: vec![],
+        protocol_version: CURRENT_PROTOCOL_VERSION,
+        coordinator_metadata: None,
+        depends_on: vec![],
+        asset_updates: vec![],
+    };
+    let digest = transition_content_hash(&transition).unwrap();
+    transition.user_signature = sign_digest(key, &digest);
+    transition
+}
+
+/// `ValidateTransition` from `node{node}`, with the partial signature over the
+/// vote digest for `transition` on `env`'s deployment when the vote is valid
+pub fn signed_vote(env: &Env, node: u8, validation_id: &str, transition: &StateTransition, valid: bool) -> ExecuteMsg {
+    ExecuteMsg::ValidateTransition {
+        validation_id: validation_id.to_string(),
+        valid,
+        partial_signature: if valid { signed_partial(env, node, validation_id, transition) } else { vec![] },
+        attested_root: None,
+    }
+}
+
+/// `node{node}`'s partial signature accepting `transition` under `validation_id`
+pub fn signed_partial(env: &Env, node: u8, validation_id: &str, transition: &StateTransition) -> Vec<u8> {
+    let digest = vote_digest(
+        &env.block.chain_id,
+        env.contract.address.as_str(),
+        validation_id,
+        transition.protocol_version,
+        &transition.new_state_root,
+        &asset_updates_digest(&transition.asset_updates),
+    );
+    sign_digest(&node_signing_key(node), &digest)
+}
+
+/// `AttestBuild` for `node{node}` bound to `contract_address`, signed by
+/// `node{signer}`
+pub fn signed_attestation(contract_address: &str, signer: u8, git_commit: &str, binary_hash: &[u8]) -> ExecuteMsg {
+    let digest = attestation_digest(contract_address, git_commit, binary_hash);
+    ExecuteMsg::AttestBuild {
+        git_commit: git_commit.to_string(),
+        binary_hash: binary_hash.to_vec(),
+        signature: sign_digest(&node_signing_key(signer), &digest),
+    }
+}
+
+/// `SetUserKey` registering `public_key` for `user` at `nonce`, signed by
+/// `signer` (the current key for a rotation, the new key for a first set)
+pub fn signed_user_key(env: &Env, user: &str, public_key: &[u8], signer: &SigningKey, nonce: u64) -> ExecuteMsg {
+    let digest = user_key_digest(&env.block.chain_id, env.contract.address.as_str(), user, public_key, nonce);
+    ExecuteMsg::SetUserKey { public_key: public_key.to_vec(), nonce, signature: sign_digest(signer, &digest) }
+}
+
+/// Registers `user`'s deterministic key while they have none, signed for itself
+pub fn register_user_key(deps: &mut MockDeps, env: &Env, user: &str) -> StdResult<Response> {
+    let key = user_signing_key(user);
+    let nonce = USER_KEY_NONCES.get(&deps.storage, &user.to_string()).unwrap_or(0);
+    let msg = signed_user_key(env, user, &compressed_public_key(&key), &key, nonce);
+    execute(deps.as_mut(), env.clone(), mock_info(user, &[]), msg)
+}
+
+/// Submits `transition` as its user and returns the new validation id
+pub fn submit(deps: &mut MockDeps, env: &Env, transition: &StateTransition) -> StdResult<String> {
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&transition.user_address, &[]),
+        ExecuteMsg::SubmitStateTransition { transition: transition.clone() },
+    )?;
+    res.attributes.iter()
+        .find(|a| a.key == "validation_id")
+        .map(|a| a.value.clone())
+        .ok_or_else(|| StdError::generic_err("No validation_id in response"))
+}
+
+/// Casts signed valid votes from `node1..=votes`, returning the last response
+pub fn drive_to_threshold(
+    deps: &mut MockDeps,
+    env: &Env,
+    validation_id: &str,
+    transition: &StateTransition,
+    votes: u8,
+) -> StdResult<Response> {
+    let mut last = Response::new();
+    for node in 1..=votes {
+        last = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(&format!("node{}", node), &[]),
+            signed_vote(env, node, validation_id, transition, true),
+        )?;
+    }
+    Ok(last)
+}
+
+/// An unsigned transition for `user` with one single-byte share per node
+pub fn test_transition(user: &str, new_root: u8, nodes: u32) -> StateTransition {
+    StateTransition {
+        user_address: user.to_string(),
+        old_state_root: vec![0; 32],
+        new_state_root: vec![new_root; 32],
+        merkle_proof: vec![],
+        new_state_ipfs: format!("Qm{}{}", user, new_root),
+        ipfs_gateway_hint: None,
+        content_hash: Sha256::digest(format!("Qm{}{}", user, new_root)).to_vec(),
+        user_signature: vec![1],
+        encrypted_shares: (1..=nodes)
+            .map(|node_id| EncryptedShares { node_id, encrypted_data: vec![node_id as u8], encoding: ShareEncoding::Raw })
+            .collect(),
+        vss_commitments: vec![],
+        vss_proof_polynomial: vec![],
+        protocol_version: CURRENT_PROTOCOL_VERSION,
+        coordinator_metadata: None,
+        depends_on: vec![],
+        asset_updates: vec![],
+    }
+}
+
+/// `node{node}`'s vote on `validation_id`
+pub fn vote(
+    deps: &mut MockDeps,
+    env: &Env,
+    node: u8,
+    validation_id: &str,
+    valid: bool,
+) -> StdResult<Response> {
+    // Signed over the pending transition, so partials verify like a real node's
+    let msg = match PENDING_VALIDATIONS.get(&deps.storage, &validation_id.to_string()) {
+        Some(pending) => signed_vote(env, node, validation_id, &pending.transition, valid),
+        None => ExecuteMsg::ValidateTransition {
+            validation_id: validation_id.to_string(),
+            valid,
+            partial_signature: vec![node; 64],
+            attested_root: None,
+        },
+    };
+    execute(deps.as_mut(), env.clone(), mock_info(&format!("node{}", node), &[]), msg)
+}
+
+/// `node{node}`'s partial accepting a pending validation
+pub fn pending_partial(deps: &MockDeps, env: &Env, node: u8, validation_id: &str) -> Vec<u8> {
+    let pending = PENDING_VALIDATIONS.get(&deps.storage, &validation_id.to_string()).unwrap();
+    signed_partial(env, node, validation_id, &pending.transition)
+}
+
+/// Sets `node{node}`'s viewing key and returns the auth that presents it
+pub fn node_auth(
+    deps: &mut MockDeps,
+    env: &Env,
+    node: u8,
+) -> ViewerAuth {
+    let address = format!("node{}", node);
+    let viewing_key = format!("{}-key", address);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info(&address, &[]),
+        ExecuteMsg::SetViewingKey { key: viewing_key.clone() },
+    ).unwrap();
+    ViewerAuth { address, viewing_key }
+}